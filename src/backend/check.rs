@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+
+use crate::{
+    errors::error::{ErrorClass, ErrorKind, Errors},
+    frontend::expr::{Expr, ExprKind, Span},
+};
+
+use super::{directive, eval::qualified_name, regex::Regex, value::Type};
+
+/// A name that has been declared, and where if it was declared in the source
+#[derive(Clone, Copy)]
+struct Binding {
+    span: Option<Span>,
+    constant: bool,
+}
+
+/// The globals known to the checker, kept between checks when a program arrives in pieces
+#[derive(Clone, Default)]
+pub struct Globals {
+    /// Globals declared so far at the top level
+    declared: HashMap<String, Binding>,
+    /// Every global the program declares, since function bodies can refer to globals that are
+    /// declared after them as long as they're called later
+    all: HashMap<String, Binding>,
+    /// What each module that can be imported from has in it
+    modules: HashMap<String, Vec<String>>,
+}
+
+impl Globals {
+    /// Declares a global that doesn't come from the source, like a host function
+    pub fn define(&mut self, name: &str, constant: bool) {
+        let binding = Binding {
+            span: None,
+            constant,
+        };
+        self.declared.insert(name.to_string(), binding);
+        self.all.insert(name.to_string(), binding);
+    }
+
+    /// Makes `symbol` importable from `module`
+    pub fn export(&mut self, module: &str, symbol: &str) {
+        let symbols = self.modules.entry(module.to_string()).or_default();
+        if !symbols.iter().any(|s| s == symbol) {
+            symbols.push(symbol.to_string());
+        }
+    }
+
+    /// Forgets every global that `keep` returns false for, like ones whose declaration never ran
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.declared.retain(|name, _| keep(name));
+        self.all.retain(|name, _| keep(name));
+    }
+}
+
+/// Finds the errors that running the program would be certain to hit, without running it
+/// Names are resolved the same way the evaluator resolves them: a function body sees its own
+/// locals and the globals, and nothing in between
+pub struct Checker<'c, 'e> {
+    errors: &'c mut Errors<'e>,
+    globals: &'c mut Globals,
+    /// Locals of the function body being checked, if any
+    locals: Option<HashMap<String, Binding>>,
+}
+
+impl<'c, 'e> Checker<'c, 'e> {
+    pub fn new(errors: &'c mut Errors<'e>, globals: &'c mut Globals) -> Self {
+        Self {
+            errors,
+            globals,
+            locals: None,
+        }
+    }
+
+    pub fn check(&mut self, tree: &[Expr]) {
+        for e in tree {
+            if let Some((name, binding)) = declared(e) {
+                self.globals.all.entry(name).or_insert(binding);
+            }
+        }
+        tree.iter().for_each(|e| self.statement(e));
+    }
+
+    fn statement(&mut self, expr: &Expr) {
+        match &expr.0 {
+            ExprKind::VariableExpr(name, typ, value) | ExprKind::ConstExpr(name, typ, value) => {
+                self.expr(value);
+                if let Some(typ) = typ {
+                    self.annotation(typ);
+                }
+                let constant = matches!(expr.0, ExprKind::ConstExpr(..));
+                if !matches!(name.0, ExprKind::Ident(_)) {
+                    let keyword = if constant { "const" } else { "var" };
+                    self.errors.new(
+                        ErrorClass::Error,
+                        ErrorKind::InvalidDeclaration { keyword },
+                        name.1 .0,
+                        name.1 .1,
+                    );
+                }
+                self.declare(name, constant);
+            }
+            ExprKind::MutateExpr(name, value) => {
+                self.expr(value);
+                if let ExprKind::Ident(n) = &name.0 {
+                    match self.resolve(n) {
+                        None => self.undefined(n, name.1),
+                        Some(b) if b.constant => {
+                            let err = self.errors.new(
+                                ErrorClass::Error,
+                                ErrorKind::ConstantMutation { name: n.clone() },
+                                name.1 .0,
+                                name.1 .1,
+                            );
+                            if let Some(span) = b.span {
+                                err.label(span.0, span.1, "declared as a constant here");
+                            }
+                            err.help(format!("declare '{}' with 'var' if it needs to change", n));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+            ExprKind::FunctionExpr(name, params, returns, body) => {
+                self.declare(name, true);
+                self.function(params, returns.as_deref(), body);
+            }
+            ExprKind::ReturnExpr(value) => {
+                self.expr(value);
+                if self.locals.is_none() {
+                    self.errors.new(
+                        ErrorClass::Error,
+                        ErrorKind::ReturnOutsideFunction,
+                        expr.1 .0,
+                        expr.1 .1,
+                    );
+                }
+            }
+            ExprKind::ImportExpr(module, symbols) => self.import(module, symbols),
+            ExprKind::ClassExpr(name, _) => self.declare(name, true),
+            ExprKind::Directive(directive) => {
+                if let Err((kind, span)) = directive::parse(directive) {
+                    self.errors.new(ErrorClass::Error, kind, span.0, span.1);
+                }
+            }
+            ExprKind::ClassMethods(_, _) => {}
+            _ => self.expr(expr),
+        }
+    }
+
+    fn function(&mut self, params: &Expr, returns: Option<&Expr>, body: &Expr) {
+        let outer = self.locals.replace(HashMap::new());
+        if let ExprKind::FunctionArgs(params) = &params.0 {
+            for p in params {
+                match &p.0 {
+                    ExprKind::Parameter(name, typ) => {
+                        self.annotation(typ);
+                        self.declare(name, false);
+                    }
+                    _ => self.declare(p, false),
+                }
+            }
+        }
+        if let Some(returns) = returns {
+            self.annotation(returns);
+        }
+        if let ExprKind::BlockExpr(body) = &body.0 {
+            body.iter().for_each(|e| self.statement(e));
+        }
+        self.locals = outer;
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.0 {
+            ExprKind::Ident(name) if self.resolve(name).is_none() => self.undefined(name, expr.1),
+            ExprKind::ParensExpr(inner)
+            | ExprKind::PrintExpr(inner)
+            | ExprKind::QMark(inner)
+            | ExprKind::Negate(inner) => self.expr(inner),
+            ExprKind::BinaryExpr(left, right, _) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::FunctionCall(callee, args) => {
+                self.expr(callee);
+                self.expr(args);
+            }
+            ExprKind::FunctionArgs(args) | ExprKind::ListExpr(args) => {
+                args.iter().for_each(|a| self.expr(a))
+            }
+            // The name is a parameter of the function being called, not a name in scope
+            ExprKind::NamedArg(_, value) => self.expr(value),
+            ExprKind::MapExpr(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            ExprKind::IndexExpr(value, index) => {
+                self.expr(value);
+                self.expr(index);
+            }
+            // Only the leftmost part of `a.b` is a name in scope
+            ExprKind::QualifiedIdent(left, _) => self.expr(left),
+            ExprKind::Regex(pattern) => {
+                if let Err(err) = Regex::new(pattern) {
+                    let span = err.span_in(expr.1);
+                    self.errors
+                        .new(ErrorClass::Error, err.kind(), span.0, span.1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Reports annotations that don't name a type
+    fn annotation(&mut self, typ: &Expr) {
+        if let ExprKind::Ident(name) = &typ.0 {
+            if Type::Nil.matches_annotation(name).is_none() {
+                self.errors
+                    .new(
+                        ErrorClass::Error,
+                        ErrorKind::UndefinedName { name: name.clone() },
+                        typ.1 .0,
+                        typ.1 .1,
+                    )
+                    .primary("not a type");
+            }
+        }
+    }
+
+    /// Imported names are declared as constants where the import is
+    fn import(&mut self, module: &Expr, symbols: &Expr) {
+        let name = qualified_name(module);
+        let Some(exported) = self.globals.modules.get(&name).cloned() else {
+            self.errors.new(
+                ErrorClass::Error,
+                ErrorKind::UnknownModule { module: name },
+                module.1 .0,
+                module.1 .1,
+            );
+            return;
+        };
+        let ExprKind::ImportArgs(symbols) = &symbols.0 else {
+            return;
+        };
+
+        for symbol in symbols {
+            match &symbol.0 {
+                ExprKind::Wildcard => exported
+                    .iter()
+                    .for_each(|s| self.declare_name(s, symbol.1, true)),
+                ExprKind::Ident(s) if !exported.contains(s) => {
+                    self.errors.new(
+                        ErrorClass::Error,
+                        ErrorKind::UnknownImport {
+                            module: name.clone(),
+                            symbol: s.clone(),
+                        },
+                        symbol.1 .0,
+                        symbol.1 .1,
+                    );
+                }
+                _ => self.declare(symbol, true),
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Expr, constant: bool) {
+        if let ExprKind::Ident(n) = &name.0 {
+            self.declare_name(n, name.1, constant);
+        }
+    }
+
+    fn declare_name(&mut self, n: &str, span: Span, constant: bool) {
+        let binding = Binding {
+            span: Some(span),
+            constant,
+        };
+        let scope = self.locals.as_mut().unwrap_or(&mut self.globals.declared);
+        if let Some(first) = scope.insert(n.to_string(), binding) {
+            let err = self.errors.new(
+                ErrorClass::Error,
+                ErrorKind::Redefinition {
+                    name: n.to_string(),
+                },
+                span.0,
+                span.1,
+            );
+            match first.span {
+                Some(span) => {
+                    err.label(span.0, span.1, "first defined here");
+                }
+                None if first.constant => {
+                    err.help(format!("'{}' is built in, so this needs a different name", n));
+                    return;
+                }
+                None => {}
+            }
+            err.help(format!("use '{} -> ...;' to change its value instead", n));
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<Binding> {
+        if let Some(locals) = &self.locals {
+            // Inside a function any global may have been declared by the time it's called
+            return locals
+                .get(name)
+                .or_else(|| self.globals.all.get(name))
+                .copied();
+        }
+        self.globals.declared.get(name).copied()
+    }
+
+    fn undefined(&mut self, name: &str, span: Span) {
+        let err = self.errors.new(
+            ErrorClass::Error,
+            ErrorKind::UndefinedName {
+                name: name.to_string(),
+            },
+            span.0,
+            span.1,
+        );
+        if self.locals.is_none() {
+            if let Some(later) = self.globals.all.get(name).and_then(|b| b.span) {
+                err.label(later.0, later.1, "defined later here");
+            }
+        }
+    }
+}
+
+/// The name a top-level statement declares, if any
+fn declared(expr: &Expr) -> Option<(String, Binding)> {
+    let (name, constant) = match &expr.0 {
+        ExprKind::VariableExpr(name, _, _) => (name, false),
+        ExprKind::ConstExpr(name, _, _)
+        | ExprKind::FunctionExpr(name, _, _, _)
+        | ExprKind::ClassExpr(name, _) => (name, true),
+        _ => return None,
+    };
+    match &name.0 {
+        ExprKind::Ident(n) => Some((
+            n.clone(),
+            Binding {
+                span: Some(name.1),
+                constant,
+            },
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        errors::formatter::Formatter,
+        frontend::{lexer::Lexer, parser::Parser},
+    };
+
+    /// Checks `source`, giving each error's message and the labels attached to it
+    fn check(source: &str) -> Vec<(String, Vec<String>)> {
+        let source = source.to_string();
+        let formatter = Formatter::initialize("test", &source);
+        let mut errors = Errors::initialize(&formatter);
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize(&mut errors);
+        let mut parser = Parser::new(&mut errors, tokens.iter());
+        parser.parse();
+        let tree = std::mem::take(&mut parser.tree);
+        assert!(!errors.has_errors(), "{:?}", errors.errors());
+        Checker::new(&mut errors, &mut Globals::default()).check(&tree);
+        errors
+            .errors()
+            .iter()
+            .map(|err| {
+                let labels = err.labels.iter().map(|l| l.message.clone()).collect();
+                (err.kind.to_string(), labels)
+            })
+            .collect()
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        check(source).into_iter().map(|(msg, _)| msg).collect()
+    }
+
+    #[test]
+    fn valid_programs() {
+        assert!(check("var a = 1;\na -> a + 1;\n$ a;").is_empty());
+        // Function bodies can use globals declared after them
+        assert!(check("def f(): int { return g; }\nvar g = 1;\n$ f();").is_empty());
+        assert!(check("def f(x: int): int { var y = x; return y; }").is_empty());
+    }
+
+    #[test]
+    fn declares_only_names() {
+        assert_eq!(
+            messages("var a.b = 1;"),
+            ["Expected a name to declare after 'var'"]
+        );
+        assert_eq!(
+            messages("const 3 = 1;"),
+            ["Expected a name to declare after 'const'"]
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            messages("$ missing;"),
+            ["Cannot find 'missing' in this scope"]
+        );
+        assert_eq!(
+            check("$ later;\nvar later = 1;"),
+            [(
+                "Cannot find 'later' in this scope".to_string(),
+                vec!["defined later here".to_string()]
+            )]
+        );
+        assert_eq!(
+            check("var a = 1;\nvar a = 2;"),
+            [(
+                "'a' is already defined in this scope".to_string(),
+                vec!["first defined here".to_string()]
+            )]
+        );
+        // Locals belong to their function
+        assert_eq!(
+            messages("def f(x: int) { var y = x; }\n$ y;"),
+            ["Cannot find 'y' in this scope"]
+        );
+    }
+
+    #[test]
+    fn constants_returns_and_annotations() {
+        assert_eq!(
+            check("const c = 1;\nc -> 2;"),
+            [(
+                "Cannot mutate 'c' because it is a constant".to_string(),
+                vec!["declared as a constant here".to_string()]
+            )]
+        );
+        assert_eq!(messages("return 1;"), ["'return' outside of a function"]);
+        assert_eq!(
+            messages("var a: widget = 1;"),
+            ["Cannot find 'widget' in this scope"]
+        );
+    }
+
+    #[test]
+    fn pattern_literals() {
+        assert!(messages("var r = r\"a(b)\";").is_empty());
+        // Checked before anything runs, even where the pattern is never used
+        assert_eq!(
+            messages("def f() { var r = r\"a(b\"; }"),
+            ["Invalid regular expression: this group is never closed"]
+        );
+    }
+}
@@ -3,23 +3,23 @@ use std::collections::HashMap;
 use super::value::Value;
 
 /// Contexts are the owners and handlers of all their values
-/// Values store a reference to thier context and their address
-/// in that context, but otherwise they exist entirely independent
-/// of scope and context
-pub struct Context<'r> {
-    pub locals: HashMap<String, Value<'r>>,
+/// Each function call gets its own context which is thrown away, along with everything in it,
+/// once the call returns
+#[derive(Default)]
+pub struct Context {
+    pub locals: HashMap<String, Value>,
 }
 
-impl<'r> Context<'r> {
+impl Context {
     pub fn new() -> Self {
-        Self { locals: HashMap::new() }
+        Self::default()
     }
 
-    pub fn store(&mut self, key: String, value: Value<'r>) {
-        self.locals.insert(key, value).unwrap();
-    }  
+    pub fn store(&mut self, key: String, value: Value) {
+        self.locals.insert(key, value);
+    }
 
-    pub fn get(&mut self, key: &String) -> Option<&Value<'r>> {
+    pub fn get(&self, key: &str) -> Option<&Value> {
         self.locals.get(key)
     }
 
@@ -27,13 +27,12 @@ impl<'r> Context<'r> {
     /// If a value doesn't exist at that key, it will return `None`
     /// If a value does exist but is constant, then it will return `None`
     /// Will return `Some(())` when there is a value at the key and the value is not constant
-    pub fn set(&mut self, key: &String, value: Value<'r>) -> Option<()> {
-        if let Some(v) = self.locals.get(key) {
-            if v.constant { return None }
-            self.locals.insert(key.clone(), value).unwrap();
-            return Some(())
-        } else {
-            return None
+    pub fn set(&mut self, key: &str, value: Value) -> Option<()> {
+        let v = self.locals.get_mut(key)?;
+        if v.constant {
+            return None;
         }
+        *v = value;
+        Some(())
     }
-}
\ No newline at end of file
+}
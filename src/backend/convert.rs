@@ -0,0 +1,219 @@
+use std::{fmt, rc::Rc};
+
+use super::{
+    map::Map,
+    table::Table,
+    value::{Type, Value},
+};
+
+/// A value didn't have the type that a Rust value was being read out as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {} but found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Rust values that can be handed to Starkey
+pub trait IntoValue {
+    fn into_value(self) -> Type;
+}
+
+/// Rust values that can be read back out of Starkey
+pub trait FromValue: Sized {
+    /// The type a value needs to have to be read as this, written as an annotation
+    const TYPE: &'static str;
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError>;
+}
+
+impl IntoValue for Type {
+    fn into_value(self) -> Type {
+        self
+    }
+}
+
+impl FromValue for Type {
+    const TYPE: &'static str = "any";
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Type {
+        self.typ
+    }
+}
+
+/// Generates both conversions for a type that maps onto a single variant
+macro_rules! convert {
+    ($rust:ty, $variant:ident, $name:literal) => {
+        impl IntoValue for $rust {
+            fn into_value(self) -> Type {
+                Type::$variant(self)
+            }
+        }
+
+        impl FromValue for $rust {
+            const TYPE: &'static str = $name;
+
+            fn from_value(value: &Type) -> Result<Self, ConversionError> {
+                match value {
+                    Type::$variant(v) => Ok(v.clone()),
+                    other => Err(ConversionError {
+                        expected: $name,
+                        found: other.type_name(),
+                    }),
+                }
+            }
+        }
+    };
+}
+
+convert!(i32, Int, "int");
+convert!(f32, Float, "float");
+convert!(bool, Bool, "bool");
+convert!(String, Str, "str");
+
+impl IntoValue for &str {
+    fn into_value(self) -> Type {
+        Type::Str(self.to_string())
+    }
+}
+
+/// Floats are single precision, so only reading them widens to `f64`
+/// Writing one would round it, so `f64` has no `IntoValue`
+impl FromValue for f64 {
+    const TYPE: &'static str = "float";
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError> {
+        f32::from_value(value).map(f64::from)
+    }
+}
+
+impl FromValue for i64 {
+    const TYPE: &'static str = "int";
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError> {
+        i32::from_value(value).map(i64::from)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Type {
+        Type::Nil
+    }
+}
+
+impl FromValue for () {
+    const TYPE: &'static str = "nil";
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError> {
+        match value {
+            Type::Nil => Ok(()),
+            other => Err(ConversionError {
+                expected: "nil",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl IntoValue for Table {
+    fn into_value(self) -> Type {
+        Type::Table(Rc::new(self))
+    }
+}
+
+/// Tables share their columns, so reading one out doesn't copy any values
+impl FromValue for Table {
+    const TYPE: &'static str = "table";
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError> {
+        match value {
+            Type::Table(table) => Ok(table.as_ref().clone()),
+            other => Err(ConversionError {
+                expected: "table",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl IntoValue for Map {
+    fn into_value(self) -> Type {
+        Type::Map(Rc::new(self))
+    }
+}
+
+impl FromValue for Map {
+    const TYPE: &'static str = "map";
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError> {
+        match value {
+            Type::Map(map) => Ok(map.as_ref().clone()),
+            other => Err(ConversionError {
+                expected: "map",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+/// `None` is nil
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Type {
+        self.map_or(Type::Nil, T::into_value)
+    }
+}
+
+/// Nil or a `T`, which annotations can't express, so any value is let through to be converted
+impl<T: FromValue> FromValue for Option<T> {
+    const TYPE: &'static str = "any";
+
+    fn from_value(value: &Type) -> Result<Self, ConversionError> {
+        match value {
+            Type::Nil => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(i32::from_value(&7.into_value()), Ok(7));
+        assert_eq!(String::from_value(&"a".into_value()), Ok("a".to_string()));
+        assert_eq!(i64::from_value(&Type::Int(-3)), Ok(-3));
+        assert_eq!(f64::from_value(&0.1f32.into_value()), Ok(f64::from(0.1f32)));
+        assert_eq!(
+            Option::<i32>::from_value(&None::<i32>.into_value()),
+            Ok(None)
+        );
+        assert_eq!(
+            Option::<bool>::from_value(&Type::Bool(true)),
+            Ok(Some(true))
+        );
+        assert_eq!(<()>::from_value(&().into_value()), Ok(()));
+    }
+
+    #[test]
+    fn mismatches() {
+        let err = f32::from_value(&Type::Int(1)).unwrap_err();
+        assert_eq!(err.to_string(), "expected float but found int");
+        assert!(<()>::from_value(&Type::Str(String::new())).is_err());
+        assert!(Option::<i32>::from_value(&Type::Float(1.0)).is_err());
+    }
+}
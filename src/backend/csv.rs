@@ -0,0 +1,621 @@
+//! Reading tables from CSV and writing them back out
+//!
+//! Fields are split as RFC 4180 describes, with any delimiter and quote character. An unquoted
+//! empty field is a missing value, and a quoted one is an empty string. Type inference treats a
+//! quoted field as text even if it looks like a number, which is what lets `write` quote every
+//! string and have `read` give back exactly the table that was written.
+
+use std::{fmt, rc::Rc};
+
+use super::{
+    series::Bitmap,
+    table::{Column, ColumnData, Table},
+};
+
+/// How many rows are looked at to infer a column's type
+/// A later value that doesn't fit widens the column instead of failing
+const SAMPLE_ROWS: usize = 1000;
+
+/// How the first row is treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Header {
+    /// A header if it looks like one, see `detect_header`
+    Auto,
+    Present,
+    Absent,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub delimiter: char,
+    pub quote: char,
+    pub header: Header,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            header: Header::Auto,
+        }
+    }
+}
+
+/// Something wrong with the file, lines and columns count from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, column {}: {}", self.line, column, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Field {
+    text: String,
+    quoted: bool,
+}
+
+impl Field {
+    fn is_missing(&self) -> bool {
+        self.text.is_empty() && !self.quoted
+    }
+}
+
+#[derive(Debug)]
+struct Record {
+    /// The line the record starts on, a quoted field can run over several
+    line: usize,
+    fields: Vec<Field>,
+}
+
+/// Splits the text into records of fields
+fn split(text: &str, options: &Options) -> Result<Vec<Record>, Problem> {
+    let (delimiter, quote) = (options.delimiter, options.quote);
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let mut record = Record {
+            line,
+            fields: Vec::new(),
+        };
+        loop {
+            let mut field = Field {
+                text: String::new(),
+                quoted: false,
+            };
+            if chars.peek() == Some(&quote) {
+                chars.next();
+                field.quoted = true;
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some(c) if c == quote => {
+                            // A doubled quote is a quote, anything else ends the field
+                            if chars.peek() == Some(&quote) {
+                                chars.next();
+                                field.text.push(quote);
+                            } else {
+                                break;
+                            }
+                        }
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.text.push(c);
+                        }
+                        None => {
+                            return Err(Problem {
+                                line: start,
+                                column: Some(record.fields.len() + 1),
+                                message: format!(
+                                    "the {} opening this field is never closed",
+                                    quote
+                                ),
+                            })
+                        }
+                    }
+                }
+                match chars.peek() {
+                    Some(&c) if c == delimiter || c == '\n' || c == '\r' => {}
+                    None => {}
+                    Some(&c) => {
+                        return Err(Problem {
+                            line,
+                            column: Some(record.fields.len() + 1),
+                            message: format!(
+                                "found '{}' after the closing {}, quotes inside a quoted field \
+                                 have to be doubled",
+                                c, quote
+                            ),
+                        })
+                    }
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c == delimiter || c == '\n' || c == '\r' {
+                        break;
+                    }
+                    field.text.push(c);
+                    chars.next();
+                }
+            }
+            record.fields.push(field);
+
+            match chars.next() {
+                Some(c) if c == delimiter => continue,
+                Some('\r') => {
+                    if chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                    line += 1;
+                    break;
+                }
+                Some('\n') => {
+                    line += 1;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// The types a column can be read as, from most to least specific
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Bool,
+    Int,
+    Float,
+    Str,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Bool => "bool",
+            Kind::Int => "int",
+            Kind::Float => "float",
+            Kind::Str => "str",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bool" => Some(Kind::Bool),
+            "int" => Some(Kind::Int),
+            "float" => Some(Kind::Float),
+            "str" => Some(Kind::Str),
+            _ => None,
+        }
+    }
+
+    fn accepts(self, text: &str) -> bool {
+        match self {
+            Kind::Bool => text == "true" || text == "false",
+            Kind::Int => text.parse::<i32>().is_ok(),
+            Kind::Float => parse_float(text).is_some(),
+            Kind::Str => true,
+        }
+    }
+
+    /// The type after this one that could hold `text`
+    fn widen(self, text: &str) -> Self {
+        [Kind::Int, Kind::Float, Kind::Str]
+            .into_iter()
+            .find(|kind| *kind > self && kind.accepts(text))
+            .unwrap_or(Kind::Str)
+    }
+}
+
+/// Reads a float the way `Type`'s `Display` writes one
+/// Rust accepts words like "infinity" too, which are far more likely to be text
+fn parse_float(text: &str) -> Option<f32> {
+    let numeric = text
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+    if numeric || matches!(text, "NaN" | "inf" | "-inf") {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// The most specific type every sampled value fits, where a quoted value is always text
+fn infer(values: &[&Field]) -> Kind {
+    let mut kind = Kind::Bool;
+    for field in values
+        .iter()
+        .filter(|field| !field.is_missing())
+        .take(SAMPLE_ROWS)
+    {
+        if field.quoted {
+            return Kind::Str;
+        }
+        if !kind.accepts(&field.text) {
+            kind = kind.widen(&field.text);
+        }
+    }
+    // A column with nothing to go on holds whatever text it's given
+    if values.iter().all(|field| field.is_missing()) {
+        Kind::Str
+    } else {
+        kind
+    }
+}
+
+/// Converts the values of a column, or gives the row of the first one that isn't a `kind`
+fn convert(values: &[&Field], kind: Kind) -> Result<Column, usize> {
+    let validity: Option<Bitmap> = values
+        .iter()
+        .any(|field| field.is_missing())
+        .then(|| values.iter().map(|field| !field.is_missing()).collect());
+
+    macro_rules! parse {
+        ($variant:ident, $parse:expr) => {
+            ColumnData::$variant(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(row, field)| {
+                        if field.is_missing() {
+                            Ok(Default::default())
+                        } else {
+                            $parse(&field.text).ok_or(row)
+                        }
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+        };
+    }
+
+    let data = match kind {
+        Kind::Bool => parse!(Bool, |text: &str| match text {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }),
+        Kind::Int => parse!(Int, |text: &str| text.parse().ok()),
+        Kind::Float => parse!(Float, parse_float),
+        Kind::Str => parse!(Str, |text: &str| Some(text.to_string())),
+    };
+    Ok(Column { data, validity })
+}
+
+/// Guesses whether the first row names the columns
+/// It does if its values are all there and different, and either every column is text or some
+/// value in it doesn't fit the type of the rest of its column
+fn detect_header(first: &Record, body: &[Record]) -> bool {
+    let present = first.fields.iter().all(|field| !field.is_missing());
+    let distinct = first.fields.iter().enumerate().all(|(i, field)| {
+        first.fields[..i]
+            .iter()
+            .all(|other| other.text != field.text)
+    });
+    if !present || !distinct {
+        return false;
+    }
+
+    let kinds: Vec<Kind> = (0..first.fields.len())
+        .map(|i| {
+            let values: Vec<&Field> = body.iter().filter_map(|r| r.fields.get(i)).collect();
+            infer(&values)
+        })
+        .collect();
+    kinds.iter().all(|kind| *kind == Kind::Str)
+        || first
+            .fields
+            .iter()
+            .zip(&kinds)
+            .any(|(field, kind)| field.quoted || !kind.accepts(&field.text))
+}
+
+/// Reads a table, inferring the type of each column from its values
+pub fn read(text: &str, options: &Options) -> Result<Table, Vec<Problem>> {
+    let records = split(text, options).map_err(|problem| vec![problem])?;
+    let Some(first) = records.first() else {
+        return Ok(Table::default());
+    };
+    let header = match options.header {
+        Header::Present => true,
+        Header::Absent => false,
+        Header::Auto => detect_header(first, &records[1..]),
+    };
+    let (names, body) = names(&records, header)?;
+    check_widths(body, names.len())?;
+
+    let mut columns = Vec::with_capacity(names.len());
+    for (i, name) in names.into_iter().enumerate() {
+        let values: Vec<&Field> = body.iter().map(|record| &record.fields[i]).collect();
+        let mut kind = infer(&values);
+        let column = loop {
+            match convert(&values, kind) {
+                Ok(column) => break column,
+                Err(row) => kind = kind.widen(&values[row].text),
+            }
+        };
+        columns.push((name, Rc::new(column)));
+    }
+    Table::new(columns).map_err(|err| {
+        vec![Problem {
+            line: 1,
+            column: None,
+            message: err.to_string(),
+        }]
+    })
+}
+
+/// The types a schema can give a column
+pub const TYPES: [&str; 4] = ["int", "float", "str", "bool"];
+
+/// Reads a table whose columns have to have the names and types in `schema`
+/// Every value that doesn't fit is reported, not just the first
+pub fn read_strict(
+    text: &str,
+    options: &Options,
+    schema: &[(String, String)],
+) -> Result<Table, Vec<Problem>> {
+    let mut problems = Vec::new();
+    // Anything else has already been turned away as an invalid argument
+    let kinds: Vec<Kind> = schema
+        .iter()
+        .map(|(_, typ)| Kind::from_name(typ).unwrap_or(Kind::Str))
+        .collect();
+
+    let records = split(text, options).map_err(|problem| vec![problem])?;
+    let header = match (options.header, records.first()) {
+        (_, None) => false,
+        (Header::Present, _) => true,
+        (Header::Absent, _) => false,
+        (Header::Auto, Some(first)) => {
+            first.fields.len() == schema.len()
+                && first
+                    .fields
+                    .iter()
+                    .zip(schema)
+                    .all(|(field, (name, _))| field.text == *name)
+        }
+    };
+    let body = if header {
+        let first = &records[0];
+        for (i, (name, _)) in schema.iter().enumerate() {
+            match first.fields.get(i) {
+                Some(field) if field.text == *name => {}
+                found => problems.push(Problem {
+                    line: first.line,
+                    column: Some(i + 1),
+                    message: match found {
+                        Some(field) => {
+                            format!("expected the column '{}' but found '{}'", name, field.text)
+                        }
+                        None => format!("expected the column '{}' but the header ends", name),
+                    },
+                }),
+            }
+        }
+        &records[1..]
+    } else {
+        &records[..]
+    };
+    if let Err(mut widths) = check_widths(body, schema.len()) {
+        problems.append(&mut widths);
+        return Err(problems);
+    }
+
+    let mut columns = Vec::with_capacity(schema.len());
+    for (i, ((name, _), kind)) in schema.iter().zip(&kinds).enumerate() {
+        let values: Vec<&Field> = body.iter().map(|record| &record.fields[i]).collect();
+        // Quoting doesn't matter here, the schema already says what every value is
+        for (record, field) in body.iter().zip(&values) {
+            if !field.is_missing() && !kind.accepts(&field.text) {
+                problems.push(Problem {
+                    line: record.line,
+                    column: Some(i + 1),
+                    message: format!("'{}' is {}, but found {:?}", name, kind.name(), field.text),
+                });
+            }
+        }
+        if let Ok(column) = convert(&values, *kind) {
+            columns.push((name.clone(), Rc::new(column)));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    Table::new(columns).map_err(|err| {
+        vec![Problem {
+            line: 1,
+            column: None,
+            message: err.to_string(),
+        }]
+    })
+}
+
+/// The column names, from the header or made up, and the records after the header
+fn names(records: &[Record], header: bool) -> Result<(Vec<String>, &[Record]), Vec<Problem>> {
+    if !header {
+        let width = records.first().map_or(0, |record| record.fields.len());
+        let names = (1..=width).map(|i| format!("column{}", i)).collect();
+        return Ok((names, records));
+    }
+
+    let first = &records[0];
+    let mut names: Vec<String> = Vec::with_capacity(first.fields.len());
+    for (i, field) in first.fields.iter().enumerate() {
+        if names.contains(&field.text) {
+            return Err(vec![Problem {
+                line: first.line,
+                column: Some(i + 1),
+                message: format!("there are two columns named '{}'", field.text),
+            }]);
+        }
+        names.push(field.text.clone());
+    }
+    Ok((names, &records[1..]))
+}
+
+/// Makes sure every record has one field for each column
+fn check_widths(records: &[Record], width: usize) -> Result<(), Vec<Problem>> {
+    let problems: Vec<Problem> = records
+        .iter()
+        .filter(|record| record.fields.len() != width)
+        .map(|record| Problem {
+            line: record.line,
+            column: None,
+            message: format!(
+                "has {} field{} but there {} {} column{}",
+                record.fields.len(),
+                if record.fields.len() == 1 { "" } else { "s" },
+                if width == 1 { "is" } else { "are" },
+                width,
+                if width == 1 { "" } else { "s" },
+            ),
+        })
+        .collect();
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// Writes a table with a header, quoting every name and string so that `read` can tell them
+/// apart from numbers
+pub fn write(table: &Table, options: &Options) -> String {
+    let (delimiter, quote) = (options.delimiter, options.quote);
+    let quoted = |text: &str| {
+        let doubled = text.replace(quote, &format!("{}{}", quote, quote));
+        format!("{}{}{}", quote, doubled, quote)
+    };
+    let mut out = String::new();
+    if table.width() == 0 {
+        return out;
+    }
+
+    let names: Vec<String> = table.names().map(quoted).collect();
+    out.push_str(&names.join(&delimiter.to_string()));
+    out.push('\n');
+    for row in 0..table.rows() {
+        for (i, (_, column)) in table.columns().iter().enumerate() {
+            if i > 0 {
+                out.push(delimiter);
+            }
+            if !column.is_valid(row) {
+                continue;
+            }
+            match &column.data {
+                ColumnData::Str(values) => out.push_str(&quoted(&values[row])),
+                _ => out.push_str(&column.get(row).to_string()),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{rows, table};
+
+    fn schema(columns: &[(&str, &str)]) -> Vec<(String, String)> {
+        columns
+            .iter()
+            .map(|(name, typ)| (name.to_string(), typ.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn inference() {
+        let t = table("id,score,name,ok\n1,2.5,ann,true\n2,3,\"bo, b\",false\n");
+        assert_eq!(
+            rows(&t),
+            ["id score name ok", "1 2.5 ann true", "2 3.0 bo, b false"]
+        );
+        assert_eq!(
+            rows(&t.schema()),
+            ["name type", "id int", "score float", "name str", "ok bool"]
+        );
+    }
+
+    #[test]
+    fn quoting_and_missing_values() {
+        let t = table("a,b\n\"x\"\"y\",\n\"line\nbreak\",\"\"\n");
+        assert_eq!(t.column("a").unwrap().get(0).to_string(), "x\"y");
+        assert_eq!(t.column("a").unwrap().get(1).to_string(), "line\nbreak");
+        // Unquoted and empty is missing, quoted and empty is an empty string
+        assert_eq!(t.column("b").unwrap().get(0).to_string(), "NA");
+        assert_eq!(t.column("b").unwrap().get(1).to_string(), "");
+        // A quoted number is text
+        assert_eq!(
+            table("n\n\"1\"\n2\n").column("n").unwrap().type_name(),
+            "str"
+        );
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(rows(&table("1,2\n3,4\n"))[0], "column1 column2");
+        let options = Options {
+            header: Header::Present,
+            delimiter: ';',
+            ..Options::default()
+        };
+        assert_eq!(rows(&read("1;2\n3;4\n", &options).unwrap())[0], "1 2");
+    }
+
+    #[test]
+    fn widening_past_the_sample() {
+        let mut text = String::from("n\n");
+        for i in 0..SAMPLE_ROWS {
+            text.push_str(&format!("{}\n", i));
+        }
+        text.push_str("0.5\n");
+        assert_eq!(table(&text).column("n").unwrap().type_name(), "float");
+    }
+
+    #[test]
+    fn problems() {
+        let problems = read("a,b\n1,2\n3\n", &Options::default()).unwrap_err();
+        assert_eq!(problems[0].line, 3);
+        let problems = read("a\n\"open\n", &Options::default()).unwrap_err();
+        assert_eq!(problems[0].line, 2);
+    }
+
+    #[test]
+    fn strict_schemas() {
+        let columns = schema(&[("a", "int"), ("b", "bool")]);
+        let t = read_strict("a,b\n1,true\n,false\n", &Options::default(), &columns).unwrap();
+        assert_eq!(rows(&t), ["a b", "1 true", "NA false"]);
+        assert_eq!(t.column("a").unwrap().type_name(), "int");
+
+        // Every value that doesn't fit is reported
+        let problems =
+            read_strict("a,b\nx,true\n2,maybe\n", &Options::default(), &columns).unwrap_err();
+        let at: Vec<_> = problems.iter().map(|p| (p.line, p.column)).collect();
+        assert_eq!(at, [(2, Some(1)), (3, Some(2))]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let t = table("s,n\n\"1\",1\n\"a,\"\"b\"\"\",\n");
+        let written = write(&t, &Options::default());
+        let read_back = table(&written);
+        assert_eq!(rows(&read_back), rows(&t));
+        assert_eq!(rows(&read_back.schema()), rows(&t.schema()));
+    }
+}
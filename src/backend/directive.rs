@@ -0,0 +1,198 @@
+use crate::{
+    errors::error::ErrorKind,
+    frontend::expr::{Expr, ExprKind, Span},
+};
+
+use super::eval::qualified_name;
+
+/// How float results that are NaN or infinite are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFinite {
+    /// They're values like any other, and carry on through whatever uses them
+    #[default]
+    Propagate,
+    /// Producing one is an error at the operation or call that did
+    Error,
+}
+
+/// How missing values in series and tables are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Missing {
+    /// They're values like any other, and carry on through whatever uses them
+    #[default]
+    Propagate,
+    /// Producing one, or a series or table with one, is an error at the operation or call that
+    /// did, so strict scripts can rely on having every value
+    Error,
+}
+
+/// Everything that directives can change about how a program runs
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub nonfinite: NonFinite,
+    pub missing: Missing,
+    /// The seed `std.random` was last given, if it was
+    pub seed: Option<u64>,
+}
+
+/// A directive whose name and arguments have been checked
+#[derive(Debug, Clone, Copy)]
+pub enum Directive {
+    /// `directive nonfinite("propagate" | "error");`
+    NonFinite(NonFinite),
+    /// `directive missing("propagate" | "error");`
+    Missing(Missing),
+    /// `directive seed(n);`, which makes `std.random` draw the same numbers on every run
+    Seed(u64),
+}
+
+impl Settings {
+    pub fn apply(&mut self, directive: Directive) {
+        match directive {
+            Directive::NonFinite(mode) => self.nonfinite = mode,
+            Directive::Missing(mode) => self.missing = mode,
+            Directive::Seed(seed) => self.seed = Some(seed),
+        }
+    }
+}
+
+/// Reads a `directive name(args);` statement
+/// Arguments have to be literals, so a directive can be checked without running anything
+pub fn parse(expr: &Expr) -> Result<Directive, (ErrorKind, Span)> {
+    let (name, args) = match &expr.0 {
+        ExprKind::FunctionCall(name, args) => match &args.0 {
+            ExprKind::FunctionArgs(args) => (name.as_ref(), args.as_slice()),
+            _ => (name.as_ref(), &[][..]),
+        },
+        _ => (expr, &[][..]),
+    };
+    let directive = qualified_name(name);
+    let invalid = |message: &str, span: Span| {
+        (
+            ErrorKind::InvalidDirective {
+                name: directive.clone(),
+                message: message.to_string(),
+            },
+            span,
+        )
+    };
+
+    match directive.as_str() {
+        "nonfinite" => match args {
+            [arg] => match &arg.0 {
+                ExprKind::Str(mode) if mode == "propagate" => {
+                    Ok(Directive::NonFinite(NonFinite::Propagate))
+                }
+                ExprKind::Str(mode) if mode == "error" => {
+                    Ok(Directive::NonFinite(NonFinite::Error))
+                }
+                _ => Err(invalid("expected \"propagate\" or \"error\"", arg.1)),
+            },
+            _ => Err(invalid(
+                "takes one argument, \"propagate\" or \"error\"",
+                expr.1,
+            )),
+        },
+        "missing" => match args {
+            [arg] => match &arg.0 {
+                ExprKind::Str(mode) if mode == "propagate" => {
+                    Ok(Directive::Missing(Missing::Propagate))
+                }
+                ExprKind::Str(mode) if mode == "error" => Ok(Directive::Missing(Missing::Error)),
+                _ => Err(invalid("expected \"propagate\" or \"error\"", arg.1)),
+            },
+            _ => Err(invalid(
+                "takes one argument, \"propagate\" or \"error\"",
+                expr.1,
+            )),
+        },
+        "seed" => match args {
+            [arg] => match &arg.0 {
+                ExprKind::Integer(seed) => Ok(Directive::Seed(*seed as u64)),
+                _ => Err(invalid("expected an integer literal", arg.1)),
+            },
+            _ => Err(invalid("takes one argument, an integer", expr.1)),
+        },
+        _ => Err((ErrorKind::UnknownDirective { name: directive }, name.1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::evaluate;
+
+    #[test]
+    fn nonfinite() {
+        let sqrt = "from std.math import sqrt;\n";
+        assert_eq!(evaluate(&format!("{}sqrt(0 - 1);", sqrt)).unwrap(), "NaN");
+        assert_eq!(
+            evaluate(&format!(
+                "{}directive nonfinite(\"error\");\nsqrt(0 - 1);",
+                sqrt
+            ))
+            .unwrap_err(),
+            "SK0402: Trying to call 'sqrt' gave NaN, which isn't a finite number"
+        );
+        assert_eq!(
+            evaluate("directive nonfinite(\"error\");\n1.0 / 0.0;").unwrap_err(),
+            "SK0402: Trying to divide gave inf, which isn't a finite number"
+        );
+        assert_eq!(
+            evaluate(
+                "directive nonfinite(\"error\");\ndirective nonfinite(\"propagate\");\n1.0 / 0.0;"
+            )
+            .unwrap(),
+            "inf"
+        );
+    }
+
+    #[test]
+    fn missing() {
+        let missing = |source: &str| {
+            let setup = "from std.table import *;\nvar s = series([1, na(\"int\"), 3]);\n";
+            evaluate(&format!("{}{}", setup, source))
+        };
+        assert_eq!(missing("s + 1;").unwrap(), "series<int>[2, NA, 4]");
+        assert_eq!(
+            missing("directive missing(\"error\");\ns + 1;").unwrap_err(),
+            "SK0404: Trying to add gave a missing value in row 1"
+        );
+        assert_eq!(
+            missing("directive missing(\"error\");\nna(\"int\");").unwrap_err(),
+            "SK0404: Trying to call 'na' gave a missing value"
+        );
+        assert_eq!(
+            missing("directive missing(\"error\");\ntable(\"x\", s).select(\"x\");").unwrap_err(),
+            "SK0404: Trying to call 'table' gave a missing value in column 'x' at row 1"
+        );
+        // Values filled in before they're used are fine
+        assert_eq!(
+            missing("directive missing(\"error\");\ns.fill_na(0) + 1;").unwrap(),
+            "series<int>[2, 1, 4]"
+        );
+        assert_eq!(
+            missing("directive missing(\"error\");\ndirective missing(\"propagate\");\ns + 1;")
+                .unwrap(),
+            "series<int>[2, NA, 4]"
+        );
+    }
+
+    #[test]
+    fn invalid_directives() {
+        assert_eq!(
+            evaluate("directive speed(1);").unwrap_err(),
+            "SK0903: No directive named 'speed'"
+        );
+        assert_eq!(
+            evaluate("directive nonfinite(\"loud\");").unwrap_err(),
+            "SK0904: Invalid 'nonfinite' directive: expected \"propagate\" or \"error\""
+        );
+        assert_eq!(
+            evaluate("directive missing(\"loud\");").unwrap_err(),
+            "SK0904: Invalid 'missing' directive: expected \"propagate\" or \"error\""
+        );
+        assert!(evaluate("directive nonfinite();")
+            .unwrap_err()
+            .contains("takes one argument"));
+    }
+}
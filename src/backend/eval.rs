@@ -1,31 +1,1238 @@
-use std::{cell::RefCell, collections::HashMap, iter::Peekable};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::frontend::expr::Expr;
+use crate::{
+    errors::{
+        error::{ErrorClass, ErrorKind, SkError},
+        suggest,
+        trace::Frame,
+    },
+    frontend::expr::{BinaryOperator, Expr, ExprKind, Span},
+};
 
-use super::{context::Context, value::Value};
+use super::{
+    context::Context,
+    directive::{self, Directive, Missing, NonFinite, Settings},
+    map::{Key, Map},
+    prelude,
+    random::Generator,
+    ops,
+    regex::Regex,
+    series,
+    stdlib,
+    table::ColumnData,
+    value::{Caller, Function, NativeError, NativeFunction, Type, Value},
+};
 
-pub struct Runtime<'r, Iter: Iterator<Item = Expr>> {
-    pub globals: HashMap<String, Value<'r>>,
-    pub contexts: Vec<Context<'r>>,
-    tree: Iter,
+/// How deep calls can go before the runtime gives up on them
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+pub type RuntimeResult<T> = Result<T, Box<SkError>>;
+
+/// Creates a runtime error pointing at `span`
+pub fn error(kind: ErrorKind, span: Span) -> Box<SkError> {
+    Box::new(SkError::new(ErrorClass::Error, kind, span.0, span.1))
+}
+
+/// What happens after a statement has been executed
+enum Flow {
+    Next,
+    Return(Type),
+}
+
+pub struct Runtime {
+    pub globals: HashMap<String, Value>,
+    /// What can be imported, by module name then symbol
+    pub modules: HashMap<String, HashMap<String, Type>>,
+    /// What can be called with `value.method(...)`, by the type of the value then method name
+    pub methods: HashMap<String, HashMap<String, Rc<NativeFunction>>>,
+    /// What the directives run so far have set
+    pub settings: Settings,
+    /// Pattern literals that have been compiled
+    patterns: HashMap<String, Rc<Regex>>,
+    /// What `std.random` draws from when it isn't given a generator
+    random: Rc<RefCell<Generator>>,
+    pub contexts: Vec<Context>,
+    /// Calls currently being evaluated, outermost first
+    calls: Vec<Frame>,
+    /// Name of the module the tree came from, recorded in each frame
+    module: String,
 }
 
-impl<'r, Iter: Iterator<Item = Expr>> Runtime<'r, Iter> {
-    pub fn initialize(tree: Iter) -> Self {
+impl Runtime {
+    /// Creates a runtime whose globals are the prelude's built-in functions, with the standard
+    /// library ready to import
+    pub fn initialize() -> Self {
+        let globals = prelude::functions()
+            .into_iter()
+            .map(|func| {
+                let value = Value {
+                    name: func.name.clone(),
+                    typ: Type::Native(Rc::new(func)),
+                    constant: true,
+                };
+                (value.name.clone(), value)
+            })
+            .collect();
+
+        let random = Rc::new(RefCell::new(Generator::from_entropy()));
         Self {
-            globals: HashMap::new(),
+            globals,
+            modules: stdlib::modules(&random)
+                .into_iter()
+                .map(|module| (module.name.to_string(), module.members))
+                .collect(),
+            settings: Settings::default(),
+            methods: methods(),
+            patterns: HashMap::new(),
+            random,
             contexts: Vec::new(),
-            tree
+            calls: Vec::new(),
+            module: String::from("main"),
+        }
+    }
+
+    /// Runs every statement in the tree, stopping at the first error
+    /// Globals are kept afterwards, so a program can be evaluated a piece at a time
+    /// Returns the value of the last statement if it was a bare expression
+    /// Errors that happen inside a call come back with the calls that led to them
+    pub fn evaluate(&mut self, tree: &[Expr]) -> RuntimeResult<Option<Type>> {
+        let mut last = None;
+        for e in tree {
+            let result = if is_statement(e) {
+                self.exec(e).map(|_| None)
+            } else {
+                self.eval_expr(e).map(Some)
+            };
+            match result {
+                Ok(value) => last = value,
+                Err(mut err) => {
+                    if err.trace.is_empty() {
+                        err.trace = std::mem::take(&mut self.calls);
+                    }
+                    self.calls.clear();
+                    self.contexts.clear();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(last)
+    }
+
+    /// Executes a single statement
+    fn exec(&mut self, expr: &Expr) -> RuntimeResult<Flow> {
+        match &expr.0 {
+            ExprKind::VariableExpr(name, typ, value) => {
+                self.exec_declaration(name, typ.as_deref(), value, false)
+            }
+            ExprKind::ConstExpr(name, typ, value) => {
+                self.exec_declaration(name, typ.as_deref(), value, true)
+            }
+            ExprKind::MutateExpr(name, value) => self.exec_mutation(name, value),
+            ExprKind::PrintExpr(value) => {
+                let value = self.eval_expr(value)?;
+                println!("{}", value);
+                Ok(Flow::Next)
+            }
+            ExprKind::FunctionExpr(name, params, returns, body) => {
+                self.exec_function(name, params, returns.as_deref(), body)
+            }
+            ExprKind::ReturnExpr(value) => {
+                if self.contexts.is_empty() {
+                    return Err(error(ErrorKind::ReturnOutsideFunction, expr.1));
+                }
+                Ok(Flow::Return(self.eval_expr(value)?))
+            }
+            ExprKind::ImportExpr(module, symbols) => self.exec_import(module, symbols),
+            ExprKind::Directive(directive) => {
+                let directive = directive::parse(directive).map_err(|(kind, at)| error(kind, at))?;
+                if let Directive::Seed(seed) = directive {
+                    self.random.borrow_mut().reseed(seed);
+                }
+                self.settings.apply(directive);
+                Ok(Flow::Next)
+            }
+            ExprKind::ClassExpr(_, _) | ExprKind::ClassMethods(_, _) => {
+                Err(error(ErrorKind::Unsupported { feature: "Classes" }, expr.1))
+            }
+            _ => {
+                self.eval_expr(expr)?;
+                Ok(Flow::Next)
+            }
+        }
+    }
+
+    fn exec_declaration(
+        &mut self,
+        name: &Expr,
+        typ: Option<&Expr>,
+        value: &Expr,
+        constant: bool,
+    ) -> RuntimeResult<Flow> {
+        let name_str = ident(name);
+        let value_t = self.eval_expr(value)?;
+        if let Some(typ) = typ {
+            check_annotation(typ, &value_t, value.1)?;
+        }
+
+        let scope = self.scope();
+        if scope.contains_key(&name_str) {
+            return Err(error(ErrorKind::Redefinition { name: name_str }, name.1));
+        }
+        scope.insert(
+            name_str.clone(),
+            Value {
+                typ: value_t,
+                name: name_str,
+                constant,
+            },
+        );
+        Ok(Flow::Next)
+    }
+
+    fn exec_mutation(&mut self, name: &Expr, value: &Expr) -> RuntimeResult<Flow> {
+        let name_str = ident(name);
+        let (old_type, constant) = match self.lookup(&name_str) {
+            Some(v) => (ops::element_type(&v.typ), v.constant),
+            None => return Err(error(ErrorKind::UndefinedName { name: name_str }, name.1)),
+        };
+        if constant {
+            return Err(error(
+                ErrorKind::ConstantMutation { name: name_str },
+                name.1,
+            ));
+        }
+
+        // Variables keep the type they were declared with, which a missing value has too
+        let value_t = self.eval_expr(value)?;
+        if ops::element_type(&value_t) != old_type {
+            return Err(error(
+                ErrorKind::TypeMismatch {
+                    expected: old_type.to_string(),
+                    found: ops::element_type(&value_t).to_string(),
+                },
+                value.1,
+            ));
+        }
+
+        let new = Value {
+            typ: value_t,
+            name: name_str.clone(),
+            constant,
+        };
+        let set = match self.contexts.last_mut() {
+            Some(ctx) if ctx.get(&name_str).is_some() => ctx.set(&name_str, new),
+            _ => self.globals.get_mut(&name_str).map(|v| *v = new),
+        };
+        debug_assert!(set.is_some());
+        Ok(Flow::Next)
+    }
+
+    fn exec_function(
+        &mut self,
+        name: &Expr,
+        params: &Expr,
+        returns: Option<&Expr>,
+        body: &Expr,
+    ) -> RuntimeResult<Flow> {
+        let name_str = ident(name);
+        let params = match &params.0 {
+            ExprKind::FunctionArgs(params) => params
+                .iter()
+                .map(|p| match &p.0 {
+                    ExprKind::Parameter(n, t) => (ident(n), Some(qualified_name(t))),
+                    _ => (ident(p), None),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let body = match &body.0 {
+            ExprKind::BlockExpr(body) => body.iter().map(|e| e.as_ref().clone()).collect(),
+            _ => Vec::new(),
+        };
+
+        let func = Function {
+            name: name_str.clone(),
+            params,
+            returns: returns.map(qualified_name),
+            body,
+        };
+
+        let scope = self.scope();
+        if scope.contains_key(&name_str) {
+            return Err(error(ErrorKind::Redefinition { name: name_str }, name.1));
+        }
+        scope.insert(
+            name_str.clone(),
+            Value {
+                typ: Type::Function(Rc::new(func)),
+                name: name_str,
+                constant: true,
+            },
+        );
+        Ok(Flow::Next)
+    }
+
+    /// Compiles a pattern literal, once however many times it's evaluated
+    fn eval_pattern(&mut self, pattern: &str, span: Span) -> RuntimeResult<Type> {
+        if let Some(regex) = self.patterns.get(pattern) {
+            return Ok(Type::Regex(regex.clone()));
+        }
+        let regex = Rc::new(Regex::new(pattern).map_err(|err| error(err.kind(), err.span_in(span)))?);
+        self.patterns.insert(pattern.to_string(), regex.clone());
+        Ok(Type::Regex(regex))
+    }
+
+    /// Binds the imported symbols as constants in the current scope
+    /// `*` imports everything the module has
+    fn exec_import(&mut self, module: &Expr, symbols: &Expr) -> RuntimeResult<Flow> {
+        let name = qualified_name(module);
+        let Some(exported) = self.modules.get(&name) else {
+            return Err(error(ErrorKind::UnknownModule { module: name }, module.1));
+        };
+
+        let mut imports = Vec::new();
+        if let ExprKind::ImportArgs(symbols) = &symbols.0 {
+            for symbol in symbols {
+                if let ExprKind::Wildcard = symbol.0 {
+                    let mut all: Vec<_> = exported.iter().collect();
+                    all.sort_by(|a, b| a.0.cmp(b.0));
+                    imports.extend(all.into_iter().map(|(s, v)| (s.clone(), v.clone(), symbol.1)));
+                    continue;
+                }
+                let s = ident(symbol);
+                match exported.get(&s) {
+                    Some(v) => imports.push((s, v.clone(), symbol.1)),
+                    None => {
+                        return Err(error(
+                            ErrorKind::UnknownImport {
+                                module: name,
+                                symbol: s,
+                            },
+                            symbol.1,
+                        ))
+                    }
+                }
+            }
+        }
+
+        let scope = self.scope();
+        for (s, typ, span) in imports {
+            if scope.contains_key(&s) {
+                return Err(error(ErrorKind::Redefinition { name: s }, span));
+            }
+            scope.insert(
+                s.clone(),
+                Value {
+                    typ,
+                    name: s,
+                    constant: true,
+                },
+            );
+        }
+        Ok(Flow::Next)
+    }
+
+    /// The innermost scope, where new names are declared
+    fn scope(&mut self) -> &mut HashMap<String, Value> {
+        match self.contexts.last_mut() {
+            Some(ctx) => &mut ctx.locals,
+            None => &mut self.globals,
+        }
+    }
+
+    /// Finds a name in the current function's context, then in the globals
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.contexts
+            .last()
+            .and_then(|ctx| ctx.get(name))
+            .or_else(|| self.globals.get(name))
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> RuntimeResult<Type> {
+        match &expr.0 {
+            ExprKind::Integer(i) => Ok(Type::Int(*i)),
+            ExprKind::Float(f) => Ok(Type::Float(*f)),
+            ExprKind::Str(s) => Ok(Type::Str(s.clone())),
+            ExprKind::Regex(pattern) => self.eval_pattern(pattern, expr.1),
+            ExprKind::Ident(name) => match self.lookup(name) {
+                Some(v) => Ok(v.typ.clone()),
+                None => Err(error(
+                    ErrorKind::UndefinedName { name: name.clone() },
+                    expr.1,
+                )),
+            },
+            ExprKind::ParensExpr(inner) => self.eval_expr(inner),
+            ExprKind::BinaryExpr(left, right, op) => {
+                let l = self.eval_expr(left)?;
+                let r = self.eval_expr(right)?;
+                let value = binary(l, r, *op).map_err(|kind| {
+                    let mut err = error(kind, expr.1);
+                    if let ErrorKind::LengthMismatch { left: l, right: r } = err.kind {
+                        err.label(left.1 .0, left.1 .1, format!("{} values", l))
+                            .label(right.1 .0, right.1 .1, format!("{} values", r));
+                    }
+                    err
+                })?;
+                self.check_result(value, || op.verb().to_string(), expr.1)
+            }
+            ExprKind::Negate(inner) => {
+                let value = self.eval_expr(inner)?;
+                let value = negate(value).map_err(|kind| error(kind, expr.1))?;
+                self.check_result(value, || String::from("negate"), expr.1)
+            }
+            ExprKind::FunctionCall(callee, args) => self.eval_call(callee, args, expr.1),
+            ExprKind::MapExpr(entries) => {
+                let mut map = Map::new();
+                for (key, value) in entries {
+                    let k = self.eval_expr(key)?;
+                    let k = Key::from_value(&k).ok_or_else(|| {
+                        error(
+                            ErrorKind::UnhashableKey {
+                                found: k.type_name().to_string(),
+                            },
+                            key.1,
+                        )
+                    })?;
+                    // A key written twice keeps its first place and its last value
+                    map.insert(k, self.eval_expr(value)?);
+                }
+                Ok(Type::Map(Rc::new(map)))
+            }
+            ExprKind::ListExpr(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(self.eval_expr(item)?);
+                }
+                Ok(Type::List(Rc::new(values)))
+            }
+            ExprKind::IndexExpr(value, index) => self.eval_index(value, index),
+            ExprKind::QualifiedIdent(_, _) => Err(error(
+                ErrorKind::Unsupported {
+                    feature: "Qualified names",
+                },
+                expr.1,
+            )),
+            ExprKind::QMark(_) => Err(error(
+                ErrorKind::Unsupported {
+                    feature: "'?' expressions",
+                },
+                expr.1,
+            )),
+            _ => Err(error(
+                ErrorKind::Unsupported {
+                    feature: "Statements in expression position",
+                },
+                expr.1,
+            )),
+        }
+    }
+
+    /// Looks a key up in a map, or an item up in a list by its position
+    fn eval_index(&mut self, value: &Expr, index: &Expr) -> RuntimeResult<Type> {
+        let container = self.eval_expr(value)?;
+        let i = self.eval_expr(index)?;
+        match (&container, &i) {
+            (Type::Map(map), _) => {
+                let key = Key::from_value(&i).ok_or_else(|| {
+                    error(
+                        ErrorKind::UnhashableKey {
+                            found: i.type_name().to_string(),
+                        },
+                        index.1,
+                    )
+                })?;
+                map.get(&key).cloned().ok_or_else(|| {
+                    let mut err = error(
+                        ErrorKind::MissingKey {
+                            key: key.to_string(),
+                        },
+                        index.1,
+                    );
+                    if let Key::Str(name) = &key {
+                        let keys = map.iter().filter_map(|(key, _)| match key {
+                            Key::Str(key) => Some(key.as_str()),
+                            _ => None,
+                        });
+                        if let Some(similar) = suggest::closest(name, keys) {
+                            err.help(format!("did you mean {:?}?", similar));
+                        }
+                    }
+                    err
+                })
+            }
+            (Type::Table(table), Type::Str(name)) => table
+                .column(name)
+                .map(|column| Type::Series(column.clone()))
+                .ok_or_else(|| {
+                    let mut err = error(
+                        ErrorKind::MissingColumn {
+                            column: name.clone(),
+                        },
+                        index.1,
+                    );
+                    if let Some(similar) = suggest::closest(name, table.names()) {
+                        err.help(format!("did you mean '{}'?", similar));
+                    }
+                    err
+                }),
+            (Type::Table(_), other) => Err(error(
+                ErrorKind::TypeMismatch {
+                    expected: String::from("str"),
+                    found: other.type_name().to_string(),
+                },
+                index.1,
+            )),
+            (Type::List(items), Type::Int(n)) => usize::try_from(*n)
+                .ok()
+                .and_then(|n| items.get(n))
+                .cloned()
+                .ok_or_else(|| {
+                    error(
+                        ErrorKind::IndexOutOfRange {
+                            index: *n,
+                            len: items.len(),
+                        },
+                        index.1,
+                    )
+                }),
+            (Type::List(_), other) => Err(error(
+                ErrorKind::TypeMismatch {
+                    expected: String::from("int"),
+                    found: other.type_name().to_string(),
+                },
+                index.1,
+            )),
+            (other, _) => Err(error(
+                ErrorKind::TypeMismatch {
+                    expected: String::from("map, list or table"),
+                    found: other.type_name().to_string(),
+                },
+                value.1,
+            )),
+        }
+    }
+
+    fn eval_call(&mut self, callee: &Expr, args: &Expr, span: Span) -> RuntimeResult<Type> {
+        let args = match &args.0 {
+            ExprKind::FunctionArgs(args) => args.as_slice(),
+            _ => &[],
+        };
+        if let ExprKind::QualifiedIdent(receiver, method) = &callee.0 {
+            return self.eval_method(receiver, method, args, span);
+        }
+        let func = self.eval_expr(callee)?;
+        if let Some((expected, at_most)) = arity(&func) {
+            if args.len() < expected || at_most.is_some_and(|most| args.len() > most) {
+                return Err(error(
+                    ErrorKind::ArgumentCount {
+                        name: callee_name(&func),
+                        expected,
+                        found: args.len(),
+                        at_most,
+                    },
+                    span,
+                ));
+            }
+        }
+
+        match func {
+            Type::Function(_) | Type::Native(_) => {
+                // Arguments are evaluated in the caller's context
+                let (values, spans) = self.eval_args(&func, Vec::new(), Vec::new(), args, span)?;
+                self.call_value(&func, values, &spans, span)
+            }
+            other => Err(error(
+                ErrorKind::NotCallable {
+                    found: other.type_name().to_string(),
+                },
+                callee.1,
+            )),
+        }
+    }
+
+    /// Calls a method, a native function found by the type of the value before the dot, which
+    /// is passed to it as the first argument
+    fn eval_method(
+        &mut self,
+        receiver: &Expr,
+        method: &Expr,
+        args: &[Box<Expr>],
+        span: Span,
+    ) -> RuntimeResult<Type> {
+        let value = self.eval_expr(receiver)?;
+        let name = ident(method);
+        let methods = self.methods.get(value.type_name());
+        let Some(func) = methods.and_then(|methods| methods.get(&name)).cloned() else {
+            let mut err = error(
+                ErrorKind::UnknownMethod {
+                    typ: value.type_name().to_string(),
+                    method: name.clone(),
+                },
+                method.1,
+            );
+            let known = methods.into_iter().flat_map(|methods| methods.keys());
+            if let Some(similar) = suggest::closest(&name, known.map(String::as_str)) {
+                err.suggest(
+                    method.1 .0,
+                    method.1 .1,
+                    similar,
+                    format!("did you mean '{}'?", similar),
+                );
+            }
+            return Err(err);
+        };
+
+        // The receiver doesn't count towards the arguments written in the parentheses
+        let at_most = func.rest.is_none().then_some(func.params.len() - 1);
+        if args.len() + 1 < func.required || at_most.is_some_and(|most| args.len() > most) {
+            return Err(error(
+                ErrorKind::ArgumentCount {
+                    name: format!("{}.{}", value.type_name(), func.name),
+                    expected: func.required - 1,
+                    found: args.len(),
+                    at_most,
+                },
+                span,
+            ));
+        }
+        let (values, spans) = self.eval_args(
+            &Type::Native(func.clone()),
+            vec![value],
+            vec![receiver.1],
+            args,
+            span,
+        )?;
+        self.call_native(&func, values, &spans, span)
+    }
+
+    /// Evaluates the arguments of a call to `func`, after any already in `values`, putting named
+    /// ones in the place of the parameter with that name
+    /// Named arguments can skip over optional parameters of a native function, which get nil
+    fn eval_args(
+        &mut self,
+        func: &Type,
+        mut values: Vec<Type>,
+        mut spans: Vec<Span>,
+        args: &[Box<Expr>],
+        span: Span,
+    ) -> RuntimeResult<(Vec<Type>, Vec<Span>)> {
+        // The parser makes sure positional arguments come first
+        let mut named: Vec<Option<(Type, Span)>> = Vec::new();
+        for arg in args {
+            let ExprKind::NamedArg(name, value) = &arg.0 else {
+                values.push(self.eval_expr(arg)?);
+                spans.push(arg.1);
+                continue;
+            };
+            let names: Vec<&str> = match func {
+                Type::Function(f) => f.params.iter().map(|(n, _)| n.as_str()).collect(),
+                Type::Native(f) => f.names.clone(),
+                _ => Vec::new(),
+            };
+            let label = ident(name);
+            let Some(index) = names.iter().position(|n| *n == label) else {
+                let mut err = error(
+                    ErrorKind::UnknownParameter {
+                        function: callee_name(func),
+                        name: label.clone(),
+                    },
+                    name.1,
+                );
+                if names.is_empty() {
+                    err.help(format!(
+                        "'{}' only takes its arguments in order",
+                        callee_name(func)
+                    ));
+                } else if let Some(similar) = suggest::closest(&label, names.iter().copied()) {
+                    err.suggest(
+                        name.1 .0,
+                        name.1 .1,
+                        similar,
+                        format!("did you mean '{}'?", similar),
+                    );
+                }
+                return Err(err);
+            };
+            if index < values.len() || named.get(index).is_some_and(Option::is_some) {
+                return Err(error(
+                    ErrorKind::InvalidArgument {
+                        function: callee_name(func),
+                        message: format!("'{}' was given more than once", label),
+                    },
+                    name.1,
+                ));
+            }
+            if named.len() <= index {
+                named.resize(index + 1, None);
+            }
+            named[index] = Some((self.eval_expr(value)?, arg.1));
+        }
+        // Only optional parameters can be skipped over
+        let required = match func {
+            Type::Native(f) => f.required,
+            _ => named.len(),
+        };
+        let skipped = (values.len()..required.min(named.len())).find(|&i| named[i].is_none());
+        if let Some(missing) = skipped {
+            let name = match func {
+                Type::Function(f) => f.params[missing].0.clone(),
+                Type::Native(f) => f.names[missing].to_string(),
+                _ => String::new(),
+            };
+            return Err(error(
+                ErrorKind::InvalidArgument {
+                    function: callee_name(func),
+                    message: format!("the argument '{}' is missing", name),
+                },
+                span,
+            ));
+        }
+        for slot in named.into_iter().skip(values.len()) {
+            let (value, at) = slot.unwrap_or((Type::Nil, span));
+            values.push(value);
+            spans.push(at);
+        }
+        Ok((values, spans))
+    }
+
+    /// Calls a function with arguments that have already been evaluated
+    /// `spans` are where each argument came from, and `span` is the whole call
+    fn call_value(
+        &mut self,
+        func: &Type,
+        values: Vec<Type>,
+        spans: &[Span],
+        span: Span,
+    ) -> RuntimeResult<Type> {
+        match func {
+            Type::Function(func) => self.call_function(func, values, spans, span),
+            Type::Native(func) => self.call_native(func, values, spans, span),
+            other => Err(error(
+                ErrorKind::NotCallable {
+                    found: other.type_name().to_string(),
+                },
+                span,
+            )),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        func: &Function,
+        values: Vec<Type>,
+        spans: &[Span],
+        span: Span,
+    ) -> RuntimeResult<Type> {
+        if values.len() != func.params.len() {
+            return Err(error(
+                ErrorKind::ArgumentCount {
+                    name: func.name.clone(),
+                    expected: func.params.len(),
+                    found: values.len(),
+                    at_most: Some(func.params.len()),
+                },
+                span,
+            ));
+        }
+
+        // Arguments are bound in a fresh context
+        let mut ctx = Context::new();
+        for ((value, (name, typ)), at) in values.into_iter().zip(&func.params).zip(spans) {
+            if let Some(typ) = typ {
+                check_type_name(typ, &value, *at)?;
+            }
+            ctx.store(
+                name.clone(),
+                Value {
+                    typ: value,
+                    name: name.clone(),
+                    constant: false,
+                },
+            );
+        }
+
+        self.calls.push(Frame {
+            function: func.name.clone(),
+            call_site: span.0..span.1 + 1,
+            module: self.module.clone(),
+        });
+        if self.calls.len() > MAX_CALL_DEPTH {
+            return Err(error(
+                ErrorKind::RecursionLimit {
+                    limit: MAX_CALL_DEPTH,
+                },
+                span,
+            ));
+        }
+        self.contexts.push(ctx);
+
+        let mut result = Type::Nil;
+        for statement in &func.body {
+            if let Flow::Return(value) = self.exec(statement)? {
+                result = value;
+                break;
+            }
         }
+        if let Some(typ) = &func.returns {
+            check_type_name(typ, &result, span)?;
+        }
+
+        self.contexts.pop();
+        self.calls.pop();
+        Ok(result)
     }
 
-    pub fn evaluate(&mut self) {
-        while let Some(e) = self.tree.next() {
-            self.eval_expr(&e);
+    /// Calls a function implemented in Rust, checking its arguments the same way a user
+    /// function's are checked
+    fn call_native(
+        &mut self,
+        func: &NativeFunction,
+        values: Vec<Type>,
+        spans: &[Span],
+        span: Span,
+    ) -> RuntimeResult<Type> {
+        let at_most = func.rest.is_none().then_some(func.params.len());
+        if values.len() < func.required || at_most.is_some_and(|most| values.len() > most) {
+            return Err(error(
+                ErrorKind::ArgumentCount {
+                    name: func.name.clone(),
+                    expected: func.required,
+                    found: values.len(),
+                    at_most,
+                },
+                span,
+            ));
         }
+        for (i, (value, at)) in values.iter().zip(spans).enumerate() {
+            let typ = func.params.get(i).copied().or(func.rest).unwrap_or("any");
+            // Optional parameters skipped over by a named argument are nil
+            let skipped =
+                i >= func.required && !func.names.is_empty() && matches!(value, Type::Nil);
+            if !skipped {
+                check_type_name(typ, value, *at)?;
+            }
+            // Natives only take missing values where they take anything, since the rest need
+            // an actual value to work with
+            if let (Type::Na(missing), false) = (value, matches!(typ, "any" | "na")) {
+                let mut err = error(
+                    ErrorKind::TypeMismatch {
+                        expected: typ.to_string(),
+                        found: value.type_name().to_string(),
+                    },
+                    *at,
+                );
+                err.help(format!(
+                    "this is a missing {}, which can be replaced with 'fill_na' or 'coalesce'",
+                    missing
+                ));
+                return Err(err);
+            }
+        }
+
+        let mut caller = Callback {
+            runtime: self,
+            span,
+        };
+        let value = (func.func)(&values, &mut caller).map_err(|err| {
+            if let Some(raised) = err.raised {
+                return raised;
+            }
+            let at = err
+                .argument
+                .and_then(|i| spans.get(i))
+                .map_or(span, |at| *at);
+            let mut e = error(err.kind, at);
+            for note in err.notes {
+                e.help(note);
+            }
+            e
+        })?;
+        self.check_result(value, || format!("call '{}'", func.name), span)
     }
 
-    fn eval_expr(&mut self, expr: &Expr) {
+    /// Lets the result of an operation or call through unless a directive says what's in it is
+    /// an error
+    fn check_result(
+        &self,
+        value: Type,
+        operation: impl Fn() -> String,
+        span: Span,
+    ) -> RuntimeResult<Type> {
+        let value = self.check_finite(value, &operation, span)?;
+        self.check_missing(value, &operation, span)
+    }
 
+    /// Lets a value through unless it's a float that isn't finite and the `nonfinite` directive
+    /// says that's an error
+    fn check_finite(
+        &self,
+        value: Type,
+        operation: impl FnOnce() -> String,
+        span: Span,
+    ) -> RuntimeResult<Type> {
+        match value {
+            _ if self.settings.nonfinite == NonFinite::Propagate => Ok(value),
+            Type::Float(x) if !x.is_finite() => Err(error(
+                ErrorKind::NonFinite {
+                    operation: operation(),
+                    value: x.to_string(),
+                },
+                span,
+            )),
+            Type::Series(ref column) => match &column.data {
+                ColumnData::Float(values) => {
+                    let bad = (0..values.len())
+                        .find(|&row| column.is_valid(row) && !values[row].is_finite());
+                    match bad {
+                        Some(row) => Err(error(
+                            ErrorKind::NonFinite {
+                                operation: operation(),
+                                value: format!("{} in row {}", values[row], row),
+                            },
+                            span,
+                        )),
+                        None => Ok(value),
+                    }
+                }
+                _ => Ok(value),
+            },
+            value => Ok(value),
+        }
     }
-}
\ No newline at end of file
+
+    /// Lets a value through unless it's missing or holds a missing value and the `missing`
+    /// directive says that's an error
+    fn check_missing(
+        &self,
+        value: Type,
+        operation: impl FnOnce() -> String,
+        span: Span,
+    ) -> RuntimeResult<Type> {
+        if self.settings.missing == Missing::Propagate {
+            return Ok(value);
+        }
+        let location = match &value {
+            Type::Na(_) => Some(None),
+            Type::Series(column) => column
+                .first_missing()
+                .map(|row| Some(format!("row {}", row))),
+            Type::Table(table) => table.columns().iter().find_map(|(name, column)| {
+                let row = column.first_missing()?;
+                Some(Some(format!("column '{}' at row {}", name, row)))
+            }),
+            _ => None,
+        };
+        match location {
+            Some(location) => Err(error(
+                ErrorKind::MissingResult {
+                    operation: operation(),
+                    location,
+                },
+                span,
+            )),
+            None => Ok(value),
+        }
+    }
+}
+
+/// The methods of the standard library, each filed under the type of its first parameter
+fn methods() -> HashMap<String, HashMap<String, Rc<NativeFunction>>> {
+    let mut methods: HashMap<String, HashMap<String, Rc<NativeFunction>>> = HashMap::new();
+    for func in stdlib::methods() {
+        methods
+            .entry(func.params[0].to_string())
+            .or_default()
+            .insert(func.name.clone(), Rc::new(func));
+    }
+    methods
+}
+
+/// Lets a native function call the functions it was handed, as if from the call to it at `span`
+struct Callback<'r> {
+    runtime: &'r mut Runtime,
+    span: Span,
+}
+
+impl Caller for Callback<'_> {
+    fn call(&mut self, func: &Type, args: Vec<Type>) -> Result<Type, NativeError> {
+        let spans = vec![self.span; args.len()];
+        self.runtime
+            .call_value(func, args, &spans, self.span)
+            .map_err(NativeError::raised)
+    }
+}
+
+/// How many arguments a function takes, at least and at most, or `None` if it isn't one
+fn arity(func: &Type) -> Option<(usize, Option<usize>)> {
+    match func {
+        Type::Function(func) => Some((func.params.len(), Some(func.params.len()))),
+        Type::Native(func) => Some((
+            func.required,
+            func.rest.is_none().then_some(func.params.len()),
+        )),
+        _ => None,
+    }
+}
+
+fn callee_name(func: &Type) -> String {
+    match func {
+        Type::Function(func) => func.name.clone(),
+        Type::Native(func) => func.name.clone(),
+        other => other.type_name().to_string(),
+    }
+}
+
+/// True for nodes that do something rather than produce a value
+fn is_statement(expr: &Expr) -> bool {
+    matches!(
+        expr.0,
+        ExprKind::VariableExpr(..)
+            | ExprKind::ConstExpr(..)
+            | ExprKind::MutateExpr(..)
+            | ExprKind::PrintExpr(..)
+            | ExprKind::FunctionExpr(..)
+            | ExprKind::ReturnExpr(..)
+            | ExprKind::ImportExpr(..)
+            | ExprKind::Directive(..)
+            | ExprKind::ClassExpr(..)
+            | ExprKind::ClassMethods(..)
+    )
+}
+
+/// The name held by an identifier node
+fn ident(expr: &Expr) -> String {
+    match &expr.0 {
+        ExprKind::Ident(name) => name.clone(),
+        _ => qualified_name(expr),
+    }
+}
+
+/// Turns `a.b.c` back into a dotted name
+pub(crate) fn qualified_name(expr: &Expr) -> String {
+    match &expr.0 {
+        ExprKind::Ident(name) => name.clone(),
+        ExprKind::QualifiedIdent(left, right) => {
+            format!("{}.{}", qualified_name(left), qualified_name(right))
+        }
+        _ => String::from("?"),
+    }
+}
+
+fn check_annotation(typ: &Expr, value: &Type, span: Span) -> RuntimeResult<()> {
+    let name = qualified_name(typ);
+    match value.matches_annotation(&name) {
+        None => Err(error(ErrorKind::UndefinedName { name }, typ.1)),
+        Some(_) => check_type_name(&name, value, span),
+    }
+}
+
+fn check_type_name(name: &str, value: &Type, span: Span) -> RuntimeResult<()> {
+    match value.matches_annotation(name) {
+        Some(true) => Ok(()),
+        Some(false) => Err(error(
+            ErrorKind::TypeMismatch {
+                expected: name.to_string(),
+                found: value.type_name().to_string(),
+            },
+            span,
+        )),
+        None => Err(error(
+            ErrorKind::UndefinedName {
+                name: name.to_string(),
+            },
+            span,
+        )),
+    }
+}
+
+/// Applies an operator, value by value if either side is a series
+fn binary(l: Type, r: Type, op: BinaryOperator) -> Result<Type, ErrorKind> {
+    match (&l, &r) {
+        (Type::Series(_), _) | (_, Type::Series(_)) => {
+            series::binary(&l, &r, op).map(|column| Type::Series(Rc::new(column)))
+        }
+        _ => ops::scalar(l, r, op),
+    }
+}
+
+/// Flips the sign of a number, or of each number in a series, by multiplying it by -1 of its own
+/// type, which keeps NA missing and turns 0.0 into -0.0
+fn negate(value: Type) -> Result<Type, ErrorKind> {
+    let minus_one = match series::element_type(&value) {
+        "int" => Type::Int(-1),
+        "float" => Type::Float(-1.0),
+        other => {
+            return Err(ErrorKind::TypeMismatch {
+                expected: String::from("int or float"),
+                found: other.to_string(),
+            })
+        }
+    };
+    binary(value, minus_one, BinaryOperator::Multiply).map_err(|kind| match kind {
+        ErrorKind::Overflow { .. } => ErrorKind::Overflow {
+            operation: "negate",
+        },
+        kind => kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        errors::{error::Errors, formatter::Formatter},
+        frontend::{lexer::Lexer, parser::Parser},
+    };
+
+    /// Runs `source` on a thread with as much stack as the binary gives the evaluator
+    /// Gives back the globals as they were written, or the error that stopped it
+    fn run(source: &str) -> RuntimeResult<HashMap<String, String>> {
+        let source = source.to_string();
+        std::thread::Builder::new()
+            .stack_size(256 * 1024 * 1024)
+            .spawn(move || {
+                let formatter = Formatter::initialize("test", &source);
+                let mut errors = Errors::initialize(&formatter);
+                let mut lexer = Lexer::new(&source);
+                let tokens = lexer.tokenize(&mut errors);
+                let mut parser = Parser::new(&mut errors, tokens.iter());
+                parser.parse();
+                let tree = std::mem::take(&mut parser.tree);
+                assert!(!errors.has_errors(), "{:?}", errors.errors());
+
+                let mut runtime = Runtime::initialize();
+                runtime.evaluate(&tree)?;
+                Ok(runtime
+                    .globals
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.typ.to_string()))
+                    .collect())
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn global(source: &str, name: &str) -> String {
+        run(source).unwrap().remove(name).unwrap()
+    }
+
+    fn fail(source: &str) -> Box<SkError> {
+        run(source).err().unwrap()
+    }
+
+    #[test]
+    fn functions_and_scopes() {
+        let source = "
+            def square(x: int): int { return x * x; }
+            def sum_squares(a: int, b: int): int {
+                var total = square(a);
+                total -> total + (b * b);
+                return total;
+            }
+            var r = sum_squares(3, 4);
+            var s = \"a\" + \"b\";
+        ";
+        assert_eq!(global(source, "r"), "25");
+        assert_eq!(global(source, "s"), "ab");
+        // Locals don't leak out of the call
+        assert!(!run(source).unwrap().contains_key("total"));
+    }
+
+    #[test]
+    fn runtime_errors() {
+        assert!(matches!(fail("$ 1 / 0;").kind, ErrorKind::DivisionByZero));
+        assert!(matches!(
+            fail("$ 2147483647 + 1;").kind,
+            ErrorKind::Overflow { operation: "add" }
+        ));
+        assert!(matches!(
+            fail("const c = 1;\nc -> 2;").kind,
+            ErrorKind::ConstantMutation { .. }
+        ));
+        assert!(matches!(
+            fail("var v = 1;\nv -> \"x\";").kind,
+            ErrorKind::TypeMismatch { .. }
+        ));
+        assert!(matches!(
+            fail("def f(x: int) { return x; }\n$ f(1, 2);").kind,
+            ErrorKind::ArgumentCount {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            fail("return 1;").kind,
+            ErrorKind::ReturnOutsideFunction
+        ));
+    }
+
+    #[test]
+    fn traces_record_each_call() {
+        let source = "def inner(x: int): int { return x / 0; }\n\
+                      def outer(x: int): int { return inner(x); }\n\
+                      $ outer(1);";
+        let err = fail(source);
+        assert!(matches!(err.kind, ErrorKind::DivisionByZero));
+        let calls: Vec<_> = err
+            .trace
+            .iter()
+            .map(|f| (f.function.as_str(), &source[f.call_site.clone()]))
+            .collect();
+        assert_eq!(calls, [("outer", "outer(1)"), ("inner", "inner(x)")]);
+        assert!(err.trace.iter().all(|f| f.module == "main"));
+
+        // Errors outside of any call have nothing to trace
+        assert!(fail("$ 1 / 0;").trace.is_empty());
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let err = fail("def down(n: int): int { return down(n + 1); }\n$ down(0);");
+        assert!(matches!(
+            err.kind,
+            ErrorKind::RecursionLimit {
+                limit: MAX_CALL_DEPTH
+            }
+        ));
+        assert_eq!(err.trace.len(), MAX_CALL_DEPTH + 1);
+    }
+
+    #[test]
+    fn list_literals_and_named_arguments() {
+        assert_eq!(global("var l = [1, [2], \"a\",];", "l"), "[1, [2], \"a\"]");
+        assert_eq!(global("var l = [];", "l"), "[]");
+        let source = "def f(a: int, b: int): int { return a - b; }\nvar r = f(b: 1, a: 3);";
+        assert_eq!(global(source, "r"), "2");
+        assert!(matches!(
+            fail("def f(a: int, b: int): int { return a; }\n$ f(1, a: 2);").kind,
+            ErrorKind::InvalidArgument { .. }
+        ));
+        let err = fail("def f(a: int, b: int): int { return a; }\n$ f(1, c: 2);");
+        assert!(matches!(err.kind, ErrorKind::UnknownParameter { .. }));
+    }
+
+    #[test]
+    fn negation() {
+        use crate::engine::evaluate;
+
+        assert_eq!(evaluate("var a = 2;\n-a * 3;").unwrap(), "-6");
+        assert_eq!(evaluate("var x = 0.0;\n-x;").unwrap(), "-0.0");
+        assert_eq!(evaluate("max(1, -2);").unwrap(), "1");
+        assert_eq!(evaluate("abs(-3);").unwrap(), "3");
+        assert_eq!(evaluate("round(-2.5);").unwrap(), "-3");
+        assert_eq!(
+            evaluate("from std.table import series;\n-series([1, 2]);").unwrap(),
+            "series<int>[-1, -2]"
+        );
+        assert_eq!(
+            evaluate("var a = -2147483648;\n-a;").unwrap_err(),
+            "SK0401: Arithmetic overflow while trying to negate"
+        );
+        assert_eq!(
+            evaluate("-\"a\";").unwrap_err(),
+            "SK0200: Mismatched types, expected int or float but found str"
+        );
+    }
+}
@@ -0,0 +1,471 @@
+//! Grouping the rows of a table by the values in some of its columns, and aggregating each group
+//!
+//! Groups are numbered in the order their first row appears, so the same table always groups the
+//! same way. Missing values in key columns form a group of their own.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+};
+
+use super::{
+    stdlib::math::neumaier_sum,
+    table::{Column, ColumnData, Table, TableError},
+    value::Type,
+};
+
+/// The rows of a table split up by the values in its key columns
+#[derive(Debug, Clone)]
+pub struct Groups {
+    table: Rc<Table>,
+    keys: Vec<String>,
+    /// The rows in each group, in the order they appear in the table
+    groups: Vec<Vec<usize>>,
+}
+
+/// A built-in way of reducing a group's values in one column to a single value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Sum,
+    Mean,
+    Min,
+    Max,
+    /// The number of values, not counting missing ones
+    Count,
+    CountDistinct,
+    Median,
+    /// The value below which this fraction of the values fall, between 0 and 1
+    Quantile(f64),
+    /// The sample standard deviation
+    Std,
+    /// The sample variance
+    Var,
+}
+
+/// What an aggregate does with missing values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Missing {
+    /// Leaves them out, as if the group didn't have those rows
+    Skip,
+    /// Makes the result missing for any group that has one
+    Propagate,
+    /// Refuses to aggregate at all
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggregateError {
+    /// The aggregate only works on numbers
+    NotNumeric {
+        aggregate: &'static str,
+        found: &'static str,
+    },
+    /// A missing value under `Missing::Error`
+    Missing { row: usize },
+    /// A sum or count too large for an int
+    Overflow { aggregate: &'static str },
+}
+
+impl Groups {
+    /// Groups the rows of `table` by the values in the `keys` columns together
+    pub fn new(table: Rc<Table>, keys: &[&str]) -> Result<Self, TableError> {
+        let columns = keys
+            .iter()
+            .map(|name| {
+                table.column(name).ok_or_else(|| TableError::Missing {
+                    column: name.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Each key column refines the groups of the ones before it, so a row's group is found
+        // by hashing a group number and one value at a time, rather than a whole row of keys
+        let mut ids = vec![0usize; table.rows()];
+        let mut count = usize::from(table.rows() > 0);
+        for column in &columns {
+            let mut seen = HashMap::with_capacity(count);
+            for (row, id) in ids.iter_mut().enumerate() {
+                let next = seen.len();
+                *id = *seen.entry((*id, column.datum(row))).or_insert(next);
+            }
+            count = seen.len();
+        }
+
+        let mut groups = vec![Vec::new(); count];
+        for (row, id) in ids.into_iter().enumerate() {
+            groups[id].push(row);
+        }
+        Ok(Self {
+            keys: keys.iter().map(|name| name.to_string()).collect(),
+            table,
+            groups,
+        })
+    }
+
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    /// The number of groups
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// The rows in each group
+    pub fn rows(&self) -> &[Vec<usize>] {
+        &self.groups
+    }
+
+    /// A table with a row for each group, holding the values of its keys
+    pub fn keys(&self) -> Table {
+        let first: Vec<usize> = self.groups.iter().map(|rows| rows[0]).collect();
+        let names: Vec<&str> = self.keys.iter().map(String::as_str).collect();
+        self.table
+            .project(&names)
+            .expect("the keys were checked when grouping")
+            .take(&first)
+    }
+
+    /// How many rows are in each group
+    pub fn sizes(&self) -> Result<Column, AggregateError> {
+        let sizes = self
+            .groups
+            .iter()
+            .map(|rows| i32::try_from(rows.len()))
+            .collect::<Result<_, _>>()
+            .map_err(|_| AggregateError::Overflow { aggregate: "count" })?;
+        Ok(Column::new(ColumnData::Int(sizes)))
+    }
+
+    /// Reduces the values of `column`, one of the grouped table's columns, in each group
+    pub fn aggregate(
+        &self,
+        column: &Column,
+        how: Aggregate,
+        missing: Missing,
+    ) -> Result<Column, AggregateError> {
+        let numeric = matches!(column.data, ColumnData::Int(_) | ColumnData::Float(_));
+        if how.needs_numbers() && !numeric {
+            return Err(AggregateError::NotNumeric {
+                aggregate: how.name(),
+                found: column.type_name(),
+            });
+        }
+
+        let mut values = Vec::with_capacity(self.groups.len());
+        for rows in &self.groups {
+            match (missing, rows.iter().find(|&&row| !column.is_valid(row))) {
+                (Missing::Error, Some(&row)) => return Err(AggregateError::Missing { row }),
+                (Missing::Propagate, Some(_)) => values.push(Type::Nil),
+                _ => {
+                    let present = rows.iter().copied().filter(|&row| column.is_valid(row));
+                    values.push(how.apply(column, present)?);
+                }
+            }
+        }
+        let typ = match how {
+            Aggregate::Sum | Aggregate::Min | Aggregate::Max => column.type_name(),
+            Aggregate::Count | Aggregate::CountDistinct => "int",
+            _ => "float",
+        };
+        Ok(Column::from_values("", &values).unwrap_or_else(|_| Column::missing(typ, values.len())))
+    }
+}
+
+impl fmt::Display for Groups {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<{} groups of {} rows by {}>",
+            self.groups.len(),
+            self.table.rows(),
+            self.keys.join(", ")
+        )
+    }
+}
+
+impl Aggregate {
+    /// The aggregate called `name`, with `arg` for the ones that take one
+    pub fn from_name(name: &str, arg: Option<f64>) -> Option<Self> {
+        Some(match (name, arg) {
+            ("sum", None) => Aggregate::Sum,
+            ("mean", None) => Aggregate::Mean,
+            ("min", None) => Aggregate::Min,
+            ("max", None) => Aggregate::Max,
+            ("count", None) => Aggregate::Count,
+            ("count_distinct", None) => Aggregate::CountDistinct,
+            ("median", None) => Aggregate::Median,
+            ("quantile", Some(q)) => Aggregate::Quantile(q),
+            ("std", None) => Aggregate::Std,
+            ("var", None) => Aggregate::Var,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Mean => "mean",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Count => "count",
+            Aggregate::CountDistinct => "count_distinct",
+            Aggregate::Median => "median",
+            Aggregate::Quantile(_) => "quantile",
+            Aggregate::Std => "std",
+            Aggregate::Var => "var",
+        }
+    }
+
+    fn needs_numbers(&self) -> bool {
+        !matches!(
+            self,
+            Aggregate::Min | Aggregate::Max | Aggregate::Count | Aggregate::CountDistinct
+        )
+    }
+
+    /// Reduces the values in `rows`, which all have one
+    /// Aggregates of nothing are missing, except sums and counts, which are 0
+    fn apply(
+        &self,
+        column: &Column,
+        rows: impl Iterator<Item = usize> + Clone,
+    ) -> Result<Type, AggregateError> {
+        let overflow = || AggregateError::Overflow {
+            aggregate: self.name(),
+        };
+        let number = |row: usize| match &column.data {
+            ColumnData::Int(v) => f64::from(v[row]),
+            ColumnData::Float(v) => f64::from(v[row]),
+            _ => f64::NAN,
+        };
+        Ok(match self {
+            Aggregate::Sum => match &column.data {
+                ColumnData::Int(v) => {
+                    let sum: i64 = rows.map(|row| i64::from(v[row])).sum();
+                    Type::Int(i32::try_from(sum).map_err(|_| overflow())?)
+                }
+                _ => Type::Float(neumaier_sum(rows.map(number)) as f32),
+            },
+            Aggregate::Mean => {
+                let stats = Welford::new(rows.map(number));
+                match stats.n {
+                    0 => Type::Nil,
+                    _ => Type::Float(stats.mean as f32),
+                }
+            }
+            Aggregate::Var | Aggregate::Std => {
+                let stats = Welford::new(rows.map(number));
+                match stats.variance() {
+                    None => Type::Nil,
+                    Some(var) if *self == Aggregate::Var => Type::Float(var as f32),
+                    Some(var) => Type::Float(var.sqrt() as f32),
+                }
+            }
+            Aggregate::Min => rows
+                .reduce(|best, row| {
+                    if column.compare(row, best).is_lt() {
+                        row
+                    } else {
+                        best
+                    }
+                })
+                .map_or(Type::Nil, |row| column.get(row)),
+            Aggregate::Max => rows
+                .reduce(|best, row| {
+                    if column.compare(row, best).is_gt() {
+                        row
+                    } else {
+                        best
+                    }
+                })
+                .map_or(Type::Nil, |row| column.get(row)),
+            Aggregate::Count => Type::Int(i32::try_from(rows.count()).map_err(|_| overflow())?),
+            Aggregate::CountDistinct => {
+                let distinct: HashSet<_> = rows.map(|row| column.datum(row)).collect();
+                Type::Int(i32::try_from(distinct.len()).map_err(|_| overflow())?)
+            }
+            Aggregate::Median => quantile(rows.map(number).collect(), 0.5),
+            Aggregate::Quantile(q) => quantile(rows.map(number).collect(), *q),
+        })
+    }
+}
+
+/// A running count, mean and sum of squared differences from the mean, which stays accurate
+/// where summing squares would cancel out catastrophically
+#[derive(Default)]
+struct Welford {
+    n: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welford {
+    fn new(values: impl Iterator<Item = f64> + Clone) -> Self {
+        // An infinity turns the running mean into NaN, where the plain mean is infinite, and
+        // anything with an infinity or a NaN in it has no variance
+        if values.clone().any(|x| !x.is_finite()) {
+            let n = values.clone().count();
+            return Self {
+                n,
+                mean: neumaier_sum(values) / n as f64,
+                m2: f64::NAN,
+            };
+        }
+        let mut stats = Self::default();
+        for x in values {
+            stats.n += 1;
+            let delta = x - stats.mean;
+            stats.mean += delta / stats.n as f64;
+            stats.m2 += delta * (x - stats.mean);
+        }
+        stats
+    }
+
+    /// The sample variance, which needs at least two values
+    fn variance(&self) -> Option<f64> {
+        (self.n > 1).then(|| self.m2 / (self.n - 1) as f64)
+    }
+}
+
+/// Interpolates linearly between the two values either side of the quantile, the way most
+/// spreadsheets and statistics packages do by default
+fn quantile(mut values: Vec<f64>, q: f64) -> Type {
+    if values.is_empty() {
+        return Type::Nil;
+    }
+    values.sort_unstable_by(f64::total_cmp);
+    let position = q * (values.len() - 1) as f64;
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    let fraction = position - below as f64;
+    Type::Float((values[below] + (values[above] - values[below]) * fraction) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{self, table};
+
+    /// Aggregates `x` grouped by `k`, one value for each group
+    fn aggregate(
+        table: Table,
+        how: Aggregate,
+        missing: Missing,
+    ) -> Result<Vec<String>, AggregateError> {
+        let table = Rc::new(table);
+        let groups = Groups::new(table.clone(), &["k"]).unwrap();
+        let column = groups.aggregate(table.column("x").unwrap(), how, missing)?;
+        Ok((0..column.len())
+            .map(|row| column.get(row).to_string())
+            .collect())
+    }
+
+    #[test]
+    fn groups_in_order_of_appearance() {
+        let t = Rc::new(table("k,x\nb,1\na,2\nb,3\nc,4\na,5\n"));
+        let groups = Groups::new(t, &["k"]).unwrap();
+        assert_eq!(groups.rows(), [vec![0, 2], vec![1, 4], vec![3]]);
+        assert_eq!(groups.to_string(), "<3 groups of 5 rows by k>");
+        assert_eq!(testing::rows(&groups.keys()), ["k", "b", "a", "c"]);
+        assert_eq!(
+            testing::rows(
+                &Table::new(vec![(String::from("n"), Rc::new(groups.sizes().unwrap()))]).unwrap()
+            ),
+            ["n", "2", "2", "1"]
+        );
+    }
+
+    #[test]
+    fn aggregates() {
+        let t = || table("k,x\na,1\na,4\nb,2\na,4\nb,6\n");
+        let each = |how| aggregate(t(), how, Missing::Skip).unwrap();
+        assert_eq!(each(Aggregate::Sum), ["9", "8"]);
+        assert_eq!(each(Aggregate::Mean), ["3.0", "4.0"]);
+        assert_eq!(each(Aggregate::Min), ["1", "2"]);
+        assert_eq!(each(Aggregate::Max), ["4", "6"]);
+        assert_eq!(each(Aggregate::Count), ["3", "2"]);
+        assert_eq!(each(Aggregate::CountDistinct), ["2", "2"]);
+        assert_eq!(each(Aggregate::Median), ["4.0", "4.0"]);
+        assert_eq!(each(Aggregate::Quantile(0.25)), ["2.5", "3.0"]);
+        assert_eq!(each(Aggregate::Var), ["3.0", "8.0"]);
+        assert_eq!(each(Aggregate::Std), ["1.7320508", "2.828427"]);
+    }
+
+    #[test]
+    fn compensated_sum() {
+        let t = table("k,x\na,1.0\na,100000000.0\na,1.0\na,-100000000.0\n");
+        assert_eq!(
+            aggregate(t, Aggregate::Sum, Missing::Skip).unwrap(),
+            ["2.0"]
+        );
+    }
+
+    #[test]
+    fn missing_values() {
+        let t = || table("k,x\na,1\na,\nb,2\n");
+        assert_eq!(
+            aggregate(t(), Aggregate::Sum, Missing::Skip).unwrap(),
+            ["1", "2"]
+        );
+        assert_eq!(
+            aggregate(t(), Aggregate::Count, Missing::Skip).unwrap(),
+            ["1", "1"]
+        );
+        assert_eq!(
+            aggregate(t(), Aggregate::Sum, Missing::Propagate).unwrap(),
+            ["NA", "2"]
+        );
+        assert_eq!(
+            aggregate(t(), Aggregate::Sum, Missing::Error),
+            Err(AggregateError::Missing { row: 1 })
+        );
+    }
+
+    #[test]
+    fn aggregate_errors() {
+        let t = table("k,x\na,p\nb,q\n");
+        assert_eq!(
+            aggregate(t, Aggregate::Mean, Missing::Skip),
+            Err(AggregateError::NotNumeric {
+                aggregate: "mean",
+                found: "str"
+            })
+        );
+        let t = table("k,x\na,2000000000\na,2000000000\n");
+        assert_eq!(
+            aggregate(t, Aggregate::Sum, Missing::Skip),
+            Err(AggregateError::Overflow { aggregate: "sum" })
+        );
+    }
+
+    #[test]
+    fn non_finite_values() {
+        let floats = |keys: &str, values: Vec<f32>| {
+            let keys = table(&format!("k\n{}\n", keys.replace(' ', "\n")));
+            let values = Rc::new(Column::new(ColumnData::Float(values)));
+            Table::new(vec![keys.columns()[0].clone(), (String::from("x"), values)]).unwrap()
+        };
+        // An infinity once turned the sum and mean of its group into NaN
+        let t = || {
+            floats(
+                "a a b b c",
+                vec![f32::INFINITY, 1.0, 2.0, 4.0, f32::NEG_INFINITY],
+            )
+        };
+        let each = |how| aggregate(t(), how, Missing::Skip).unwrap();
+        assert_eq!(each(Aggregate::Sum), ["inf", "6.0", "-inf"]);
+        assert_eq!(each(Aggregate::Mean), ["inf", "3.0", "-inf"]);
+        assert_eq!(each(Aggregate::Var), ["NaN", "2.0", "NA"]);
+
+        let t = floats("a a", vec![f32::INFINITY, f32::NEG_INFINITY]);
+        assert_eq!(
+            aggregate(t, Aggregate::Mean, Missing::Skip).unwrap(),
+            ["NaN"]
+        );
+    }
+}
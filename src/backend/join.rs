@@ -0,0 +1,426 @@
+//! Joining two tables by matching up rows with equal values in their key columns
+//!
+//! Rows come out in the order of the left table, with each left row's matches in the order of the
+//! right table, except for right joins, which follow the right table instead. Missing values in
+//! key columns never match anything, not even each other.
+//!
+//! The joined table has the key columns first, then the rest of the left table's columns, then
+//! the rest of the right table's.
+
+use std::{collections::HashMap, rc::Rc};
+
+use super::table::{Column, Datum, Table, TableError};
+
+/// Which rows a join keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum How {
+    /// Pairs of matching rows
+    Inner,
+    /// Pairs of matching rows, and left rows without a match
+    Left,
+    /// Pairs of matching rows, and right rows without a match
+    Right,
+    /// Pairs of matching rows, and rows without a match from both sides
+    Outer,
+    /// Left rows that have a match, once each, with just the left columns
+    Semi,
+    /// Left rows that don't have a match, with just the left columns
+    Anti,
+}
+
+/// How many rows on each side a key is expected to match, checked before joining
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cardinality {
+    OneToOne,
+    OneToMany,
+    ManyToOne,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Everything about a join besides the tables
+pub struct Options<'a> {
+    pub on: &'a [&'a str],
+    pub how: How,
+    /// Added to the names of non-key columns both tables have, on the left and on the right
+    pub suffixes: (&'a str, &'a str),
+    pub validate: Option<Cardinality>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinError {
+    Missing {
+        column: String,
+        side: Side,
+    },
+    /// A key column holds different types on each side
+    KeyTypes {
+        column: String,
+        left: &'static str,
+        right: &'static str,
+    },
+    /// A key that's meant to be unique on one side is there more than once
+    Cardinality {
+        expected: Cardinality,
+        side: Side,
+        /// The key's values, as they would be written
+        key: String,
+        rows: (usize, usize),
+    },
+    Table(TableError),
+}
+
+impl How {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "inner" => How::Inner,
+            "left" => How::Left,
+            "right" => How::Right,
+            "outer" => How::Outer,
+            "semi" => How::Semi,
+            "anti" => How::Anti,
+            _ => return None,
+        })
+    }
+}
+
+impl Cardinality {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "one_to_one" => Cardinality::OneToOne,
+            "one_to_many" => Cardinality::OneToMany,
+            "many_to_one" => Cardinality::ManyToOne,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cardinality::OneToOne => "one_to_one",
+            Cardinality::OneToMany => "one_to_many",
+            Cardinality::ManyToOne => "many_to_one",
+        }
+    }
+
+    /// Whether each key can only be in one row on `side`
+    fn unique(&self, side: Side) -> bool {
+        match side {
+            Side::Left => matches!(self, Cardinality::OneToOne | Cardinality::OneToMany),
+            Side::Right => matches!(self, Cardinality::OneToOne | Cardinality::ManyToOne),
+        }
+    }
+}
+
+impl Side {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+}
+
+/// The key columns of one of the tables
+struct Keys<'t> {
+    columns: Vec<&'t Column>,
+    rows: usize,
+}
+
+impl<'t> Keys<'t> {
+    fn new(table: &'t Table, on: &[&str], side: Side) -> Result<Self, JoinError> {
+        let columns = on
+            .iter()
+            .map(|name| {
+                table
+                    .column(name)
+                    .map(|column| column.as_ref())
+                    .ok_or_else(|| JoinError::Missing {
+                        column: name.to_string(),
+                        side,
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            columns,
+            rows: table.rows(),
+        })
+    }
+
+    /// The key in `row`, unless one of its values is missing
+    fn get(&self, row: usize) -> Option<Vec<Datum<'t>>> {
+        self.columns
+            .iter()
+            .map(|column| match column.datum(row) {
+                Datum::Missing => None,
+                datum => Some(datum),
+            })
+            .collect()
+    }
+
+    /// Where each key is, keeping rows in order
+    /// `unique` makes a key in more than one row an error, which says where the first two are
+    fn index(&self, unique: bool) -> Result<HashMap<Vec<Datum<'t>>, Vec<usize>>, (usize, usize)> {
+        let mut index: HashMap<_, Vec<usize>> = HashMap::new();
+        for row in 0..self.rows {
+            if let Some(key) = self.get(row) {
+                let rows = index.entry(key).or_default();
+                if unique && !rows.is_empty() {
+                    return Err((rows[0], row));
+                }
+                rows.push(row);
+            }
+        }
+        Ok(index)
+    }
+
+    /// How the key in `row` would be written
+    fn describe(&self, row: usize) -> String {
+        let values: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| column.get(row).quoted().to_string())
+            .collect();
+        match values.as_slice() {
+            [value] => value.clone(),
+            _ => format!("({})", values.join(", ")),
+        }
+    }
+}
+
+pub fn join(left: &Table, right: &Table, options: &Options) -> Result<Table, JoinError> {
+    let left_keys = Keys::new(left, options.on, Side::Left)?;
+    let right_keys = Keys::new(right, options.on, Side::Right)?;
+    for ((name, l), r) in options
+        .on
+        .iter()
+        .zip(&left_keys.columns)
+        .zip(&right_keys.columns)
+    {
+        if l.type_name() != r.type_name() {
+            return Err(JoinError::KeyTypes {
+                column: name.to_string(),
+                left: l.type_name(),
+                right: r.type_name(),
+            });
+        }
+    }
+
+    let unique = |side| options.validate.is_some_and(|c| c.unique(side));
+    let cardinality = |keys: &Keys, side, rows: (usize, usize)| JoinError::Cardinality {
+        expected: options.validate.expect("only unique keys are checked"),
+        side,
+        key: keys.describe(rows.1),
+        rows,
+    };
+    // Right joins look rows up in the left table, and every other kind in the right one
+    let (probe, build, probe_side, build_side) = match options.how {
+        How::Right => (&right_keys, &left_keys, Side::Right, Side::Left),
+        _ => (&left_keys, &right_keys, Side::Left, Side::Right),
+    };
+    if unique(probe_side) {
+        probe
+            .index(true)
+            .map_err(|rows| cardinality(probe, probe_side, rows))?;
+    }
+    let index = build
+        .index(unique(build_side))
+        .map_err(|rows| cardinality(build, build_side, rows))?;
+
+    let mut pairs: Vec<(Option<usize>, Option<usize>)> = Vec::new();
+    let mut matched = vec![false; build.rows];
+    for row in 0..probe.rows {
+        let matches = probe
+            .get(row)
+            .and_then(|key| index.get(&key))
+            .map_or(&[][..], Vec::as_slice);
+        match options.how {
+            How::Semi if !matches.is_empty() => pairs.push((Some(row), None)),
+            How::Anti if matches.is_empty() => pairs.push((Some(row), None)),
+            How::Semi | How::Anti => {}
+            _ if matches.is_empty() && options.how != How::Inner => pairs.push((Some(row), None)),
+            _ => {
+                for &other in matches {
+                    matched[other] = true;
+                    pairs.push((Some(row), Some(other)));
+                }
+            }
+        }
+    }
+    if options.how == How::Outer {
+        let unmatched = matched.iter().enumerate().filter(|(_, m)| !**m);
+        pairs.extend(unmatched.map(|(row, _)| (None, Some(row))));
+    }
+    if options.how == How::Right {
+        pairs = pairs.into_iter().map(|(r, l)| (l, r)).collect();
+    }
+
+    if matches!(options.how, How::Semi | How::Anti) {
+        let rows: Vec<usize> = pairs.iter().filter_map(|(l, _)| *l).collect();
+        return Ok(left.take(&rows));
+    }
+    let from = |source: usize, rows: &[Option<usize>]| -> Vec<Option<(usize, usize)>> {
+        rows.iter()
+            .map(|row| row.map(|row| (source, row)))
+            .collect()
+    };
+    let (lefts, rights): (Vec<_>, Vec<_>) = pairs.into_iter().unzip();
+
+    // Keys come from whichever side has them, which is the left unless the row is only on the
+    // right
+    let mut columns = Vec::with_capacity(left.width() + right.width());
+    for ((name, l), r) in options
+        .on
+        .iter()
+        .zip(&left_keys.columns)
+        .zip(&right_keys.columns)
+    {
+        let picks: Vec<_> = lefts
+            .iter()
+            .zip(&rights)
+            .map(|(left, right)| left.map(|row| (0, row)).or(right.map(|row| (1, row))))
+            .collect();
+        columns.push((name.to_string(), Rc::new(Column::gather(&[l, r], &picks))));
+    }
+    let is_key = |name: &str| options.on.contains(&name);
+    let shared = |name: &str, other: &Table| !is_key(name) && other.column(name).is_some();
+    let sides = [
+        (left, right, &lefts, options.suffixes.0),
+        (right, left, &rights, options.suffixes.1),
+    ];
+    for (table, other, rows, suffix) in sides {
+        let picks = from(0, rows);
+        for (name, column) in table.columns() {
+            if is_key(name) {
+                continue;
+            }
+            let name = match shared(name, other) {
+                true => format!("{}{}", name, suffix),
+                false => name.clone(),
+            };
+            columns.push((name, Rc::new(Column::gather(&[column], &picks))));
+        }
+    }
+    Table::new(columns).map_err(JoinError::Table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{rows, table};
+
+    fn options<'a>(on: &'a [&'a str], how: How) -> Options<'a> {
+        Options {
+            on,
+            how,
+            suffixes: ("_x", "_y"),
+            validate: None,
+        }
+    }
+
+    fn joined(how: How) -> Vec<String> {
+        let left = table("k,a\n1,x\n2,y\n2,z\n,w\n");
+        let right = table("k,b\n2,p\n3,q\n1,r\n,s\n2,t\n");
+        rows(&join(&left, &right, &options(&["k"], how)).unwrap())
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(
+            joined(How::Inner),
+            ["k a b", "1 x r", "2 y p", "2 y t", "2 z p", "2 z t"]
+        );
+        assert_eq!(
+            joined(How::Left),
+            [
+                "k a b",
+                "1 x r",
+                "2 y p",
+                "2 y t",
+                "2 z p",
+                "2 z t",
+                "NA w NA"
+            ]
+        );
+        assert_eq!(
+            joined(How::Right),
+            [
+                "k a b",
+                "2 y p",
+                "2 z p",
+                "3 NA q",
+                "1 x r",
+                "NA NA s",
+                "2 y t",
+                "2 z t"
+            ]
+        );
+        assert_eq!(
+            joined(How::Outer),
+            [
+                "k a b",
+                "1 x r",
+                "2 y p",
+                "2 y t",
+                "2 z p",
+                "2 z t",
+                "NA w NA",
+                "3 NA q",
+                "NA NA s"
+            ]
+        );
+        assert_eq!(joined(How::Semi), ["k a", "1 x", "2 y", "2 z"]);
+        assert_eq!(joined(How::Anti), ["k a", "NA w"]);
+    }
+
+    #[test]
+    fn compound_keys_and_suffixes() {
+        let left = table("a,b,v\n1,x,10\n1,y,20\n");
+        let right = table("b,a,v\ny,1,30\nx,2,40\n");
+        let t = join(&left, &right, &options(&["a", "b"], How::Inner)).unwrap();
+        assert_eq!(rows(&t), ["a b v_x v_y", "1 y 20 30"]);
+    }
+
+    #[test]
+    fn validation() {
+        let left = table("k,a\n1,x\n2,y\n2,z\n");
+        let right = table("k,b\n1,p\n2,q\n");
+        let mut opts = options(&["k"], How::Inner);
+        opts.validate = Some(Cardinality::ManyToOne);
+        assert!(join(&left, &right, &opts).is_ok());
+        opts.validate = Some(Cardinality::OneToOne);
+        assert_eq!(
+            join(&left, &right, &opts).err(),
+            Some(JoinError::Cardinality {
+                expected: Cardinality::OneToOne,
+                side: Side::Left,
+                key: "2".to_string(),
+                rows: (1, 2),
+            })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let left = table("k,a\n1,x\n");
+        let right = table("k,b\n\"1\",p\n");
+        assert_eq!(
+            join(&left, &right, &options(&["k"], How::Inner)).err(),
+            Some(JoinError::KeyTypes {
+                column: "k".to_string(),
+                left: "int",
+                right: "str",
+            })
+        );
+        assert_eq!(
+            join(&left, &right, &options(&["a"], How::Inner)).err(),
+            Some(JoinError::Missing {
+                column: "a".to_string(),
+                side: Side::Right,
+            })
+        );
+    }
+}
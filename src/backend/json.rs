@@ -0,0 +1,936 @@
+//! Reading and writing JSON, and NDJSON, where every line is an object that becomes a row
+//!
+//! Numbers are never rounded through another type on the way in: an integer becomes an int if
+//! it fits in one, anything with a fraction or an exponent becomes the float nearest to its
+//! decimal digits, and a number neither can hold is an error rather than a silent
+//! approximation. Floats are written with the fewest digits that read back as the same float.
+//!
+//! Floats are 32 bits, which hold about 7 significant digits, so `0.1234567890123` reads as
+//! `0.12345679`. In strict mode that's an error instead: a float has to write back out as the
+//! same number it was read from, the way an int has to fit without wrapping.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::{self, Write},
+    rc::Rc,
+};
+
+use super::{
+    map::{Key, Map},
+    series::Bitmap,
+    table::{Column, ColumnData, Table},
+    value::Type,
+};
+
+/// How deeply arrays and objects can be nested
+const MAX_DEPTH: usize = 256;
+
+/// Where some JSON stops being valid, as a byte offset from the start of the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.message)
+    }
+}
+
+/// The line and column, both counting from 1, of a byte offset into `text`
+pub fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[start..].chars().count() + 1)
+}
+
+/// One entry of an object, with where its value starts
+struct Member {
+    key: String,
+    value_offset: usize,
+    value: Type,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Where the parser has to stop, which for NDJSON is the end of the current line
+    end: usize,
+    /// Whether an object with the same key twice, or a float with more digits than a float
+    /// holds, is an error, rather than the last key winning and the float being rounded
+    strict: bool,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn problem(&self, offset: usize, message: impl Into<String>) -> Problem {
+        Problem {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        (self.pos < self.end).then(|| self.text.as_bytes()[self.pos])
+    }
+
+    /// Describes whatever is at the current position, for saying it isn't what was expected
+    fn found(&self) -> String {
+        match self.text[self.pos..self.end].chars().next() {
+            Some(c) => format!("found {:?}", c),
+            None => String::from("the input ended"),
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, context: &str) -> Result<(), Problem> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.problem(
+                self.pos,
+                format!(
+                    "expected '{}' {} but {}",
+                    byte as char,
+                    context,
+                    self.found()
+                ),
+            ))
+        }
+    }
+
+    fn value(&mut self) -> Result<Type, Problem> {
+        match self.peek() {
+            Some(b'{') => {
+                let members = self.object()?;
+                let mut map = Map::new();
+                for member in members {
+                    map.insert(Key::Str(member.key), member.value);
+                }
+                Ok(Type::Map(Rc::new(map)))
+            }
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Type::Str),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b't') => self.literal("true", Type::Bool(true)),
+            Some(b'f') => self.literal("false", Type::Bool(false)),
+            Some(b'n') => self.literal("null", Type::Nil),
+            _ => Err(self.problem(self.pos, format!("expected a value but {}", self.found()))),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Type) -> Result<Type, Problem> {
+        if self.text[self.pos..self.end].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.problem(self.pos, format!("expected a value but {}", self.found())))
+        }
+    }
+
+    fn nest(&mut self) -> Result<(), Problem> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.problem(
+                self.pos,
+                format!("arrays and objects can only be nested {} deep", MAX_DEPTH),
+            ));
+        }
+        Ok(())
+    }
+
+    fn array(&mut self) -> Result<Type, Problem> {
+        self.nest()?;
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.whitespace();
+                items.push(self.value()?);
+                self.whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => {
+                        return Err(self.problem(
+                            self.pos,
+                            format!("expected ',' or ']' after an item but {}", self.found()),
+                        ))
+                    }
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Type::List(Rc::new(items)))
+    }
+
+    /// Reads an object's members in order, checking for repeated keys in strict mode
+    fn object(&mut self) -> Result<Vec<Member>, Problem> {
+        self.nest()?;
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(members);
+        }
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        loop {
+            self.whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.problem(
+                    self.pos,
+                    format!("expected a key in double quotes but {}", self.found()),
+                ));
+            }
+            let key_offset = self.pos;
+            let key = self.string()?;
+            if self.strict {
+                match seen.entry(key.clone()) {
+                    Entry::Occupied(first) => {
+                        return Err(self.problem(
+                            key_offset,
+                            format!(
+                                "the key {:?} appears twice in this object, first at byte {}",
+                                key,
+                                first.get()
+                            ),
+                        ))
+                    }
+                    Entry::Vacant(slot) => {
+                        slot.insert(key_offset);
+                    }
+                }
+            }
+            self.whitespace();
+            self.expect(b':', "after a key")?;
+            self.whitespace();
+            let value_offset = self.pos;
+            let value = self.value()?;
+            members.push(Member {
+                key,
+                value_offset,
+                value,
+            });
+            self.whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {
+                    return Err(self.problem(
+                        self.pos,
+                        format!("expected ',' or '}}' after a value but {}", self.found()),
+                    ))
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(members)
+    }
+
+    fn string(&mut self) -> Result<String, Problem> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut out = String::new();
+        // The start of the text not yet copied into `out`
+        let mut run = self.pos;
+        loop {
+            match self.peek() {
+                None => return Err(self.problem(start, "this string is never closed")),
+                Some(b'"') => {
+                    out.push_str(&self.text[run..self.pos]);
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    out.push_str(&self.text[run..self.pos]);
+                    out.push(self.escape()?);
+                    run = self.pos;
+                }
+                Some(byte) if byte < 0x20 => return Err(self.problem(
+                    self.pos,
+                    "control characters have to be escaped in strings, like \\n for a line break",
+                )),
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// Reads an escape sequence, starting at its backslash
+    fn escape(&mut self) -> Result<char, Problem> {
+        let start = self.pos;
+        self.pos += 1;
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let unit = self.hex(start)?;
+                return match unit {
+                    0xD800..=0xDBFF => {
+                        // The first half of a surrogate pair, the second has to follow
+                        let low_start = self.pos;
+                        if !self.text[self.pos..self.end].starts_with("\\u") {
+                            return Err(self.problem(start, "this escape is half of a surrogate pair, but the other half doesn't follow it"));
+                        }
+                        self.pos += 2;
+                        let low = self.hex(low_start)?;
+                        if !(0xDC00..=0xDFFF).contains(&low) {
+                            return Err(self.problem(
+                                low_start,
+                                "expected the second half of a surrogate pair",
+                            ));
+                        }
+                        let code = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                        Ok(char::from_u32(code).expect("surrogate pairs are valid characters"))
+                    }
+                    0xDC00..=0xDFFF => Err(self.problem(
+                        start,
+                        "this escape is the second half of a surrogate pair, without the first",
+                    )),
+                    _ => Ok(char::from_u32(unit).expect("non-surrogates are valid characters")),
+                };
+            }
+            _ => {
+                return Err(self.problem(
+                    start,
+                    format!(
+                        "expected an escape like \\n or \\u00e9 after the backslash but {}",
+                        self.found()
+                    ),
+                ))
+            }
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    /// Reads the four hex digits of a `\u` escape starting at `start`
+    fn hex(&mut self, start: usize) -> Result<u32, Problem> {
+        let digits = &self.text.as_bytes()[self.pos..self.end];
+        if digits.len() < 4 || !digits[..4].iter().all(u8::is_ascii_hexdigit) {
+            return Err(self.problem(start, "expected four hex digits after \\u"));
+        }
+        let unit = u32::from_str_radix(&self.text[self.pos..self.pos + 4], 16)
+            .expect("the digits were just checked");
+        self.pos += 4;
+        Ok(unit)
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<Type, Problem> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => {
+                self.pos += 1;
+                if let Some(b'0'..=b'9') = self.peek() {
+                    return Err(self.problem(start, "numbers can't have leading zeros"));
+                }
+            }
+            Some(b'1'..=b'9') => {
+                self.digits();
+            }
+            _ => {
+                return Err(self.problem(
+                    self.pos,
+                    format!("expected a digit after '-' but {}", self.found()),
+                ))
+            }
+        }
+        let mut float = false;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            float = true;
+            if self.digits() == 0 {
+                return Err(self.problem(
+                    self.pos,
+                    format!("expected a digit after '.' but {}", self.found()),
+                ));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            float = true;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.problem(
+                    self.pos,
+                    format!("expected a digit in the exponent but {}", self.found()),
+                ));
+            }
+        }
+
+        let text = &self.text[start..self.pos];
+        if !float {
+            return text.parse().map(Type::Int).map_err(|_| {
+                self.problem(
+                    start,
+                    format!(
+                        "{} doesn't fit in an int, which holds {} to {}",
+                        text,
+                        i32::MIN,
+                        i32::MAX
+                    ),
+                )
+            });
+        }
+        // Parsing straight from the digits rounds once, to the nearest float
+        let x = match text.parse::<f32>() {
+            Ok(x) if x.is_finite() => x,
+            _ => return Err(self.problem(start, format!("{} is too large for a float", text))),
+        };
+        if self.strict && decimal(text) != decimal(&format!("{:e}", x)) {
+            return Err(self.problem(
+                start,
+                format!("{} can't be a float without rounding it to {}", text, x),
+            ));
+        }
+        Ok(Type::Float(x))
+    }
+}
+
+/// The sign, significant digits and exponent of a number written in decimal, so that numbers
+/// written differently can be compared, `1.50` and `15e-1` being the same
+/// An exponent too large to read gives `None`, which matches nothing a float writes.
+fn decimal(text: &str) -> Option<(bool, String, i64)> {
+    let negative = text.starts_with('-');
+    let text = text.trim_start_matches(['-', '+']);
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, fraction);
+    let significant = digits.trim_start_matches('0').trim_end_matches('0');
+    let trailing = digits.len() - digits.trim_end_matches('0').len();
+    let exponent = exponent - fraction.len() as i64 + trailing as i64;
+    match significant {
+        "" => Some((negative, String::new(), 0)),
+        _ => Some((negative, significant.to_string(), exponent)),
+    }
+}
+
+/// The number of bytes a leading byte order mark takes up, which is skipped
+fn bom(text: &str) -> usize {
+    if text.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    }
+}
+
+/// Parses a single JSON value, where objects become maps with string keys and arrays lists
+pub fn parse(text: &str, strict: bool) -> Result<Type, Problem> {
+    let mut parser = Parser {
+        text,
+        pos: bom(text),
+        end: text.len(),
+        strict,
+        depth: 0,
+    };
+    parser.whitespace();
+    let value = parser.value()?;
+    parser.whitespace();
+    if parser.pos < parser.end {
+        return Err(parser.problem(
+            parser.pos,
+            format!("expected nothing after the value but {}", parser.found()),
+        ));
+    }
+    Ok(value)
+}
+
+/// The values of one column of an NDJSON table while it's being read
+struct Field {
+    name: String,
+    /// One per row so far, `Nil` where the row doesn't have the key
+    values: Vec<Type>,
+    /// The type of the values, and the line it was first seen on
+    kind: Option<(&'static str, usize)>,
+}
+
+/// Reads NDJSON, one object per line, into a table with a column for every key
+/// Columns are in the order their keys first appear, and a row without a key is missing that
+/// value. A column with both ints and floats is a float column, and one that's only ever null
+/// is a column of missing strings.
+pub fn parse_ndjson(text: &str, strict: bool) -> Result<Table, Problem> {
+    let mut fields: Vec<Field> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    // The line each row came from, since blank lines are skipped
+    let mut lines: Vec<usize> = Vec::new();
+
+    let mut start = bom(text);
+    for (number, line) in text[start..].split('\n').enumerate() {
+        let number = number + 1;
+        let mut parser = Parser {
+            text,
+            pos: start,
+            end: start + line.len(),
+            strict,
+            depth: 0,
+        };
+        start += line.len() + 1;
+        parser.whitespace();
+        match parser.peek() {
+            None => continue,
+            Some(b'{') => {}
+            Some(_) => {
+                return Err(parser.problem(
+                    parser.pos,
+                    format!(
+                        "expected an object, as every line of NDJSON is one row, but {}",
+                        parser.found()
+                    ),
+                ))
+            }
+        }
+        let members = parser.object()?;
+        parser.whitespace();
+        if parser.pos < parser.end {
+            return Err(parser.problem(
+                parser.pos,
+                format!(
+                    "expected the line to end after the object but {}",
+                    parser.found()
+                ),
+            ));
+        }
+
+        let row = lines.len();
+        lines.push(number);
+        for member in members {
+            let i = *index.entry(member.key.clone()).or_insert_with(|| {
+                fields.push(Field {
+                    name: member.key.clone(),
+                    values: vec![Type::Nil; row],
+                    kind: None,
+                });
+                fields.len() - 1
+            });
+            let field = &mut fields[i];
+            let kind = match &member.value {
+                Type::Nil => None,
+                Type::Int(_) | Type::Float(_) | Type::Str(_) | Type::Bool(_) => {
+                    Some(member.value.type_name())
+                }
+                other => {
+                    let what = if matches!(other, Type::List(_)) {
+                        "an array"
+                    } else {
+                        "an object"
+                    };
+                    return Err(parser.problem(
+                        member.value_offset,
+                        format!(
+                            "'{}' is {}, but a column can only hold nulls, bools, numbers and strings",
+                            member.key, what
+                        ),
+                    ));
+                }
+            };
+            if let Some(kind) = kind {
+                field.kind = match field.kind {
+                    None => Some((kind, number)),
+                    Some((first, _)) if first == kind => field.kind,
+                    Some(("int" | "float", since)) if matches!(kind, "int" | "float") => {
+                        widen(field, &lines, member.value_offset)?;
+                        Some(("float", since))
+                    }
+                    Some((first, since)) => {
+                        return Err(parser.problem(
+                            member.value_offset,
+                            format!(
+                                "'{}' is {} here, but was {} on line {}",
+                                member.key, kind, first, since
+                            ),
+                        ))
+                    }
+                };
+            }
+            let value = match (field.kind, member.value) {
+                (Some(("float", _)), Type::Int(i)) => {
+                    Type::Float(exact(i, number, member.value_offset)?)
+                }
+                (_, value) => value,
+            };
+            // A repeated key outside strict mode replaces the earlier value, as it does in a map
+            if field.values.len() > row {
+                field.values[row] = value;
+            } else {
+                field.values.push(value);
+            }
+        }
+        for field in &mut fields {
+            field.values.resize(row + 1, Type::Nil);
+        }
+    }
+
+    let columns = fields
+        .into_iter()
+        .map(|field| (field.name, Rc::new(column(field.values))))
+        .collect();
+    Ok(Table::new(columns).expect("every column has a value for every row, under its own key"))
+}
+
+/// The float an int converts to, as long as it's exactly the same number
+fn exact(i: i32, line: usize, offset: usize) -> Result<f32, Problem> {
+    let x = i as f32;
+    if x as i64 == i as i64 {
+        Ok(x)
+    } else {
+        Err(Problem {
+            offset,
+            message: format!(
+                "the column mixes ints and floats, but the int {} on line {} can't be a float without rounding",
+                i, line
+            ),
+        })
+    }
+}
+
+/// Turns every int read so far in a column into a float
+/// `offset` is where the first float is, which is blamed if one of the ints can't be converted
+fn widen(field: &mut Field, lines: &[usize], offset: usize) -> Result<(), Problem> {
+    for (row, value) in field.values.iter_mut().enumerate() {
+        if let Type::Int(i) = *value {
+            *value = Type::Float(exact(i, lines[row], offset)?);
+        }
+    }
+    Ok(())
+}
+
+/// Builds a column from values that are all the same type or nil
+fn column(values: Vec<Type>) -> Column {
+    let validity: Option<Bitmap> =
+        values
+            .iter()
+            .any(|value| matches!(value, Type::Nil))
+            .then(|| {
+                values
+                    .iter()
+                    .map(|value| !matches!(value, Type::Nil))
+                    .collect()
+            });
+
+    macro_rules! collect {
+        ($variant:ident) => {
+            ColumnData::$variant(
+                values
+                    .into_iter()
+                    .map(|value| match value {
+                        Type::$variant(v) => v,
+                        _ => Default::default(),
+                    })
+                    .collect(),
+            )
+        };
+    }
+
+    let data = match values.iter().find(|value| !matches!(value, Type::Nil)) {
+        Some(Type::Int(_)) => collect!(Int),
+        Some(Type::Float(_)) => collect!(Float),
+        Some(Type::Bool(_)) => collect!(Bool),
+        _ => collect!(Str),
+    };
+    Column { data, validity }
+}
+
+/// Writes a value as JSON, indenting nested arrays and objects by `indent` spaces if given
+/// Maps with int or bool keys are written with the keys as strings, since JSON has no others,
+/// and a table becomes an array with an object for every row.
+pub fn write(value: &Type, indent: Option<usize>) -> Result<String, String> {
+    let mut out = String::new();
+    Writer {
+        out: &mut out,
+        indent,
+    }
+    .value(value, 0)?;
+    Ok(out)
+}
+
+/// Writes a table as NDJSON, an object for every row on a line of its own
+pub fn write_ndjson(table: &Table) -> String {
+    let mut out = String::new();
+    for row in 0..table.rows() {
+        Writer {
+            out: &mut out,
+            indent: None,
+        }
+        .row(table, row, 0)
+        .expect("columns only hold values that JSON can write");
+        out.push('\n');
+    }
+    out
+}
+
+struct Writer<'a> {
+    out: &'a mut String,
+    indent: Option<usize>,
+}
+
+impl Writer<'_> {
+    /// Starts a new line at `depth`, when indenting
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = self.indent {
+            self.out.push('\n');
+            self.out.extend(std::iter::repeat_n(' ', indent * depth));
+        }
+    }
+
+    fn separator(&self) -> &'static str {
+        if self.indent.is_some() {
+            ": "
+        } else {
+            ":"
+        }
+    }
+
+    /// Writes a sequence of items between `open` and `close`, one per line when indenting
+    fn sequence<T>(
+        &mut self,
+        open: char,
+        close: char,
+        items: impl ExactSizeIterator<Item = T>,
+        depth: usize,
+        mut item: impl FnMut(&mut Self, T) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.out.push(open);
+        let empty = items.len() == 0;
+        for (i, value) in items.enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline(depth + 1);
+            item(self, value)?;
+        }
+        if !empty {
+            self.newline(depth);
+        }
+        self.out.push(close);
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Type, depth: usize) -> Result<(), String> {
+        match value {
+            Type::Nil | Type::Na(_) => self.out.push_str("null"),
+            Type::Bool(b) => write!(self.out, "{}", b).expect("writing to a string can't fail"),
+            Type::Int(i) => write!(self.out, "{}", i).expect("writing to a string can't fail"),
+            Type::Float(x) if !x.is_finite() => {
+                return Err(format!("JSON has no way to write the float {}", x))
+            }
+            // Debug is the shortest text that reads back as the same float, with a decimal point
+            // or an exponent so it stays a float
+            Type::Float(x) => write!(self.out, "{:?}", x).expect("writing to a string can't fail"),
+            Type::Str(s) => self.string(s),
+            Type::List(items) => self.sequence('[', ']', items.iter(), depth, |w, item| {
+                w.value(item, depth + 1)
+            })?,
+            Type::Series(column) => self.sequence('[', ']', 0..column.len(), depth, |w, row| {
+                w.value(&column.get(row), depth + 1)
+            })?,
+            Type::Map(map) => self.sequence('{', '}', map.iter(), depth, |w, (key, value)| {
+                match key {
+                    Key::Str(s) => w.string(s),
+                    other => w.string(&other.to_string()),
+                }
+                w.out.push_str(w.separator());
+                w.value(value, depth + 1)
+            })?,
+            Type::Table(table) => self.sequence('[', ']', 0..table.rows(), depth, |w, row| {
+                w.row(table, row, depth + 1)
+            })?,
+            other => return Err(format!("JSON has no way to write a {}", other.type_name())),
+        }
+        Ok(())
+    }
+
+    /// Writes a row of a table as an object
+    fn row(&mut self, table: &Table, row: usize, depth: usize) -> Result<(), String> {
+        self.sequence(
+            '{',
+            '}',
+            table.columns().iter(),
+            depth,
+            |w, (name, column)| {
+                w.string(name);
+                w.out.push_str(w.separator());
+                w.value(&column.get(row), depth + 1)
+            },
+        )
+    }
+
+    fn string(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                '\u{8}' => self.out.push_str("\\b"),
+                '\u{c}' => self.out.push_str("\\f"),
+                c if (c as u32) < 0x20 => {
+                    write!(self.out, "\\u{:04x}", c as u32).expect("writing to a string can't fail")
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> String {
+        parse(text, false).unwrap().to_string()
+    }
+
+    fn problem(text: &str, strict: bool) -> (usize, String) {
+        let problem = parse(text, strict).unwrap_err();
+        (problem.offset, problem.message)
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            read(r#" [1, -2.5, "aé\n", true, null] "#),
+            r#"[1, -2.5, "aé\n", true, nil]"#
+        );
+        assert_eq!(
+            read(r#"{"b": {"c": []}, "a": 1e2}"#),
+            r#"{"b": {"c": []}, "a": 100.0}"#
+        );
+        assert_eq!(read(r#"{"k": 1, "k": 2}"#), r#"{"k": 2}"#);
+    }
+
+    #[test]
+    fn problems() {
+        assert_eq!(
+            problem("[1, 2", false),
+            (
+                5,
+                String::from("expected ',' or ']' after an item but the input ended")
+            )
+        );
+        assert_eq!(
+            problem("2147483648", false),
+            (
+                0,
+                String::from(
+                    "2147483648 doesn't fit in an int, which holds -2147483648 to 2147483647"
+                )
+            )
+        );
+        assert_eq!(
+            problem("[1e39]", false),
+            (1, String::from("1e39 is too large for a float"))
+        );
+        assert_eq!(problem(r#"{"k": 1, "k": 2}"#, true).0, 9);
+        assert_eq!(
+            problem(&"[".repeat(300), false).1,
+            "arrays and objects can only be nested 256 deep"
+        );
+    }
+
+    #[test]
+    fn writing() {
+        let value = parse(r#"{"a": [1, 2.5, null], "b": {}, "c": "é\n\"q\""}"#, false).unwrap();
+        assert_eq!(
+            write(&value, None).unwrap(),
+            r#"{"a":[1,2.5,null],"b":{},"c":"é\n\"q\""}"#
+        );
+        assert_eq!(
+            write(&value, Some(2)).unwrap(),
+            "{\n  \"a\": [\n    1,\n    2.5,\n    null\n  ],\n  \"b\": {},\n  \"c\": \"é\\n\\\"q\\\"\"\n}"
+        );
+        assert_eq!(
+            write(&Type::Float(f32::NAN), None).unwrap_err(),
+            "JSON has no way to write the float NaN"
+        );
+        assert_eq!(position("ab\ncdé", 7), (2, 4));
+    }
+
+    #[test]
+    fn float_precision() {
+        assert_eq!(read("0.1234567890123"), "0.12345679");
+        assert_eq!(
+            problem("[0.1234567890123]", true),
+            (
+                1,
+                String::from("0.1234567890123 can't be a float without rounding it to 0.12345679")
+            )
+        );
+        assert_eq!(problem("16777217.0", true).0, 0);
+        assert_eq!(
+            problem("1e-50", true).1,
+            "1e-50 can't be a float without rounding it to 0"
+        );
+        for exact in [
+            "0.1",
+            "1.50",
+            "-0.0",
+            "15e-1",
+            "1E+3",
+            "0.000001",
+            "3.4028235e38",
+            "16777216.0",
+        ] {
+            assert!(parse(exact, true).is_ok(), "{} should be exact", exact);
+        }
+    }
+
+    #[test]
+    fn ndjson() {
+        let table = parse_ndjson("{\"a\": 1, \"b\": \"x\"}\n\n{\"a\": 2.5}\n", true).unwrap();
+        assert_eq!(table.rows(), 2);
+        assert_eq!(table.column("a").unwrap().type_name(), "float");
+        assert_eq!(table.column("b").unwrap().get(1).to_string(), "NA");
+
+        let problem = parse_ndjson("{\"a\": 16777217}\n{\"a\": 0.5}", false).unwrap_err();
+        assert_eq!(problem.offset, 22);
+        assert!(problem.message.contains("the int 16777217 on line 1"));
+        assert_eq!(parse_ndjson("[1]", false).unwrap_err().offset, 0);
+
+        let text = "{\"a\":1,\"b\":\"x\"}\n{\"a\":2,\"b\":null}\n";
+        assert_eq!(write_ndjson(&parse_ndjson(text, true).unwrap()), text);
+    }
+}
@@ -0,0 +1,127 @@
+//! Maps from keys to values, which remember the order keys were first inserted in
+
+use std::{collections::HashMap, fmt};
+
+use super::value::Type;
+
+/// The values that can be used as keys, the ones with a meaningful notion of equality
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Int(i32),
+    Str(String),
+    Bool(bool),
+}
+
+impl Key {
+    /// The key for a value, or `None` if values of its type can't be keys
+    pub fn from_value(value: &Type) -> Option<Self> {
+        match value {
+            Type::Int(i) => Some(Key::Int(*i)),
+            Type::Str(s) => Some(Key::Str(s.clone())),
+            Type::Bool(b) => Some(Key::Bool(*b)),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Type {
+        match self {
+            Key::Int(i) => Type::Int(*i),
+            Key::Str(s) => Type::Str(s.clone()),
+            Key::Bool(b) => Type::Bool(*b),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Int(i) => write!(f, "{}", i),
+            Key::Str(s) => write!(f, "{:?}", s),
+            Key::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Key, Type)>,
+    /// Where each key's entry is
+    index: HashMap<Key, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Type> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Sets the value for `key`, returning the one it replaced
+    /// A key that's already there keeps its place in the order
+    pub fn insert(&mut self, key: Key, value: Type) -> Option<Type> {
+        match self.index.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Every entry, in the order its key was first inserted
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&Key, &Type)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", key, value.quoted())?;
+        }
+        write!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_keep_their_first_place() {
+        let mut map = Map::new();
+        assert!(map
+            .insert(Key::Str(String::from("b")), Type::Int(1))
+            .is_none());
+        map.insert(Key::Int(1), Type::Nil);
+        let replaced = map.insert(Key::Str(String::from("b")), Type::Int(2));
+        assert!(matches!(replaced, Some(Type::Int(1))));
+        map.insert(Key::Bool(true), Type::Str(String::from("x")));
+
+        assert_eq!(map.len(), 3);
+        assert!(map.contains(&Key::Int(1)));
+        assert!(map.get(&Key::Int(2)).is_none());
+        assert_eq!(map.to_string(), r#"{"b": 2, 1: nil, true: "x"}"#);
+        assert!(Key::from_value(&Type::Float(1.0)).is_none());
+        assert_eq!(Key::from_value(&Type::Int(3)), Some(Key::Int(3)));
+    }
+}
@@ -1,3 +1,22 @@
+pub mod check;
+pub mod context;
+pub mod convert;
+pub mod csv;
+pub mod directive;
 pub mod eval;
+pub mod group;
+pub mod join;
+pub mod json;
+pub mod map;
+pub mod native;
+pub mod ops;
+pub mod prelude;
+pub mod random;
+pub mod regex;
+pub mod reshape;
+pub mod series;
+pub mod stdlib;
+pub mod table;
+#[cfg(test)]
+mod testing;
 pub mod value;
-pub mod context;
\ No newline at end of file
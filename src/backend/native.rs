@@ -0,0 +1,81 @@
+use std::fmt::Display;
+
+use crate::errors::error::ErrorKind;
+
+use super::{
+    convert::{FromValue, IntoValue},
+    value::{NativeError, NativeFunction, Type},
+};
+
+/// What a host function can return: a value, or a `Result` whose error is reported at the call
+pub trait HostReturn {
+    fn into_result(self) -> Result<Type, String>;
+}
+
+impl<T: IntoValue> HostReturn for T {
+    fn into_result(self) -> Result<Type, String> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue, E: Display> HostReturn for Result<T, E> {
+    fn into_result(self) -> Result<Type, String> {
+        self.map(T::into_value).map_err(|e| e.to_string())
+    }
+}
+
+/// Rust closures that can be called from Starkey, with their parameter types taken from the
+/// closure's own signature. `Args` is the tuple of parameter types
+pub trait HostFunction<Args> {
+    fn into_native(self, name: &str) -> NativeFunction;
+}
+
+/// Implements `HostFunction` for closures taking each of the given parameters
+macro_rules! host_function {
+    ($($arg:ident $index:tt),*) => {
+        impl<F, R, $($arg),*> HostFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: HostReturn,
+            $($arg: FromValue,)*
+        {
+            #[allow(unused_variables)]
+            fn into_native(self, name: &str) -> NativeFunction {
+                let function = name.to_string();
+                NativeFunction::new(name, vec![$($arg::TYPE),*], move |args| {
+                    self($(convert::<$arg>(args, $index)?),*)
+                        .into_result()
+                        .map_err(|message| {
+                            NativeError::call(ErrorKind::HostError {
+                                function: function.clone(),
+                                message,
+                            })
+                        })
+                })
+            }
+        }
+    };
+}
+
+host_function!();
+host_function!(A 0);
+host_function!(A 0, B 1);
+host_function!(A 0, B 1, C 2);
+host_function!(A 0, B 1, C 2, D 3);
+host_function!(A 0, B 1, C 2, D 3, E 4);
+host_function!(A 0, B 1, C 2, D 3, E 4, G 5);
+
+/// Reads an argument as the type the closure wants
+/// Arguments have been checked against the parameter types already, so this only fails for
+/// types like `Option<T>` that let anything through
+fn convert<T: FromValue>(args: &[Type], index: usize) -> Result<T, NativeError> {
+    T::from_value(&args[index]).map_err(|err| {
+        NativeError::argument(
+            index,
+            ErrorKind::TypeMismatch {
+                expected: err.expected.to_string(),
+                found: err.found.to_string(),
+            },
+        )
+    })
+}
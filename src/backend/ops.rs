@@ -0,0 +1,147 @@
+//! What the binary operators do to single values
+//!
+//! Series apply the same functions to each of their values, so a series and a scalar always
+//! agree on what an operator means.
+//!
+//! A missing value (NA) on either side makes the result missing too, with the type the operator
+//! would have given for values, so `NA + 1` is an int NA and `NA < 1` a bool NA. Operators are
+//! held to the same rules as they are for values, so an int NA can't be added to a float. An NA
+//! is never equal to anything, itself included, since nothing is known about what it would have
+//! been: comparing one gives another NA, and `is_na` is the way to find them.
+
+use crate::{errors::error::ErrorKind, frontend::expr::BinaryOperator};
+
+use super::value::Type;
+
+/// Applies an arithmetic operator to two ints, which never wraps around
+pub fn int(a: i32, b: i32, op: BinaryOperator) -> Result<i32, ErrorKind> {
+    use BinaryOperator::*;
+
+    let overflow = |operation| ErrorKind::Overflow { operation };
+    match op {
+        Plus => a.checked_add(b).ok_or_else(|| overflow("add")),
+        Minus => a.checked_sub(b).ok_or_else(|| overflow("subtract")),
+        Multiply => a.checked_mul(b).ok_or_else(|| overflow("multiply")),
+        Divide | Modulo if b == 0 => Err(ErrorKind::DivisionByZero),
+        Divide => a.checked_div(b).ok_or_else(|| overflow("divide")),
+        Modulo => a
+            .checked_rem(b)
+            .ok_or_else(|| overflow("take the remainder")),
+        Exponent => {
+            let exp = u32::try_from(b).map_err(|_| ErrorKind::TypeMismatch {
+                expected: String::from("a non-negative int exponent"),
+                found: b.to_string(),
+            })?;
+            a.checked_pow(exp)
+                .ok_or_else(|| overflow("raise to a power"))
+        }
+        _ => unreachable!("comparisons are handled by `compare`"),
+    }
+}
+
+/// Applies an arithmetic operator to two floats
+pub fn float(a: f32, b: f32, op: BinaryOperator) -> f32 {
+    use BinaryOperator::*;
+
+    match op {
+        Plus => a + b,
+        Minus => a - b,
+        Multiply => a * b,
+        Divide => a / b,
+        Modulo => a % b,
+        Exponent => a.powf(b),
+        _ => unreachable!("comparisons are handled by `compare`"),
+    }
+}
+
+/// Applies a comparison operator, where NaN is unequal to everything, itself included
+pub fn compare<T: PartialOrd + ?Sized>(a: &T, b: &T, op: BinaryOperator) -> bool {
+    use BinaryOperator::*;
+
+    match op {
+        Equal => a == b,
+        NotEqual => a != b,
+        Less => a < b,
+        LessEqual => a <= b,
+        Greater => a > b,
+        GreaterEqual => a >= b,
+        _ => unreachable!("arithmetic is handled by `int` and `float`"),
+    }
+}
+
+/// What an operator needed that it was given something else, for when the operands don't fit
+pub fn mismatch(left: &str, right: &str, op: BinaryOperator) -> ErrorKind {
+    let expected = if left != right {
+        left
+    } else if op.is_comparison() {
+        // Only ordering can get here with two of the same type
+        "int, float or str"
+    } else {
+        "int or float"
+    };
+    ErrorKind::TypeMismatch {
+        expected: expected.to_string(),
+        found: right.to_string(),
+    }
+}
+
+/// Applies an operator to two values that aren't series
+pub fn scalar(l: Type, r: Type, op: BinaryOperator) -> Result<Type, ErrorKind> {
+    use BinaryOperator::*;
+
+    if let (Type::Na(_), _) | (_, Type::Na(_)) = (&l, &r) {
+        return missing(element_type(&l), element_type(&r), op);
+    }
+    if op.is_comparison() {
+        let result = match (&l, &r) {
+            (Type::Int(a), Type::Int(b)) => compare(a, b, op),
+            (Type::Float(a), Type::Float(b)) => compare(a, b, op),
+            (Type::Str(a), Type::Str(b)) => compare(a, b, op),
+            (Type::Bool(a), Type::Bool(b)) if matches!(op, Equal | NotEqual) => compare(a, b, op),
+            (Type::Nil, Type::Nil) if matches!(op, Equal | NotEqual) => op == Equal,
+            _ => return Err(mismatch(l.type_name(), r.type_name(), op)),
+        };
+        return Ok(Type::Bool(result));
+    }
+
+    match (l, r) {
+        (Type::Int(a), Type::Int(b)) => int(a, b, op).map(Type::Int),
+        (Type::Float(a), Type::Float(b)) => Ok(Type::Float(float(a, b, op))),
+        (Type::Str(a), Type::Str(b)) if op == Plus => Ok(Type::Str(a + &b)),
+        (l, r) => Err(ErrorKind::TypeMismatch {
+            expected: l.type_name().to_string(),
+            found: r.type_name().to_string(),
+        }),
+    }
+}
+
+/// The type of a value, which is the type of the column it came from for an NA
+pub fn element_type(value: &Type) -> &'static str {
+    match value {
+        Type::Na(typ) => typ,
+        other => other.type_name(),
+    }
+}
+
+/// Applies an operator where one side is missing, to values of types `l` and `r`, giving a
+/// missing value of the type values would have given
+fn missing(l: &'static str, r: &'static str, op: BinaryOperator) -> Result<Type, ErrorKind> {
+    use BinaryOperator::*;
+
+    let applies = l == r
+        && match l {
+            "int" | "float" => true,
+            "str" => op.is_comparison() || op == Plus,
+            "bool" => matches!(op, Equal | NotEqual),
+            _ => false,
+        };
+    match applies {
+        true if op.is_comparison() => Ok(Type::Na("bool")),
+        true => Ok(Type::Na(l)),
+        false if op.is_comparison() => Err(mismatch(l, r, op)),
+        false => Err(ErrorKind::TypeMismatch {
+            expected: l.to_string(),
+            found: r.to_string(),
+        }),
+    }
+}
@@ -0,0 +1,332 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use crate::errors::error::ErrorKind;
+
+use super::value::{NativeError, NativeFunction, Type};
+
+/// The built-in functions every program starts with, bound into the globals as constants
+/// Their arguments are checked against their parameters the same way a user function's are
+pub fn functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("len", vec!["any"], len),
+        NativeFunction::new("type_of", vec!["any"], |args| {
+            Ok(Type::Str(args[0].type_name().to_string()))
+        }),
+        NativeFunction::new("str", vec!["any"], |args| {
+            Ok(Type::Str(args[0].to_string()))
+        }),
+        NativeFunction::new("int", vec!["any"], int),
+        NativeFunction::new("float", vec!["any"], float),
+        NativeFunction::new("abs", vec!["number"], abs),
+        NativeFunction::new("min", vec!["number"], |args| extreme(args, Ordering::Less))
+            .variadic("number"),
+        NativeFunction::new("max", vec!["number"], |args| {
+            extreme(args, Ordering::Greater)
+        })
+        .variadic("number"),
+        NativeFunction::new("round", vec!["number", "int"], round).optional(1),
+        NativeFunction::new("range", vec!["int", "int", "int"], range).optional(2),
+        NativeFunction::new("assert", vec!["bool", "str"], assert).optional(1),
+        NativeFunction::new("panic", vec!["any"], |args| {
+            let message = args
+                .first()
+                .map_or_else(|| String::from("explicit panic"), Type::to_string);
+            Err(NativeError::call(ErrorKind::Panic { message }))
+        })
+        .optional(1),
+    ]
+}
+
+/// An argument that has the right type but a value the function can't use
+fn invalid(function: &str, index: usize, message: String) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::InvalidArgument {
+            function: function.to_string(),
+            message,
+        },
+    )
+}
+
+fn mismatch(index: usize, expected: &str, found: &Type) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::TypeMismatch {
+            expected: expected.to_string(),
+            found: found.type_name().to_string(),
+        },
+    )
+}
+
+fn overflow(index: usize, operation: &'static str) -> NativeError {
+    NativeError::argument(index, ErrorKind::Overflow { operation })
+}
+
+/// The number of characters in a string, items in a list, entries in a map, or rows in a table
+/// or series
+fn len(args: &[Type]) -> Result<Type, NativeError> {
+    let n = match &args[0] {
+        Type::Str(s) => s.chars().count(),
+        Type::List(items) => items.len(),
+        Type::Map(map) => map.len(),
+        Type::Table(table) => table.rows(),
+        Type::Series(column) => column.len(),
+        other => return Err(mismatch(0, "str, list, map, table or series", other)),
+    };
+    i32::try_from(n)
+        .map(Type::Int)
+        .map_err(|_| overflow(0, "count its length"))
+}
+
+/// Truncates floats towards zero, parses strings, and turns bools into 0 or 1
+fn int(args: &[Type]) -> Result<Type, NativeError> {
+    match &args[0] {
+        Type::Int(i) => Ok(Type::Int(*i)),
+        Type::Float(x) => float_to_int("int", *x, x.trunc()),
+        Type::Str(s) => s
+            .trim()
+            .parse()
+            .map(Type::Int)
+            .map_err(|_| invalid("int", 0, format!("'{}' isn't an integer", s))),
+        Type::Bool(b) => Ok(Type::Int(*b as i32)),
+        other => Err(mismatch(0, "int, float, str or bool", other)),
+    }
+}
+
+fn float(args: &[Type]) -> Result<Type, NativeError> {
+    match &args[0] {
+        Type::Int(i) => Ok(Type::Float(*i as f32)),
+        Type::Float(x) => Ok(Type::Float(*x)),
+        Type::Str(s) => s
+            .trim()
+            .parse()
+            .map(Type::Float)
+            .map_err(|_| invalid("float", 0, format!("'{}' isn't a number", s))),
+        Type::Bool(b) => Ok(Type::Float(if *b { 1.0 } else { 0.0 })),
+        other => Err(mismatch(0, "int, float, str or bool", other)),
+    }
+}
+
+/// Turns `whole`, the result of rounding `x` somehow, into an int
+fn float_to_int(function: &str, x: f32, whole: f32) -> Result<Type, NativeError> {
+    if !x.is_finite() {
+        return Err(invalid(function, 0, format!("{} has no integer value", x)));
+    }
+    // i32::MAX isn't a float, so the upper bound has to be exclusive
+    if whole < i32::MIN as f32 || whole >= i32::MAX as f32 {
+        return Err(overflow(0, "convert to int"));
+    }
+    Ok(Type::Int(whole as i32))
+}
+
+fn abs(args: &[Type]) -> Result<Type, NativeError> {
+    match &args[0] {
+        Type::Int(i) => i
+            .checked_abs()
+            .map(Type::Int)
+            .ok_or_else(|| overflow(0, "take the absolute value")),
+        Type::Float(x) => Ok(Type::Float(x.abs())),
+        other => Err(mismatch(0, "number", other)),
+    }
+}
+
+/// Finds the argument that compares as `wanted` against every other one
+/// They all have to be ints or all floats, as they do to be compared with `<`, and a NaN anywhere
+/// makes the result NaN
+fn extreme(args: &[Type], wanted: Ordering) -> Result<Type, NativeError> {
+    let mut best = args[0].clone();
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        best = match (&best, arg) {
+            (Type::Int(a), Type::Int(b)) if b.cmp(a) == wanted => Type::Int(*b),
+            (Type::Int(a), Type::Int(_)) => Type::Int(*a),
+            (Type::Float(a), Type::Float(b)) if a.is_nan() || b.is_nan() => Type::Float(f32::NAN),
+            (Type::Float(a), Type::Float(b)) if b.partial_cmp(a) == Some(wanted) => Type::Float(*b),
+            (Type::Float(a), Type::Float(_)) => Type::Float(*a),
+            _ => {
+                return Err(mismatch(i, args[0].type_name(), arg)
+                    .note("use float() on the ints to compare them with floats"))
+            }
+        };
+    }
+    Ok(best)
+}
+
+/// Rounds half away from zero, to an int, or to a number of decimal places if given
+fn round(args: &[Type]) -> Result<Type, NativeError> {
+    match (&args[0], args.get(1)) {
+        (Type::Int(i), None) => Ok(Type::Int(*i)),
+        (Type::Float(x), None) => float_to_int("round", *x, x.round()),
+        (Type::Int(i), Some(Type::Int(digits))) if *digits >= 0 => Ok(Type::Int(*i)),
+        (Type::Int(i), Some(Type::Int(digits))) => {
+            // Negative digits round to tens, hundreds and so on
+            let scale = 10i64.checked_pow(digits.unsigned_abs()).unwrap_or(i64::MAX);
+            let i = i64::from(*i);
+            let rounded = (i + i.signum() * (scale / 2)) / scale * scale;
+            i32::try_from(rounded)
+                .map(Type::Int)
+                .map_err(|_| overflow(0, "round"))
+        }
+        (Type::Float(x), Some(Type::Int(digits))) => {
+            // Scaling in double precision keeps the scaling itself from adding rounding error
+            let scale = 10f64.powi(*digits);
+            let rounded = (f64::from(*x) * scale).round() / scale;
+            Ok(Type::Float(if rounded.is_finite() {
+                rounded as f32
+            } else {
+                *x
+            }))
+        }
+        (other, _) => Err(mismatch(0, "number", other)),
+    }
+}
+
+/// The most items `range` makes, since they're all made up front
+const MAX_RANGE: i64 = 10_000_000;
+
+/// `range(stop)`, `range(start, stop)` or `range(start, stop, step)`, never including `stop`
+fn range(args: &[Type]) -> Result<Type, NativeError> {
+    let int = |i: usize| match args.get(i) {
+        Some(Type::Int(n)) => Some(i64::from(*n)),
+        _ => None,
+    };
+    let (start, stop) = match (int(0), int(1)) {
+        (Some(stop), None) => (0, stop),
+        (Some(start), Some(stop)) => (start, stop),
+        _ => (0, 0),
+    };
+    let step = int(2).unwrap_or(1);
+    if step == 0 {
+        return Err(invalid("range", 2, String::from("step can't be zero")));
+    }
+    let len = ((stop - start) / step + i64::from((stop - start) % step != 0)).max(0);
+    if len > MAX_RANGE {
+        return Err(NativeError::call(ErrorKind::InvalidArgument {
+            function: String::from("range"),
+            message: format!(
+                "a range can have at most {} items, this one would have {}",
+                MAX_RANGE, len
+            ),
+        }));
+    }
+
+    let mut items = Vec::with_capacity(len as usize);
+    let mut i = start;
+    while (step > 0 && i < stop) || (step < 0 && i > stop) {
+        items.push(Type::Int(i as i32));
+        i += step;
+    }
+    Ok(Type::List(Rc::new(items)))
+}
+
+fn assert(args: &[Type]) -> Result<Type, NativeError> {
+    match args {
+        [Type::Bool(true), ..] => Ok(Type::Nil),
+        [_, Type::Str(message)] => Err(NativeError::call(ErrorKind::AssertionFailed {
+            message: Some(message.clone()),
+        })),
+        _ => Err(NativeError::call(ErrorKind::AssertionFailed {
+            message: None,
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::evaluate;
+
+    fn value(source: &str) -> String {
+        evaluate(source).unwrap()
+    }
+
+    fn code(source: &str) -> String {
+        evaluate(source).unwrap_err()[..6].to_string()
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(value("len(\"héllo\");"), "5");
+        assert_eq!(value("type_of(1.5);"), "float");
+        assert_eq!(value("str(12);"), "12");
+        assert_eq!(value("int(2.9);"), "2");
+        assert_eq!(value("int(\" 42 \");"), "42");
+        assert_eq!(value("float(3);"), "3.0");
+        assert_eq!(value("float(\"1.5\");"), "1.5");
+        assert_eq!(
+            evaluate("int(\"x\");").unwrap_err(),
+            "SK0800: Invalid argument to 'int': 'x' isn't an integer"
+        );
+        assert_eq!(code("len(1);"), "SK0200");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(value("abs(0 - 3);"), "3");
+        assert_eq!(code("abs(0 - 2147483647 - 1);"), "SK0401");
+        assert_eq!(value("min(3, 1, 2);"), "1");
+        assert_eq!(value("max(1);"), "1");
+        assert_eq!(
+            evaluate("min();").unwrap_err(),
+            "SK0201: 'min' takes at least 1 argument but 0 were given"
+        );
+        assert_eq!(value("round(2.5);"), "3");
+        assert_eq!(value("round(1.2345, 2);"), "1.23");
+        assert_eq!(value("round(1250, 0 - 2);"), "1300");
+        assert_eq!(code("round(1.0 / 0.0);"), "SK0800");
+        assert_eq!(
+            evaluate("round(1, 2, 3);").unwrap_err(),
+            "SK0201: 'round' takes 1 to 2 arguments but 3 were given"
+        );
+    }
+
+    #[test]
+    fn ranges_and_panics() {
+        assert_eq!(value("range(3);"), "[0, 1, 2]");
+        assert_eq!(value("range(1, 7, 2);"), "[1, 3, 5]");
+        assert_eq!(value("range(5, 0, 0 - 2);"), "[5, 3, 1]");
+        assert_eq!(code("range(1, 2, 0);"), "SK0800");
+        assert_eq!(code("range(\"a\");"), "SK0200");
+        assert_eq!(
+            evaluate("panic(\"boom\");").unwrap_err(),
+            "SK0802: Panicked: boom"
+        );
+        assert_eq!(
+            evaluate("panic();").unwrap_err(),
+            "SK0802: Panicked: explicit panic"
+        );
+        // Built-ins are constants
+        assert!(evaluate("len -> 1;").is_err());
+    }
+
+    #[test]
+    fn range_limit() {
+        assert_eq!(
+            evaluate("len(range(0, 10000000));").unwrap(),
+            "10000000"
+        );
+        assert_eq!(
+            evaluate("range(0, 2147483647);").unwrap_err(),
+            "SK0800: Invalid argument to 'range': a range can have at most 10000000 items, this \
+             one would have 2147483647"
+        );
+        assert_eq!(
+            evaluate("range(-2147483648, 2147483647, 2);").unwrap_err(),
+            "SK0800: Invalid argument to 'range': a range can have at most 10000000 items, this \
+             one would have 2147483648"
+        );
+    }
+
+    #[test]
+    fn extremes() {
+        assert_eq!(evaluate("min(3, 1, 2);").unwrap(), "1");
+        assert_eq!(evaluate("max(1.5, 2.5);").unwrap(), "2.5");
+        assert_eq!(evaluate("max(1.0, 0.0 / 0.0);").unwrap(), "NaN");
+        assert_eq!(
+            evaluate("min(1, 2.5);").unwrap_err(),
+            "SK0200: Mismatched types, expected int but found float"
+        );
+        assert_eq!(
+            evaluate("max(1.5, 2);").unwrap_err(),
+            "SK0200: Mismatched types, expected float but found int"
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! A seedable pseudo-random generator, xoshiro256** seeded through SplitMix64
+//!
+//! Everything here is built from integer operations and correctly rounded float arithmetic, with
+//! its own logarithm rather than the platform's, so a seed gives the same numbers on every machine.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+#[derive(Debug, Clone)]
+pub struct Generator {
+    state: [u64; 4],
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        let mut generator = Self { state: [0; 4] };
+        generator.reseed(seed);
+        generator
+    }
+
+    /// A generator seeded from the operating system's randomness, for when no seed was asked for
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        Self::new(hasher.finish())
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        let mut x = seed;
+        for word in &mut self.state {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *word = z ^ (z >> 31);
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// A float in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A float in (0, 1), for when zero can't be taken the logarithm of
+    fn open_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 12) as f64 + 0.5) / (1u64 << 52) as f64
+    }
+
+    /// An integer in [0, n), without the bias of taking a remainder
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        // Draws in the incomplete last stretch of `n`s would favour small results
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+
+    /// A standard normal draw, by Marsaglia's polar method
+    pub fn normal(&mut self) -> f64 {
+        loop {
+            let u = 2.0 * self.next_f64() - 1.0;
+            let v = 2.0 * self.next_f64() - 1.0;
+            let s = u * u + v * v;
+            if s > 0.0 && s < 1.0 {
+                return u * (-2.0 * ln(s) / s).sqrt();
+            }
+        }
+    }
+
+    /// The number of successes in `n` trials that each succeed with probability `p`
+    pub fn binomial(&mut self, n: u64, p: f64) -> u64 {
+        if p <= 0.0 || n == 0 {
+            return 0;
+        }
+        if p >= 1.0 {
+            return n;
+        }
+        // Counting failures is the same problem, and the methods below want p <= 0.5
+        if p > 0.5 {
+            return n - self.binomial(n, 1.0 - p);
+        }
+        if n as f64 * p < 10.0 {
+            self.binomial_waiting(n, p)
+        } else {
+            self.binomial_btrs(n, p)
+        }
+    }
+
+    /// Skips from success to success, with geometric gaps between them
+    fn binomial_waiting(&mut self, n: u64, p: f64) -> u64 {
+        let log_q = ln(1.0 - p);
+        let mut successes = 0;
+        let mut trial = 0.0;
+        loop {
+            trial += (ln(self.open_f64()) / log_q).floor() + 1.0;
+            if trial > n as f64 {
+                return successes;
+            }
+            successes += 1;
+        }
+    }
+
+    /// Hörmann's transformed rejection with squeeze, for when many successes are expected
+    fn binomial_btrs(&mut self, n: u64, p: f64) -> u64 {
+        let q = 1.0 - p;
+        let nf = n as f64;
+        let spq = (nf * p * q).sqrt();
+        let b = 1.15 + 2.53 * spq;
+        let a = -0.0873 + 0.0248 * b + 0.01 * p;
+        let c = nf * p + 0.5;
+        let vr = 0.92 - 4.2 / b;
+        let alpha = (2.83 + 5.1 / b) * spq;
+        let lpq = ln(p / q);
+        let m = ((nf + 1.0) * p).floor();
+        let h = ln_factorial(m) + ln_factorial(nf - m);
+        loop {
+            let u = self.next_f64() - 0.5;
+            let v = self.next_f64();
+            let us = 0.5 - u.abs();
+            let k = ((2.0 * a / us + b) * u + c).floor();
+            if k < 0.0 || k > nf {
+                continue;
+            }
+            if us >= 0.07 && v <= vr {
+                return k as u64;
+            }
+            let v = ln(v * alpha / (a / (us * us) + b));
+            if v <= h - ln_factorial(k) - ln_factorial(nf - k) + (k - m) * lpq {
+                return k as u64;
+            }
+        }
+    }
+
+    /// The number of events in an interval where `lambda` are expected
+    pub fn poisson(&mut self, lambda: f64) -> u64 {
+        if lambda <= 0.0 {
+            return 0;
+        }
+        if lambda < 10.0 {
+            self.poisson_arrivals(lambda)
+        } else {
+            self.poisson_ptrs(lambda)
+        }
+    }
+
+    /// Counts exponentially spaced arrivals until the interval is over
+    fn poisson_arrivals(&mut self, lambda: f64) -> u64 {
+        let mut events = 0;
+        let mut time = 0.0;
+        loop {
+            time -= ln(self.open_f64()) / lambda;
+            if time > 1.0 {
+                return events;
+            }
+            events += 1;
+        }
+    }
+
+    /// Hörmann's transformed rejection with squeeze
+    fn poisson_ptrs(&mut self, lambda: f64) -> u64 {
+        let slam = lambda.sqrt();
+        let loglam = ln(lambda);
+        let b = 0.931 + 2.53 * slam;
+        let a = -0.059 + 0.02483 * b;
+        let invalpha = 1.1239 + 1.1328 / (b - 3.4);
+        let vr = 0.9277 - 3.6224 / (b - 2.0);
+        loop {
+            let u = self.next_f64() - 0.5;
+            let v = self.next_f64();
+            let us = 0.5 - u.abs();
+            let k = ((2.0 * a / us + b) * u + lambda + 0.43).floor();
+            if us >= 0.07 && v <= vr {
+                return k as u64;
+            }
+            if k < 0.0 || (us < 0.013 && v > us) {
+                continue;
+            }
+            if v > 0.0
+                && ln(v) + ln(invalpha) - ln(a / (us * us) + b)
+                    <= -lambda + k * loglam - ln_factorial(k)
+            {
+                return k as u64;
+            }
+        }
+    }
+
+    /// Shuffles in place with Fisher-Yates
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+const LN_2: f64 = std::f64::consts::LN_2;
+
+/// The natural logarithm of a positive finite number
+/// Reduces to [sqrt(1/2), sqrt(2)) by powers of two, then sums the series for atanh
+pub fn ln(x: f64) -> f64 {
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if x.is_infinite() {
+        return x;
+    }
+
+    let (mut m, mut e) = (x, 0i32);
+    // Subnormals are scaled up first so the exponent bits are meaningful
+    if m < f64::MIN_POSITIVE {
+        m *= (1u64 << 54) as f64;
+        e -= 54;
+    }
+    let bits = m.to_bits();
+    e += ((bits >> 52) & 0x7ff) as i32 - 1023;
+    m = f64::from_bits((bits & !(0x7ff << 52)) | (1023 << 52));
+    if m >= std::f64::consts::SQRT_2 {
+        m /= 2.0;
+        e += 1;
+    }
+
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+    let mut term = s;
+    let mut sum = 0.0;
+    for k in 0..14 {
+        sum += term / f64::from(2 * k + 1);
+        term *= s2;
+    }
+    2.0 * sum + f64::from(e) * LN_2
+}
+
+/// ln(k!) for a whole number k, exactly summed when small and by Stirling's series otherwise
+pub fn ln_factorial(k: f64) -> f64 {
+    if k < 10.0 {
+        let mut product = 1.0;
+        let mut i = 2.0;
+        while i <= k {
+            product *= i;
+            i += 1.0;
+        }
+        return ln(product);
+    }
+    const COEFFICIENTS: [f64; 5] = [
+        1.0 / 12.0,
+        -1.0 / 360.0,
+        1.0 / 1260.0,
+        -1.0 / 1680.0,
+        1.0 / 1188.0,
+    ];
+    let x = k + 1.0;
+    let x2 = 1.0 / (x * x);
+    let mut series = 0.0;
+    for c in COEFFICIENTS.iter().rev() {
+        series = series * x2 + c;
+    }
+    series / x + 0.5 * ln(2.0 * std::f64::consts::PI) + (x - 0.5) * ln(x) - x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mean(draws: impl Iterator<Item = f64>, n: usize) -> f64 {
+        draws.take(n).sum::<f64>() / n as f64
+    }
+
+    #[test]
+    fn seeds_are_deterministic() {
+        let mut a = Generator::new(7);
+        let mut b = Generator::new(7);
+        let mut c = Generator::new(8);
+        let first: Vec<_> = (0..4).map(|_| a.next_u64()).collect();
+        assert_eq!(first, (0..4).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(first, (0..4).map(|_| c.next_u64()).collect::<Vec<_>>());
+
+        a.reseed(7);
+        assert_eq!(a.next_u64(), first[0]);
+    }
+
+    #[test]
+    fn draws_stay_in_range() {
+        let mut g = Generator::new(1);
+        for _ in 0..10_000 {
+            let x = g.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            assert!(g.below(6) < 6);
+        }
+        assert_eq!(g.below(1), 0);
+        assert!(g.binomial(10, 0.5) <= 10);
+        assert_eq!(g.binomial(10, 0.0), 0);
+        assert_eq!(g.binomial(10, 1.0), 10);
+
+        let mut items: Vec<_> = (0..20).collect();
+        g.shuffle(&mut items);
+        assert_ne!(items, (0..20).collect::<Vec<_>>());
+        items.sort();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn distributions_have_the_right_means() {
+        let mut g = Generator::new(42);
+        let n = 20_000;
+        assert!(mean(std::iter::from_fn(|| Some(g.normal())), n).abs() < 0.05);
+        // Both the small-mean and the large-mean algorithms
+        let binomial = mean(std::iter::from_fn(|| Some(g.binomial(20, 0.3) as f64)), n);
+        assert!((binomial - 6.0).abs() < 0.1);
+        let binomial = mean(std::iter::from_fn(|| Some(g.binomial(1000, 0.4) as f64)), n);
+        assert!((binomial - 400.0).abs() < 1.0);
+        let poisson = mean(std::iter::from_fn(|| Some(g.poisson(3.0) as f64)), n);
+        assert!((poisson - 3.0).abs() < 0.1);
+        let poisson = mean(std::iter::from_fn(|| Some(g.poisson(50.0) as f64)), n);
+        assert!((poisson - 50.0).abs() < 0.3);
+    }
+
+    #[test]
+    fn logarithms() {
+        for x in [1e-310, 0.1, 0.5, 1.0, 2.0, 10.0, 1e300] {
+            assert!(
+                (ln(x) - x.ln()).abs() <= 1e-15 * x.ln().abs().max(1.0),
+                "ln({})",
+                x
+            );
+        }
+        assert_eq!(ln(0.0), f64::NEG_INFINITY);
+        assert!(ln(-1.0).is_nan());
+        // ln(20!) by Stirling's series against the exact product
+        let exact: f64 = (2..=20).map(|i| (i as f64).ln()).sum();
+        assert!((ln_factorial(20.0) - exact).abs() < 1e-12);
+        assert!((ln_factorial(5.0) - 120f64.ln()).abs() < 1e-14);
+    }
+}
@@ -0,0 +1,826 @@
+//! Regular expressions for `std.regex` and `r"..."` literals
+//!
+//! Patterns are compiled to a small instruction set and run on a Pike VM, which tries every
+//! alternative at once rather than backtracking. Matching takes time proportional to the length
+//! of the text times the size of the pattern, whatever the pattern is.
+//!
+//! The syntax is the usual one: `.`, `[...]` classes with ranges and `^` negation, the escapes
+//! `\d \w \s \D \W \S \b \B \n \t \r`, `^` and `$` anchors, `|`, groups `(...)`, non-capturing
+//! `(?:...)`, named `(?<name>...)` or `(?P<name>...)`, and `* + ? {n} {n,} {n,m}` with a trailing
+//! `?` to repeat as few times as possible.
+
+use std::fmt;
+
+use crate::{errors::error::ErrorKind, frontend::expr::Span};
+
+/// The most a `{n,m}` repetition may ask for
+const MAX_REPEAT: u32 = 1000;
+/// The most instructions a pattern may compile to
+const MAX_PROGRAM: usize = 100_000;
+
+/// What was wrong with a pattern, and where in it, in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    pub message: String,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl PatternError {
+    /// Where the error is in the source, given the span of the `r"..."` literal it's from
+    pub fn span_in(&self, literal: Span) -> Span {
+        let start = literal.0 + 2 + self.offset;
+        Span(start, start + self.len.max(1) - 1)
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidPattern {
+            message: self.message.clone(),
+        }
+    }
+}
+
+/// A compiled pattern
+pub struct Regex {
+    pattern: String,
+    program: Vec<Inst>,
+    /// The name of each group, with the whole match as group 0
+    names: Vec<Option<String>>,
+}
+
+/// Where each group matched, in bytes, with the whole match as group 0
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Regex").field(&self.pattern).finish()
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, PatternError> {
+        let mut parser = Parser {
+            chars: pattern.char_indices().collect(),
+            len: pattern.len(),
+            i: 0,
+            names: vec![None],
+        };
+        let node = parser.alternation()?;
+        if let Some(&(offset, _)) = parser.chars.get(parser.i) {
+            // The only thing that stops an alternation early is a `)` without a `(`
+            return Err(PatternError {
+                message: String::from("unmatched ')'"),
+                offset,
+                len: 1,
+            });
+        }
+
+        let mut program = vec![Inst::Save(0)];
+        compile(&node, &mut program);
+        program.push(Inst::Save(1));
+        program.push(Inst::Match);
+        if program.len() > MAX_PROGRAM {
+            return Err(PatternError {
+                message: String::from("the pattern is too large"),
+                offset: 0,
+                len: pattern.len(),
+            });
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            program,
+            names: parser.names,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The names of the groups, `None` for the whole match and groups without one
+    pub fn names(&self) -> &[Option<String>] {
+        &self.names
+    }
+
+    /// The number of the group called `name`
+    pub fn group(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n.as_deref() == Some(name))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.captures(text).is_some()
+    }
+
+    /// The first match
+    pub fn captures(&self, text: &str) -> Option<Captures> {
+        let text = Text::new(text);
+        self.search(&text, 0).map(|slots| text.captures(&slots))
+    }
+
+    /// Every match that doesn't overlap an earlier one, left to right
+    pub fn captures_all(&self, text: &str) -> Vec<Captures> {
+        let text = Text::new(text);
+        let mut all = Vec::new();
+        let mut from = 0;
+        while from <= text.chars.len() {
+            let Some(slots) = self.search(&text, from) else {
+                break;
+            };
+            let (start, end) = (slots[0].unwrap_or(from), slots[1].unwrap_or(from));
+            all.push(text.captures(&slots));
+            // An empty match would be found again at the same place
+            from = if end == start { end + 1 } else { end };
+        }
+        all
+    }
+
+    /// Finds the leftmost match starting at or after the character `from`, preferring
+    /// alternatives in the order they're written
+    fn search(&self, text: &Text, from: usize) -> Option<Vec<Option<usize>>> {
+        let slots = self.names.len() * 2;
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched = None;
+
+        for pos in from..=text.chars.len() {
+            if matched.is_none() {
+                // A new attempt starting here, behind every attempt that started earlier
+                self.add(&mut current, 0, pos, vec![None; slots], text);
+            }
+            // Once something has matched, nothing new is started, so this is the end
+            if current.list.is_empty() && matched.is_some() {
+                break;
+            }
+
+            let c = text.chars.get(pos).copied();
+            for (pc, caps) in std::mem::take(&mut current.list) {
+                match &self.program[pc] {
+                    Inst::Match => {
+                        matched = Some(caps);
+                        // Everything after this thread was a worse alternative
+                        break;
+                    }
+                    inst => {
+                        if c.is_some_and(|c| inst.accepts(c)) {
+                            self.add(&mut next, pc + 1, pos + 1, caps, text);
+                        }
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        matched
+    }
+
+    /// Adds a thread at `pc`, following jumps, splits, saves and assertions to the instructions
+    /// that need a character, in priority order
+    fn add(
+        &self,
+        threads: &mut Threads,
+        pc: usize,
+        pos: usize,
+        caps: Vec<Option<usize>>,
+        text: &Text,
+    ) {
+        let mut stack = vec![(pc, caps)];
+        while let Some((pc, mut caps)) = stack.pop() {
+            if threads.seen[pc] == threads.generation {
+                continue;
+            }
+            threads.seen[pc] = threads.generation;
+            match &self.program[pc] {
+                Inst::Jump(to) => stack.push((*to, caps)),
+                Inst::Split(first, second) => {
+                    stack.push((*second, caps.clone()));
+                    stack.push((*first, caps));
+                }
+                Inst::Save(slot) => {
+                    caps[*slot] = Some(pos);
+                    stack.push((pc + 1, caps));
+                }
+                Inst::Assert(assertion) => {
+                    if assertion.holds(text, pos) {
+                        stack.push((pc + 1, caps));
+                    }
+                }
+                _ => threads.list.push((pc, caps)),
+            }
+        }
+    }
+}
+
+/// The text being searched, split into characters so positions are code points
+struct Text {
+    chars: Vec<char>,
+    /// Where each character starts in bytes, plus the length at the end
+    offsets: Vec<usize>,
+}
+
+impl Text {
+    fn new(text: &str) -> Self {
+        let (offsets, chars): (Vec<usize>, Vec<char>) = text.char_indices().unzip();
+        let mut offsets = offsets;
+        offsets.push(text.len());
+        Self { chars, offsets }
+    }
+
+    fn captures(&self, slots: &[Option<usize>]) -> Captures {
+        slots
+            .chunks(2)
+            .map(|pair| match pair {
+                [Some(start), Some(end)] => Some((self.offsets[*start], self.offsets[*end])),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// The threads alive at one position, each an instruction and what it has captured
+struct Threads {
+    list: Vec<(usize, Vec<Option<usize>>)>,
+    /// Which instructions already have a thread, stamped with `generation`
+    seen: Vec<usize>,
+    generation: usize,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Self {
+            list: Vec::new(),
+            seen: vec![0; len],
+            generation: 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.generation += 1;
+    }
+}
+
+#[derive(Debug)]
+enum Inst {
+    Char(char),
+    /// Any character but a newline
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    /// Try the first, then the second
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    Match,
+}
+
+impl Inst {
+    fn accepts(&self, c: char) -> bool {
+        match self {
+            Inst::Char(expected) => c == *expected,
+            Inst::Any => c != '\n',
+            Inst::Class(class) => class.contains(c),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+impl Assertion {
+    fn holds(self, text: &Text, pos: usize) -> bool {
+        let word = |i: Option<usize>| {
+            i.and_then(|i| text.chars.get(i))
+                .is_some_and(|c| is_word(*c))
+        };
+        let boundary = word(pos.checked_sub(1)) != word(Some(pos));
+        match self {
+            Assertion::Start => pos == 0,
+            Assertion::End => pos == text.chars.len(),
+            Assertion::WordBoundary => boundary,
+            Assertion::NotWordBoundary => !boundary,
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ClassItem {
+    Range(char, char),
+    /// `\d`, `\w` or `\s`, or their opposites
+    Perl(char, bool),
+}
+
+impl Class {
+    fn contains(&self, c: char) -> bool {
+        let found = self.items.iter().any(|item| match *item {
+            ClassItem::Range(low, high) => low <= c && c <= high,
+            ClassItem::Perl(kind, negated) => perl(kind, c) != negated,
+        });
+        found != self.negated
+    }
+}
+
+fn perl(kind: char, c: char) -> bool {
+    match kind {
+        'd' => c.is_ascii_digit(),
+        'w' => is_word(c),
+        _ => c.is_whitespace(),
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    // Patterns over the size limit are rejected once compiled, this stops them growing further
+    if program.len() > MAX_PROGRAM {
+        return;
+    }
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Assert(assertion) => program.push(Inst::Assert(*assertion)),
+        Node::Group(inner, None) => compile(inner, program),
+        Node::Group(inner, Some(index)) => {
+            program.push(Inst::Save(index * 2));
+            compile(inner, program);
+            program.push(Inst::Save(index * 2 + 1));
+        }
+        Node::Concat(nodes) => nodes.iter().for_each(|n| compile(n, program)),
+        Node::Alternate(branches) => {
+            let mut jumps = Vec::new();
+            for (i, branch) in branches.iter().enumerate() {
+                if i + 1 == branches.len() {
+                    compile(branch, program);
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(branch, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                let after = program.len();
+                program[split] = Inst::Split(split + 1, after);
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            let split = |program: &mut Vec<Inst>, at: usize, body: usize, out: usize| {
+                program[at] = if *greedy {
+                    Inst::Split(body, out)
+                } else {
+                    Inst::Split(out, body)
+                };
+            };
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Match);
+                    compile(node, program);
+                    program.push(Inst::Jump(start));
+                    let out = program.len();
+                    split(program, start, start + 1, out);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Match);
+                        compile(node, program);
+                    }
+                    let out = program.len();
+                    for at in splits {
+                        split(program, at, at + 1, out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<(usize, char)>,
+    len: usize,
+    i: usize,
+    names: Vec<Option<String>>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.i).map(|&(_, c)| c)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars.get(self.i).map_or(self.len, |&(o, _)| o)
+    }
+
+    fn error<T>(&self, message: &str, offset: usize, len: usize) -> Result<T, PatternError> {
+        Err(PatternError {
+            message: message.to_string(),
+            offset,
+            len,
+        })
+    }
+
+    fn alternation(&mut self) -> Result<Node, PatternError> {
+        let mut branches = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.i += 1;
+            branches.push(self.concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap_or(Node::Empty)
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, PatternError> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.repeat(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn repeat(&mut self, mut node: Node) -> Result<Node, PatternError> {
+        let mut repeated = false;
+        loop {
+            let start = self.offset();
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.counted()? {
+                    Some(bounds) => bounds,
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            // `counted` has already moved past its `}`, the others are a single character
+            if start == self.offset() {
+                self.i += 1;
+            }
+            if repeated {
+                return self.error(
+                    "a repetition can't be repeated, wrap it in a group",
+                    start,
+                    1,
+                );
+            }
+            if matches!(node, Node::Assert(_) | Node::Empty) {
+                return self.error("there's nothing to repeat", start, 1);
+            }
+            let greedy = self.peek() != Some('?');
+            if !greedy {
+                self.i += 1;
+            }
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+                greedy,
+            };
+            repeated = true;
+        }
+    }
+
+    /// Reads `{n}`, `{n,}` or `{n,m}`, consuming it
+    /// Returns `None` without consuming anything if the `{` isn't one, so it's a literal
+    fn counted(&mut self) -> Result<Option<(u32, Option<u32>)>, PatternError> {
+        let start = self.i;
+        let offset = self.offset();
+        self.i += 1;
+        let min = self.number();
+        let max = if self.peek() == Some(',') {
+            self.i += 1;
+            self.number()
+        } else {
+            min
+        };
+        if min.is_none() || self.peek() != Some('}') {
+            self.i = start;
+            return Ok(None);
+        }
+        self.i += 1;
+        let len = self.offset() - offset;
+
+        let min = min.unwrap_or(0);
+        if min > MAX_REPEAT || max.is_some_and(|m| m > MAX_REPEAT) {
+            return self.error(
+                &format!("repetitions are limited to {}", MAX_REPEAT),
+                offset,
+                len,
+            );
+        }
+        if max.is_some_and(|m| m < min) {
+            return self.error("the range of repetitions is backwards", offset, len);
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let mut n: Option<u32> = None;
+        while let Some(d) = self.peek().and_then(|c| c.to_digit(10)) {
+            n = Some(n.unwrap_or(0).saturating_mul(10).saturating_add(d));
+            self.i += 1;
+        }
+        n
+    }
+
+    fn atom(&mut self) -> Result<Node, PatternError> {
+        let offset = self.offset();
+        let Some(c) = self.peek() else {
+            return Ok(Node::Empty);
+        };
+        self.i += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Assert(Assertion::Start)),
+            '$' => Ok(Node::Assert(Assertion::End)),
+            '*' | '+' | '?' => self.error("there's nothing to repeat", offset, 1),
+            '[' => self.class(offset).map(Node::Class),
+            '(' => self.group(offset),
+            '\\' => self.escape(offset, false).map(|item| match item {
+                Escape::Char(c) => Node::Char(c),
+                Escape::Item(item) => Node::Class(Class {
+                    items: vec![item],
+                    negated: false,
+                }),
+                Escape::Assert(a) => Node::Assert(a),
+            }),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn group(&mut self, offset: usize) -> Result<Node, PatternError> {
+        let mut index = None;
+        if self.peek() == Some('?') {
+            self.i += 1;
+            match self.peek() {
+                Some(':') => self.i += 1,
+                Some('P') | Some('<') => {
+                    if self.peek() == Some('P') {
+                        self.i += 1;
+                    }
+                    if self.peek() != Some('<') {
+                        return self.error(
+                            "expected '<' to start the group's name",
+                            self.offset(),
+                            1,
+                        );
+                    }
+                    self.i += 1;
+                    let start = self.offset();
+                    let mut name = String::new();
+                    while let Some(c) = self.peek().filter(|c| is_word(*c)) {
+                        name.push(c);
+                        self.i += 1;
+                    }
+                    if self.peek() != Some('>') || name.is_empty() {
+                        return self.error("a group name is a word followed by '>'", start, 1);
+                    }
+                    self.i += 1;
+                    if self.names.iter().any(|n| n.as_deref() == Some(&name)) {
+                        return self.error(
+                            &format!("there's already a group named '{}'", name),
+                            start,
+                            name.len(),
+                        );
+                    }
+                    index = Some(self.names.len());
+                    self.names.push(Some(name));
+                }
+                _ => return self.error("unknown group flag", self.offset(), 1),
+            }
+        } else {
+            index = Some(self.names.len());
+            self.names.push(None);
+        }
+
+        let inner = self.alternation()?;
+        if self.peek() != Some(')') {
+            return self.error("this group is never closed", offset, 1);
+        }
+        self.i += 1;
+        Ok(Node::Group(Box::new(inner), index))
+    }
+
+    fn class(&mut self, offset: usize) -> Result<Class, PatternError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.i += 1;
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let at = self.offset();
+            let Some(c) = self.peek() else {
+                return self.error("this class is never closed", offset, 1);
+            };
+            self.i += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let low = match c {
+                '\\' => match self.escape(at, true)? {
+                    Escape::Char(c) => c,
+                    Escape::Item(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                    Escape::Assert(_) => unreachable!("escapes in classes aren't assertions"),
+                },
+                c => c,
+            };
+
+            // A `-` at the end is just a `-`
+            let dash = self.peek() == Some('-');
+            let after = self.chars.get(self.i + 1).map(|&(_, c)| c);
+            if !dash || after.is_none() || after == Some(']') {
+                items.push(ClassItem::Range(low, low));
+                continue;
+            }
+            self.i += 1;
+            let high_at = self.offset();
+            self.i += 1;
+            let high = match after {
+                Some('\\') => match self.escape(high_at, true)? {
+                    Escape::Char(c) => c,
+                    _ => return self.error("a range has to end in a character", high_at, 2),
+                },
+                Some(c) => c,
+                None => low,
+            };
+            if high < low {
+                return self.error("this range is backwards", at, self.offset() - at);
+            }
+            items.push(ClassItem::Range(low, high));
+        }
+        Ok(Class { items, negated })
+    }
+
+    /// Reads what follows a `\`, which is at `offset`
+    fn escape(&mut self, offset: usize, in_class: bool) -> Result<Escape, PatternError> {
+        let Some(c) = self.peek() else {
+            return self.error("the pattern ends in a '\\'", offset, 1);
+        };
+        self.i += 1;
+        Ok(match c {
+            'd' | 'w' | 's' => Escape::Item(ClassItem::Perl(c, false)),
+            'D' | 'W' | 'S' => Escape::Item(ClassItem::Perl(c.to_ascii_lowercase(), true)),
+            'b' if !in_class => Escape::Assert(Assertion::WordBoundary),
+            'B' if !in_class => Escape::Assert(Assertion::NotWordBoundary),
+            'n' => Escape::Char('\n'),
+            't' => Escape::Char('\t'),
+            'r' => Escape::Char('\r'),
+            '1'..='9' => {
+                return self.error("backreferences aren't supported in patterns", offset, 2)
+            }
+            c if c.is_alphanumeric() => {
+                return self.error(
+                    &format!("'\\{}' isn't an escape", c),
+                    offset,
+                    1 + c.len_utf8(),
+                )
+            }
+            c => Escape::Char(c),
+        })
+    }
+}
+
+enum Escape {
+    Char(char),
+    Item(ClassItem),
+    Assert(Assertion),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of each group of the first match, `-` for a group that didn't take part
+    fn groups(pattern: &str, text: &str) -> Option<Vec<String>> {
+        let captures = Regex::new(pattern).unwrap().captures(text)?;
+        Some(
+            captures
+                .iter()
+                .map(|group| match group {
+                    Some((start, end)) => text[*start..*end].to_string(),
+                    None => String::from("-"),
+                })
+                .collect(),
+        )
+    }
+
+    fn error(pattern: &str) -> (String, usize) {
+        let err = Regex::new(pattern).unwrap_err();
+        (err.message, err.offset)
+    }
+
+    #[test]
+    fn matching() {
+        assert_eq!(groups(r"\d+", "ab 123 45").unwrap(), ["123"]);
+        assert_eq!(groups(r"^a.c$", "abc").unwrap(), ["abc"]);
+        assert_eq!(groups(r"^a.c$", "abcd"), None);
+        assert_eq!(groups(r"[^a-c]+", "abcxyz").unwrap(), ["xyz"]);
+        assert_eq!(groups(r"\bcat\b", "concat cat").unwrap(), ["cat"]);
+        assert_eq!(groups(r"é.", "café!").unwrap(), ["é!"]);
+        assert_eq!(groups(r"a{2,3}", "aaaa").unwrap(), ["aaa"]);
+    }
+
+    #[test]
+    fn alternatives_and_laziness() {
+        // The leftmost match wins, then the alternative written first
+        assert_eq!(groups("b|ab", "xab").unwrap(), ["ab"]);
+        assert_eq!(groups("a|ab", "ab").unwrap(), ["a"]);
+        assert_eq!(groups("<.+>", "<a><b>").unwrap(), ["<a><b>"]);
+        assert_eq!(groups("<.+?>", "<a><b>").unwrap(), ["<a>"]);
+    }
+
+    #[test]
+    fn groups_and_names() {
+        let regex = Regex::new(r"(?<year>\d{4})-(?:\d\d)-(\d\d)?").unwrap();
+        assert_eq!(regex.group("year"), Some(1));
+        assert_eq!(
+            groups(r"(?<year>\d{4})-(?:\d\d)-(\d\d)?", "2024-05-").unwrap(),
+            ["2024-05-", "2024", "-"]
+        );
+        assert_eq!(groups("(a)|(b)", "b").unwrap(), ["b", "-", "b"]);
+    }
+
+    #[test]
+    fn all_matches() {
+        let regex = Regex::new("a*").unwrap();
+        let spans: Vec<_> = regex
+            .captures_all("baab")
+            .iter()
+            .map(|c| c[0].unwrap())
+            .collect();
+        assert_eq!(spans, [(0, 0), (1, 3), (3, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn pathological_patterns_stay_linear() {
+        let text = "a".repeat(5000);
+        assert!(!Regex::new("(a*)*b").unwrap().is_match(&text));
+    }
+
+    #[test]
+    fn pattern_errors() {
+        assert_eq!(error("a)"), (String::from("unmatched ')'"), 1));
+        assert_eq!(error("a{1001}").0, "repetitions are limited to 1000");
+        assert_eq!(error("(a{1000}){1000}").0, "the pattern is too large");
+    }
+}
@@ -0,0 +1,293 @@
+//! Reshaping tables between long form, a row per observation, and wide form, a column per value
+//! of some variable
+//!
+//! Pivoted columns come in the sorted order of the values that name them, and index rows in the
+//! order they first appear, so the same table always reshapes the same way.
+
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    group::Groups,
+    table::{Column, ColumnData, Table, TableError},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReshapeError {
+    /// A missing value where a column name has to come from
+    Missing {
+        column: String,
+        row: usize,
+    },
+    /// Columns melted into one that hold different types
+    Types {
+        first: (String, &'static str),
+        other: (String, &'static str),
+    },
+    /// Nothing to melt
+    NoValues,
+    Table(TableError),
+}
+
+/// The cells of a pivot table, the groups of rows with each pair of index and column values
+pub struct Cells {
+    groups: Groups,
+    /// The table with a row for each index value
+    index: Table,
+    /// The names of the new columns
+    names: Vec<String>,
+    /// The row and column of each cell
+    positions: Vec<(usize, usize)>,
+}
+
+impl Cells {
+    /// Splits the rows of `table` up by the `index` columns and the values of `columns`
+    pub fn new(table: Rc<Table>, index: &[&str], columns: &str) -> Result<Self, ReshapeError> {
+        let missing = |name: &str| {
+            ReshapeError::Table(TableError::Missing {
+                column: name.to_string(),
+            })
+        };
+        let column = table.column(columns).ok_or_else(|| missing(columns))?;
+        if let Some(row) = column.first_missing() {
+            return Err(ReshapeError::Missing {
+                column: columns.to_string(),
+                row,
+            });
+        }
+
+        // Each distinct value becomes a column, in sorted order
+        let mut first: HashMap<_, usize> = HashMap::new();
+        for row in 0..table.rows() {
+            first.entry(column.datum(row)).or_insert(row);
+        }
+        let mut order: Vec<usize> = first.into_values().collect();
+        order.sort_by(|&a, &b| column.compare(a, b));
+        let slots: HashMap<_, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(slot, &row)| (column.datum(row), slot))
+            .collect();
+
+        let rows = Groups::new(table.clone(), index).map_err(ReshapeError::Table)?;
+        let mut row_of = vec![0; table.rows()];
+        for (i, rows) in rows.rows().iter().enumerate() {
+            for &row in rows {
+                row_of[row] = i;
+            }
+        }
+
+        let mut keys = index.to_vec();
+        keys.push(columns);
+        let groups = Groups::new(table.clone(), &keys).map_err(ReshapeError::Table)?;
+        let positions = groups
+            .rows()
+            .iter()
+            .map(|rows| (row_of[rows[0]], slots[&column.datum(rows[0])]))
+            .collect();
+        Ok(Self {
+            index: rows.keys(),
+            names: order
+                .iter()
+                .map(|&row| column.get(row).to_string())
+                .collect(),
+            groups,
+            positions,
+        })
+    }
+
+    pub fn groups(&self) -> &Groups {
+        &self.groups
+    }
+
+    /// The first two rows of the first cell that has more than one
+    pub fn duplicate(&self) -> Option<(usize, usize)> {
+        self.groups
+            .rows()
+            .iter()
+            .find(|rows| rows.len() > 1)
+            .map(|rows| (rows[0], rows[1]))
+    }
+
+    /// Lays out `values`, one for each cell, with the index columns first and then a column for
+    /// each value of the pivoted column
+    /// Pairs of index and column values without any rows are missing
+    pub fn pivot(&self, values: &Column) -> Result<Table, ReshapeError> {
+        let mut grid = vec![vec![None; self.index.rows()]; self.names.len()];
+        for (cell, &(row, slot)) in self.positions.iter().enumerate() {
+            grid[slot][row] = Some((0, cell));
+        }
+        let mut columns = self.index.columns().to_vec();
+        for (name, picks) in self.names.iter().zip(grid) {
+            columns.push((name.clone(), Rc::new(Column::gather(&[values], &picks))));
+        }
+        Table::new(columns).map_err(ReshapeError::Table)
+    }
+}
+
+/// Turns each of the `values` columns into rows, with the `ids` columns repeated alongside, a
+/// column of the name each value came from, and a column of the values
+/// All the rows for the first of `values` come first, then all the rows for the next one.
+pub fn melt(
+    table: &Table,
+    ids: &[&str],
+    values: &[&str],
+    names: (&str, &str),
+) -> Result<Table, ReshapeError> {
+    let rows = table.rows();
+    gather(table, ids, values, names, |melted| {
+        (0..melted.len())
+            .flat_map(|source| (0..rows).map(move |row| (source, row)))
+            .collect()
+    })
+}
+
+/// Like `melt`, but with every column not in `values` repeated alongside, the values of each row
+/// together, and without the missing ones
+/// Stacking the table a pivot laid out gives back the rows it came from.
+pub fn stack(table: &Table, values: &[&str], names: (&str, &str)) -> Result<Table, ReshapeError> {
+    let ids: Vec<&str> = table
+        .names()
+        .filter(|name| !values.contains(name))
+        .collect();
+    gather(table, &ids, values, names, |stacked| {
+        (0..table.rows())
+            .flat_map(|row| (0..stacked.len()).map(move |source| (source, row)))
+            .filter(|&(source, row)| stacked[source].is_valid(row))
+            .collect()
+    })
+}
+
+/// Builds a long table out of the `values` columns, with a row for each (column, row) pair that
+/// `cells` picks from them
+fn gather(
+    table: &Table,
+    ids: &[&str],
+    values: &[&str],
+    names: (&str, &str),
+    cells: impl FnOnce(&[&Column]) -> Vec<(usize, usize)>,
+) -> Result<Table, ReshapeError> {
+    let column = |name: &str| {
+        table
+            .column(name)
+            .map(|column| column.as_ref())
+            .ok_or_else(|| {
+                ReshapeError::Table(TableError::Missing {
+                    column: name.to_string(),
+                })
+            })
+    };
+    let melted = values
+        .iter()
+        .map(|name| column(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(first) = melted.first() else {
+        return Err(ReshapeError::NoValues);
+    };
+    if let Some((name, other)) = values
+        .iter()
+        .zip(&melted)
+        .find(|(_, other)| other.type_name() != first.type_name())
+    {
+        return Err(ReshapeError::Types {
+            first: (values[0].to_string(), first.type_name()),
+            other: (name.to_string(), other.type_name()),
+        });
+    }
+
+    let cells = cells(&melted);
+    let repeated: Vec<usize> = cells.iter().map(|&(_, row)| row).collect();
+    let mut columns = Vec::with_capacity(ids.len() + 2);
+    for name in ids {
+        columns.push((name.to_string(), Rc::new(column(name)?.take(&repeated))));
+    }
+    let variable = cells
+        .iter()
+        .map(|&(source, _)| values[source].to_string())
+        .collect();
+    columns.push((
+        names.0.to_string(),
+        Rc::new(Column::new(ColumnData::Str(variable))),
+    ));
+    let picks: Vec<_> = cells.into_iter().map(Some).collect();
+    columns.push((
+        names.1.to_string(),
+        Rc::new(Column::gather(&melted, &picks)),
+    ));
+    Table::new(columns).map_err(ReshapeError::Table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{rows, table};
+
+    /// A long table of a value for some years in some cities
+    fn long() -> Rc<Table> {
+        Rc::new(table(
+            "city,year,n\nb,2021,1\na,2021,2\nb,2020,3\na,2020,4\nb,2022,5\n",
+        ))
+    }
+
+    #[test]
+    fn pivot() {
+        let table = long();
+        let cells = Cells::new(table.clone(), &["city"], "year").unwrap();
+        assert_eq!(cells.duplicate(), None);
+        let wide = cells.pivot(table.column("n").unwrap()).unwrap();
+        assert_eq!(rows(&wide), ["city 2020 2021 2022", "b 3 1 5", "a 4 2 NA"]);
+    }
+
+    #[test]
+    fn pivot_duplicates() {
+        let cells = Cells::new(long(), &[], "city").unwrap();
+        assert_eq!(cells.duplicate(), Some((0, 2)));
+    }
+
+    #[test]
+    fn melt() {
+        let table = long();
+        let melted = super::melt(&table, &["city"], &["year", "n"], ("var", "value")).unwrap();
+        let lines = rows(&melted);
+        assert_eq!(lines[0], "city var value");
+        assert_eq!(
+            lines[4..8],
+            ["a year 2020", "b year 2022", "b n 1", "a n 2"]
+        );
+
+        assert_eq!(
+            super::melt(&table, &[], &["n", "city"], ("var", "value")).unwrap_err(),
+            ReshapeError::Types {
+                first: (String::from("n"), "int"),
+                other: (String::from("city"), "str"),
+            }
+        );
+        assert_eq!(
+            super::melt(&table, &["city"], &[], ("var", "value")).unwrap_err(),
+            ReshapeError::NoValues
+        );
+    }
+
+    #[test]
+    fn stack() {
+        let table = long();
+        let cells = Cells::new(table.clone(), &["city"], "year").unwrap();
+        let wide = cells.pivot(table.column("n").unwrap()).unwrap();
+        let stacked = super::stack(&wide, &["2020", "2021", "2022"], ("year", "n")).unwrap();
+        // A row at a time, without the cell the pivot left missing
+        assert_eq!(
+            rows(&stacked),
+            [
+                "city year n",
+                "b 2020 3",
+                "b 2021 1",
+                "b 2022 5",
+                "a 2020 4",
+                "a 2021 2"
+            ]
+        );
+        assert_eq!(
+            super::stack(&table, &[], ("var", "value")).unwrap_err(),
+            ReshapeError::NoValues
+        );
+    }
+}
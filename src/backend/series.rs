@@ -0,0 +1,342 @@
+//! Series, single columns used as values, and operators applied to every value of one at once
+//!
+//! Missing values are tracked in a validity bitmap beside the values, rather than inside them, so
+//! the values themselves stay unboxed. They follow the same rules as missing scalars, described
+//! in `ops`: a row missing on either side is missing in the result.
+
+use crate::{errors::error::ErrorKind, frontend::expr::BinaryOperator};
+
+use super::{
+    ops,
+    table::{Column, ColumnData},
+    value::Type,
+};
+
+/// One bit per row, set where the row has a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    /// A bitmap of `len` bits, all set or all clear
+    pub fn new(len: usize, set: bool) -> Self {
+        let fill = if set { u64::MAX } else { 0 };
+        Self {
+            words: vec![fill; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn set(&mut self, i: usize, value: bool) {
+        if value {
+            self.words[i / 64] |= 1 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    /// Bits set in both
+    pub fn and(&self, other: &Bitmap) -> Bitmap {
+        Bitmap {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & b)
+                .collect(),
+            len: self.len,
+        }
+    }
+
+    /// How many of the bits are set
+    pub fn count(&self) -> usize {
+        let full = self.len / 64;
+        let mut count: u32 = self.words[..full]
+            .iter()
+            .map(|word| word.count_ones())
+            .sum();
+        // Bits past the end of the last word aren't part of the bitmap
+        if !self.len.is_multiple_of(64) {
+            count += (self.words[full] & ((1 << (self.len % 64)) - 1)).count_ones();
+        }
+        count as usize
+    }
+}
+
+impl FromIterator<bool> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bitmap = Bitmap::new(0, false);
+        for bit in iter {
+            if bitmap.len.is_multiple_of(64) {
+                bitmap.words.push(0);
+            }
+            bitmap.len += 1;
+            bitmap.set(bitmap.len - 1, bit);
+        }
+        bitmap
+    }
+}
+
+/// One side of an operator, either every value of a series or one value for all of them
+enum Operand<'a, T> {
+    Many(&'a [T]),
+    One(&'a T),
+}
+
+impl<'a, T> Operand<'a, T> {
+    fn at(&self, i: usize) -> &'a T {
+        match self {
+            Operand::Many(values) => &values[i],
+            Operand::One(value) => value,
+        }
+    }
+}
+
+/// Generates a function that reads one side of an operator as values of a single type
+/// A missing value of this type stands in as `$missing`, which is never read, since every row
+/// paired with it is missing
+macro_rules! operand {
+    ($name:ident, $variant:ident, $rust:ty, $type_name:literal, $missing:expr) => {
+        fn $name(side: &Type) -> Option<Operand<'_, $rust>> {
+            static MISSING: $rust = $missing;
+            match side {
+                Type::Series(column) => match &column.data {
+                    ColumnData::$variant(values) => Some(Operand::Many(values)),
+                    _ => None,
+                },
+                Type::$variant(value) => Some(Operand::One(value)),
+                Type::Na($type_name) => Some(Operand::One(&MISSING)),
+                _ => None,
+            }
+        }
+    };
+}
+
+operand!(ints, Int, i32, "int", 0);
+operand!(floats, Float, f32, "float", 0.0);
+operand!(strs, Str, String, "str", String::new());
+operand!(bools, Bool, bool, "bool", false);
+
+/// The type of the values in a series, or of a single value
+pub fn element_type(side: &Type) -> &'static str {
+    match side {
+        Type::Series(column) => column.type_name(),
+        other => ops::element_type(other),
+    }
+}
+
+/// Runs `f` for every row that both sides have a value in, leaving the rest as the default
+fn map<R: Default>(
+    len: usize,
+    validity: Option<&Bitmap>,
+    mut f: impl FnMut(usize) -> Result<R, ErrorKind>,
+) -> Result<Vec<R>, ErrorKind> {
+    (0..len)
+        .map(|i| match validity {
+            Some(valid) if !valid.get(i) => Ok(R::default()),
+            _ => f(i),
+        })
+        .collect()
+}
+
+/// Applies an operator to every pair of values, where at least one side is a series and any
+/// scalar is paired with every value of the other side
+/// A row missing from either side is missing from the result
+pub fn binary(l: &Type, r: &Type, op: BinaryOperator) -> Result<Column, ErrorKind> {
+    use BinaryOperator::*;
+
+    let (len, validity) = match (l, r) {
+        (Type::Series(a), Type::Series(b)) if a.len() != b.len() => {
+            return Err(ErrorKind::LengthMismatch {
+                left: a.len(),
+                right: b.len(),
+            })
+        }
+        (Type::Series(a), Type::Series(b)) => (
+            a.len(),
+            match (&a.validity, &b.validity) {
+                (Some(a), Some(b)) => Some(a.and(b)),
+                (valid, None) | (None, valid) => valid.clone(),
+            },
+        ),
+        (Type::Series(column), Type::Na(_)) | (Type::Na(_), Type::Series(column)) => {
+            (column.len(), Some(Bitmap::new(column.len(), false)))
+        }
+        (Type::Series(column), _) | (_, Type::Series(column)) => {
+            (column.len(), column.validity.clone())
+        }
+        _ => unreachable!("scalars are handled by `ops::scalar`"),
+    };
+    let valid = validity.as_ref();
+    let mismatch = || ops::mismatch(element_type(l), element_type(r), op);
+
+    let data = if op.is_comparison() {
+        let compared = if let (Some(a), Some(b)) = (ints(l), ints(r)) {
+            map(len, valid, |i| Ok(ops::compare(a.at(i), b.at(i), op)))
+        } else if let (Some(a), Some(b)) = (floats(l), floats(r)) {
+            map(len, valid, |i| Ok(ops::compare(a.at(i), b.at(i), op)))
+        } else if let (Some(a), Some(b)) = (strs(l), strs(r)) {
+            map(len, valid, |i| Ok(ops::compare(a.at(i), b.at(i), op)))
+        } else if let (Some(a), Some(b), Equal | NotEqual) = (bools(l), bools(r), op) {
+            map(len, valid, |i| Ok(ops::compare(a.at(i), b.at(i), op)))
+        } else {
+            return Err(mismatch());
+        };
+        ColumnData::Bool(compared?)
+    } else if let (Some(a), Some(b)) = (ints(l), ints(r)) {
+        ColumnData::Int(map(len, valid, |i| ops::int(*a.at(i), *b.at(i), op))?)
+    } else if let (Some(a), Some(b)) = (floats(l), floats(r)) {
+        ColumnData::Float(map(len, valid, |i| Ok(ops::float(*a.at(i), *b.at(i), op)))?)
+    } else if let (Some(a), Some(b), Plus) = (strs(l), strs(r), op) {
+        ColumnData::Str(map(len, valid, |i| Ok(format!("{}{}", a.at(i), b.at(i))))?)
+    } else {
+        return Err(mismatch());
+    };
+
+    Ok(Column { data, validity })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{engine::evaluate, Engine};
+
+    fn series(source: &str) -> Result<String, String> {
+        let imports = "from std.table import series, to_list;\nfrom std.string import split;\n";
+        evaluate(&format!("{}{}", imports, source))
+    }
+
+    fn column(values: Vec<Type>) -> Type {
+        Type::Series(Rc::new(Column::from_values("x", &values).unwrap()))
+    }
+
+    #[test]
+    fn bitmaps() {
+        let mut bits = Bitmap::new(130, true);
+        assert_eq!(bits.count(), 130);
+        bits.set(64, false);
+        bits.set(129, false);
+        assert!(!bits.get(64) && bits.get(65));
+        assert_eq!(bits.count(), 128);
+
+        let other: Bitmap = (0..130).map(|i| i % 2 == 0).collect();
+        assert_eq!(other.len(), 130);
+        assert_eq!(bits.and(&other).count(), 64);
+        assert_eq!(Bitmap::new(3, false).count(), 0);
+    }
+
+    #[test]
+    fn broadcasting() {
+        let s = "var s = series(range(4));\n";
+        assert_eq!(
+            series(&format!("{}s * 2;", s)).unwrap(),
+            "series<int>[0, 2, 4, 6]"
+        );
+        assert_eq!(
+            series(&format!("{}10 - s;", s)).unwrap(),
+            "series<int>[10, 9, 8, 7]"
+        );
+        assert_eq!(
+            series(&format!("{}s + s;", s)).unwrap(),
+            "series<int>[0, 2, 4, 6]"
+        );
+        assert_eq!(
+            series(&format!("{}to_list(s * 3);", s)).unwrap(),
+            "[0, 3, 6, 9]"
+        );
+        assert_eq!(
+            series(r#"series(split("a b")) + "!";"#).unwrap(),
+            r#"series<str>["a!", "b!"]"#
+        );
+        assert_eq!(
+            series("series(range(12));").unwrap(),
+            "series<int>[0, 1, 2, 3, 4, ..., 7, 8, 9, 10, 11]"
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(
+            series("series(range(3)) > 1;").unwrap(),
+            "series<bool>[false, false, true]"
+        );
+        assert_eq!(
+            series(r#"series(split("a b")) == "a";"#).unwrap(),
+            "series<bool>[true, false]"
+        );
+        assert_eq!(series("1 == 1;").unwrap(), "true");
+        assert_eq!(series(r#""a" < "b";"#).unwrap(), "true");
+        let nan = Type::Float(f32::NAN);
+        let compared = binary(&column(vec![nan.clone()]), &nan, BinaryOperator::Equal).unwrap();
+        assert_eq!(compared.to_string(), "series<bool>[false]");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            series("series(range(3)) + series(range(4));").unwrap_err(),
+            "SK0403: Series of different lengths, 3 on the left and 4 on the right"
+        );
+        assert_eq!(
+            series("series(range(3)) + 1.5;").unwrap_err(),
+            "SK0200: Mismatched types, expected int but found float"
+        );
+        assert_eq!(
+            series(r#"series(split("a b")) - "a";"#).unwrap_err(),
+            "SK0200: Mismatched types, expected int or float but found str"
+        );
+        assert_eq!(
+            series("series(range(2)) / 0;").unwrap_err(),
+            "SK0400: Attempted to divide by zero"
+        );
+
+        // Both sides are labelled with their lengths
+        let source =
+            "from std.table import series;\nvar d = series(range(3)) - series(range(2));\n";
+        let report = Engine::new().eval("test", source).unwrap_err().to_string();
+        assert!(report.contains("3 values"), "{}", report);
+        assert!(report.contains("2 values"), "{}", report);
+    }
+
+    #[test]
+    fn missing_values_stay_missing() {
+        let left = column(vec![Type::Int(1), Type::Nil, Type::Int(3)]);
+        let right = column(vec![Type::Int(1), Type::Int(2), Type::Nil]);
+        let sum = binary(&left, &right, BinaryOperator::Plus).unwrap();
+        assert_eq!(sum.to_string(), "series<int>[2, NA, NA]");
+        assert_eq!(sum.null_count(), 2);
+
+        let zeros = column(vec![Type::Int(0), Type::Nil]);
+        let divided = binary(&Type::Int(1), &zeros, BinaryOperator::Divide);
+        assert!(matches!(divided, Err(ErrorKind::DivisionByZero)));
+        // Missing rows aren't divided, so a zero stored in one isn't an error
+        let mut zeros = Column::from_values("x", &[Type::Nil, Type::Int(2)]).unwrap();
+        zeros.data = ColumnData::Int(vec![0, 2]);
+        let divided = binary(
+            &Type::Int(1),
+            &Type::Series(Rc::new(zeros)),
+            BinaryOperator::Divide,
+        );
+        assert_eq!(divided.unwrap().to_string(), "series<int>[NA, 0]");
+
+        let floats = column(vec![Type::Float(1.0), Type::Nil]);
+        let halved = binary(&floats, &Type::Float(2.0), BinaryOperator::Divide).unwrap();
+        assert_eq!(halved.to_string(), "series<float>[0.5, NA]");
+    }
+}
@@ -0,0 +1,301 @@
+//! `std.io`, reading and writing tables as files
+//!
+//! Paths are relative to the directory Starkey is run from.
+
+use std::{fs, rc::Rc};
+
+use crate::{
+    backend::{
+        csv::{self, Header, Options, Problem},
+        table::{ColumnData, Table},
+        value::{NativeError, NativeFunction, Type},
+    },
+    errors::error::ErrorKind,
+};
+
+use super::Module;
+
+/// At most this many problems are listed under the first, the rest are only counted
+const MAX_NOTES: usize = 20;
+
+pub fn module() -> Module {
+    Module::new("std.io")
+        .function(
+            NativeFunction::new("read_csv", vec!["str", "str", "str", "str"], |args| {
+                let path = text(&args[0]);
+                let options = options("read_csv", args, 1)?;
+                let table = csv::read(&read(path)?, &options)
+                    .map_err(|problems| invalid_csv(path, problems))?;
+                Ok(Type::Table(Rc::new(table)))
+            })
+            .optional(3),
+        )
+        .function(
+            NativeFunction::new(
+                "read_csv_strict",
+                vec!["str", "table", "str", "str", "str"],
+                |args| {
+                    let path = text(&args[0]);
+                    let schema = schema(&args[1])?;
+                    let options = options("read_csv_strict", args, 2)?;
+                    let table = csv::read_strict(&read(path)?, &options, &schema)
+                        .map_err(|problems| invalid_csv(path, problems))?;
+                    Ok(Type::Table(Rc::new(table)))
+                },
+            )
+            .optional(3),
+        )
+        .function(
+            NativeFunction::new("write_csv", vec!["table", "str", "str", "str"], |args| {
+                let Type::Table(table) = &args[0] else {
+                    unreachable!("the runtime checks arguments against their annotations")
+                };
+                let path = text(&args[1]);
+                let mut options = Options::default();
+                if let Some(delimiter) = args.get(2) {
+                    options.delimiter = character("write_csv", 2, delimiter)?;
+                }
+                if let Some(quote) = args.get(3) {
+                    options.quote = character("write_csv", 3, quote)?;
+                }
+                check_dialect("write_csv", &options, 3)?;
+                fs::write(path, csv::write(table, &options))
+                    .map_err(|err| io_error(1, path, err))?;
+                Ok(Type::Nil)
+            })
+            .optional(2),
+        )
+}
+
+fn text(value: &Type) -> &str {
+    match value {
+        Type::Str(s) => s,
+        _ => "",
+    }
+}
+
+fn invalid(function: &str, index: usize, message: String) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::InvalidArgument {
+            function: function.to_string(),
+            message,
+        },
+    )
+}
+
+fn io_error(index: usize, path: &str, err: std::io::Error) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::Io {
+            path: path.to_string(),
+            reason: err.to_string(),
+        },
+    )
+}
+
+fn read(path: &str) -> Result<String, NativeError> {
+    fs::read_to_string(path).map_err(|err| io_error(0, path, err))
+}
+
+/// Reports the first problem as the error and lists the rest under it
+fn invalid_csv(path: &str, problems: Vec<Problem>) -> NativeError {
+    let mut problems = problems.into_iter();
+    let first = problems
+        .next()
+        .expect("a failed read has at least one problem");
+    let mut err = NativeError::argument(
+        0,
+        ErrorKind::InvalidCsv {
+            path: path.to_string(),
+            line: first.line,
+            column: first.column,
+            message: first.message,
+        },
+    );
+    let rest: Vec<Problem> = problems.collect();
+    for problem in rest.iter().take(MAX_NOTES) {
+        err = err.note(format!("also at {}", problem));
+    }
+    if rest.len() > MAX_NOTES {
+        err = err.note(format!("and {} more problems", rest.len() - MAX_NOTES));
+    }
+    err
+}
+
+/// A single character option
+fn character(function: &str, index: usize, value: &Type) -> Result<char, NativeError> {
+    let mut chars = text(value).chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(invalid(
+            function,
+            index,
+            format!("expected a single character but found {:?}", text(value)),
+        )),
+    }
+}
+
+/// Reads the delimiter, header and quote options, which start at argument `start`
+fn options(function: &str, args: &[Type], start: usize) -> Result<Options, NativeError> {
+    let mut options = Options::default();
+    if let Some(delimiter) = args.get(start) {
+        options.delimiter = character(function, start, delimiter)?;
+    }
+    if let Some(header) = args.get(start + 1) {
+        options.header = match text(header) {
+            "auto" => Header::Auto,
+            "yes" => Header::Present,
+            "no" => Header::Absent,
+            other => {
+                return Err(invalid(
+                    function,
+                    start + 1,
+                    format!("expected \"auto\", \"yes\" or \"no\" but found {:?}", other),
+                ))
+            }
+        };
+    }
+    if let Some(quote) = args.get(start + 2) {
+        options.quote = character(function, start + 2, quote)?;
+    }
+    check_dialect(function, &options, start)?;
+    Ok(options)
+}
+
+fn check_dialect(function: &str, options: &Options, index: usize) -> Result<(), NativeError> {
+    if matches!(options.delimiter, '\n' | '\r') {
+        return Err(invalid(
+            function,
+            index,
+            String::from("a line break can't be the delimiter"),
+        ));
+    }
+    if options.delimiter == options.quote {
+        return Err(invalid(
+            function,
+            index,
+            String::from("the delimiter and the quote have to be different"),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads a schema, a table with a `name` and a `type` column like `std.table.schema` gives
+fn schema(value: &Type) -> Result<Vec<(String, String)>, NativeError> {
+    let invalid = |message: String| invalid("read_csv_strict", 1, message);
+    let Type::Table(table) = value else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let strings = |table: &Table, name: &str| match table.column(name).map(|c| &c.data) {
+        Some(ColumnData::Str(values)) => Ok(values.clone()),
+        _ => Err(invalid(format!(
+            "a schema needs a '{}' column of strings, like the table `schema` gives",
+            name
+        ))),
+    };
+    let names = strings(table, "name")?;
+    let types = strings(table, "type")?;
+    for typ in &types {
+        if !csv::TYPES.contains(&typ.as_str()) {
+            return Err(invalid(format!(
+                "a column can't have the type '{}', expected int, float, str or bool",
+                typ
+            )));
+        }
+    }
+    Ok(names.into_iter().zip(types).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use crate::{engine::evaluate, Engine};
+
+    /// A file in the temporary directory, with a name no other test uses
+    fn scratch(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("starkey-io-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn io(source: &str) -> Result<String, String> {
+        let imports =
+            "from std.io import *;\nfrom std.table import *;\nfrom std.string import split;\n";
+        evaluate(&format!("{}{}", imports, source))
+    }
+
+    #[test]
+    fn reading_and_writing() {
+        let path = scratch("read.csv", "a;b\n1;x\n2;\"y;z\"\n");
+        let out = env::temp_dir().join(format!("starkey-io-{}-out.csv", std::process::id()));
+        let (path, out) = (path.display(), out.display());
+        let table = "| a   | b   |\n\
+                     | int | str |\n\
+                     |-----|-----|\n\
+                     |   1 | x   |\n\
+                     |   2 | y;z |\n\
+                     2 rows, 2 columns";
+        assert_eq!(
+            io(&format!("read_csv(\"{}\", \";\");", path)).unwrap(),
+            table
+        );
+
+        let round_trip = format!(
+            "var t = read_csv(\"{}\", \";\");\nwrite_csv(t, \"{}\");\nread_csv(\"{}\");",
+            path, out, out
+        );
+        assert_eq!(io(&round_trip).unwrap(), table);
+
+        let strict = format!(
+            "read_csv_strict(\"{0}\", schema(read_csv(\"{0}\", \";\")), \";\");",
+            path
+        );
+        assert_eq!(io(&strict).unwrap(), table);
+
+        let headless = io(&format!("read_csv(\"{}\", \";\", \"no\");", path)).unwrap();
+        assert!(headless.starts_with("| column1 | column2 |"));
+        assert!(headless.ends_with("3 rows, 2 columns"));
+    }
+
+    #[test]
+    fn options_and_schemas() {
+        let path = scratch("options.csv", "a\n1\n");
+        let call = |args: &str| io(&format!("read_csv(\"{}\"{});", path.display(), args));
+        assert!(call(", \";\", \"maybe\"")
+            .unwrap_err()
+            .ends_with("expected \"auto\", \"yes\" or \"no\" but found \"maybe\""));
+        assert!(call(", \";;\"")
+            .unwrap_err()
+            .ends_with("expected a single character but found \";;\""));
+        assert!(call(", \",\", \"auto\", \",\"")
+            .unwrap_err()
+            .ends_with("the delimiter and the quote have to be different"));
+
+        let schema = "table(\"name\", split(\"a\"), \"type\", split(\"date\"))";
+        let strict = format!("read_csv_strict(\"{}\", {});", path.display(), schema);
+        assert!(io(&strict)
+            .unwrap_err()
+            .ends_with("a column can't have the type 'date', expected int, float, str or bool"));
+    }
+
+    #[test]
+    fn problems() {
+        let missing = env::temp_dir().join("starkey-io-missing.csv");
+        let err = io(&format!("read_csv(\"{}\");", missing.display())).unwrap_err();
+        assert!(err.starts_with("SK0500"), "{}", err);
+
+        // The first problem is the error and the rest are listed under it
+        let path = scratch("bad.csv", "a,b\n1,2\n3\n4\n");
+        let source = format!(
+            "from std.io import read_csv;\nvar t = read_csv(\"{}\");\n",
+            path.display()
+        );
+        let diagnostics = Engine::new().eval("test", &source).unwrap_err();
+        let report = diagnostics.report();
+        assert!(report.contains("at line 3: has 1 field but there are 2 columns"));
+        assert!(report.contains("also at line 4: has 1 field but there are 2 columns"));
+        assert_eq!(diagnostics.errors()[0].kind.code(), "SK0501");
+    }
+}
@@ -0,0 +1,256 @@
+//! `std.json`, reading and writing JSON values and NDJSON tables
+//!
+//! Every reading function takes an optional mode, `"lenient"` by default, where an object with
+//! the same key twice keeps the last value and a float with more digits than it can hold is
+//! rounded, or `"strict"`, where both are errors. Paths are relative to the directory Starkey is
+//! run from.
+
+use std::{fs, rc::Rc};
+
+use crate::{
+    backend::{
+        json::{self, Problem},
+        value::{NativeError, NativeFunction, Type},
+    },
+    errors::error::ErrorKind,
+};
+
+use super::Module;
+
+pub fn module() -> Module {
+    Module::new("std.json")
+        .function(
+            NativeFunction::new("parse", vec!["str", "str"], |args| {
+                let strict = mode("parse", args.get(1))?;
+                json::parse(text(&args[0]), strict).map_err(|problem| invalid_json(None, problem))
+            })
+            .optional(1),
+        )
+        .function(
+            NativeFunction::new("read", vec!["str", "str"], |args| {
+                let path = text(&args[0]);
+                let strict = mode("read", args.get(1))?;
+                let contents = read(path)?;
+                json::parse(&contents, strict)
+                    .map_err(|problem| invalid_json(Some((path, &contents)), problem))
+            })
+            .optional(1),
+        )
+        .function(
+            NativeFunction::new("read_ndjson", vec!["str", "str"], |args| {
+                let path = text(&args[0]);
+                let strict = mode("read_ndjson", args.get(1))?;
+                let contents = read(path)?;
+                let table = json::parse_ndjson(&contents, strict)
+                    .map_err(|problem| invalid_json(Some((path, &contents)), problem))?;
+                Ok(Type::Table(Rc::new(table)))
+            })
+            .optional(1),
+        )
+        .function(
+            NativeFunction::new("stringify", vec!["any", "int"], |args| {
+                let indent = indent("stringify", args, 1)?;
+                json::write(&args[0], indent)
+                    .map(Type::Str)
+                    .map_err(|message| invalid("stringify", 0, message))
+            })
+            .optional(1),
+        )
+        .function(
+            NativeFunction::new("write", vec!["any", "str", "int"], |args| {
+                let path = text(&args[1]);
+                let indent = indent("write", args, 2)?;
+                let mut out = json::write(&args[0], indent)
+                    .map_err(|message| invalid("write", 0, message))?;
+                out.push('\n');
+                fs::write(path, out).map_err(|err| io_error(1, path, err))?;
+                Ok(Type::Nil)
+            })
+            .optional(1),
+        )
+        .function(NativeFunction::new(
+            "write_ndjson",
+            vec!["table", "str"],
+            |args| {
+                let Type::Table(table) = &args[0] else {
+                    unreachable!("the runtime checks arguments against their annotations")
+                };
+                let path = text(&args[1]);
+                fs::write(path, json::write_ndjson(table)).map_err(|err| io_error(1, path, err))?;
+                Ok(Type::Nil)
+            },
+        ))
+}
+
+fn text(value: &Type) -> &str {
+    match value {
+        Type::Str(s) => s,
+        _ => "",
+    }
+}
+
+fn invalid(function: &str, index: usize, message: String) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::InvalidArgument {
+            function: function.to_string(),
+            message,
+        },
+    )
+}
+
+fn io_error(index: usize, path: &str, err: std::io::Error) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::Io {
+            path: path.to_string(),
+            reason: err.to_string(),
+        },
+    )
+}
+
+fn read(path: &str) -> Result<String, NativeError> {
+    fs::read_to_string(path).map_err(|err| io_error(0, path, err))
+}
+
+/// Reports where the JSON went wrong, with the line and column as well when it's from a file
+fn invalid_json(file: Option<(&str, &str)>, problem: Problem) -> NativeError {
+    let err = NativeError::argument(
+        0,
+        ErrorKind::InvalidJson {
+            path: file.map(|(path, _)| path.to_string()),
+            offset: problem.offset,
+            message: problem.message,
+        },
+    );
+    match file {
+        Some((_, contents)) => {
+            let (line, column) = json::position(contents, problem.offset);
+            err.note(format!("that's line {}, column {}", line, column))
+        }
+        None => err,
+    }
+}
+
+/// Whether the mode argument, if given, asks for strict parsing
+fn mode(function: &str, value: Option<&Type>) -> Result<bool, NativeError> {
+    match value.map(text) {
+        None | Some("lenient") => Ok(false),
+        Some("strict") => Ok(true),
+        Some(other) => Err(invalid(
+            function,
+            1,
+            format!("expected \"lenient\" or \"strict\" but found {:?}", other),
+        )),
+    }
+}
+
+/// The number of spaces to indent by, from argument `index` if it was given
+fn indent(function: &str, args: &[Type], index: usize) -> Result<Option<usize>, NativeError> {
+    match args.get(index) {
+        None => Ok(None),
+        Some(Type::Int(n)) if *n >= 0 => Ok(Some(*n as usize)),
+        Some(other) => Err(invalid(
+            function,
+            index,
+            format!("the indent can't be negative, but was {}", other),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use crate::{engine::evaluate, Engine};
+
+    /// A path in the temporary directory that no other test uses
+    fn scratch(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("starkey-json-{}-{}", std::process::id(), name))
+    }
+
+    fn json(source: &str) -> Result<String, String> {
+        evaluate(&format!("from std.json import *;\n{}", source))
+    }
+
+    #[test]
+    fn reading_and_writing() {
+        let path = scratch("value.json");
+        fs::write(&path, r#"{"a": [1, 2.5, null], "k": 1, "k": 2}"#).unwrap();
+        let path = path.display();
+        assert_eq!(
+            json(&format!("read(\"{}\");", path)).unwrap(),
+            r#"{"a": [1, 2.5, nil], "k": 2}"#
+        );
+        assert_eq!(
+            json(&format!("stringify(read(\"{}\"));", path)).unwrap(),
+            r#"{"a":[1,2.5,null],"k":2}"#
+        );
+        assert!(json(&format!("read(\"{}\", \"strict\");", path))
+            .unwrap_err()
+            .starts_with("SK0502"));
+
+        let out = scratch("out.json");
+        let round_trip = format!(
+            "write(read(\"{0}\"), \"{1}\", 2);\nstringify(read(\"{1}\"));",
+            path,
+            out.display()
+        );
+        assert_eq!(json(&round_trip).unwrap(), r#"{"a":[1,2.5,null],"k":2}"#);
+        assert!(fs::read_to_string(&out)
+            .unwrap()
+            .starts_with("{\n  \"a\": [\n"));
+    }
+
+    #[test]
+    fn ndjson_tables() {
+        let path = scratch("rows.ndjson");
+        fs::write(&path, "{\"a\": 1, \"b\": \"x\"}\n{\"a\": 2}\n").unwrap();
+        let out = scratch("rows-out.ndjson");
+        let source = format!(
+            "var t = read_ndjson(\"{}\");\nwrite_ndjson(t, \"{}\");\nt;",
+            path.display(),
+            out.display()
+        );
+        assert_eq!(
+            json(&source).unwrap(),
+            "| a   | b   |\n\
+             | int | str |\n\
+             |-----|-----|\n\
+             |   1 | x   |\n\
+             |   2 | NA  |\n\
+             2 rows, 2 columns"
+        );
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "{\"a\":1,\"b\":\"x\"}\n{\"a\":2,\"b\":null}\n"
+        );
+    }
+
+    #[test]
+    fn problems() {
+        assert_eq!(
+            json("parse(\"[1,\");").unwrap_err(),
+            "SK0502: Invalid JSON at byte 3: expected a value but the input ended"
+        );
+        assert!(json("parse(\"[1,\", \"loose\");")
+            .unwrap_err()
+            .ends_with("expected \"lenient\" or \"strict\" but found \"loose\""));
+        assert!(json("stringify(1.0 / 0.0);")
+            .unwrap_err()
+            .ends_with("JSON has no way to write the float inf"));
+        assert!(json("stringify(1, 0 - 1);")
+            .unwrap_err()
+            .ends_with("the indent can't be negative, but was -1"));
+
+        // Problems in a file say which line and column they're at
+        let path = scratch("bad.json");
+        fs::write(&path, "[1,\n  2,,\n]").unwrap();
+        let source = format!(
+            "from std.json import read;\nvar v = read(\"{}\");\n",
+            path.display()
+        );
+        let report = Engine::new().eval("test", &source).unwrap_err().to_string();
+        assert!(report.contains("that's line 2, column 5"), "{}", report);
+    }
+}
@@ -0,0 +1,124 @@
+//! `std.map`, for taking maps apart
+//!
+//! Everything comes back in the order the keys were first inserted.
+
+use std::rc::Rc;
+
+use crate::{
+    backend::{
+        map::{Key, Map},
+        value::{NativeError, NativeFunction, Type},
+    },
+    errors::error::ErrorKind,
+};
+
+use super::Module;
+
+pub fn module() -> Module {
+    functions()
+        .into_iter()
+        .fold(Module::new("std.map"), Module::function)
+}
+
+/// Every function in the module, which can be called as methods of a map too
+pub fn functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("keys", vec!["map"], |args| {
+            let keys = map(&args[0]).iter().map(|(key, _)| key.to_value());
+            Ok(Type::List(Rc::new(keys.collect())))
+        }),
+        NativeFunction::new("values", vec!["map"], |args| {
+            let values = map(&args[0]).iter().map(|(_, value)| value.clone());
+            Ok(Type::List(Rc::new(values.collect())))
+        }),
+        NativeFunction::new("items", vec!["map"], |args| {
+            let items = map(&args[0])
+                .iter()
+                .map(|(key, value)| Type::List(Rc::new(vec![key.to_value(), value.clone()])));
+            Ok(Type::List(Rc::new(items.collect())))
+        }),
+        NativeFunction::new("has", vec!["map", "any"], |args| {
+            let key = Key::from_value(&args[1]).ok_or_else(|| {
+                NativeError::argument(
+                    1,
+                    ErrorKind::UnhashableKey {
+                        found: args[1].type_name().to_string(),
+                    },
+                )
+            })?;
+            Ok(Type::Bool(map(&args[0]).contains(&key)))
+        }),
+    ]
+}
+
+fn map(value: &Type) -> &Map {
+    match value {
+        Type::Map(map) => map,
+        _ => unreachable!("the runtime checks arguments against their annotations"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::evaluate;
+
+    fn map(source: &str) -> Result<String, String> {
+        evaluate(&format!("from std.map import *;\n{}", source))
+    }
+
+    #[test]
+    fn literals_and_indexing() {
+        assert_eq!(map(r#"{"b": 1, "a": 2};"#).unwrap(), r#"{"b": 1, "a": 2}"#);
+        assert_eq!(map("{};").unwrap(), "{}");
+        assert_eq!(map(r#"{"a": 1,};"#).unwrap(), r#"{"a": 1}"#);
+        // A key written twice keeps its first place and its last value
+        assert_eq!(
+            map("{\"b\": 1, 2: \"x\", \"b\": 3};").unwrap(),
+            r#"{"b": 3, 2: "x"}"#
+        );
+        assert_eq!(
+            map("var m = {\"a\": {\"b\": 2}};\nm[\"a\"][\"b\"];").unwrap(),
+            "2"
+        );
+        assert_eq!(map("var m = {1 == 1: \"yes\"};\nm[2 > 1];").unwrap(), "yes");
+        assert_eq!(map("range(3)[1];").unwrap(), "1");
+        assert_eq!(map("len({\"a\": 1});").unwrap(), "1");
+        assert_eq!(map("type_of({});").unwrap(), "map");
+    }
+
+    #[test]
+    fn functions() {
+        let m = "var m = {\"b\": 1, \"a\": 2};\n";
+        assert_eq!(map(&format!("{}keys(m);", m)).unwrap(), r#"["b", "a"]"#);
+        assert_eq!(map(&format!("{}values(m);", m)).unwrap(), "[1, 2]");
+        assert_eq!(
+            map(&format!("{}items(m);", m)).unwrap(),
+            r#"[["b", 1], ["a", 2]]"#
+        );
+        assert_eq!(map(&format!("{}has(m, \"a\");", m)).unwrap(), "true");
+        assert_eq!(map(&format!("{}has(m, \"c\");", m)).unwrap(), "false");
+    }
+
+    #[test]
+    fn key_errors() {
+        let unhashable = "SK0203: Cannot use a value of type float as a map key";
+        assert_eq!(map("var m = {1.5: 1};").unwrap_err(), unhashable);
+        assert_eq!(map("has({\"b\": 1}, 1.0);").unwrap_err(), unhashable);
+        assert_eq!(
+            map("var m = {\"b\": 1};\nm[\"c\"];").unwrap_err(),
+            "SK0905: No entry for the key \"c\""
+        );
+        assert_eq!(
+            map("range(3)[0 - 1];").unwrap_err(),
+            "SK0906: Index -1 is out of range for a list of 3 items"
+        );
+        assert_eq!(
+            map("1[0];").unwrap_err(),
+            "SK0200: Mismatched types, expected map, list or table but found int"
+        );
+        assert_eq!(
+            map(r#"{"a" 1};"#).unwrap_err(),
+            "SK0001: Expected ',' between map entries"
+        );
+    }
+}
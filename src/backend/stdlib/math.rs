@@ -0,0 +1,284 @@
+//! `std.math`, computed in double precision and rounded once to a float at the end
+
+use std::f64::consts;
+
+use crate::{backend::value::NativeError, errors::error::ErrorKind};
+
+use super::{
+    super::value::{NativeFunction, Type},
+    Module,
+};
+
+pub fn module() -> Module {
+    Module::new("std.math")
+        .constant("pi", Type::Float(consts::PI as f32))
+        .constant("e", Type::Float(consts::E as f32))
+        .constant("tau", Type::Float(consts::TAU as f32))
+        .constant("inf", Type::Float(f32::INFINITY))
+        .constant("nan", Type::Float(f32::NAN))
+        .function(unary("sin", f64::sin))
+        .function(unary("cos", f64::cos))
+        .function(unary("tan", f64::tan))
+        .function(unary("asin", f64::asin))
+        .function(unary("acos", f64::acos))
+        .function(unary("atan", f64::atan))
+        .function(unary("sinh", f64::sinh))
+        .function(unary("cosh", f64::cosh))
+        .function(unary("tanh", f64::tanh))
+        .function(unary("exp", f64::exp))
+        .function(unary("expm1", f64::exp_m1))
+        .function(unary("ln", f64::ln))
+        .function(unary("log2", f64::log2))
+        .function(unary("log10", f64::log10))
+        .function(unary("log1p", f64::ln_1p))
+        .function(unary("sqrt", f64::sqrt))
+        .function(unary("cbrt", f64::cbrt))
+        .function(unary("floor", f64::floor))
+        .function(unary("ceil", f64::ceil))
+        .function(unary("trunc", f64::trunc))
+        .function(unary("lgamma", lgamma))
+        .function(unary("erf", erf))
+        .function(unary("erfc", erfc))
+        .function(binary("atan2", f64::atan2))
+        .function(binary("hypot", f64::hypot))
+        .function(binary("pow", f64::powf))
+        .function(NativeFunction::new("log", vec!["number", "number"], log).optional(1))
+        .function(NativeFunction::new("sum", vec!["list"], sum))
+        .function(predicate("is_nan", f64::is_nan))
+        .function(predicate("is_inf", f64::is_infinite))
+        .function(predicate("is_finite", f64::is_finite))
+}
+
+/// Reads an argument that's been checked to be a number
+pub(crate) fn number(value: &Type) -> f64 {
+    match value {
+        Type::Int(i) => f64::from(*i),
+        Type::Float(x) => f64::from(*x),
+        _ => f64::NAN,
+    }
+}
+
+fn unary(name: &str, f: fn(f64) -> f64) -> NativeFunction {
+    NativeFunction::new(name, vec!["number"], move |args| {
+        Ok(Type::Float(f(number(&args[0])) as f32))
+    })
+}
+
+fn binary(name: &str, f: fn(f64, f64) -> f64) -> NativeFunction {
+    NativeFunction::new(name, vec!["number", "number"], move |args| {
+        Ok(Type::Float(f(number(&args[0]), number(&args[1])) as f32))
+    })
+}
+
+fn predicate(name: &str, f: fn(f64) -> bool) -> NativeFunction {
+    NativeFunction::new(name, vec!["number"], move |args| {
+        Ok(Type::Bool(f(number(&args[0]))))
+    })
+}
+
+/// The natural log, or the log to `base` if one is given
+fn log(args: &[Type]) -> Result<Type, NativeError> {
+    let x = number(&args[0]);
+    let result = match args.get(1) {
+        Some(base) => x.ln() / number(base).ln(),
+        None => x.ln(),
+    };
+    Ok(Type::Float(result as f32))
+}
+
+/// Adds up a list of numbers, see `neumaier_sum`
+/// A list of only ints sums to an int
+fn sum(args: &[Type]) -> Result<Type, NativeError> {
+    let Type::List(items) = &args[0] else {
+        return Ok(Type::Nil);
+    };
+    let mut ints = Some(0i32);
+    for (i, item) in items.iter().enumerate() {
+        match item {
+            Type::Int(n) => {
+                ints = match ints.map(|total| total.checked_add(*n)) {
+                    Some(None) => {
+                        return Err(NativeError::argument(
+                            0,
+                            ErrorKind::Overflow { operation: "add" },
+                        ))
+                    }
+                    total => total.flatten(),
+                }
+            }
+            Type::Float(_) => ints = None,
+            other => {
+                return Err(NativeError::argument(
+                    0,
+                    ErrorKind::TypeMismatch {
+                        expected: String::from("a list of numbers"),
+                        found: format!("{} at index {}", other.type_name(), i),
+                    },
+                ))
+            }
+        }
+    }
+    Ok(match ints {
+        Some(total) => Type::Int(total),
+        None => Type::Float(neumaier_sum(items.iter().map(number)) as f32),
+    })
+}
+
+/// Compensated summation, which keeps the low-order bits that adding a small number to a large
+/// running total would lose
+/// Neumaier's variant of Kahan's algorithm also stays exact when an addend is larger than the
+/// total so far, as in `[1.0, 1e100, 1.0, -1e100]`
+pub fn neumaier_sum(values: impl IntoIterator<Item = f64>) -> f64 {
+    let mut sum = 0.0;
+    let mut compensation = 0.0;
+    for x in values {
+        let t = sum + x;
+        if sum.abs() >= x.abs() {
+            compensation += (sum - t) + x;
+        } else {
+            compensation += (x - t) + sum;
+        }
+        sum = t;
+    }
+    // An infinity in the input makes the compensation NaN, the plain sum is the right answer
+    if sum.is_finite() {
+        sum + compensation
+    } else {
+        sum
+    }
+}
+
+/// Coefficients of the Lanczos approximation with g = 7, n = 9
+const LANCZOS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// The log of the absolute value of the gamma function
+/// Poles at zero and the negative integers give infinity
+pub fn lgamma(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+    if x <= 0.0 && x == x.floor() {
+        return f64::INFINITY;
+    }
+    if x < 0.5 {
+        // The reflection formula, Γ(x)Γ(1 - x) = π / sin(πx)
+        return (consts::PI / (consts::PI * x).sin().abs()).ln() - lgamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let mut a = LANCZOS[0];
+    for (i, c) in LANCZOS.iter().enumerate().skip(1) {
+        a += c / (x + i as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2.0 * consts::PI).ln() + (x + 0.5) * t.ln() - t + a.ln()
+}
+
+/// The error function
+/// Near zero it's summed as a series, since `1 - erfc(x)` would lose the digits that matter there
+pub fn erf(x: f64) -> f64 {
+    if x.abs() >= 0.5 {
+        return 1.0 - erfc(x);
+    }
+    // erf(x) = 2/√π Σ (-1)^n x^(2n+1) / (n! (2n+1))
+    let mut term = x;
+    let mut total = x;
+    for n in 1..30 {
+        term *= -x * x / n as f64;
+        let next = term / (2 * n + 1) as f64;
+        total += next;
+        if next.abs() < 1e-17 * total.abs() {
+            break;
+        }
+    }
+    total * consts::FRAC_2_SQRT_PI
+}
+
+/// The complementary error function, `1 - erf(x)`, accurate to about 1e-7 relative error
+/// This is the Chebyshev fit from Numerical Recipes, which doesn't lose precision for large `x`
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let result = t * (-z * z + poly).exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::evaluate;
+
+    #[test]
+    fn functions() {
+        let value = |source: &str| {
+            let source = format!(
+                "from std.math import sqrt, pi, sum, log, is_nan, nan;\n{}",
+                source
+            );
+            evaluate(&source).unwrap()
+        };
+        assert_eq!(value("sqrt(2);"), "1.4142135");
+        assert_eq!(value("pi;"), "3.1415927");
+        assert_eq!(value("log(8, 2);"), "3.0");
+        assert_eq!(value("sqrt(0 - 1);"), "NaN");
+        assert_eq!(value("is_nan(nan);"), "true");
+        // Ints sum to an int, and overflowing is an error rather than wrapping
+        assert_eq!(value("sum(range(5));"), "10");
+        assert_eq!(
+            evaluate("from std.math import sum;\nsum(range(2147483647, 2147483645, 0 - 1));")
+                .unwrap_err(),
+            "SK0401: Arithmetic overflow while trying to add"
+        );
+        assert_eq!(
+            evaluate("from std.math import nope;").unwrap_err(),
+            "SK0601: Module 'std.math' has nothing named 'nope'"
+        );
+    }
+
+    #[test]
+    fn compensated_sum() {
+        assert_eq!(neumaier_sum([1.0, 1e100, 1.0, -1e100]), 2.0);
+        // A naive sum of 0.1 ten million times drifts by about 1e-4
+        let total = neumaier_sum(std::iter::repeat_n(0.1, 10_000_000));
+        assert!((total - 1_000_000.0).abs() < 1e-6);
+        assert_eq!(neumaier_sum([1.0, f64::INFINITY]), f64::INFINITY);
+        assert!(neumaier_sum([f64::INFINITY, f64::NEG_INFINITY]).is_nan());
+        assert_eq!(neumaier_sum([]), 0.0);
+    }
+
+    #[test]
+    fn special_functions() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-7 * b.abs().max(1.0);
+        // lgamma(n) = ln((n - 1)!)
+        assert!(close(lgamma(5.0), 24f64.ln()));
+        assert!(close(lgamma(0.5), consts::PI.sqrt().ln()));
+        assert_eq!(lgamma(0.0), f64::INFINITY);
+        assert_eq!(lgamma(-2.0), f64::INFINITY);
+        assert!(close(erf(1.0), 0.842_700_792_949_715));
+        assert!(close(erf(0.1), 0.112_462_916_018_285));
+        assert!(close(erf(-1.0), -0.842_700_792_949_715));
+        assert!(close(erfc(3.0), 2.209_049_699_858_544e-5));
+    }
+}
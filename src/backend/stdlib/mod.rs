@@ -0,0 +1,64 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{
+    random::Generator,
+    value::{NativeFunction, Type},
+};
+
+pub mod io;
+pub mod json;
+pub mod map;
+pub mod math;
+pub mod random;
+pub mod regex;
+pub mod string;
+pub mod table;
+
+/// A module of the standard library, imported with `from std.<name> import ...;`
+pub struct Module {
+    pub name: &'static str,
+    pub members: HashMap<String, Type>,
+}
+
+impl Module {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            members: HashMap::new(),
+        }
+    }
+
+    pub fn function(mut self, func: NativeFunction) -> Self {
+        self.members
+            .insert(func.name.clone(), Type::Native(Rc::new(func)));
+        self
+    }
+
+    pub fn constant(mut self, name: &str, value: Type) -> Self {
+        self.members.insert(name.to_string(), value);
+        self
+    }
+}
+
+/// Every module in the standard library
+/// `random` is the generator that `std.random` draws from when it isn't handed one
+pub fn modules(random: &Rc<RefCell<Generator>>) -> Vec<Module> {
+    vec![
+        math::module(),
+        string::module(),
+        regex::module(),
+        random::module(random),
+        table::module(),
+        io::module(),
+        json::module(),
+        map::module(),
+    ]
+}
+
+/// The functions that can be called as methods, like `sales.select("region")`
+/// Each is a method of the type its first parameter is annotated with, which is the receiver
+pub fn methods() -> Vec<NativeFunction> {
+    let mut methods = table::methods();
+    methods.extend(map::functions());
+    methods
+}
@@ -0,0 +1,280 @@
+//! `std.random`
+//!
+//! Every function takes an optional generator as its last argument, made with `generator(seed)`.
+//! Without one they draw from the program's shared generator, which `directive seed(n);` reseeds.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    backend::{
+        random::Generator,
+        value::{NativeError, NativeFunction, Type},
+    },
+    errors::error::ErrorKind,
+};
+
+use super::{math::number, Module};
+
+pub fn module(shared: &Rc<RefCell<Generator>>) -> Module {
+    let draw = |name: &str, params: Vec<&'static str>, f: Draw| {
+        let at = params.len() - 1;
+        let shared = shared.clone();
+        NativeFunction::new(name, params, move |args| {
+            let generator = match args.get(at) {
+                Some(Type::Generator(generator)) => generator.clone(),
+                _ => shared.clone(),
+            };
+            let mut generator = generator.borrow_mut();
+            f(args, &mut generator)
+        })
+    };
+
+    Module::new("std.random")
+        .function(NativeFunction::new("generator", vec!["int"], |args| {
+            let seed = match args[0] {
+                Type::Int(seed) => seed,
+                _ => 0,
+            };
+            // Sign-extending keeps every seed distinct
+            let generator = Generator::new(i64::from(seed) as u64);
+            Ok(Type::Generator(Rc::new(RefCell::new(generator))))
+        }))
+        .function(
+            draw("random", vec!["generator"], |_, g| {
+                Ok(Type::Float(g.next_f64() as f32))
+            })
+            .optional(1),
+        )
+        .function(draw("uniform", vec!["number", "number", "generator"], uniform).optional(1))
+        .function(draw("randint", vec!["int", "int", "generator"], randint).optional(1))
+        .function(draw("normal", vec!["number", "number", "generator"], normal).optional(1))
+        .function(draw("binomial", vec!["int", "number", "generator"], binomial).optional(1))
+        .function(draw("poisson", vec!["number", "generator"], poisson).optional(1))
+        .function(
+            draw("shuffle", vec!["list", "generator"], |args, g| {
+                let mut items = list(&args[0]).to_vec();
+                g.shuffle(&mut items);
+                Ok(Type::List(Rc::new(items)))
+            })
+            .optional(1),
+        )
+        .function(
+            draw("choice", vec!["list", "generator"], |args, g| {
+                let items = list(&args[0]);
+                if items.is_empty() {
+                    return Err(invalid("choice", 0, "can't choose from an empty list"));
+                }
+                Ok(items[g.below(items.len() as u64) as usize].clone())
+            })
+            .optional(1),
+        )
+        .function(draw("sample", vec!["list", "int", "generator"], sample).optional(1))
+        .function(draw("choices", vec!["list", "int", "generator"], choices).optional(1))
+}
+
+type Draw = fn(&[Type], &mut Generator) -> Result<Type, NativeError>;
+
+fn invalid(function: &str, index: usize, message: &str) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::InvalidArgument {
+            function: function.to_string(),
+            message: message.to_string(),
+        },
+    )
+}
+
+fn list(value: &Type) -> &[Type] {
+    match value {
+        Type::List(items) => items,
+        _ => &[],
+    }
+}
+
+fn int(value: &Type) -> i32 {
+    match value {
+        Type::Int(i) => *i,
+        _ => 0,
+    }
+}
+
+/// Reads a number argument that has to be finite
+fn finite(function: &str, args: &[Type], index: usize) -> Result<f64, NativeError> {
+    let x = number(&args[index]);
+    if x.is_finite() {
+        Ok(x)
+    } else {
+        Err(invalid(function, index, "has to be finite"))
+    }
+}
+
+fn count(function: &str, n: u64) -> Result<Type, NativeError> {
+    i32::try_from(n).map(Type::Int).map_err(|_| {
+        NativeError::call(ErrorKind::InvalidArgument {
+            function: function.to_string(),
+            message: format!("drew {}, which is too large for an int", n),
+        })
+    })
+}
+
+/// A float in [low, high)
+fn uniform(args: &[Type], g: &mut Generator) -> Result<Type, NativeError> {
+    let low = finite("uniform", args, 0)?;
+    let high = finite("uniform", args, 1)?;
+    if low >= high {
+        return Err(invalid("uniform", 1, "has to be greater than the low end"));
+    }
+    let x = (low + (high - low) * g.next_f64()) as f32;
+    // Rounding to single precision can land on the excluded end
+    let high = high as f32;
+    Ok(Type::Float(if x >= high {
+        high.next_down().max(low as f32)
+    } else {
+        x
+    }))
+}
+
+/// An int in [low, high], both ends included
+fn randint(args: &[Type], g: &mut Generator) -> Result<Type, NativeError> {
+    let (low, high) = (int(&args[0]), int(&args[1]));
+    if low > high {
+        return Err(invalid("randint", 1, "can't be less than the low end"));
+    }
+    let span = (i64::from(high) - i64::from(low)) as u64 + 1;
+    Ok(Type::Int((i64::from(low) + g.below(span) as i64) as i32))
+}
+
+fn normal(args: &[Type], g: &mut Generator) -> Result<Type, NativeError> {
+    let mean = finite("normal", args, 0)?;
+    let std = finite("normal", args, 1)?;
+    if std < 0.0 {
+        return Err(invalid(
+            "normal",
+            1,
+            "a standard deviation can't be negative",
+        ));
+    }
+    Ok(Type::Float((mean + std * g.normal()) as f32))
+}
+
+fn binomial(args: &[Type], g: &mut Generator) -> Result<Type, NativeError> {
+    let n = u64::try_from(int(&args[0]))
+        .map_err(|_| invalid("binomial", 0, "the number of trials can't be negative"))?;
+    let p = number(&args[1]);
+    if !(0.0..=1.0).contains(&p) {
+        return Err(invalid(
+            "binomial",
+            1,
+            "a probability has to be between 0 and 1",
+        ));
+    }
+    count("binomial", g.binomial(n, p))
+}
+
+fn poisson(args: &[Type], g: &mut Generator) -> Result<Type, NativeError> {
+    let lambda = finite("poisson", args, 0)?;
+    if lambda < 0.0 {
+        return Err(invalid("poisson", 0, "a rate can't be negative"));
+    }
+    count("poisson", g.poisson(lambda))
+}
+
+/// `sample(items, k)` picks `k` different items
+fn sample(args: &[Type], g: &mut Generator) -> Result<Type, NativeError> {
+    let items = list(&args[0]);
+    let k = amount("sample", &args[1])?;
+    if k > items.len() {
+        return Err(invalid(
+            "sample",
+            1,
+            "is more than the list has, use choices to pick items more than once",
+        ));
+    }
+    // The first `k` steps of a Fisher-Yates shuffle
+    let mut items = items.to_vec();
+    for i in 0..k {
+        let j = i + g.below((items.len() - i) as u64) as usize;
+        items.swap(i, j);
+    }
+    items.truncate(k);
+    Ok(Type::List(Rc::new(items)))
+}
+
+/// `choices(items, k)` picks `k` items, any of which can be picked again
+fn choices(args: &[Type], g: &mut Generator) -> Result<Type, NativeError> {
+    let items = list(&args[0]);
+    let k = amount("choices", &args[1])?;
+    if items.is_empty() && k > 0 {
+        return Err(invalid("choices", 0, "can't choose from an empty list"));
+    }
+    let picked = (0..k)
+        .map(|_| items[g.below(items.len() as u64) as usize].clone())
+        .collect();
+    Ok(Type::List(Rc::new(picked)))
+}
+
+fn amount(function: &str, value: &Type) -> Result<usize, NativeError> {
+    usize::try_from(int(value))
+        .map_err(|_| invalid(function, 1, "can't take a negative number of items"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::evaluate;
+
+    fn random(source: &str) -> Result<String, String> {
+        evaluate(&format!("from std.random import *;\n{}", source))
+    }
+
+    fn invalid(source: &str) -> String {
+        let message = random(source).unwrap_err();
+        assert!(message.starts_with("SK0800"), "{}", message);
+        message
+    }
+
+    #[test]
+    fn seeds_repeat_draws() {
+        let draws = "choices(range(100), 10);";
+        let seeded = |seed: i32| random(&format!("directive seed({});\n{}", seed, draws)).unwrap();
+        assert_eq!(seeded(3), seeded(3));
+        assert_ne!(seeded(3), seeded(4));
+        assert_eq!(
+            random("directive seed(3);\nvar a = random();\ndirective seed(3);\nrandom() - a;")
+                .unwrap(),
+            "0.0"
+        );
+
+        // A seed gives the same numbers everywhere, so exact values can be relied on
+        assert_eq!(
+            random("var g = generator(5);\nshuffle(range(5), g);").unwrap(),
+            "[2, 1, 3, 4, 0]"
+        );
+        assert_eq!(
+            random("directive seed(1);\nsample(range(10), 3);").unwrap(),
+            "[7, 2, 6]"
+        );
+    }
+
+    #[test]
+    fn generators_are_independent() {
+        // Drawing from the shared generator in between doesn't move one made with `generator`
+        let source = "directive seed(9);\nvar g = generator(1);\nrandom(g);\nrandom();\n\
+                      var b = random(g);\nvar h = generator(1);\nrandom(h);\nrandom(h) - b;";
+        assert_eq!(random(source).unwrap(), "0.0");
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(invalid("choice(range(0));").contains("can't choose from an empty list"));
+        assert!(invalid("randint(5, 1);").contains("can't be less than the low end"));
+        assert!(invalid("uniform(1.0, 1.0 / 0.0);").contains("has to be finite"));
+        assert!(invalid("sample(range(3), 4);").contains("use choices"));
+        assert!(invalid("normal(0, 0 - 1);").contains("can't be negative"));
+        assert!(invalid("poisson(0 - 1);").contains("can't be negative"));
+        assert!(invalid("binomial(3, 2.0);").contains("between 0 and 1"));
+        assert_eq!(
+            random("directive seed(\"x\");").unwrap_err(),
+            "SK0904: Invalid 'seed' directive: expected an integer literal"
+        );
+    }
+}
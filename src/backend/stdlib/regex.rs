@@ -0,0 +1,362 @@
+//! `std.regex`, see `backend::regex` for the pattern syntax
+//!
+//! Every function takes its pattern as a `r"..."` literal, a value made by `regex`, or a string
+//! that's compiled on the spot.
+
+use std::rc::Rc;
+
+use crate::{
+    backend::{
+        map::{Key, Map},
+        regex::{Captures, Regex},
+        value::{NativeError, NativeFunction, Type},
+    },
+    errors::error::ErrorKind,
+};
+
+use super::Module;
+
+pub fn module() -> Module {
+    Module::new("std.regex")
+        .function(NativeFunction::new("regex", vec!["str"], |args| {
+            pattern(args).map(Type::Regex)
+        }))
+        .function(search("matches", |re, text| {
+            Ok(Type::Bool(re.is_match(text)))
+        }))
+        .function(search("find", |re, text| {
+            Ok(re
+                .captures(text)
+                .map_or(Type::Nil, |caps| group(text, &caps, 0)))
+        }))
+        .function(search("find_all", |re, text| {
+            let all = re.captures_all(text);
+            Ok(list(all.iter().map(|caps| group(text, caps, 0))))
+        }))
+        .function(search("captures", |re, text| {
+            Ok(re
+                .captures(text)
+                .map_or(Type::Nil, |caps| captures(re, text, &caps)))
+        }))
+        .function(NativeFunction::new(
+            "group",
+            vec!["any", "str", "any"],
+            named_group,
+        ))
+        .function(NativeFunction::new("group_names", vec!["any"], |args| {
+            let re = pattern(args)?;
+            Ok(list(re.names().iter().skip(1).map(|name| {
+                name.as_ref().map_or(Type::Nil, |n| Type::Str(n.clone()))
+            })))
+        }))
+        .function(NativeFunction::new(
+            "replace",
+            vec!["any", "str", "str"],
+            replace,
+        ))
+        .function(search("split", |re, text| {
+            let mut parts = Vec::new();
+            let mut last = 0;
+            for caps in re.captures_all(text) {
+                if let Some((start, end)) = caps[0] {
+                    // An empty match at either end would only add an empty part
+                    if end == start && (start == 0 || start == text.len()) {
+                        continue;
+                    }
+                    parts.push(Type::Str(text[last..start].to_string()));
+                    last = end;
+                }
+            }
+            parts.push(Type::Str(text[last..].to_string()));
+            Ok(Type::List(Rc::new(parts)))
+        }))
+}
+
+/// Reads the pattern in the first argument, compiling it if it's a string
+fn pattern(args: &[Type]) -> Result<Rc<Regex>, NativeError> {
+    match &args[0] {
+        Type::Regex(re) => Ok(re.clone()),
+        Type::Str(s) => Regex::new(s)
+            .map(Rc::new)
+            .map_err(|err| NativeError::argument(0, err.kind())),
+        other => Err(NativeError::argument(
+            0,
+            ErrorKind::TypeMismatch {
+                expected: String::from("regex or str"),
+                found: other.type_name().to_string(),
+            },
+        )),
+    }
+}
+
+fn text(args: &[Type]) -> &str {
+    match &args[1] {
+        Type::Str(s) => s,
+        _ => "",
+    }
+}
+
+/// A function of a pattern and the text to search
+fn search(name: &str, f: fn(&Regex, &str) -> Result<Type, NativeError>) -> NativeFunction {
+    NativeFunction::new(name, vec!["any", "str"], move |args| {
+        f(pattern(args)?.as_ref(), text(args))
+    })
+}
+
+fn list(items: impl Iterator<Item = Type>) -> Type {
+    Type::List(Rc::new(items.collect()))
+}
+
+/// What group `i` matched, or nil if it didn't take part in the match
+fn group(text: &str, caps: &Captures, i: usize) -> Type {
+    match caps.get(i).copied().flatten() {
+        Some((start, end)) => Type::Str(text[start..end].to_string()),
+        None => Type::Nil,
+    }
+}
+
+/// What each group matched in `captures`, by number and, for named groups, by name as well
+fn captures(re: &Regex, text: &str, caps: &Captures) -> Type {
+    let mut map = Map::new();
+    for (i, name) in re.names().iter().enumerate() {
+        let value = group(text, caps, i);
+        map.insert(Key::Int(i as i32), value.clone());
+        if let Some(name) = name {
+            map.insert(Key::Str(name.clone()), value);
+        }
+    }
+    Type::Map(Rc::new(map))
+}
+
+/// `group(pattern, text, name)` is what the group called `name`, or numbered `name`, matched in
+/// the first match, or nil
+fn named_group(args: &[Type]) -> Result<Type, NativeError> {
+    let re = pattern(args)?;
+    let index = match &args[2] {
+        Type::Str(name) => re.group(name),
+        Type::Int(i) => usize::try_from(*i).ok().filter(|i| *i < re.names().len()),
+        other => {
+            return Err(NativeError::argument(
+                2,
+                ErrorKind::TypeMismatch {
+                    expected: String::from("str or int"),
+                    found: other.type_name().to_string(),
+                },
+            ))
+        }
+    };
+    let Some(index) = index else {
+        return Err(no_group(&args[2]));
+    };
+    let text = text(args);
+    Ok(re
+        .captures(text)
+        .map_or(Type::Nil, |caps| group(text, &caps, index)))
+}
+
+fn no_group(name: &Type) -> NativeError {
+    NativeError::argument(
+        2,
+        ErrorKind::InvalidArgument {
+            function: String::from("group"),
+            message: format!("the pattern has no group {}", name),
+        },
+    )
+}
+
+/// A piece of a replacement string
+enum Piece {
+    Text(String),
+    Group(usize),
+}
+
+/// Replaces every match
+/// In the replacement `$1` or `${1}` is what a group matched, `${name}` is what a named group
+/// matched, `$0` is the whole match, and `$$` is a `$`
+fn replace(args: &[Type]) -> Result<Type, NativeError> {
+    let re = pattern(args)?;
+    let text = text(args);
+    let template = match &args[2] {
+        Type::Str(s) => s.as_str(),
+        _ => "",
+    };
+    let pieces = parse_replacement(&re, template)?;
+
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in re.captures_all(text) {
+        let Some((start, end)) = caps[0] else {
+            continue;
+        };
+        out.push_str(&text[last..start]);
+        for piece in &pieces {
+            match piece {
+                Piece::Text(s) => out.push_str(s),
+                Piece::Group(i) => {
+                    if let Some((s, e)) = caps[*i] {
+                        out.push_str(&text[s..e]);
+                    }
+                }
+            }
+        }
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    Ok(Type::Str(out))
+}
+
+fn parse_replacement(re: &Regex, template: &str) -> Result<Vec<Piece>, NativeError> {
+    let invalid = |message: String| {
+        NativeError::argument(
+            2,
+            ErrorKind::InvalidArgument {
+                function: String::from("replace"),
+                message,
+            },
+        )
+    };
+
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            literal.push(c);
+            continue;
+        }
+        let name: String = match chars.peek() {
+            Some('$') => {
+                chars.next();
+                literal.push('$');
+                continue;
+            }
+            Some('{') => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => {
+                            return Err(invalid(format!(
+                                "'${{{}' is never closed, add a '}}' after the group",
+                                name
+                            )))
+                        }
+                    }
+                }
+                if name.is_empty() {
+                    return Err(invalid(String::from("'${}' doesn't name a group")));
+                }
+                name
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(*d);
+                    chars.next();
+                }
+                digits
+            }
+            _ => {
+                return Err(invalid(String::from(
+                    "a '$' has to be followed by a group, write '$$' for a '$'",
+                )))
+            }
+        };
+
+        let index = match name.parse::<usize>() {
+            Ok(i) if i < re.names().len() => Some(i),
+            Ok(_) => None,
+            Err(_) => re.group(&name),
+        };
+        let Some(index) = index else {
+            return Err(invalid(format!("the pattern has no group '{}'", name)));
+        };
+        if !literal.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut literal)));
+        }
+        pieces.push(Piece::Group(index));
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Text(literal));
+    }
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::evaluate;
+
+    fn regex(source: &str) -> Result<String, String> {
+        evaluate(&format!("from std.regex import *;\n{}", source))
+    }
+
+    #[test]
+    fn searching() {
+        assert_eq!(regex(r#"matches(r"^\d+$", "123");"#).unwrap(), "true");
+        assert_eq!(regex(r#"find(r"\d+", "ab 12 3");"#).unwrap(), "12");
+        assert_eq!(regex(r#"find(regex("b+"), "abbb");"#).unwrap(), "bbb");
+        assert_eq!(
+            regex(r#"find_all(r"\d+", "ab 12 3");"#).unwrap(),
+            r#"["12", "3"]"#
+        );
+        assert_eq!(
+            regex(r#"split(r",\s*", "a, b,c");"#).unwrap(),
+            r#"["a", "b", "c"]"#
+        );
+        assert_eq!(
+            regex(r#"matches(1, "a");"#).unwrap_err(),
+            "SK0200: Mismatched types, expected regex or str but found int"
+        );
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(
+            regex(r#"captures(r"(?<y>\d{4})-(\d\d)?", "2024-");"#).unwrap(),
+            r#"{0: "2024-", 1: "2024", "y": "2024", 2: nil}"#
+        );
+        assert_eq!(
+            regex(r#"captures(r"(?<y>\d{4})", "in 2024")["y"];"#).unwrap(),
+            "2024"
+        );
+        assert_eq!(regex(r#"captures(r"\d", "none");"#).unwrap(), "nil");
+        assert_eq!(
+            regex(r#"group(r"(?<y>\d{4})", "in 2024", "y");"#).unwrap(),
+            "2024"
+        );
+        assert_eq!(
+            regex(r#"group(r"(?<y>\d{4})", "in 2024", 1);"#).unwrap(),
+            "2024"
+        );
+        assert_eq!(
+            regex(r#"group_names(r"(?<y>\d)(\d)");"#).unwrap(),
+            r#"["y", nil]"#
+        );
+    }
+
+    #[test]
+    fn replacing() {
+        assert_eq!(
+            regex(r#"replace(r"(?<w>\w+)", "a b", "<${w}>");"#).unwrap(),
+            "<a> <b>"
+        );
+        assert_eq!(regex(r#"replace(r"(\w)", "ab", "$1$1");"#).unwrap(), "aabb");
+        assert_eq!(
+            regex(r#"replace(r"(\w)", "ab", "${nope}");"#).unwrap_err(),
+            "SK0800: Invalid argument to 'replace': the pattern has no group 'nope'"
+        );
+        assert_eq!(
+            regex(r#"replace(r"(?<w>\w+)", "ab", "${w");"#).unwrap_err(),
+            "SK0800: Invalid argument to 'replace': '${w' is never closed, add a '}' after the group"
+        );
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        let unclosed = "SK0803: Invalid regular expression: this group is never closed";
+        assert_eq!(regex(r#"var x = r"(";"#).unwrap_err(), unclosed);
+        assert_eq!(regex(r#"matches("(", "x");"#).unwrap_err(), unclosed);
+    }
+}
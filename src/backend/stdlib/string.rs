@@ -0,0 +1,543 @@
+//! `std.string`, where lengths, indices and widths count Unicode code points rather than bytes
+
+use std::rc::Rc;
+
+use crate::{
+    backend::value::{NativeError, NativeFunction, Type},
+    errors::error::ErrorKind,
+};
+
+use super::Module;
+
+pub fn module() -> Module {
+    Module::new("std.string")
+        .function(NativeFunction::new("split", vec!["str", "str"], split).optional(1))
+        .function(NativeFunction::new("join", vec!["list", "str"], join))
+        .function(map("trim", |s| s.trim().to_string()))
+        .function(map("trim_start", |s| s.trim_start().to_string()))
+        .function(map("trim_end", |s| s.trim_end().to_string()))
+        .function(map("upper", str::to_uppercase))
+        .function(map("lower", str::to_lowercase))
+        .function(map("title", title))
+        .function(NativeFunction::new("find", vec!["str", "str"], find))
+        .function(NativeFunction::new(
+            "replace",
+            vec!["str", "str", "str"],
+            replace,
+        ))
+        .function(test("contains", |s, part| s.contains(part)))
+        .function(test("starts_with", |s, part| s.starts_with(part)))
+        .function(test("ends_with", |s, part| s.ends_with(part)))
+        .function(pad("pad_start", Align::End))
+        .function(pad("pad_end", Align::Start))
+        .function(pad("center", Align::Center))
+        .function(NativeFunction::new("repeat", vec!["str", "int"], repeat))
+        .function(NativeFunction::new("char_len", vec!["str"], |args| {
+            count(str_arg(args, 0).chars().count())
+        }))
+        .function(NativeFunction::new("byte_len", vec!["str"], |args| {
+            count(str_arg(args, 0).len())
+        }))
+        .function(NativeFunction::new("format", vec!["str"], format).variadic("any"))
+}
+
+/// Reads an argument that's been checked to be a string
+fn str_arg(args: &[Type], index: usize) -> &str {
+    match &args[index] {
+        Type::Str(s) => s,
+        _ => "",
+    }
+}
+
+fn invalid(function: &str, index: usize, message: String) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::InvalidArgument {
+            function: function.to_string(),
+            message,
+        },
+    )
+}
+
+fn count(n: usize) -> Result<Type, NativeError> {
+    i32::try_from(n)
+        .map(Type::Int)
+        .map_err(|_| NativeError::argument(0, ErrorKind::Overflow { operation: "count" }))
+}
+
+fn map(name: &str, f: fn(&str) -> String) -> NativeFunction {
+    NativeFunction::new(name, vec!["str"], move |args| {
+        Ok(Type::Str(f(str_arg(args, 0))))
+    })
+}
+
+fn test(name: &str, f: fn(&str, &str) -> bool) -> NativeFunction {
+    NativeFunction::new(name, vec!["str", "str"], move |args| {
+        Ok(Type::Bool(f(str_arg(args, 0), str_arg(args, 1))))
+    })
+}
+
+/// Splits on `sep`, or on runs of whitespace if there isn't one
+fn split(args: &[Type]) -> Result<Type, NativeError> {
+    let s = str_arg(args, 0);
+    let parts: Vec<Type> = match args.get(1) {
+        Some(Type::Str(sep)) if sep.is_empty() => {
+            return Err(invalid(
+                "split",
+                1,
+                String::from("the separator can't be empty"),
+            ))
+        }
+        Some(Type::Str(sep)) => s
+            .split(sep.as_str())
+            .map(|p| Type::Str(p.to_string()))
+            .collect(),
+        _ => s
+            .split_whitespace()
+            .map(|p| Type::Str(p.to_string()))
+            .collect(),
+    };
+    Ok(Type::List(Rc::new(parts)))
+}
+
+fn join(args: &[Type]) -> Result<Type, NativeError> {
+    let Type::List(items) = &args[0] else {
+        return Ok(Type::Nil);
+    };
+    let mut parts = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        match item {
+            Type::Str(s) => parts.push(s.as_str()),
+            other => {
+                return Err(NativeError::argument(
+                    0,
+                    ErrorKind::TypeMismatch {
+                        expected: String::from("a list of str"),
+                        found: format!("{} at index {}", other.type_name(), i),
+                    },
+                ))
+            }
+        }
+    }
+    Ok(Type::Str(parts.join(str_arg(args, 1))))
+}
+
+/// Capitalizes the first letter of every word and lowercases the rest
+fn title(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut start = true;
+    for c in s.chars() {
+        if start {
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        start = !c.is_alphanumeric();
+    }
+    out
+}
+
+/// The index of the first code point of `part` in `s`, or -1 if it isn't there
+fn find(args: &[Type]) -> Result<Type, NativeError> {
+    let s = str_arg(args, 0);
+    match s.find(str_arg(args, 1)) {
+        Some(byte) => count(s[..byte].chars().count()),
+        None => Ok(Type::Int(-1)),
+    }
+}
+
+/// Replaces every occurrence
+fn replace(args: &[Type]) -> Result<Type, NativeError> {
+    let from = str_arg(args, 1);
+    if from.is_empty() {
+        return Err(invalid(
+            "replace",
+            1,
+            String::from("can't replace an empty string"),
+        ));
+    }
+    Ok(Type::Str(str_arg(args, 0).replace(from, str_arg(args, 2))))
+}
+
+/// Where the original text ends up once padded
+#[derive(Clone, Copy)]
+enum Align {
+    Start,
+    End,
+    Center,
+}
+
+/// `name(s, width)` or `name(s, width, fill)`, where `fill` is a single character and defaults to
+/// a space
+fn pad(name: &'static str, align: Align) -> NativeFunction {
+    NativeFunction::new(name, vec!["str", "int", "str"], move |args| {
+        let s = str_arg(args, 0);
+        let width = match args[1] {
+            Type::Int(w) => usize::try_from(w).unwrap_or(0),
+            _ => 0,
+        };
+        let fill = match args.get(2) {
+            Some(Type::Str(fill)) => {
+                let mut chars = fill.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => {
+                        return Err(invalid(
+                            name,
+                            2,
+                            format!("the fill has to be one character, not '{}'", fill),
+                        ))
+                    }
+                }
+            }
+            _ => ' ',
+        };
+        Ok(Type::Str(aligned(s, width, fill, align)))
+    })
+    .optional(1)
+}
+
+fn aligned(s: &str, width: usize, fill: char, align: Align) -> String {
+    let missing = width.saturating_sub(s.chars().count());
+    let (before, after) = match align {
+        Align::Start => (0, missing),
+        Align::End => (missing, 0),
+        Align::Center => (missing / 2, missing - missing / 2),
+    };
+    let mut out = String::with_capacity(s.len() + missing);
+    out.extend(std::iter::repeat_n(fill, before));
+    out.push_str(s);
+    out.extend(std::iter::repeat_n(fill, after));
+    out
+}
+
+fn repeat(args: &[Type]) -> Result<Type, NativeError> {
+    let s = str_arg(args, 0);
+    let Type::Int(n) = args[1] else {
+        return Ok(Type::Nil);
+    };
+    let n = usize::try_from(n)
+        .map_err(|_| invalid("repeat", 1, format!("can't repeat a string {} times", n)))?;
+    if s.len()
+        .checked_mul(n)
+        .is_none_or(|len| len > i32::MAX as usize)
+    {
+        return Err(NativeError::argument(
+            1,
+            ErrorKind::Overflow {
+                operation: "repeat",
+            },
+        ));
+    }
+    Ok(Type::Str(s.repeat(n)))
+}
+
+/// The widest a `%` conversion can pad to and the most digits it can print after the point
+/// Rust prints at most `u16::MAX` digits of a float, and `%e` puts one of them before the point
+const MAX_WIDTH: usize = u16::MAX as usize - 1;
+
+/// A `%` conversion in a format string, like `%-8.2f`
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+/// printf-style formatting
+///
+/// Conversions are `%d` (or `%i`) for ints, `%f` and `%e` for numbers, `%x`, `%X` and `%o` for
+/// ints in hex and octal, `%s` for any value, and `%%` for a literal `%`. Between the `%` and the
+/// conversion can come the flags `-` (left align), `0` (pad numbers with zeros), `+` and ` ` (mark
+/// positive numbers), then a width, then a `.` and a precision: digits after the point for `%f`
+/// and `%e`, and the most characters printed for `%s`. Neither can be more than 65534.
+fn format(args: &[Type]) -> Result<Type, NativeError> {
+    let template = str_arg(args, 0);
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    let mut next = 1;
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let mut spec = Spec {
+            left: false,
+            zero: false,
+            plus: false,
+            space: false,
+            width: 0,
+            precision: None,
+            conversion: '%',
+        };
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '0' => spec.zero = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        spec.width = digits(&mut chars).unwrap_or(0);
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(digits(&mut chars).unwrap_or(0));
+        }
+        for (what, n) in [("width", Some(spec.width)), ("precision", spec.precision)] {
+            if n.is_some_and(|n| n > MAX_WIDTH) {
+                return Err(invalid(
+                    "format",
+                    0,
+                    format!("a {} can be at most {}", what, MAX_WIDTH),
+                ));
+            }
+        }
+        spec.conversion = match chars.next() {
+            Some(c) => c,
+            None => {
+                return Err(invalid(
+                    "format",
+                    0,
+                    String::from("the format ends in the middle of a '%' conversion"),
+                ))
+            }
+        };
+
+        if spec.conversion == '%' {
+            out.push('%');
+            continue;
+        }
+        let Some(value) = args.get(next) else {
+            return Err(NativeError::call(ErrorKind::InvalidArgument {
+                function: String::from("format"),
+                message: format!("the format needs more values than the {} given", next - 1),
+            }));
+        };
+        out.push_str(&convert(&spec, value, next)?);
+        next += 1;
+    }
+
+    if next < args.len() {
+        return Err(invalid(
+            "format",
+            next,
+            format!(
+                "the format only uses {} value{}",
+                next - 1,
+                if next == 2 { "" } else { "s" }
+            ),
+        ));
+    }
+    Ok(Type::Str(out))
+}
+
+fn digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut n: Option<usize> = None;
+    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+        n = Some(n.unwrap_or(0).saturating_mul(10).saturating_add(d as usize));
+        chars.next();
+    }
+    n
+}
+
+/// Formats one value, `index` being the argument it came from
+fn convert(spec: &Spec, value: &Type, index: usize) -> Result<String, NativeError> {
+    let mismatch = |expected: &str| {
+        NativeError::argument(
+            index,
+            ErrorKind::TypeMismatch {
+                expected: expected.to_string(),
+                found: value.type_name().to_string(),
+            },
+        )
+    };
+
+    let (sign, body) = match (spec.conversion, value) {
+        ('s', value) => {
+            let s = value.to_string();
+            let s = match spec.precision {
+                Some(p) => s.chars().take(p).collect(),
+                None => s,
+            };
+            return Ok(aligned(&s, spec.width, ' ', alignment(spec)));
+        }
+        ('d' | 'i', Type::Int(i)) => signed(spec, *i < 0, i.unsigned_abs().to_string()),
+        ('x', Type::Int(i)) => (String::new(), format!("{:x}", i)),
+        ('X', Type::Int(i)) => (String::new(), format!("{:X}", i)),
+        ('o', Type::Int(i)) => (String::new(), format!("{:o}", i)),
+        ('d' | 'i' | 'x' | 'X' | 'o', _) => return Err(mismatch("int")),
+        ('f' | 'e', Type::Int(_) | Type::Float(_)) => {
+            let x = match value {
+                Type::Int(i) => f64::from(*i),
+                Type::Float(x) => f64::from(*x),
+                _ => 0.0,
+            };
+            let precision = spec.precision.unwrap_or(6);
+            let body = if !x.is_finite() {
+                x.abs().to_string()
+            } else if spec.conversion == 'f' {
+                format!("{:.*}", precision, x.abs())
+            } else {
+                exponent(x.abs(), precision)
+            };
+            signed(spec, x.is_sign_negative() && !x.is_nan(), body)
+        }
+        ('f' | 'e', _) => return Err(mismatch("number")),
+        (c, _) => {
+            return Err(invalid(
+                "format",
+                0,
+                format!(
+                    "'%{}' isn't a conversion, use one of %d %i %f %e %x %X %o %s",
+                    c
+                ),
+            ))
+        }
+    };
+
+    let len = sign.chars().count() + body.chars().count();
+    if spec.zero && !spec.left && len < spec.width {
+        // Zeros go between the sign and the digits
+        let zeros = "0".repeat(spec.width - len);
+        Ok(format!("{}{}{}", sign, zeros, body))
+    } else {
+        Ok(aligned(
+            &format!("{}{}", sign, body),
+            spec.width,
+            ' ',
+            alignment(spec),
+        ))
+    }
+}
+
+fn alignment(spec: &Spec) -> Align {
+    if spec.left {
+        Align::Start
+    } else {
+        Align::End
+    }
+}
+
+/// The sign a number is printed with, given the flags
+fn signed(spec: &Spec, negative: bool, body: String) -> (String, String) {
+    let sign = if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    };
+    (sign.to_string(), body)
+}
+
+/// Scientific notation the way printf writes it, `1.500000e+03`
+fn exponent(x: f64, precision: usize) -> String {
+    let rust = format!("{:.*e}", precision, x);
+    let (mantissa, exp) = rust.split_once('e').unwrap_or((&rust, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exp < 0 { '-' } else { '+' },
+        exp.unsigned_abs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::evaluate;
+
+    fn string(source: &str) -> Result<String, String> {
+        evaluate(&format!("from std.string import *;\n{}", source))
+    }
+
+    fn format(args: &str) -> Result<String, String> {
+        evaluate(&format!("from std.string import format; format({});", args))
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            string(r#"split("a,b,,c", ",");"#).unwrap(),
+            r#"["a", "b", "", "c"]"#
+        );
+        assert_eq!(string(r#"split(" a  b ");"#).unwrap(), r#"["a", "b"]"#);
+        assert_eq!(string(r#"join(split("a b"), "-");"#).unwrap(), "a-b");
+        assert_eq!(string(r#"title("hello wORLD");"#).unwrap(), "Hello World");
+        assert_eq!(string(r#"upper("straße");"#).unwrap(), "STRASSE");
+        assert_eq!(string(r#"replace("aaa", "a", "b");"#).unwrap(), "bbb");
+        assert_eq!(string(r#"contains("abc", "b");"#).unwrap(), "true");
+        assert_eq!(string(r#"repeat("ab", 3);"#).unwrap(), "ababab");
+        assert_eq!(
+            string(r#"repeat("a", 0 - 1);"#).unwrap_err(),
+            "SK0800: Invalid argument to 'repeat': can't repeat a string -1 times"
+        );
+    }
+
+    #[test]
+    fn unicode_positions_and_padding() {
+        // Positions and lengths count characters, not bytes
+        assert_eq!(string(r#"find("héllo", "l");"#).unwrap(), "2");
+        assert_eq!(string(r#"find("abc", "z");"#).unwrap(), "-1");
+        assert_eq!(string(r#"char_len("é");"#).unwrap(), "1");
+        assert_eq!(string(r#"byte_len("é");"#).unwrap(), "2");
+        assert_eq!(string(r#"pad_start("7", 3, "0");"#).unwrap(), "007");
+        assert_eq!(string(r#"pad_end("é", 3, ".");"#).unwrap(), "é..");
+        assert_eq!(string(r#"center("ab", 6);"#).unwrap(), "  ab  ");
+        assert!(string(r#"pad_end("a", 3, "xy");"#)
+            .unwrap_err()
+            .contains("the fill has to be one character"));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            format(r#""%5d|%-5d|%05d", 42, 42, 0 - 42"#).unwrap(),
+            "   42|42   |-0042"
+        );
+        assert_eq!(
+            format(r#""%+.2f %e", 3.14159, 1500.0"#).unwrap(),
+            "+3.14 1.500000e+03"
+        );
+        assert_eq!(
+            format(r#""%x %X %o %%", 255, 255, 8"#).unwrap(),
+            "ff FF 10 %"
+        );
+        assert_eq!(format(r#""%.3s|%4s", "abcdef", "ab""#).unwrap(), "abc|  ab");
+        assert_eq!(
+            format(r#""%d", 1.5"#).unwrap_err(),
+            "SK0200: Mismatched types, expected int but found float"
+        );
+        assert_eq!(
+            format(r#""%d %d", 1"#).unwrap_err(),
+            "SK0800: Invalid argument to 'format': the format needs more values than the 1 given"
+        );
+    }
+
+    #[test]
+    fn width_and_precision_limits() {
+        assert_eq!(
+            format(r#""%99999999999999999999d", 1"#).unwrap_err(),
+            "SK0800: Invalid argument to 'format': a width can be at most 65534"
+        );
+        assert_eq!(
+            format(r#""%.99999999999f", 1.0"#).unwrap_err(),
+            "SK0800: Invalid argument to 'format': a precision can be at most 65534"
+        );
+        assert_eq!(
+            format(r#""%.65535e", 1.0"#).unwrap_err(),
+            "SK0800: Invalid argument to 'format': a precision can be at most 65534"
+        );
+        assert_eq!(format(r#""%65534d", 1"#).unwrap().len(), 65534);
+        assert_eq!(format(r#""%.65534e", 1.5"#).unwrap().len(), 65540);
+    }
+}
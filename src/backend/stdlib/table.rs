@@ -0,0 +1,1779 @@
+//! `std.table`, for building tables and series and taking them apart
+
+use std::rc::Rc;
+
+use crate::{
+    backend::{
+        group::{Aggregate, AggregateError, Groups, Missing},
+        join::{self, Cardinality, How, JoinError, Options, Side},
+        map::Key,
+        reshape::{self, Cells, ReshapeError},
+        series,
+        table::{Column, ColumnData, SortKey, Table, TableError},
+        value::{Caller, NativeError, NativeFunction, Type},
+    },
+    errors::{error::ErrorKind, suggest},
+};
+
+use super::Module;
+
+pub fn module() -> Module {
+    functions().into_iter().chain(missing()).fold(
+        Module::new("std.table")
+            .function(NativeFunction::new("table", vec![], table).variadic("any"))
+            .function(NativeFunction::new("series", vec!["list"], |args| {
+                let values = match &args[0] {
+                    Type::List(values) => values,
+                    _ => unreachable!("the runtime checks arguments against their annotations"),
+                };
+                let column = Column::from_values("series", values)
+                    .map_err(|err| invalid("series", 0, err))?;
+                Ok(Type::Series(Rc::new(column)))
+            })),
+        Module::function,
+    )
+}
+
+/// The functions for taking tables and series apart or putting them together, which can be
+/// called as methods too
+fn functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("column", vec!["table", "str"], |args| {
+            let table = table_arg(args);
+            let name = text(&args[1]);
+            Ok(Type::Series(lookup(table, 1, name)?.clone()))
+        }),
+        NativeFunction::new("to_list", vec!["series"], |args| match &args[0] {
+            Type::Series(column) => Ok(Type::List(Rc::new(column.values()))),
+            _ => unreachable!("the runtime checks arguments against their annotations"),
+        }),
+        NativeFunction::new("columns", vec!["table"], |args| {
+            let names = table_arg(args)
+                .names()
+                .map(|name| Type::Str(name.to_string()))
+                .collect();
+            Ok(Type::List(Rc::new(names)))
+        }),
+        NativeFunction::new("schema", vec!["table"], |args| {
+            Ok(Type::Table(Rc::new(table_arg(args).schema())))
+        }),
+        NativeFunction::new(
+            "join",
+            vec!["table", "table", "any", "str", "list", "str"],
+            join,
+        )
+        .optional(3)
+        .named(vec!["left", "right", "on", "how", "suffixes", "validate"]),
+    ]
+}
+
+/// The functions for finding and dealing with missing values, which work on single values too
+fn missing() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("na", vec!["str"], na),
+        NativeFunction::new("is_na", vec!["any"], is_na),
+        NativeFunction::new("fill_na", vec!["any", "any"], fill_na),
+        NativeFunction::new("drop_na", vec!["any"], drop_na).variadic("str"),
+        NativeFunction::new("coalesce", vec!["any", "any"], coalesce).variadic("any"),
+    ]
+}
+
+/// The verbs for manipulating tables, called as methods like `sales.sort_by("region")`
+/// Each one returns a new table, sharing every column it didn't have to change
+pub fn methods() -> Vec<NativeFunction> {
+    let mut methods = functions();
+    methods.extend([
+        NativeFunction::new("select", vec!["table", "str"], select).variadic("str"),
+        NativeFunction::calling("filter", vec!["table", "any"], filter),
+        NativeFunction::calling("mutate", vec!["table", "str", "any"], mutate).variadic("any"),
+        NativeFunction::new("sort_by", vec!["table", "str"], sort_by).variadic("str"),
+        NativeFunction::new("head", vec!["table", "int"], |args| slice("head", args)).optional(1),
+        NativeFunction::new("tail", vec!["table", "int"], |args| slice("tail", args)).optional(1),
+        NativeFunction::new("distinct", vec!["table"], distinct).variadic("str"),
+        NativeFunction::new("rename", vec!["table", "map"], rename),
+        NativeFunction::new("group_by", vec!["table", "str"], group_by).variadic("str"),
+        NativeFunction::calling("agg", vec!["groups", "map", "str"], agg).optional(1),
+        NativeFunction::calling("pivot", vec!["table", "any", "str", "str", "any"], pivot)
+            .optional(1)
+            .named(vec!["table", "index", "columns", "values", "agg"]),
+        NativeFunction::new("melt", vec!["table", "any", "any", "str", "str"], melt)
+            .optional(3)
+            .named(vec![
+                "table",
+                "id_vars",
+                "value_vars",
+                "var_name",
+                "value_name",
+            ]),
+        NativeFunction::new("stack", vec!["table", "any", "str", "str"], stack)
+            .optional(2)
+            .named(vec!["table", "columns", "var_name", "value_name"]),
+        NativeFunction::new("unstack", vec!["table", "str", "str"], unstack)
+            .named(vec!["table", "column", "values"]),
+        NativeFunction::new("is_na", vec!["series"], is_na),
+        NativeFunction::new("fill_na", vec!["series", "any"], fill_na),
+        NativeFunction::new("drop_na", vec!["series"], drop_na),
+        NativeFunction::new("coalesce", vec!["series", "any"], coalesce).variadic("any"),
+        NativeFunction::new("fill_na", vec!["table", "map"], fill_na),
+        NativeFunction::new("drop_na", vec!["table"], drop_na).variadic("str"),
+    ]);
+    methods
+}
+
+fn invalid(function: &str, index: usize, err: TableError) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::InvalidArgument {
+            function: function.to_string(),
+            message: err.to_string(),
+        },
+    )
+}
+
+fn text(value: &Type) -> &str {
+    match value {
+        Type::Str(s) => s,
+        _ => "",
+    }
+}
+
+fn mismatch(index: usize, expected: &str, found: &Type) -> NativeError {
+    NativeError::argument(
+        index,
+        ErrorKind::TypeMismatch {
+            expected: expected.to_string(),
+            found: found.type_name().to_string(),
+        },
+    )
+}
+
+/// The column called `name`, or an error on argument `index` suggesting a similar name
+fn lookup<'t>(table: &'t Table, index: usize, name: &str) -> Result<&'t Rc<Column>, NativeError> {
+    table.column(name).ok_or_else(|| {
+        let err = NativeError::argument(
+            index,
+            ErrorKind::MissingColumn {
+                column: name.to_string(),
+            },
+        );
+        match suggest::closest(name, table.names()) {
+            Some(similar) => err.note(format!("did you mean '{}'?", similar)),
+            None => err,
+        }
+    })
+}
+
+/// A str or a list of strs, as a list
+fn names(value: &Type) -> Option<Vec<&str>> {
+    match value {
+        Type::Str(name) => Some(vec![name.as_str()]),
+        Type::List(items) => items
+            .iter()
+            .map(|item| match item {
+                Type::Str(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+fn table_arg(args: &[Type]) -> &Table {
+    match &args[0] {
+        Type::Table(table) => table,
+        _ => unreachable!("the runtime checks arguments against their annotations"),
+    }
+}
+
+/// `table(name, values, name, values, ...)` builds a table out of a series or list of values for
+/// each column, inferring the type of a list's values
+fn table(args: &[Type]) -> Result<Type, NativeError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(NativeError::call(ErrorKind::InvalidArgument {
+            function: String::from("table"),
+            message: String::from("takes a name and then a list of values for each column"),
+        }));
+    }
+
+    let mut columns: Vec<(String, Rc<Column>)> = Vec::with_capacity(args.len() / 2);
+    for (i, pair) in args.chunks(2).enumerate() {
+        let (name_at, values_at) = (2 * i, 2 * i + 1);
+        let name = match &pair[0] {
+            Type::Str(name) => name.clone(),
+            other => {
+                return Err(NativeError::argument(
+                    name_at,
+                    ErrorKind::TypeMismatch {
+                        expected: String::from("str"),
+                        found: other.type_name().to_string(),
+                    },
+                ))
+            }
+        };
+        if columns.iter().any(|(other, _)| *other == name) {
+            return Err(invalid(
+                "table",
+                name_at,
+                TableError::Duplicate { column: name },
+            ));
+        }
+        let column = match &pair[1] {
+            Type::List(values) => Rc::new(
+                Column::from_values(&name, values)
+                    .map_err(|err| invalid("table", values_at, err))?,
+            ),
+            // A series is already a column, and can be shared as it is
+            Type::Series(column) => column.clone(),
+            other => {
+                return Err(NativeError::argument(
+                    values_at,
+                    ErrorKind::TypeMismatch {
+                        expected: String::from("list or series"),
+                        found: other.type_name().to_string(),
+                    },
+                ))
+            }
+        };
+        if let Some((first, rows)) = columns.first().map(|(n, c)| (n, c.len())) {
+            if column.len() != rows {
+                return Err(invalid(
+                    "table",
+                    values_at,
+                    TableError::Length {
+                        column: name,
+                        found: column.len(),
+                        expected: rows,
+                        first: first.clone(),
+                    },
+                ));
+            }
+        }
+        columns.push((name, column));
+    }
+
+    let table = Table::new(columns).map_err(|err| {
+        NativeError::call(ErrorKind::InvalidArgument {
+            function: String::from("table"),
+            message: err.to_string(),
+        })
+    })?;
+    Ok(Type::Table(Rc::new(table)))
+}
+
+fn wrap(table: Table) -> Result<Type, NativeError> {
+    Ok(Type::Table(Rc::new(table)))
+}
+
+/// `select(name, ...)` keeps just the named columns, in the order they're named
+fn select(args: &[Type]) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let names: Vec<&str> = args[1..].iter().map(text).collect();
+    for (i, name) in names.iter().enumerate() {
+        lookup(table, i + 1, name)?;
+    }
+    table
+        .project(&names)
+        .map_err(|err| {
+            NativeError::call(ErrorKind::InvalidArgument {
+                function: String::from("select"),
+                message: err.to_string(),
+            })
+        })
+        .and_then(wrap)
+}
+
+/// `filter(mask)` keeps the rows where a series of bools is true, and `filter(predicate)` the
+/// rows a function returns true for, given each row as a map from column names to values
+/// A missing value in the mask, or returned by the predicate, drops the row
+fn filter(args: &[Type], caller: &mut dyn Caller) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let rows: Vec<usize> = match &args[1] {
+        Type::Series(mask) => {
+            let ColumnData::Bool(values) = &mask.data else {
+                return Err(mismatch(1, "series of bools", &args[1])
+                    .note(format!("the series holds {} values", mask.type_name())));
+            };
+            if mask.len() != table.rows() {
+                return Err(NativeError::argument(
+                    1,
+                    ErrorKind::LengthMismatch {
+                        left: table.rows(),
+                        right: mask.len(),
+                    },
+                ));
+            }
+            (0..table.rows())
+                .filter(|&row| mask.is_valid(row) && values[row])
+                .collect()
+        }
+        Type::Function(_) | Type::Native(_) => {
+            let mut rows = Vec::new();
+            for row in 0..table.rows() {
+                let keep = caller.call(&args[1], vec![Type::Map(Rc::new(table.row(row)))])?;
+                match keep {
+                    Type::Bool(true) => rows.push(row),
+                    Type::Bool(false) | Type::Na("bool") => {}
+                    other => {
+                        return Err(NativeError::argument(
+                            1,
+                            ErrorKind::InvalidArgument {
+                                function: String::from("filter"),
+                                message: format!(
+                                "the predicate has to return a bool, but returned {} for row {}",
+                                other.quoted(),
+                                row
+                            ),
+                            },
+                        ))
+                    }
+                }
+            }
+            rows
+        }
+        other => return Err(mismatch(1, "series or function", other)),
+    };
+    wrap(table.take(&rows))
+}
+
+/// `mutate(name, value, name, value, ...)` sets each column in turn, so later values can use
+/// the columns set before them
+/// A value is a series or list with a value for each row, a function of the row as a map, or a
+/// single int, float, str or bool for every row. A column that's already there keeps its place.
+fn mutate(args: &[Type], caller: &mut dyn Caller) -> Result<Type, NativeError> {
+    if args.len().is_multiple_of(2) {
+        return Err(NativeError::call(ErrorKind::InvalidArgument {
+            function: String::from("mutate"),
+            message: String::from("takes a name and then a value for each column"),
+        }));
+    }
+
+    let mut table = table_arg(args).clone();
+    for (i, pair) in args[1..].chunks(2).enumerate() {
+        let (name_at, value_at) = (2 * i + 1, 2 * i + 2);
+        let name = match &pair[0] {
+            Type::Str(name) => name.as_str(),
+            other => return Err(mismatch(name_at, "str", other)),
+        };
+        let rows = table.rows();
+        let column = match &pair[1] {
+            Type::Series(column) => column.clone(),
+            Type::List(values) => Rc::new(
+                Column::from_values(name, values)
+                    .map_err(|err| invalid("mutate", value_at, err))?,
+            ),
+            Type::Function(_) | Type::Native(_) => {
+                let mut values = Vec::with_capacity(rows);
+                for row in 0..rows {
+                    values.push(caller.call(&pair[1], vec![Type::Map(Rc::new(table.row(row)))])?);
+                }
+                Rc::new(
+                    Column::from_values(name, &values)
+                        .map_err(|err| invalid("mutate", value_at, err))?,
+                )
+            }
+            Type::Int(v) => Rc::new(Column::new(ColumnData::Int(vec![*v; rows]))),
+            Type::Float(v) => Rc::new(Column::new(ColumnData::Float(vec![*v; rows]))),
+            Type::Str(v) => Rc::new(Column::new(ColumnData::Str(vec![v.clone(); rows]))),
+            Type::Bool(v) => Rc::new(Column::new(ColumnData::Bool(vec![*v; rows]))),
+            other => {
+                return Err(mismatch(
+                    value_at,
+                    "series, list, function, int, float, str or bool",
+                    other,
+                ))
+            }
+        };
+        table = table
+            .with_column(name, column)
+            .map_err(|err| invalid("mutate", value_at, err))?;
+    }
+    wrap(table)
+}
+
+/// `sort_by(key, ...)` sorts by each key in turn, keeping the order of rows that tie
+/// A key is a column name, optionally followed by `asc` or `desc` and then by `nulls first` or
+/// `nulls last`, like `"revenue desc nulls first"`. Missing values go last unless asked for
+/// first, whichever the direction.
+fn sort_by(args: &[Type]) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let keys = args[1..]
+        .iter()
+        .enumerate()
+        .map(|(i, spec)| sort_key(table, i + 1, text(spec)))
+        .collect::<Result<Vec<_>, _>>()?;
+    table
+        .sort(&keys)
+        .map_err(|err| invalid("sort_by", 1, err))
+        .and_then(wrap)
+}
+
+fn sort_key(table: &Table, index: usize, spec: &str) -> Result<SortKey, NativeError> {
+    // A column whose name happens to end in "desc" is still that column
+    if table.column(spec).is_some() {
+        return Ok(SortKey {
+            column: spec.to_string(),
+            descending: false,
+            nulls_first: false,
+        });
+    }
+    let mut rest = spec.trim();
+    let mut nulls_first = false;
+    if let Some(stripped) = rest.strip_suffix(" nulls first") {
+        (rest, nulls_first) = (stripped.trim_end(), true);
+    } else if let Some(stripped) = rest.strip_suffix(" nulls last") {
+        rest = stripped.trim_end();
+    }
+    let mut descending = false;
+    if let Some(stripped) = rest.strip_suffix(" desc") {
+        (rest, descending) = (stripped.trim_end(), true);
+    } else if let Some(stripped) = rest.strip_suffix(" asc") {
+        rest = stripped.trim_end();
+    }
+    lookup(table, index, rest)?;
+    Ok(SortKey {
+        column: rest.to_string(),
+        descending,
+        nulls_first,
+    })
+}
+
+/// `head(n)` and `tail(n)`, the first or last `n` rows, 5 if `n` isn't given
+fn slice(function: &str, args: &[Type]) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let n = match args.get(1) {
+        None => 5,
+        Some(Type::Int(n)) if *n >= 0 => *n as usize,
+        Some(other) => {
+            return Err(NativeError::argument(
+                1,
+                ErrorKind::InvalidArgument {
+                    function: function.to_string(),
+                    message: format!("can't take a negative number of rows, {}", other),
+                },
+            ))
+        }
+    };
+    let n = n.min(table.rows());
+    let rows: Vec<usize> = match function {
+        "head" => (0..n).collect(),
+        _ => (table.rows() - n..table.rows()).collect(),
+    };
+    wrap(table.take(&rows))
+}
+
+/// `distinct(name, ...)` keeps the first row of every set with the same values in the named
+/// columns, or in every column if none are named
+fn distinct(args: &[Type]) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let names: Vec<&str> = match &args[1..] {
+        [] => table.names().collect(),
+        names => names.iter().map(text).collect(),
+    };
+    if args.len() > 1 {
+        for (i, name) in names.iter().enumerate() {
+            lookup(table, i + 1, name)?;
+        }
+    }
+    table
+        .distinct(&names)
+        .map_err(|err| invalid("distinct", 1, err))
+        .and_then(wrap)
+}
+
+/// `rename({"old": "new", ...})`, leaving the columns where they were
+fn rename(args: &[Type]) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let Type::Map(map) = &args[1] else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let mut renames = Vec::with_capacity(map.len());
+    for (key, value) in map.iter() {
+        let Key::Str(from) = key else {
+            return Err(mismatch(1, "map from str to str", &key.to_value())
+                .note(format!("the key {} isn't a column name", key)));
+        };
+        lookup(table, 1, from)?;
+        let Type::Str(to) = value else {
+            return Err(mismatch(1, "map from str to str", value)
+                .note(format!("the new name for '{}' isn't a str", from)));
+        };
+        renames.push((from.clone(), to.clone()));
+    }
+    table
+        .rename(&renames)
+        .map_err(|err| invalid("rename", 1, err))
+        .and_then(wrap)
+}
+
+/// `group_by(key, ...)` splits the rows up by the values in the key columns, ready for `agg`
+fn group_by(args: &[Type]) -> Result<Type, NativeError> {
+    let Type::Table(table) = &args[0] else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let keys: Vec<&str> = args[1..].iter().map(text).collect();
+    for (i, key) in keys.iter().enumerate() {
+        lookup(table, i + 1, key)?;
+    }
+    let groups = Groups::new(table.clone(), &keys).map_err(|err| invalid("group_by", 1, err))?;
+    Ok(Type::Groups(Rc::new(groups)))
+}
+
+/// `agg({name: aggregate, ...}, missing)` makes a table with a row for each group, holding its
+/// keys and then a column for each aggregate, named by the map's keys
+///
+/// An aggregate is a str like `"sum(revenue)"`, `"quantile(revenue, 0.9)"` or `"count()"`, the
+/// number of rows in the group, or a function given each group as a table, which returns a single
+/// value. `missing` is what built-in aggregates do with missing values: `"skip"` them (the
+/// default), `"propagate"` them to the group's result, or treat them as an `"error"`. Functions
+/// always get every row.
+fn agg(args: &[Type], caller: &mut dyn Caller) -> Result<Type, NativeError> {
+    let Type::Groups(groups) = &args[0] else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let Type::Map(specs) = &args[1] else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let missing = match args.get(2).map(text) {
+        None | Some("skip") => Missing::Skip,
+        Some("propagate") => Missing::Propagate,
+        Some("error") => Missing::Error,
+        Some(other) => {
+            return Err(NativeError::argument(
+                2,
+                ErrorKind::InvalidArgument {
+                    function: String::from("agg"),
+                    message: format!(
+                        "expected \"skip\", \"propagate\" or \"error\" but found {:?}",
+                        other
+                    ),
+                },
+            ))
+        }
+    };
+
+    let table = groups.table();
+    let mut columns = groups.keys().columns().to_vec();
+    for (key, spec) in specs.iter() {
+        let Key::Str(name) = key else {
+            return Err(mismatch(1, "map from str", &key.to_value())
+                .note(format!("the key {} isn't a column name", key)));
+        };
+        let column = match spec {
+            Type::Str(spec) => {
+                let (how, column) = aggregate_spec(table, spec)?;
+                let result = match column {
+                    Some(column) => groups.aggregate(table.column(column).unwrap(), how, missing),
+                    None => groups.sizes(),
+                };
+                result.map_err(|err| aggregate_error(1, column.unwrap_or_default(), err))?
+            }
+            Type::Function(_) | Type::Native(_) => {
+                each_group(("agg", 1), name, groups, spec, caller)?
+            }
+            other => return Err(mismatch(1, "map of strs or functions", other)),
+        };
+        columns.push((name.clone(), Rc::new(column)));
+    }
+    Table::new(columns)
+        .map_err(|err| invalid("agg", 1, err))
+        .and_then(wrap)
+}
+
+/// The names of the built-in aggregates, for suggesting one in place of a misspelling
+const AGGREGATES: [&str; 10] = [
+    "sum",
+    "mean",
+    "min",
+    "max",
+    "count",
+    "count_distinct",
+    "median",
+    "quantile",
+    "std",
+    "var",
+];
+
+fn aggregate_error(index: usize, column: &str, err: AggregateError) -> NativeError {
+    match err {
+        AggregateError::NotNumeric { aggregate, found } => NativeError::argument(
+            index,
+            ErrorKind::TypeMismatch {
+                expected: String::from("int or float"),
+                found: found.to_string(),
+            },
+        )
+        .note(format!(
+            "'{}' takes numbers, but column '{}' holds {} values",
+            aggregate, column, found
+        )),
+        AggregateError::Missing { row } => NativeError::argument(
+            index,
+            ErrorKind::MissingValue {
+                column: column.to_string(),
+                row,
+            },
+        )
+        .note("missing values are an error under the \"error\" policy"),
+        AggregateError::Overflow { aggregate } => NativeError::argument(
+            index,
+            ErrorKind::Overflow {
+                operation: aggregate,
+            },
+        ),
+    }
+}
+
+/// Calls `function` with each group's rows as a table, collecting what it returns into a column
+/// called `name`, where a function that only ever returns nil makes a column of missing strs
+/// `argument` is the native function and argument index the function came from, for errors
+fn each_group(
+    argument: (&str, usize),
+    name: &str,
+    groups: &Groups,
+    function: &Type,
+    caller: &mut dyn Caller,
+) -> Result<Column, NativeError> {
+    let table = groups.table();
+    let mut values = Vec::with_capacity(groups.len());
+    for rows in groups.rows() {
+        let group = Type::Table(Rc::new(table.take(rows)));
+        values.push(caller.call(function, vec![group])?);
+    }
+    if values.iter().all(|value| matches!(value, Type::Nil)) {
+        return Ok(Column::missing("str", values.len()));
+    }
+    Column::from_values(name, &values).map_err(|err| invalid(argument.0, argument.1, err))
+}
+
+/// Reads an aggregate like `"quantile(revenue, 0.9)"`, returning it and the column it's of, which
+/// is only left out for `"count()"`
+fn aggregate_spec<'s>(
+    table: &Table,
+    spec: &'s str,
+) -> Result<(Aggregate, Option<&'s str>), NativeError> {
+    let malformed = |message: String| {
+        NativeError::argument(
+            1,
+            ErrorKind::InvalidArgument {
+                function: String::from("agg"),
+                message,
+            },
+        )
+    };
+    let Some((name, inner)) = spec
+        .split_once('(')
+        .and_then(|(name, rest)| Some((name.trim(), rest.trim_end().strip_suffix(')')?)))
+    else {
+        return Err(malformed(format!(
+            "expected an aggregate like \"sum(revenue)\", but found {:?}",
+            spec
+        )));
+    };
+    let (column, arg) = match inner.split_once(',') {
+        Some((column, arg)) => {
+            let arg = arg.trim();
+            let q = arg
+                .parse::<f64>()
+                .ok()
+                .filter(|q| (0.0..=1.0).contains(q))
+                .ok_or_else(|| {
+                    malformed(format!("a quantile is between 0 and 1, but was {}", arg))
+                })?;
+            (column.trim(), Some(q))
+        }
+        None => (inner.trim(), None),
+    };
+    let Some(how) = Aggregate::from_name(name, arg) else {
+        let message = match name {
+            "quantile" => {
+                String::from("'quantile' needs a fraction, like \"quantile(revenue, 0.9)\"")
+            }
+            _ if arg.is_some() => format!("'{}' takes just a column", name),
+            _ => format!("there's no aggregate called '{}'", name),
+        };
+        let err = malformed(message);
+        return Err(match suggest::closest(name, AGGREGATES) {
+            Some(similar) if similar != name => err.note(format!("did you mean '{}'?", similar)),
+            _ => err,
+        });
+    };
+    match (how, column) {
+        (Aggregate::Count, "") => Ok((how, None)),
+        (_, "") => Err(malformed(format!(
+            "'{}' needs a column, like \"{}(revenue)\"",
+            name, name
+        ))),
+        _ => lookup(table, 1, column).map(|_| (how, Some(column))),
+    }
+}
+
+/// `join(left, right, on, how, suffixes, validate)` matches up rows of two tables with the same
+/// values in the `on` columns, a name or a list of names that both tables have
+///
+/// `how` is `"inner"` (the default), `"left"`, `"right"`, `"outer"`, `"semi"` or `"anti"`.
+/// Other columns with the same name in both get `suffixes`, `["_left", "_right"]` unless given.
+/// `validate` checks that keys are unique where they should be, and is `"one_to_one"`,
+/// `"one_to_many"` or `"many_to_one"`.
+fn join(args: &[Type]) -> Result<Type, NativeError> {
+    let (Type::Table(left), Type::Table(right)) = (&args[0], &args[1]) else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let on = names(&args[2])
+        .filter(|on| !on.is_empty())
+        .ok_or_else(|| mismatch(2, "str or list of strs", &args[2]))?;
+    for (table, side) in [(left, "left"), (right, "right")] {
+        for name in &on {
+            lookup(table, 2, name)
+                .map_err(|err| err.note(format!("the key is missing from the {} table", side)))?;
+        }
+    }
+
+    let choice = |index: usize, default: &'static str, choices: &[&str]| {
+        let value = match args.get(index) {
+            None | Some(Type::Nil) => default,
+            Some(value) => text(value),
+        };
+        if choices.contains(&value) {
+            return Ok(value);
+        }
+        let expected: Vec<String> = choices.iter().map(|c| format!("{:?}", c)).collect();
+        Err(NativeError::argument(
+            index,
+            ErrorKind::InvalidArgument {
+                function: String::from("join"),
+                message: format!(
+                    "expected one of {} but found {:?}",
+                    expected.join(", "),
+                    value
+                ),
+            },
+        ))
+    };
+    let how = choice(
+        3,
+        "inner",
+        &["inner", "left", "right", "outer", "semi", "anti"],
+    )?;
+    let validate = choice(5, "", &["", "one_to_one", "one_to_many", "many_to_one"])?;
+    let suffixes = match args.get(4) {
+        None | Some(Type::Nil) => ("_left", "_right"),
+        Some(value) => match names(value).as_deref() {
+            Some([left, right]) => (*left, *right),
+            _ => {
+                return Err(NativeError::argument(
+                    4,
+                    ErrorKind::InvalidArgument {
+                        function: String::from("join"),
+                        message: String::from("expected a list of two strs"),
+                    },
+                ))
+            }
+        },
+    };
+
+    let options = Options {
+        on: &on,
+        how: How::from_name(how).expect("checked against the choices"),
+        suffixes,
+        validate: Cardinality::from_name(validate),
+    };
+    join::join(left, right, &options)
+        .map_err(|err| match err {
+            JoinError::Missing { column, side } => {
+                let index = if side == Side::Left { 0 } else { 1 };
+                invalid("join", index, TableError::Missing { column })
+            }
+            JoinError::KeyTypes {
+                column,
+                left,
+                right,
+            } => NativeError::argument(
+                2,
+                ErrorKind::TypeMismatch {
+                    expected: left.to_string(),
+                    found: right.to_string(),
+                },
+            )
+            .note(format!(
+                "the key '{}' is {} in the left table but {} in the right one",
+                column, left, right
+            )),
+            JoinError::Cardinality {
+                expected,
+                side,
+                key,
+                rows,
+            } => NativeError::argument(
+                5,
+                ErrorKind::JoinCardinality {
+                    expected: expected.name(),
+                    side: side.name(),
+                    key,
+                },
+            )
+            .note(format!("it's in rows {} and {}", rows.0, rows.1)),
+            JoinError::Table(err) => invalid("join", 4, err),
+        })
+        .and_then(wrap)
+}
+
+/// `pivot(index, columns, values, agg)` spreads a long table out into a wide one, with a row for
+/// each value of `index`, a name or a list of names, and a column for each value of `columns`,
+/// holding what `values` is in the rows that have both
+///
+/// The new columns come in sorted order, after the index columns. More than one row for the same
+/// index and column is an error unless `agg` combines them, either the name of a built-in
+/// aggregate like `"sum"`, which skips missing values, or a function given those rows as a table.
+/// Cells without any rows are missing.
+fn pivot(args: &[Type], caller: &mut dyn Caller) -> Result<Type, NativeError> {
+    let Type::Table(table) = &args[0] else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let index = names(&args[1]).ok_or_else(|| mismatch(1, "str or list of strs", &args[1]))?;
+    for name in &index {
+        lookup(table, 1, name)?;
+    }
+    let columns = text(&args[2]);
+    let column = lookup(table, 2, columns)?;
+    if index.contains(&columns) {
+        return Err(NativeError::argument(
+            2,
+            ErrorKind::InvalidArgument {
+                function: String::from("pivot"),
+                message: format!("'{}' can't be both the index and the columns", columns),
+            },
+        ));
+    }
+    let values = text(&args[3]);
+    let value = lookup(table, 3, values)?;
+
+    let cells = Cells::new(table.clone(), &index, columns).map_err(|err| match err {
+        ReshapeError::Missing { column, row } => {
+            NativeError::argument(2, ErrorKind::MissingValue { column, row })
+                .note("the values of the pivoted column name the new columns")
+        }
+        ReshapeError::Table(err) => invalid("pivot", 1, err),
+        ReshapeError::Types { .. } | ReshapeError::NoValues => unreachable!("only melting"),
+    })?;
+    let combined = match args.get(4) {
+        None | Some(Type::Nil) => {
+            if let Some(err) = duplicate(table, &index, column, &cells) {
+                return Err(err.note("pass `agg` to combine them, like `agg: \"sum\"`"));
+            }
+            let first: Vec<usize> = cells.groups().rows().iter().map(|rows| rows[0]).collect();
+            value.take(&first)
+        }
+        Some(Type::Str(name)) => {
+            let Some(how) = Aggregate::from_name(name, None) else {
+                let err = NativeError::argument(
+                    4,
+                    ErrorKind::InvalidArgument {
+                        function: String::from("pivot"),
+                        message: match name.as_str() {
+                            "quantile" => String::from(
+                                "'quantile' needs a fraction, so pass a function that works it out",
+                            ),
+                            _ => format!("there's no aggregate called '{}'", name),
+                        },
+                    },
+                );
+                return Err(match suggest::closest(name, AGGREGATES) {
+                    Some(similar) if similar != name => {
+                        err.note(format!("did you mean '{}'?", similar))
+                    }
+                    _ => err,
+                });
+            };
+            cells
+                .groups()
+                .aggregate(value, how, Missing::Skip)
+                .map_err(|err| aggregate_error(4, values, err))?
+        }
+        Some(function @ (Type::Function(_) | Type::Native(_))) => {
+            each_group(("pivot", 4), values, cells.groups(), function, caller)?
+        }
+        Some(other) => return Err(mismatch(4, "str or function", other)),
+    };
+    cells
+        .pivot(&combined)
+        .map_err(|err| match err {
+            ReshapeError::Table(err) => invalid("pivot", 2, err),
+            _ => unreachable!("laying out cells only fails on the table"),
+        })
+        .and_then(wrap)
+}
+
+/// An error for the first cell of a pivot with more than one row, if there is one
+fn duplicate(table: &Table, index: &[&str], column: &Column, cells: &Cells) -> Option<NativeError> {
+    let (first, second) = cells.duplicate()?;
+    let keys: Vec<String> = index
+        .iter()
+        .map(|name| table.column(name).unwrap().get(first).quoted().to_string())
+        .collect();
+    let keys = match keys.as_slice() {
+        [key] => key.clone(),
+        _ => format!("({})", keys.join(", ")),
+    };
+    Some(
+        NativeError::call(ErrorKind::DuplicateEntry {
+            index: keys,
+            column: column.get(first).to_string(),
+        })
+        .note(format!("it's in rows {} and {}", first, second)),
+    )
+}
+
+/// `unstack(column, values)` spreads `values` out into a column for each value of `column`, with a
+/// row for each combination of the other columns
+///
+/// It's `pivot` with every other column as the index, so the new columns come in sorted order
+/// after them, and cells without any rows are missing. More than one row for the same cell is an
+/// error, as there's nothing to combine them with.
+fn unstack(args: &[Type]) -> Result<Type, NativeError> {
+    let Type::Table(table) = &args[0] else {
+        unreachable!("the runtime checks arguments against their annotations")
+    };
+    let columns = text(&args[1]);
+    let column = lookup(table, 1, columns)?;
+    let values = text(&args[2]);
+    let value = lookup(table, 2, values)?;
+    if columns == values {
+        return Err(NativeError::argument(
+            2,
+            ErrorKind::InvalidArgument {
+                function: String::from("unstack"),
+                message: format!("'{}' can't be both the column and the values", columns),
+            },
+        ));
+    }
+    let index: Vec<&str> = table
+        .names()
+        .filter(|&name| name != columns && name != values)
+        .collect();
+
+    let cells = Cells::new(table.clone(), &index, columns).map_err(|err| match err {
+        ReshapeError::Missing { column, row } => {
+            NativeError::argument(1, ErrorKind::MissingValue { column, row })
+                .note("the values of the unstacked column name the new columns")
+        }
+        ReshapeError::Table(err) => invalid("unstack", 1, err),
+        ReshapeError::Types { .. } | ReshapeError::NoValues => unreachable!("only melting"),
+    })?;
+    if let Some(err) = duplicate(table, &index, column, &cells) {
+        return Err(err.note("use `pivot` with `agg` to combine them"));
+    }
+    let first: Vec<usize> = cells.groups().rows().iter().map(|rows| rows[0]).collect();
+    cells
+        .pivot(&value.take(&first))
+        .map_err(|err| match err {
+            ReshapeError::Table(err) => invalid("unstack", 1, err),
+            _ => unreachable!("laying out cells only fails on the table"),
+        })
+        .and_then(wrap)
+}
+
+/// `melt(id_vars, value_vars, var_name, value_name)` gathers a wide table into a long one, with a
+/// row for each row and each of the `value_vars` columns, which default to every column not in
+/// `id_vars`
+///
+/// Each row has the `id_vars` columns, then the name of the column the value came from, in a
+/// column called `var_name` (`"variable"` unless given), and then the value, in `value_name`
+/// (`"value"` unless given). Rows come a column at a time. The melted columns must all hold the
+/// same type.
+fn melt(args: &[Type]) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let ids = names(&args[1]).ok_or_else(|| mismatch(1, "str or list of strs", &args[1]))?;
+    for name in &ids {
+        lookup(table, 1, name)?;
+    }
+    let values = match args.get(2) {
+        None | Some(Type::Nil) => table.names().filter(|name| !ids.contains(name)).collect(),
+        Some(value) => {
+            let values = names(value).ok_or_else(|| mismatch(2, "str or list of strs", value))?;
+            for name in &values {
+                lookup(table, 2, name)?;
+            }
+            values
+        }
+    };
+    let name = |index: usize, default: &'static str| match args.get(index) {
+        None | Some(Type::Nil) => default,
+        Some(value) => text(value),
+    };
+
+    let names = (name(3, "variable"), name(4, "value"));
+    reshape::melt(table, &ids, &values, names)
+        .map_err(|err| gather_error("melt", 2, err))
+        .and_then(wrap)
+}
+
+/// `stack(columns, var_name, value_name)` gathers the named columns, a name or a list of names,
+/// into rows like `melt` does, keeping every other column alongside
+///
+/// The values of each row come together, in the order the columns are named, and missing values
+/// are left out, so stacking what `unstack` spread out gives back the rows it came from. The names
+/// go in a column called `var_name` (`"variable"` unless given) and the values in `value_name`
+/// (`"value"` unless given).
+fn stack(args: &[Type]) -> Result<Type, NativeError> {
+    let table = table_arg(args);
+    let values = names(&args[1]).ok_or_else(|| mismatch(1, "str or list of strs", &args[1]))?;
+    for name in &values {
+        lookup(table, 1, name)?;
+    }
+    let name = |index: usize, default: &'static str| match args.get(index) {
+        None | Some(Type::Nil) => default,
+        Some(value) => text(value),
+    };
+
+    let names = (name(2, "variable"), name(3, "value"));
+    reshape::stack(table, &values, names)
+        .map_err(|err| gather_error("stack", 1, err))
+        .and_then(wrap)
+}
+
+/// The error for melting or stacking the columns given as argument `index`
+fn gather_error(function: &str, index: usize, err: ReshapeError) -> NativeError {
+    match err {
+        ReshapeError::Types { first, other } => NativeError::argument(
+            index,
+            ErrorKind::TypeMismatch {
+                expected: first.1.to_string(),
+                found: other.1.to_string(),
+            },
+        )
+        .note(format!(
+            "column '{}' holds {} values but '{}' holds {}, and they'd share a column",
+            first.0, first.1, other.0, other.1
+        )),
+        ReshapeError::NoValues => NativeError::argument(
+            index,
+            ErrorKind::InvalidArgument {
+                function: function.to_string(),
+                message: format!("there are no columns to {}", function),
+            },
+        ),
+        ReshapeError::Table(err) => invalid(function, index + 1, err),
+        ReshapeError::Missing { .. } => unreachable!("only pivoting"),
+    }
+}
+
+/// `na(type)` is a missing value of the type named `type`, one of `"int"`, `"float"`, `"str"` or
+/// `"bool"`, for functions with nothing to give for some rows, like those passed to `mutate`
+fn na(args: &[Type]) -> Result<Type, NativeError> {
+    Ok(Type::Na(match text(&args[0]) {
+        "int" => "int",
+        "float" => "float",
+        "str" => "str",
+        "bool" => "bool",
+        other => {
+            return Err(NativeError::argument(
+                0,
+                ErrorKind::InvalidArgument {
+                    function: String::from("na"),
+                    message: format!(
+                        "columns hold \"int\", \"float\", \"str\" or \"bool\", not {:?}",
+                        other
+                    ),
+                },
+            ))
+        }
+    }))
+}
+
+/// `is_na(value)` is whether a value is missing, or for a series, a series of whether each of
+/// its values is
+/// Nil is never missing, since it's the absence of any value rather than a value not known.
+fn is_na(args: &[Type]) -> Result<Type, NativeError> {
+    Ok(match &args[0] {
+        Type::Series(column) => {
+            let missing = (0..column.len()).map(|row| !column.is_valid(row)).collect();
+            Type::Series(Rc::new(Column::new(ColumnData::Bool(missing))))
+        }
+        Type::Table(_) => {
+            return Err(mismatch(0, "series or single value", &args[0])
+                .note("use 'is_na' on one of the table's columns"))
+        }
+        value => Type::Bool(matches!(value, Type::Na(_))),
+    })
+}
+
+/// `fill_na(value, fill)` replaces missing values with `fill`, which has the same type, and is
+/// either a single value or a series of the same length to take each row's value from
+/// A table is filled a column at a time, with `fill` a map from column names to fills.
+fn fill_na(args: &[Type]) -> Result<Type, NativeError> {
+    let (Type::Table(table), Type::Map(fills)) = (&args[0], &args[1]) else {
+        return coalesce(args);
+    };
+    let mut filled = table.as_ref().clone();
+    for (key, fill) in fills.iter() {
+        let Key::Str(name) = key else {
+            return Err(mismatch(1, "map from str", &key.to_value())
+                .note(format!("the key {} isn't a column name", key)));
+        };
+        let column = Type::Series(lookup(table, 1, name)?.clone());
+        let Type::Series(column) = coalesce(&[column, fill.clone()])
+            .map_err(|err| err.note(format!("that's what '{}' is filled with", name)))?
+        else {
+            unreachable!("filling a series gives a series")
+        };
+        filled = filled
+            .with_column(name, column)
+            .map_err(|err| invalid("fill_na", 1, err))?;
+    }
+    wrap(filled)
+}
+
+/// `drop_na(value, column, ...)` leaves out a series' missing values, or the rows of a table with
+/// a missing value in any of the named columns, or in any column at all if none are named
+fn drop_na(args: &[Type]) -> Result<Type, NativeError> {
+    match &args[0] {
+        Type::Series(column) if args.len() == 1 => {
+            let rows: Vec<usize> = (0..column.len())
+                .filter(|&row| column.is_valid(row))
+                .collect();
+            Ok(Type::Series(Rc::new(column.take(&rows))))
+        }
+        Type::Series(_) => Err(NativeError::argument(
+            1,
+            ErrorKind::InvalidArgument {
+                function: String::from("drop_na"),
+                message: String::from("only takes column names for a table"),
+            },
+        )),
+        Type::Table(table) => {
+            let columns = match args.len() {
+                1 => table.columns().iter().map(|(_, column)| column).collect(),
+                _ => (1..args.len())
+                    .map(|i| lookup(table, i, text(&args[i])))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+            let rows: Vec<usize> = (0..table.rows())
+                .filter(|&row| columns.iter().all(|column| column.is_valid(row)))
+                .collect();
+            if rows.len() == table.rows() {
+                return Ok(args[0].clone());
+            }
+            wrap(table.take(&rows))
+        }
+        other => Err(mismatch(0, "series or table", other)),
+    }
+}
+
+/// `coalesce(value, value, ...)` is the first of its arguments that isn't missing, or for series,
+/// a series of the first that isn't in each row, where a single value counts for every row
+/// The arguments all have to hold the same type, and series have to be the same length.
+fn coalesce(values: &[Type]) -> Result<Type, NativeError> {
+    let typ = series::element_type(&values[0]);
+    let mut len = None;
+    for (i, value) in values.iter().enumerate() {
+        if series::element_type(value) != typ {
+            return Err(NativeError::argument(
+                i,
+                ErrorKind::TypeMismatch {
+                    expected: typ.to_string(),
+                    found: series::element_type(value).to_string(),
+                },
+            )
+            .note(format!("missing values are only replaced with {}s", typ)));
+        }
+        if let Type::Series(column) = value {
+            match len {
+                Some(left) if left != column.len() => {
+                    return Err(NativeError::argument(
+                        i,
+                        ErrorKind::LengthMismatch {
+                            left,
+                            right: column.len(),
+                        },
+                    ))
+                }
+                _ => len = Some(column.len()),
+            }
+        }
+    }
+    let Some(len) = len else {
+        let present = values.iter().find(|value| !matches!(value, Type::Na(_)));
+        return Ok(present.unwrap_or(&values[0]).clone());
+    };
+
+    // Single values become a column of one row, which every row takes its value from
+    let sources = values
+        .iter()
+        .map(|value| match value {
+            Type::Series(column) => Ok((column.clone(), true)),
+            other => Column::from_values("", std::slice::from_ref(other))
+                .map(|column| (Rc::new(column), false)),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| invalid("coalesce", 0, err))?;
+    let columns: Vec<&Column> = sources.iter().map(|(column, _)| column.as_ref()).collect();
+    let picks: Vec<_> = (0..len)
+        .map(|row| {
+            sources
+                .iter()
+                .enumerate()
+                .find_map(|(source, (column, each))| {
+                    let row = if *each { row } else { 0 };
+                    column.is_valid(row).then_some((source, row))
+                })
+        })
+        .collect();
+    Ok(Type::Series(Rc::new(Column::gather(&columns, &picks))))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{evaluate, Engine};
+
+    const IMPORTS: &str = "from std.table import *;\nfrom std.string import split;\n";
+
+    fn table(source: &str) -> Result<String, String> {
+        evaluate(&format!("{}{}", IMPORTS, source))
+    }
+
+    /// The help and suggestions given with the error `source` fails with
+    fn hints(source: &str) -> Vec<String> {
+        let mut engine = Engine::new();
+        let source = format!("{}{}", IMPORTS, source);
+        let program = engine.compile_entry("test", &source);
+        let diagnostics = program.and_then(|p| engine.run(&p)).unwrap_err();
+        let err = &diagnostics.errors()[0];
+        let suggestions = err.suggestions.iter().map(|s| s.message.clone());
+        err.help.iter().cloned().chain(suggestions).collect()
+    }
+
+    #[test]
+    fn building_tables() {
+        assert_eq!(
+            table(r#"table("n", range(3), "s", split("a b c"));"#).unwrap(),
+            "| n   | s   |\n\
+             | int | str |\n\
+             |-----|-----|\n\
+             |   0 | a   |\n\
+             |   1 | b   |\n\
+             |   2 | c   |\n\
+             3 rows, 2 columns"
+        );
+        assert_eq!(table("table();").unwrap(), "0 rows, 0 columns");
+        assert_eq!(table(r#"len(table("n", range(4)));"#).unwrap(), "4");
+        assert_eq!(
+            table(r#"columns(table("n", range(3), "s", split("a b c")));"#).unwrap(),
+            r#"["n", "s"]"#
+        );
+        assert_eq!(
+            table(r#"column(table("n", range(3)), "n");"#).unwrap(),
+            "series<int>[0, 1, 2]"
+        );
+        assert_eq!(
+            table(r#"schema(table("n", range(3)));"#)
+                .unwrap()
+                .lines()
+                .nth(3),
+            Some("| n    | int  |")
+        );
+    }
+
+    #[test]
+    fn invalid_tables() {
+        let invalid = |source: &str| {
+            let message = table(source).unwrap_err();
+            message
+                .strip_prefix("SK0800: Invalid argument to ")
+                .map(String::from)
+                .unwrap_or(message)
+        };
+        assert_eq!(
+            invalid(r#"table("n");"#),
+            "'table': takes a name and then a list of values for each column"
+        );
+        assert_eq!(
+            invalid(r#"table("n", range(3), "n", range(3));"#),
+            "'table': there are two columns named 'n'"
+        );
+        assert_eq!(
+            invalid(r#"table("n", range(3), "m", range(2));"#),
+            "'table': column 'm' has 2 rows but 'n' has 3"
+        );
+        assert_eq!(
+            invalid(r#"table("n", range(0));"#),
+            "'table': column 'n' has no values to infer its type from"
+        );
+        assert_eq!(
+            invalid(r#"column(table("n", range(3)), "m");"#),
+            "SK0907: No column named 'm'"
+        );
+        assert_eq!(
+            invalid(r#"table(1, range(2));"#),
+            "SK0200: Mismatched types, expected str but found int"
+        );
+    }
+
+    const SALES: &str = "var t = table(\"region\", split(\"n s n\"), \"sales\", range(3));\n";
+
+    fn verbs(source: &str) -> Result<String, String> {
+        table(&format!("{}{}", SALES, source))
+    }
+
+    #[test]
+    fn verbs_as_methods() {
+        let rows = |source: &str| {
+            let rendered = verbs(source).unwrap();
+            rendered.lines().skip(3).collect::<Vec<_>>().join("\n")
+        };
+        assert_eq!(
+            rows("t.select(\"sales\");"),
+            "|     0 |\n|     1 |\n|     2 |\n3 rows, 1 column"
+        );
+        assert_eq!(
+            rows("def big(row: map): bool { return row[\"sales\"] > 0; }\nt.filter(big);"),
+            "| s      |     1 |\n| n      |     2 |\n2 rows, 2 columns"
+        );
+        assert_eq!(
+            rows("t.filter(t[\"sales\"] < 1);"),
+            "| n      |     0 |\n1 row, 2 columns"
+        );
+        assert_eq!(
+            rows("def twice(row: map): int { return row[\"sales\"] * 2; }\nt.mutate(\"double\", twice).select(\"double\");"),
+            "|      0 |\n|      2 |\n|      4 |\n3 rows, 1 column"
+        );
+        assert_eq!(
+            rows("t.sort_by(\"region\").head(2);"),
+            "| n      |     0 |\n| n      |     2 |\n2 rows, 2 columns"
+        );
+        assert_eq!(rows("t.tail(1);"), "| n      |     2 |\n1 row, 2 columns");
+        assert_eq!(
+            rows("t.distinct(\"region\");"),
+            "| n      |     0 |\n| s      |     1 |\n2 rows, 2 columns"
+        );
+        assert_eq!(
+            verbs("t.rename({\"sales\": \"total\"}).columns();").unwrap(),
+            r#"["region", "total"]"#
+        );
+        // The functions in the module can be called as methods too
+        assert_eq!(verbs("t.columns();").unwrap(), r#"["region", "sales"]"#);
+    }
+
+    #[test]
+    fn misspelled_names_get_suggestions() {
+        let suggest = |source: &str| {
+            let source = format!("{}{}", SALES, source);
+            (table(&source).unwrap_err(), hints(&source))
+        };
+        assert_eq!(
+            suggest("t.selec(\"sales\");"),
+            (
+                String::from("SK0204: A table has no method named 'selec'"),
+                vec![String::from("did you mean 'select'?")]
+            )
+        );
+        assert_eq!(
+            suggest("t.select(\"sale\");"),
+            (
+                String::from("SK0907: No column named 'sale'"),
+                vec![String::from("did you mean 'sales'?")]
+            )
+        );
+        assert_eq!(
+            suggest("t[\"regoin\"];").1,
+            vec![String::from("did you mean 'region'?")]
+        );
+        assert_eq!(
+            suggest("t.rename({\"sale\": \"total\"});").1,
+            vec![String::from("did you mean 'sales'?")]
+        );
+        // Nothing is suggested when no name is close
+        assert_eq!(
+            suggest("t.frobnicate();"),
+            (
+                String::from("SK0204: A table has no method named 'frobnicate'"),
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn grouping() {
+        let grouped = "var g = t.group_by(\"region\");\n";
+        let agg = |source: &str| verbs(&format!("{}{}", grouped, source));
+        assert_eq!(agg("g;").unwrap(), "<2 groups of 3 rows by region>");
+        assert_eq!(
+            agg("g.agg({\"total\": \"sum(sales)\", \"n\": \"count()\", \"q\": \"quantile(sales, 0.5)\"});")
+                .unwrap(),
+            "| region | total | n   | q     |\n\
+             | str    | int   | int | float |\n\
+             |--------|-------|-----|-------|\n\
+             | n      |     2 |   2 |   1.0 |\n\
+             | s      |     1 |   1 |   1.0 |\n\
+             2 rows, 4 columns"
+        );
+        // A function is given each group as a table
+        assert_eq!(
+            agg("def rows(group: table): int { return len(group); }\ng.agg({\"rows\": rows}).column(\"rows\");")
+                .unwrap(),
+            "series<int>[2, 1]"
+        );
+    }
+
+    #[test]
+    fn invalid_aggregates() {
+        let agg = |spec: &str| {
+            let source = format!(
+                "{}t.group_by(\"region\").agg({{\"x\": \"{}\"}});",
+                SALES, spec
+            );
+            let message = table(&source).unwrap_err();
+            let message = message
+                .strip_prefix("SK0800: Invalid argument to 'agg': ")
+                .map(String::from)
+                .unwrap_or(message);
+            (message, hints(&source))
+        };
+        assert_eq!(
+            agg("summ(sales)"),
+            (
+                String::from("there's no aggregate called 'summ'"),
+                vec![String::from("did you mean 'sum'?")]
+            )
+        );
+        assert_eq!(
+            agg("sum(sale)"),
+            (
+                String::from("SK0907: No column named 'sale'"),
+                vec![String::from("did you mean 'sales'?")]
+            )
+        );
+        assert_eq!(
+            agg("sum(region)"),
+            (
+                String::from("SK0200: Mismatched types, expected int or float but found str"),
+                vec![String::from(
+                    "'sum' takes numbers, but column 'region' holds str values"
+                )]
+            )
+        );
+        assert_eq!(
+            agg("quantile(sales)").0,
+            "'quantile' needs a fraction, like \"quantile(revenue, 0.9)\""
+        );
+        assert_eq!(
+            agg("quantile(sales, 2)").0,
+            "a quantile is between 0 and 1, but was 2"
+        );
+        assert_eq!(
+            agg("sum").0,
+            "expected an aggregate like \"sum(revenue)\", but found \"sum\""
+        );
+        assert_eq!(
+            agg("sum()").0,
+            "'sum' needs a column, like \"sum(revenue)\""
+        );
+        assert_eq!(
+            verbs("t.group_by(\"region\").agg({\"x\": \"count()\"}, \"drop\");").unwrap_err(),
+            "SK0800: Invalid argument to 'agg': expected \"skip\", \"propagate\" or \"error\" but found \"drop\""
+        );
+    }
+
+    const BOSSES: &str =
+        "var r = table(\"region\", split(\"n s w\"), \"boss\", split(\"ann bo cy\"));\n";
+
+    #[test]
+    fn joins() {
+        let join = |source: &str| verbs(&format!("{}{}", BOSSES, source));
+        assert_eq!(
+            join("join(t, r, \"region\");").unwrap(),
+            "| region | sales | boss |\n\
+             | str    | int   | str  |\n\
+             |--------|-------|------|\n\
+             | n      |     0 | ann  |\n\
+             | s      |     1 | bo   |\n\
+             | n      |     2 | ann  |\n\
+             3 rows, 3 columns"
+        );
+        assert_eq!(
+            join("join(t, r, on: \"region\", how: \"outer\").tail(1);")
+                .unwrap()
+                .lines()
+                .nth(3),
+            Some("| w      |    NA | cy   |")
+        );
+        assert_eq!(
+            join("t.join(r, on: [\"region\"], how: \"anti\");")
+                .unwrap()
+                .lines()
+                .last(),
+            Some("0 rows, 2 columns")
+        );
+        assert_eq!(
+            join("join(t, t, \"region\").columns();").unwrap(),
+            r#"["region", "sales_left", "sales_right"]"#
+        );
+        assert_eq!(
+            join("join(t, t, \"region\", suffixes: [\"_a\", \"_b\"]).columns();").unwrap(),
+            r#"["region", "sales_a", "sales_b"]"#
+        );
+        assert!(join("join(t, r, \"region\", validate: \"many_to_one\");").is_ok());
+    }
+
+    #[test]
+    fn invalid_joins() {
+        let join = |source: &str| {
+            let source = format!("{}{}{}", SALES, BOSSES, source);
+            (table(&source).unwrap_err(), hints(&source))
+        };
+        assert_eq!(
+            join("join(t, r, \"region\", validate: \"one_to_one\");"),
+            (
+                String::from("SK0909: Expected a one_to_one join, but the key \"n\" is in more than one row of the left table"),
+                vec![String::from("it's in rows 0 and 2")]
+            )
+        );
+        assert_eq!(
+            join("join(t, r, \"region\", hw: \"left\");"),
+            (
+                String::from("SK0205: 'join' has no parameter named 'hw'"),
+                vec![String::from("did you mean 'how'?")]
+            )
+        );
+        assert_eq!(
+            join("join(t, r, \"sales\");"),
+            (
+                String::from("SK0907: No column named 'sales'"),
+                vec![String::from("the key is missing from the right table")]
+            )
+        );
+        assert_eq!(
+            join("join(t, r, \"region\", how: \"sideways\");").0,
+            "SK0800: Invalid argument to 'join': expected one of \"inner\", \"left\", \"right\", \"outer\", \"semi\", \"anti\" but found \"sideways\""
+        );
+        assert_eq!(
+            join("join(t, r, \"region\", how: \"left\", how: \"left\");").0,
+            "SK0800: Invalid argument to 'join': 'how' was given more than once"
+        );
+        assert_eq!(
+            join("join(t, r, how: \"left\");").0,
+            "SK0800: Invalid argument to 'join': the argument 'on' is missing"
+        );
+        assert_eq!(
+            join("join(t, r, on: \"region\", \"left\");").0,
+            "SK0001: Expected a named argument, positional arguments have to come first"
+        );
+        assert_eq!(
+            join("len(a: [1]);").1,
+            vec![String::from("'len' only takes its arguments in order")]
+        );
+    }
+
+    const CITIES: &str = "var l = table(\"city\", split(\"b a b a b\"), \"year\", split(\"x x y y y\"), \"n\", range(5));\n";
+
+    #[test]
+    fn reshaping() {
+        let reshape = |source: &str| table(&format!("{}{}", CITIES, source));
+        assert_eq!(
+            reshape("l.head(4).pivot(\"city\", \"year\", \"n\");").unwrap(),
+            "| city | x   | y   |\n\
+             | str  | int | int |\n\
+             |------|-----|-----|\n\
+             | b    |   0 |   2 |\n\
+             | a    |   1 |   3 |\n\
+             2 rows, 3 columns"
+        );
+        assert_eq!(
+            reshape("l.pivot(index: [\"city\"], columns: \"year\", values: \"n\", agg: \"sum\").column(\"y\");")
+                .unwrap(),
+            "series<int>[6, 3]"
+        );
+        // A function is given the rows for each cell as a table
+        assert_eq!(
+            reshape("def rows(cell: table): int { return len(cell); }\nl.pivot(\"year\", \"city\", \"n\", rows).column(\"b\");")
+                .unwrap(),
+            "series<int>[1, 2]"
+        );
+        assert_eq!(
+            reshape("l.head(2).melt(\"city\", [\"n\"], var_name: \"what\", value_name: \"v\");")
+                .unwrap(),
+            "| city | what | v   |\n\
+             | str  | str  | int |\n\
+             |------|------|-----|\n\
+             | b    | n    |   0 |\n\
+             | a    | n    |   1 |\n\
+             2 rows, 3 columns"
+        );
+        assert_eq!(
+            reshape("l.melt([\"city\", \"n\"]).columns();").unwrap(),
+            r#"["city", "n", "variable", "value"]"#
+        );
+        assert_eq!(
+            reshape("l.head(4).unstack(\"year\", \"n\");").unwrap(),
+            reshape("l.head(4).pivot(\"city\", \"year\", \"n\");").unwrap()
+        );
+        // Stacking leaves out the cell unstacking had nothing for, giving back the rows
+        assert_eq!(
+            reshape("l.head(3).unstack(column: \"year\", values: \"n\").stack([\"x\", \"y\"], \"year\", \"n\");")
+                .unwrap(),
+            "| city | year | n   |\n\
+             | str  | str  | int |\n\
+             |------|------|-----|\n\
+             | b    | x    |   0 |\n\
+             | b    | y    |   2 |\n\
+             | a    | x    |   1 |\n\
+             3 rows, 3 columns"
+        );
+        assert_eq!(
+            reshape("l.stack(\"n\").columns();").unwrap(),
+            r#"["city", "year", "variable", "value"]"#
+        );
+    }
+
+    #[test]
+    fn invalid_reshapes() {
+        let reshape = |source: &str| {
+            let source = format!("{}{}", CITIES, source);
+            (table(&source).unwrap_err(), hints(&source))
+        };
+        assert_eq!(
+            reshape("l.pivot(\"city\", \"year\", \"n\");"),
+            (
+                String::from("SK0910: More than one value for the index \"b\" in the column 'y'"),
+                vec![
+                    String::from("it's in rows 2 and 4"),
+                    String::from("pass `agg` to combine them, like `agg: \"sum\"`")
+                ]
+            )
+        );
+        assert_eq!(
+            reshape("l.pivot(\"city\", \"year\", \"n\", \"summ\");"),
+            (
+                String::from(
+                    "SK0800: Invalid argument to 'pivot': there's no aggregate called 'summ'"
+                ),
+                vec![String::from("did you mean 'sum'?")]
+            )
+        );
+        assert_eq!(
+            reshape("l.pivot(\"city\", \"yeer\", \"n\");").1,
+            vec![String::from("did you mean 'year'?")]
+        );
+        assert_eq!(
+            reshape("l.melt(\"city\");"),
+            (
+                String::from("SK0200: Mismatched types, expected str but found int"),
+                vec![String::from(
+                    "column 'year' holds str values but 'n' holds int, and they'd share a column"
+                )]
+            )
+        );
+        assert_eq!(
+            reshape("l.melt([\"city\", \"year\", \"n\"]);").0,
+            "SK0800: Invalid argument to 'melt': there are no columns to melt"
+        );
+        assert_eq!(
+            reshape("l.unstack(\"year\", \"n\");"),
+            (
+                String::from("SK0910: More than one value for the index \"b\" in the column 'y'"),
+                vec![
+                    String::from("it's in rows 2 and 4"),
+                    String::from("use `pivot` with `agg` to combine them")
+                ]
+            )
+        );
+        assert_eq!(
+            reshape("l.unstack(\"year\", \"year\");").0,
+            "SK0800: Invalid argument to 'unstack': 'year' can't be both the column and the values"
+        );
+        assert_eq!(
+            reshape("l.stack([\"year\", \"n\"]);").1,
+            vec![String::from(
+                "column 'year' holds str values but 'n' holds int, and they'd share a column"
+            )]
+        );
+        assert_eq!(
+            reshape("l.stack(\"nn\");").1,
+            vec![String::from("did you mean 'n'?")]
+        );
+    }
+
+    const MISSING: &str = "var s = series([1, na(\"int\"), 3]);\nvar m = table(\"k\", split(\"a b c\"), \"x\", [1.5, na(\"float\"), 2.0]);\n";
+
+    #[test]
+    fn missing_values() {
+        let missing = |source: &str| table(&format!("{}{}", MISSING, source)).unwrap();
+        assert_eq!(missing("na(\"int\") + 1;"), "NA");
+        assert_eq!(missing("na(\"str\") + \"a\";"), "NA");
+        // Nothing is known about a missing value, so it isn't even equal to itself
+        assert_eq!(missing("na(\"int\") == na(\"int\");"), "NA");
+        assert_eq!(missing("is_na(na(\"int\"));"), "true");
+        assert_eq!(missing("is_na(1);"), "false");
+        assert_eq!(missing("s + 1;"), "series<int>[2, NA, 4]");
+        assert_eq!(missing("s.is_na();"), "series<bool>[false, true, false]");
+        assert_eq!(missing("s.fill_na(0);"), "series<int>[1, 0, 3]");
+        assert_eq!(missing("s.drop_na();"), "series<int>[1, 3]");
+        assert_eq!(
+            missing("s.coalesce(series([9, 9, 9]));"),
+            "series<int>[1, 9, 3]"
+        );
+        assert_eq!(missing("coalesce(na(\"int\"), 4);"), "4");
+        assert_eq!(missing("coalesce(na(\"int\"), na(\"int\"));"), "NA");
+        assert_eq!(missing("fill_na(na(\"int\"), 7);"), "7");
+        // A variable can hold a missing value of its type
+        assert_eq!(missing("var v = 1;\nv -> na(\"int\");\nv;"), "NA");
+        assert_eq!(
+            missing("m.drop_na().column(\"x\");"),
+            "series<float>[1.5, 2.0]"
+        );
+        assert_eq!(
+            missing("m.drop_na(\"k\").column(\"x\");"),
+            "series<float>[1.5, NA, 2.0]"
+        );
+        assert_eq!(
+            missing("m.fill_na({\"x\": 0.0}).column(\"x\");"),
+            "series<float>[1.5, 0.0, 2.0]"
+        );
+    }
+
+    #[test]
+    fn invalid_missing_values() {
+        let missing = |source: &str| {
+            let source = format!("{}{}", MISSING, source);
+            (table(&source).unwrap_err(), hints(&source))
+        };
+        assert_eq!(
+            missing("na(\"int\") + 1.5;").0,
+            "SK0200: Mismatched types, expected int but found float"
+        );
+        assert_eq!(
+            missing("na(\"list\");").0,
+            "SK0800: Invalid argument to 'na': columns hold \"int\", \"float\", \"str\" or \"bool\", not \"list\""
+        );
+        assert_eq!(
+            missing("var v = 1;\nv -> na(\"str\");").0,
+            "SK0200: Mismatched types, expected int but found str"
+        );
+        assert_eq!(
+            missing("abs(na(\"int\"));"),
+            (
+                String::from("SK0200: Mismatched types, expected number but found na"),
+                vec![String::from(
+                    "this is a missing int, which can be replaced with 'fill_na' or 'coalesce'"
+                )]
+            )
+        );
+        assert_eq!(
+            missing("s.fill_na(\"x\");"),
+            (
+                String::from("SK0200: Mismatched types, expected int but found str"),
+                vec![String::from("missing values are only replaced with ints")]
+            )
+        );
+        assert_eq!(
+            missing("m.fill_na({\"x\": 0});").1,
+            vec![
+                String::from("missing values are only replaced with floats"),
+                String::from("that's what 'x' is filled with")
+            ]
+        );
+        assert_eq!(
+            missing("m.drop_na(\"y\");").1,
+            vec![String::from("did you mean 'k'?")]
+        );
+    }
+}
@@ -0,0 +1,784 @@
+//! Tables, named columns of equal length that each hold values of one type
+//!
+//! Columns are reference counted, so projecting or reordering them never copies any data.
+
+use std::{cmp::Ordering, collections::HashSet, fmt, rc::Rc};
+
+use super::{
+    map::{Key, Map},
+    series::Bitmap,
+    value::Type,
+};
+
+/// The values of a column, stored unboxed
+#[derive(Debug, Clone)]
+pub enum ColumnData {
+    Int(Vec<i32>),
+    Float(Vec<f32>),
+    Str(Vec<String>),
+    Bool(Vec<bool>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub data: ColumnData,
+    /// Which rows have a value, or `None` if they all do
+    /// The values stored in rows without one are meaningless
+    pub validity: Option<Bitmap>,
+}
+
+/// Why a table or column couldn't be built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableError {
+    /// The column's values don't all have the same type
+    Mixed {
+        column: String,
+        first: &'static str,
+        other: &'static str,
+        row: usize,
+    },
+    /// A value that can't go in a column at all
+    Unsupported {
+        column: String,
+        found: &'static str,
+    },
+    /// Nothing to infer the type from
+    Empty {
+        column: String,
+    },
+    Length {
+        column: String,
+        found: usize,
+        expected: usize,
+        first: String,
+    },
+    Duplicate {
+        column: String,
+    },
+    Missing {
+        column: String,
+    },
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Mixed {
+                column,
+                first,
+                other,
+                row,
+            } => write!(
+                f,
+                "column '{}' holds {} values, but row {} is {}",
+                column, first, row, other
+            ),
+            TableError::Unsupported { column, found } => {
+                write!(f, "column '{}' can't hold a {}", column, found)
+            }
+            TableError::Empty { column } => write!(
+                f,
+                "column '{}' has no values to infer its type from",
+                column
+            ),
+            TableError::Length {
+                column,
+                found,
+                expected,
+                first,
+            } => write!(
+                f,
+                "column '{}' has {} rows but '{}' has {}",
+                column, found, first, expected
+            ),
+            TableError::Duplicate { column } => {
+                write!(f, "there are two columns named '{}'", column)
+            }
+            TableError::Missing { column } => write!(f, "there's no column named '{}'", column),
+        }
+    }
+}
+
+impl Column {
+    /// A column with a value in every row
+    pub fn new(data: ColumnData) -> Self {
+        Self {
+            data,
+            validity: None,
+        }
+    }
+
+    /// A column of `len` missing values, holding the type named `type_name`
+    pub fn missing(type_name: &str, len: usize) -> Self {
+        let data = match type_name {
+            "int" => ColumnData::Int(vec![0; len]),
+            "float" => ColumnData::Float(vec![0.0; len]),
+            "bool" => ColumnData::Bool(vec![false; len]),
+            _ => ColumnData::Str(vec![String::new(); len]),
+        };
+        Self {
+            data,
+            validity: Some(Bitmap::new(len, false)),
+        }
+    }
+
+    /// Builds a column from a list of values, which all have to have the same type
+    /// A nil is a missing value, which can be in a column of any type, and so is an NA of the
+    /// column's type
+    pub fn from_values(name: &str, values: &[Type]) -> Result<Self, TableError> {
+        let missing = |value: &Type| matches!(value, Type::Nil | Type::Na(_));
+        let Some(first) = values.iter().find(|value| !missing(value)) else {
+            // Typed missing values still say what the column holds
+            return match values.iter().find_map(|value| match value {
+                Type::Na(typ) => Some(*typ),
+                _ => None,
+            }) {
+                Some(typ) => Ok(Column::missing(typ, values.len())),
+                None => Err(TableError::Empty {
+                    column: name.to_string(),
+                }),
+            };
+        };
+        let mixed = |row: usize, other: &'static str| TableError::Mixed {
+            column: name.to_string(),
+            first: first.type_name(),
+            other,
+            row,
+        };
+
+        /// Collects every value out of one variant, or says where one wasn't
+        macro_rules! collect {
+            ($variant:ident) => {
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(row, value)| match value {
+                        Type::$variant(v) => Ok(v.clone()),
+                        Type::Nil => Ok(Default::default()),
+                        Type::Na(typ) if *typ == first.type_name() => Ok(Default::default()),
+                        Type::Na(typ) => Err(mixed(row, typ)),
+                        other => Err(mixed(row, other.type_name())),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(ColumnData::$variant)?
+            };
+        }
+
+        let data = match first {
+            Type::Int(_) => collect!(Int),
+            Type::Float(_) => collect!(Float),
+            Type::Str(_) => collect!(Str),
+            Type::Bool(_) => collect!(Bool),
+            other => {
+                return Err(TableError::Unsupported {
+                    column: name.to_string(),
+                    found: other.type_name(),
+                })
+            }
+        };
+        let validity = values
+            .iter()
+            .any(missing)
+            .then(|| values.iter().map(|value| !missing(value)).collect());
+        Ok(Self { data, validity })
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.data {
+            ColumnData::Int(v) => v.len(),
+            ColumnData::Float(v) => v.len(),
+            ColumnData::Str(v) => v.len(),
+            ColumnData::Bool(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The type of every value in the column, as written in an annotation
+    pub fn type_name(&self) -> &'static str {
+        match &self.data {
+            ColumnData::Int(_) => "int",
+            ColumnData::Float(_) => "float",
+            ColumnData::Str(_) => "str",
+            ColumnData::Bool(_) => "bool",
+        }
+    }
+
+    /// Whether row `row` has a value
+    pub fn is_valid(&self, row: usize) -> bool {
+        self.validity.as_ref().is_none_or(|valid| valid.get(row))
+    }
+
+    /// How many rows don't have a value
+    pub fn null_count(&self) -> usize {
+        self.validity
+            .as_ref()
+            .map_or(0, |valid| valid.len() - valid.count())
+    }
+
+    /// The first row without a value, if there is one
+    pub fn first_missing(&self) -> Option<usize> {
+        let valid = self.validity.as_ref()?;
+        (0..valid.len()).find(|&row| !valid.get(row))
+    }
+
+    /// The value in row `row`, which has to exist, or an NA of the column's type if it's missing
+    pub fn get(&self, row: usize) -> Type {
+        if !self.is_valid(row) {
+            return Type::Na(self.type_name());
+        }
+        match &self.data {
+            ColumnData::Int(v) => Type::Int(v[row]),
+            ColumnData::Float(v) => Type::Float(v[row]),
+            ColumnData::Str(v) => Type::Str(v[row].clone()),
+            ColumnData::Bool(v) => Type::Bool(v[row]),
+        }
+    }
+
+    pub fn values(&self) -> Vec<Type> {
+        (0..self.len()).map(|row| self.get(row)).collect()
+    }
+
+    /// The values in `rows`, in that order, which can repeat
+    pub fn take(&self, rows: &[usize]) -> Column {
+        macro_rules! gather {
+            ($variant:ident, $values:expr) => {
+                ColumnData::$variant(rows.iter().map(|&row| $values[row].clone()).collect())
+            };
+        }
+        let data = match &self.data {
+            ColumnData::Int(v) => gather!(Int, v),
+            ColumnData::Float(v) => gather!(Float, v),
+            ColumnData::Str(v) => gather!(Str, v),
+            ColumnData::Bool(v) => gather!(Bool, v),
+        };
+        let validity = self
+            .validity
+            .as_ref()
+            .map(|valid| rows.iter().map(|&row| valid.get(row)).collect());
+        Column { data, validity }
+    }
+
+    /// Builds a column a row at a time from rows of `sources`, which all hold the same type as
+    /// the first, taking row `row` of `sources[source]` for each `Some((source, row))` and a
+    /// missing value for each `None`
+    pub fn gather(sources: &[&Column], picks: &[Option<(usize, usize)>]) -> Column {
+        macro_rules! gather {
+            ($variant:ident) => {{
+                let values: Vec<_> = sources
+                    .iter()
+                    .map(|source| match &source.data {
+                        ColumnData::$variant(values) => values,
+                        _ => panic!("gathering from columns of different types"),
+                    })
+                    .collect();
+                ColumnData::$variant(
+                    picks
+                        .iter()
+                        .map(|pick| {
+                            pick.map_or_else(Default::default, |(s, row)| values[s][row].clone())
+                        })
+                        .collect(),
+                )
+            }};
+        }
+        let data = match &sources[0].data {
+            ColumnData::Int(_) => gather!(Int),
+            ColumnData::Float(_) => gather!(Float),
+            ColumnData::Str(_) => gather!(Str),
+            ColumnData::Bool(_) => gather!(Bool),
+        };
+        let valid =
+            |pick: &Option<(usize, usize)>| pick.is_some_and(|(s, row)| sources[s].is_valid(row));
+        let validity = picks
+            .iter()
+            .any(|pick| !valid(pick))
+            .then(|| picks.iter().map(valid).collect());
+        Column { data, validity }
+    }
+
+    /// Orders two rows that both have values
+    /// Floats are ordered totally, with NaN after infinity
+    pub fn compare(&self, a: usize, b: usize) -> Ordering {
+        match &self.data {
+            ColumnData::Int(v) => v[a].cmp(&v[b]),
+            ColumnData::Float(v) => v[a].total_cmp(&v[b]),
+            ColumnData::Str(v) => v[a].cmp(&v[b]),
+            ColumnData::Bool(v) => v[a].cmp(&v[b]),
+        }
+    }
+
+    /// The value in a row, as something that can be hashed and compared for equality
+    pub fn datum(&self, row: usize) -> Datum<'_> {
+        if !self.is_valid(row) {
+            return Datum::Missing;
+        }
+        match &self.data {
+            ColumnData::Int(v) => Datum::Int(v[row]),
+            // Equal floats have to hash the same, which -0.0 and 0.0 or two NaNs don't otherwise
+            ColumnData::Float(v) if v[row] == 0.0 => Datum::Float(0.0f32.to_bits()),
+            ColumnData::Float(v) if v[row].is_nan() => Datum::Float(f32::NAN.to_bits()),
+            ColumnData::Float(v) => Datum::Float(v[row].to_bits()),
+            ColumnData::Str(v) => Datum::Str(&v[row]),
+            ColumnData::Bool(v) => Datum::Bool(v[row]),
+        }
+    }
+
+    /// How a value is written in a printed table, where strings don't need quotes
+    fn cell(&self, row: usize) -> String {
+        match &self.data {
+            // Line breaks would split the row over several lines of the grid
+            ColumnData::Str(v) if self.is_valid(row) => v[row]
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+                .replace('\t', "\\t"),
+            _ => self.get(row).to_string(),
+        }
+    }
+}
+
+/// A value out of a column that can be hashed, for finding rows with equal values
+/// Missing values are all equal to each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Datum<'a> {
+    Missing,
+    Int(i32),
+    /// The bits of the float
+    Float(u32),
+    Str(&'a str),
+    Bool(bool),
+}
+
+/// One of the columns a table is sorted by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+    /// Whether missing values go before every value, rather than after, whichever the direction
+    pub nulls_first: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    columns: Vec<(String, Rc<Column>)>,
+    rows: usize,
+}
+
+impl Table {
+    /// Puts columns together, which have to have different names and the same number of rows
+    pub fn new(columns: Vec<(String, Rc<Column>)>) -> Result<Self, TableError> {
+        let rows = columns.first().map_or(0, |(_, column)| column.len());
+        for (i, (name, column)) in columns.iter().enumerate() {
+            if column.len() != rows {
+                return Err(TableError::Length {
+                    column: name.clone(),
+                    found: column.len(),
+                    expected: rows,
+                    first: columns[0].0.clone(),
+                });
+            }
+            if columns[..i].iter().any(|(other, _)| other == name) {
+                return Err(TableError::Duplicate {
+                    column: name.clone(),
+                });
+            }
+        }
+        Ok(Self { columns, rows })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn columns(&self) -> &[(String, Rc<Column>)] {
+        &self.columns
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Rc<Column>> {
+        self.columns
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, column)| column)
+    }
+
+    /// A table of just the named columns, in the order they're named
+    pub fn project(&self, names: &[&str]) -> Result<Self, TableError> {
+        let columns = names
+            .iter()
+            .map(|name| {
+                self.column(name)
+                    .map(|column| (name.to_string(), column.clone()))
+                    .ok_or_else(|| TableError::Missing {
+                        column: name.to_string(),
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::new(columns)
+    }
+
+    /// The rows at `rows`, in that order
+    pub fn take(&self, rows: &[usize]) -> Self {
+        Self {
+            columns: self
+                .columns
+                .iter()
+                .map(|(name, column)| (name.clone(), Rc::new(column.take(rows))))
+                .collect(),
+            rows: rows.len(),
+        }
+    }
+
+    /// A row as a map from column names to values, with NA where a value is missing
+    pub fn row(&self, row: usize) -> Map {
+        let mut map = Map::new();
+        for (name, column) in &self.columns {
+            map.insert(Key::Str(name.clone()), column.get(row));
+        }
+        map
+    }
+
+    /// Replaces the column called `name`, keeping its place, or adds it at the end
+    pub fn with_column(&self, name: &str, column: Rc<Column>) -> Result<Self, TableError> {
+        let mut columns = self.columns.clone();
+        match columns.iter_mut().find(|(n, _)| n == name) {
+            Some((_, old)) => *old = column,
+            None => columns.push((name.to_string(), column)),
+        }
+        Self::new(columns)
+    }
+
+    /// Renames columns, leaving them where they were
+    pub fn rename(&self, renames: &[(String, String)]) -> Result<Self, TableError> {
+        let mut columns = self.columns.clone();
+        for (from, to) in renames {
+            match columns.iter_mut().find(|(name, _)| name == from) {
+                Some((name, _)) => *name = to.clone(),
+                None => {
+                    return Err(TableError::Missing {
+                        column: from.clone(),
+                    })
+                }
+            }
+        }
+        Self::new(columns)
+    }
+
+    /// Sorts the rows by each key in turn, so later keys only order rows that earlier ones
+    /// consider equal, and rows that are equal by every key keep their order
+    pub fn sort(&self, keys: &[SortKey]) -> Result<Self, TableError> {
+        let keys = keys
+            .iter()
+            .map(|key| {
+                self.column(&key.column)
+                    .map(|column| (column, key))
+                    .ok_or_else(|| TableError::Missing {
+                        column: key.column.clone(),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut rows: Vec<usize> = (0..self.rows).collect();
+        // `sort_by` is stable
+        rows.sort_by(|&a, &b| {
+            keys.iter()
+                .map(
+                    |(column, key)| match (column.is_valid(a), column.is_valid(b)) {
+                        (true, true) if key.descending => column.compare(b, a),
+                        (true, true) => column.compare(a, b),
+                        (false, false) => Ordering::Equal,
+                        (valid, _) if valid == key.nulls_first => Ordering::Greater,
+                        _ => Ordering::Less,
+                    },
+                )
+                .find(|order| order.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(self.take(&rows))
+    }
+
+    /// Keeps the first of every set of rows with the same values in the named columns
+    pub fn distinct(&self, names: &[&str]) -> Result<Self, TableError> {
+        let columns = names
+            .iter()
+            .map(|name| {
+                self.column(name).ok_or_else(|| TableError::Missing {
+                    column: name.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut seen = HashSet::new();
+        let rows: Vec<usize> = (0..self.rows)
+            .filter(|&row| {
+                let key: Vec<Datum> = columns.iter().map(|column| column.datum(row)).collect();
+                seen.insert(key)
+            })
+            .collect();
+        Ok(self.take(&rows))
+    }
+
+    /// A table describing this one, with the name and type of each column
+    pub fn schema(&self) -> Self {
+        let names = self.columns.iter().map(|(name, _)| name.clone()).collect();
+        let types = self
+            .columns
+            .iter()
+            .map(|(_, column)| column.type_name().to_string())
+            .collect();
+        Self {
+            rows: self.columns.len(),
+            columns: vec![
+                (
+                    String::from("name"),
+                    Rc::new(Column::new(ColumnData::Str(names))),
+                ),
+                (
+                    String::from("type"),
+                    Rc::new(Column::new(ColumnData::Str(types))),
+                ),
+            ],
+        }
+    }
+}
+
+impl Table {
+    /// How many rows and columns there are, in words
+    fn shape(&self) -> String {
+        let plural =
+            |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+        format!(
+            "{}, {}",
+            plural(self.rows, "row"),
+            plural(self.columns.len(), "column")
+        )
+    }
+}
+
+/// Prints like a list, with the type of its values in front
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "series<{}>[", self.type_name())?;
+        for (i, row) in shown_rows(self.len()).into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match row {
+                Some(row) => match self.get(row) {
+                    Type::Str(s) => write!(f, "{:?}", s)?,
+                    value => write!(f, "{}", value)?,
+                },
+                None => write!(f, "...")?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+/// Longer tables only print
+const EDGE_ROWS: usize = 5;
+
+/// The rows worth printing out of `rows`, with `None` standing for the ones left out
+fn shown_rows(rows: usize) -> Vec<Option<usize>> {
+    if rows > 2 * EDGE_ROWS {
+        (0..EDGE_ROWS)
+            .map(Some)
+            .chain(std::iter::once(None))
+            .chain((rows - EDGE_ROWS..rows).map(Some))
+            .collect()
+    } else {
+        (0..rows).map(Some).collect()
+    }
+}
+
+/// Prints as a grid, with each column's type under its name
+/// Numbers are right-aligned so their digits line up, everything else is left-aligned
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.columns.is_empty() {
+            return write!(f, "{}", self.shape());
+        }
+        let shown = shown_rows(self.rows);
+
+        let cells: Vec<Vec<String>> = self
+            .columns
+            .iter()
+            .map(|(_, column)| {
+                shown
+                    .iter()
+                    .map(|row| row.map_or_else(|| String::from("..."), |row| column.cell(row)))
+                    .collect()
+            })
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .zip(&cells)
+            .map(|((name, column), cells)| {
+                cells
+                    .iter()
+                    .map(|cell| cell.chars().count())
+                    .chain([name.chars().count(), column.type_name().len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let line = |f: &mut fmt::Formatter<'_>, cells: &mut dyn Iterator<Item = (String, bool)>| {
+            write!(f, "|")?;
+            for ((cell, right), width) in cells.zip(&widths) {
+                if right {
+                    write!(f, " {:>width$} |", cell, width = width)?;
+                } else {
+                    write!(f, " {:<width$} |", cell, width = width)?;
+                }
+            }
+            writeln!(f)
+        };
+
+        line(
+            f,
+            &mut self.columns.iter().map(|(name, _)| (name.clone(), false)),
+        )?;
+        line(
+            f,
+            &mut self
+                .columns
+                .iter()
+                .map(|(_, column)| (column.type_name().to_string(), false)),
+        )?;
+        write!(f, "|")?;
+        for width in &widths {
+            write!(f, "{}|", "-".repeat(width + 2))?;
+        }
+        writeln!(f)?;
+        for (i, row) in shown.iter().enumerate() {
+            line(
+                f,
+                &mut self.columns.iter().zip(&cells).map(|((_, column), cells)| {
+                    let numeric = matches!(column.data, ColumnData::Int(_) | ColumnData::Float(_));
+                    (cells[i].clone(), numeric && row.is_some())
+                }),
+            )?;
+        }
+        write!(f, "{}", self.shape())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::rows;
+
+    fn column(name: &str, values: Vec<Type>) -> (String, Rc<Column>) {
+        (
+            name.to_string(),
+            Rc::new(Column::from_values(name, &values).unwrap()),
+        )
+    }
+
+    fn ints(values: impl IntoIterator<Item = i32>) -> Vec<Type> {
+        values.into_iter().map(Type::Int).collect()
+    }
+
+    fn strs(values: &str) -> Vec<Type> {
+        values
+            .split(' ')
+            .map(|s| Type::Str(s.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn columns() {
+        let err = Column::from_values("a", &[Type::Int(1), Type::Float(2.0)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "column 'a' holds int values, but row 1 is float"
+        );
+        assert_eq!(
+            Column::from_values("a", &[]).unwrap_err(),
+            TableError::Empty {
+                column: String::from("a")
+            }
+        );
+        assert!(matches!(
+            Column::from_values("a", &[Type::List(Rc::new(Vec::new()))]),
+            Err(TableError::Unsupported { found: "list", .. })
+        ));
+
+        // A nil is a missing value, in a column whose type comes from the rest
+        let column = Column::from_values("a", &[Type::Nil, Type::Float(1.5)]).unwrap();
+        assert_eq!(column.type_name(), "float");
+        assert_eq!(column.null_count(), 1);
+        assert!(!column.is_valid(0));
+        assert_eq!(column.values().len(), 2);
+        assert!(matches!(column.get(0), Type::Na("float")));
+        assert!(matches!(
+            Column::from_values("a", &[Type::Nil]),
+            Err(TableError::Empty { .. })
+        ));
+        let column = Column::from_values("a", &strs("x y")).unwrap();
+        assert_eq!(column.type_name(), "str");
+        assert_eq!(column.len(), 2);
+        assert_eq!(column.get(1).to_string(), "y");
+    }
+
+    #[test]
+    fn building_and_projecting() {
+        let table = Table::new(vec![column("n", ints(1..=2)), column("s", strs("a b"))]).unwrap();
+        assert_eq!(rows(&table), ["n s", "1 a", "2 b"]);
+        assert_eq!(
+            rows(&table.project(&["s", "n"]).unwrap()),
+            ["s n", "a 1", "b 2"]
+        );
+        assert_eq!(
+            table.project(&["z"]).unwrap_err().to_string(),
+            "there's no column named 'z'"
+        );
+        assert_eq!(rows(&table.schema()), ["name type", "n int", "s str"]);
+
+        let err = Table::new(vec![column("n", ints(1..=2)), column("s", strs("a"))]).unwrap_err();
+        assert_eq!(err.to_string(), "column 's' has 1 rows but 'n' has 2");
+        let err = Table::new(vec![column("n", ints(1..=2)), column("n", ints(1..=2))]).unwrap_err();
+        assert_eq!(err.to_string(), "there are two columns named 'n'");
+    }
+
+    #[test]
+    fn rendering() {
+        let table = Table::new(vec![
+            column("name", strs("ann bartholomew")),
+            column("n", ints([5, 120])),
+        ])
+        .unwrap();
+        assert_eq!(
+            table.to_string(),
+            "| name        | n   |\n\
+             | str         | int |\n\
+             |-------------|-----|\n\
+             | ann         |   5 |\n\
+             | bartholomew | 120 |\n\
+             2 rows, 2 columns"
+        );
+        assert_eq!(Table::default().to_string(), "0 rows, 0 columns");
+    }
+
+    #[test]
+    fn long_tables_are_elided() {
+        let table = Table::new(vec![column("i", ints(0..12))]).unwrap();
+        let text = table.to_string();
+        let lines: Vec<_> = text.lines().collect();
+        // Two header lines, the rule, five rows, the gap, five rows and the shape
+        assert_eq!(lines.len(), 15);
+        assert_eq!(lines[7], "|   4 |");
+        assert_eq!(lines[8], "| ... |");
+        assert_eq!(lines[9], "|   7 |");
+        assert_eq!(lines[14], "12 rows, 1 column");
+    }
+}
@@ -0,0 +1,22 @@
+//! Helpers shared by the tests of modules that work with tables
+
+use super::{csv, table::Table};
+
+/// The column names, then every row, each line's values separated by spaces
+pub fn rows(table: &Table) -> Vec<String> {
+    let mut lines = vec![table.names().collect::<Vec<_>>().join(" ")];
+    for row in 0..table.rows() {
+        let values: Vec<_> = table
+            .columns()
+            .iter()
+            .map(|(_, column)| column.get(row).to_string())
+            .collect();
+        lines.push(values.join(" "));
+    }
+    lines
+}
+
+/// Reads a table from CSV text with the default options
+pub fn table(text: &str) -> Table {
+    csv::read(text, &csv::Options::default()).unwrap()
+}
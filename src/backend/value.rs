@@ -1,16 +1,282 @@
-use super::context::Context;
+use std::{cell::RefCell, fmt, rc::Rc};
 
+use crate::{
+    errors::error::{ErrorKind, SkError},
+    frontend::expr::Expr,
+};
+
+use super::{
+    group::Groups,
+    map::Map,
+    random::Generator,
+    regex::Regex,
+    table::{Column, Table},
+};
+
+#[derive(Debug, Clone)]
 pub enum Type {
     Int(i32),
     Float(f32),
     Str(String),
     Bool(bool),
     Nil,
+    /// A missing value out of a column, which remembers the type of the column, unlike nil,
+    /// which is the absence of any value at all
+    Na(&'static str),
+    List(Rc<Vec<Type>>),
+    /// Keys to values, kept in the order the keys were first inserted
+    Map(Rc<Map>),
+    Regex(Rc<Regex>),
+    Generator(Rc<RefCell<Generator>>),
+    Table(Rc<Table>),
+    /// A column of a table, or one made on its own, that operators apply to value by value
+    Series(Rc<Column>),
+    /// A table's rows split up by `group_by`, waiting to be aggregated
+    Groups(Rc<Groups>),
+    Function(Rc<Function>),
+    Native(Rc<NativeFunction>),
+}
+
+impl Type {
+    /// The name used for this type in annotations and error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Type::Int(_) => "int",
+            Type::Float(_) => "float",
+            Type::Str(_) => "str",
+            Type::Bool(_) => "bool",
+            Type::Nil => "nil",
+            Type::Na(_) => "na",
+            Type::List(_) => "list",
+            Type::Map(_) => "map",
+            Type::Regex(_) => "regex",
+            Type::Generator(_) => "generator",
+            Type::Table(_) => "table",
+            Type::Series(_) => "series",
+            Type::Groups(_) => "groups",
+            Type::Function(_) | Type::Native(_) => "function",
+        }
+    }
+
+    /// True if a value of this type satisfies the annotation `name`
+    /// Returns `None` if `name` isn't a type at all
+    /// A missing value satisfies the annotation for the type it's missing a value of
+    pub fn matches_annotation(&self, name: &str) -> Option<bool> {
+        match name {
+            "int" | "float" | "str" | "bool" => {
+                Some(self.type_name() == name || matches!(self, Type::Na(typ) if *typ == name))
+            }
+            "nil" | "na" | "list" | "map" | "regex" | "generator" | "table" | "series"
+            | "groups" | "function" => Some(self.type_name() == name),
+            "number" => Some(matches!(
+                self,
+                Type::Int(_) | Type::Float(_) | Type::Na("int" | "float")
+            )),
+            // Anything goes
+            "any" => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Displays the value as an item of a list or map, where strings are quoted so that
+    /// `["a, b"]` can't be read as two items
+    pub fn quoted(&self) -> Quoted<'_> {
+        Quoted(self)
+    }
 }
 
-pub struct Value<'r> {
+pub struct Quoted<'a>(&'a Type);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Type::Str(s) => write!(f, "{:?}", s),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int(i) => write!(f, "{}", i),
+            // Whole floats keep their decimal point so they can't be mistaken for ints
+            Type::Float(x) if x.is_finite() && x.fract() == 0.0 => write!(f, "{:.1}", x),
+            Type::Float(x) => write!(f, "{}", x),
+            Type::Str(s) => write!(f, "{}", s),
+            Type::Bool(b) => write!(f, "{}", b),
+            Type::Nil => write!(f, "nil"),
+            Type::Na(_) => write!(f, "NA"),
+            Type::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item.quoted())?;
+                }
+                write!(f, "]")
+            }
+            Type::Map(map) => write!(f, "{}", map),
+            Type::Regex(re) => write!(f, "r\"{}\"", re.pattern()),
+            Type::Generator(_) => write!(f, "<generator>"),
+            Type::Table(table) => write!(f, "{}", table),
+            Type::Series(column) => write!(f, "{}", column),
+            Type::Groups(groups) => write!(f, "{}", groups),
+            Type::Function(func) => write!(f, "<function {}>", func.name),
+            Type::Native(func) => write!(f, "<function {}>", func.name),
+        }
+    }
+}
+
+/// A user-defined function, along with everything needed to call it
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    /// Parameter names and their type annotations
+    pub params: Vec<(String, Option<String>)>,
+    pub returns: Option<String>,
+    pub body: Vec<Expr>,
+}
+
+/// The Rust side of a native function, which gets its arguments once they've been checked
+/// against its parameters
+pub type NativeFn = dyn Fn(&[Type]) -> Result<Type, NativeError>;
+
+/// The Rust side of a native function that calls back into functions it's given, like the
+/// predicate handed to `filter`
+pub type CallingFn = dyn Fn(&[Type], &mut dyn Caller) -> Result<Type, NativeError>;
+
+/// Calls function values on behalf of a native function
+pub trait Caller {
+    /// Calls `func`, checking `args` against its parameters
+    /// An error has to be passed on as it is, since the runtime is left partway through the call
+    fn call(&mut self, func: &Type, args: Vec<Type>) -> Result<Type, NativeError>;
+}
+
+/// A function implemented in Rust, either a built-in or one provided by the program embedding
+/// Starkey
+pub struct NativeFunction {
+    pub name: String,
+    /// The type every argument must have, written the same way as an annotation
+    pub params: Vec<&'static str>,
+    /// How many of `params` have to be given, the rest can be left off the end
+    pub required: usize,
+    /// The type of any arguments after `params`, if it takes more
+    pub rest: Option<&'static str>,
+    /// The name of each parameter, if arguments can be passed by name
+    /// Optional parameters skipped over by a named argument are passed as nil
+    pub names: Vec<&'static str>,
+    pub func: Box<CallingFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        params: Vec<&'static str>,
+        func: impl Fn(&[Type]) -> Result<Type, NativeError> + 'static,
+    ) -> Self {
+        Self::calling(name, params, move |args, _| func(args))
+    }
+
+    /// A native function that can call the function values it's given
+    pub fn calling(
+        name: &str,
+        params: Vec<&'static str>,
+        func: impl Fn(&[Type], &mut dyn Caller) -> Result<Type, NativeError> + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            required: params.len(),
+            params,
+            rest: None,
+            names: Vec::new(),
+            func: Box::new(func),
+        }
+    }
+
+    /// Lets the last `count` parameters be left off
+    pub fn optional(mut self, count: usize) -> Self {
+        self.required = self.params.len() - count;
+        self
+    }
+
+    /// Names the parameters, so arguments can be passed by name like `how: "left"`
+    pub fn named(mut self, names: Vec<&'static str>) -> Self {
+        debug_assert_eq!(names.len(), self.params.len());
+        self.names = names;
+        self
+    }
+
+    /// Lets the function take any number of extra arguments of type `rest`
+    pub fn variadic(mut self, rest: &'static str) -> Self {
+        self.rest = Some(rest);
+        self
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("rest", &self.rest)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why a native function couldn't return a value
+#[derive(Debug)]
+pub struct NativeError {
+    pub kind: ErrorKind,
+    /// The argument at fault, if any, which is pointed at instead of the whole call
+    pub argument: Option<usize>,
+    /// Anything else worth knowing, shown as help under the error
+    pub notes: Vec<String>,
+    /// An error raised inside a function called through a `Caller`, which is reported as it is
+    pub raised: Option<Box<SkError>>,
+}
+
+impl NativeError {
+    /// An error about the call as a whole
+    pub fn call(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            argument: None,
+            notes: Vec::new(),
+            raised: None,
+        }
+    }
+
+    /// An error about one of the arguments
+    pub fn argument(index: usize, kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            argument: Some(index),
+            notes: Vec::new(),
+            raised: None,
+        }
+    }
+
+    /// Passes on an error from a function called through a `Caller`
+    pub fn raised(err: Box<SkError>) -> Self {
+        Self {
+            kind: err.kind.clone(),
+            argument: None,
+            notes: Vec::new(),
+            raised: Some(err),
+        }
+    }
+
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+/// A named value living in a context
+pub struct Value {
     pub typ: Type,
     pub name: String,
     pub constant: bool,
-    pub context: &'r Context<'r>,
-}
\ No newline at end of file
+}
@@ -0,0 +1,256 @@
+use crate::errors::error::ErrorFormat;
+
+pub const USAGE: &str = "\
+Usage: starkey [OPTIONS] <COMMAND> <FILE>
+       starkey [OPTIONS] <FILE>
+       starkey [OPTIONS] [repl]
+       starkey --explain <CODE>
+
+Commands:
+    repl      Start an interactive session (the default without a file)
+    run       Check the program, then run it (the default with a file)
+    check     Parse and check the program without running it
+    tokens    Print the tokens the lexer produces
+    ast       Print the syntax tree the parser produces
+
+<FILE> can be `-` to read the program from stdin.
+
+Options:
+    --error-format <human|json>   How diagnostics are written to stderr [default: human]
+    --explain <CODE>              Describe an error code in detail
+    -h, --help                    Print this message
+    -V, --version                 Print the version
+
+Exit codes:
+    0    Success
+    1    The program has errors and wasn't run
+    2    The command line was invalid
+    3    The program raised an error while running
+    4    The input couldn't be read
+
+Environment:
+    NO_COLOR              Don't color diagnostics
+    STARKEY_NO_HISTORY    Don't keep REPL entries in ~/.starkey_history, which is only written
+                          when stdin is a terminal
+";
+
+// Exit codes, as documented in `USAGE`
+pub const EXIT_SUCCESS: u8 = 0;
+pub const EXIT_DIAGNOSTICS: u8 = 1;
+pub const EXIT_USAGE: u8 = 2;
+pub const EXIT_RUNTIME: u8 = 3;
+pub const EXIT_IO: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    Check,
+    Tokens,
+    Ast,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "run" => Some(Command::Run),
+            "check" => Some(Command::Check),
+            "tokens" => Some(Command::Tokens),
+            "ast" => Some(Command::Ast),
+            _ => None,
+        }
+    }
+}
+
+/// Where the program's source comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(String),
+}
+
+impl Input {
+    /// The name shown in diagnostics
+    pub fn name(&self) -> &str {
+        match self {
+            Input::Stdin => "<stdin>",
+            Input::File(path) => path,
+        }
+    }
+}
+
+/// What the command line asked for
+#[derive(Debug)]
+pub enum Action {
+    Help,
+    Version,
+    Explain(String),
+    Repl {
+        format: ErrorFormat,
+    },
+    Compile {
+        command: Command,
+        input: Input,
+        format: ErrorFormat,
+    },
+}
+
+/// Reads the arguments, not including the program name
+/// Returns a message describing the problem if they don't make sense
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Action, String> {
+    let mut format = ErrorFormat::Human;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Action::Help),
+            "-V" | "--version" => return Ok(Action::Version),
+            "--explain" => {
+                let code = args.next().ok_or("'--explain' needs an error code")?;
+                return Ok(Action::Explain(code));
+            }
+            "--error-format" => {
+                let value = args.next().ok_or("'--error-format' needs a value")?;
+                format = error_format(&value)?;
+            }
+            _ if arg.starts_with("--error-format=") => {
+                format = error_format(&arg["--error-format=".len()..])?;
+            }
+            "-" => positional.push(arg),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let (command, path) = match positional.as_slice() {
+        [] => return Ok(Action::Repl { format }),
+        [command] if command == "repl" => return Ok(Action::Repl { format }),
+        [command, extra] if command == "repl" => {
+            return Err(format!("unexpected argument '{}'", extra))
+        }
+        [path] => (Command::Run, path),
+        [command, path] => match Command::from_name(command) {
+            Some(command) => (command, path),
+            None => return Err(format!("unknown command '{}'", command)),
+        },
+        [_, _, extra, ..] => return Err(format!("unexpected argument '{}'", extra)),
+    };
+
+    // A lone argument that names a command is almost certainly a forgotten file
+    if positional.len() == 1 && Command::from_name(path).is_some() {
+        return Err(format!("'{}' needs a file to work on", path));
+    }
+
+    let input = match path.as_str() {
+        "-" => Input::Stdin,
+        _ => Input::File(path.clone()),
+    };
+    Ok(Action::Compile {
+        command,
+        input,
+        format,
+    })
+}
+
+fn error_format(value: &str) -> Result<ErrorFormat, String> {
+    match value {
+        "human" => Ok(ErrorFormat::Human),
+        "json" => Ok(ErrorFormat::Json),
+        _ => Err(format!(
+            "unknown error format '{}', expected 'human' or 'json'",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Action, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    /// The command, input and format of a compile action
+    fn compile(args: &[&str]) -> (Command, Input, ErrorFormat) {
+        match parse(args) {
+            Ok(Action::Compile {
+                command,
+                input,
+                format,
+            }) => (command, input, format),
+            other => panic!("expected a compile action, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn commands_and_inputs() {
+        let file = |path: &str| Input::File(path.to_string());
+        assert_eq!(
+            compile(&["main.sk"]),
+            (Command::Run, file("main.sk"), ErrorFormat::Human)
+        );
+        assert_eq!(
+            compile(&["check", "main.sk"]),
+            (Command::Check, file("main.sk"), ErrorFormat::Human)
+        );
+        assert_eq!(
+            compile(&["tokens", "-"]),
+            (Command::Tokens, Input::Stdin, ErrorFormat::Human)
+        );
+        assert_eq!(
+            compile(&["--error-format", "json", "ast", "a.sk"]),
+            (Command::Ast, file("a.sk"), ErrorFormat::Json)
+        );
+        assert_eq!(
+            compile(&["run", "a.sk", "--error-format=json"]),
+            (Command::Run, file("a.sk"), ErrorFormat::Json)
+        );
+    }
+
+    #[test]
+    fn repl() {
+        assert!(matches!(
+            parse(&[]),
+            Ok(Action::Repl {
+                format: ErrorFormat::Human
+            })
+        ));
+        assert!(matches!(
+            parse(&["--error-format=json", "repl"]),
+            Ok(Action::Repl {
+                format: ErrorFormat::Json
+            })
+        ));
+        assert_eq!(
+            parse(&["repl", "a.sk"]).unwrap_err(),
+            "unexpected argument 'a.sk'"
+        );
+    }
+
+    #[test]
+    fn flags() {
+        assert!(matches!(parse(&["--help", "run"]), Ok(Action::Help)));
+        assert!(matches!(parse(&["-V"]), Ok(Action::Version)));
+        assert!(matches!(
+            parse(&["--explain", "SK0001"]),
+            Ok(Action::Explain(code)) if code == "SK0001"
+        ));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let error = |args: &[&str]| parse(args).unwrap_err();
+        assert_eq!(error(&["check"]), "'check' needs a file to work on");
+        assert_eq!(error(&["build", "a.sk"]), "unknown command 'build'");
+        assert_eq!(
+            error(&["run", "a.sk", "b.sk"]),
+            "unexpected argument 'b.sk'"
+        );
+        assert_eq!(error(&["--fast", "a.sk"]), "unknown option '--fast'");
+        assert_eq!(error(&["--explain"]), "'--explain' needs an error code");
+        assert_eq!(
+            error(&["--error-format=xml", "a.sk"]),
+            "unknown error format 'xml', expected 'human' or 'json'"
+        );
+    }
+}
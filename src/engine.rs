@@ -0,0 +1,492 @@
+use std::{fmt, rc::Rc};
+
+use crate::{
+    backend::{
+        check,
+        convert::{ConversionError, FromValue, IntoValue},
+        eval::Runtime,
+        native::HostFunction,
+        value::{NativeError, NativeFunction, Type, Value},
+    },
+    errors::{
+        error::{ErrorFormat, ErrorKind, Errors, SkError},
+        formatter::Formatter,
+    },
+    frontend::{expr::Expr, lexer::Lexer, parser::Parser},
+};
+
+/// The module that functions registered by the embedding program are imported from
+pub const HOST_MODULE: &str = "host";
+
+/// Compiles and runs Starkey source, keeping globals between runs
+///
+/// Every piece of source given to an engine is kept, so that functions defined by one program
+/// and called from another still report errors against the right lines. Programs must be run on
+/// the engine that compiled them.
+///
+/// Calls are limited to `backend::eval::MAX_CALL_DEPTH` deep, which can need more stack than the
+/// main thread has. Run the engine on a thread with a larger stack if programs recurse deeply.
+pub struct Engine {
+    source: String,
+    /// Where each piece of source begins in `source` and its name
+    segments: Vec<(usize, String)>,
+    runtime: Runtime,
+    globals: check::Globals,
+    format: ErrorFormat,
+    color: bool,
+}
+
+/// Source that has been parsed and checked, ready to run
+pub struct Program {
+    tree: Vec<Expr>,
+    warnings: Option<Diagnostics>,
+}
+
+impl Program {
+    /// Anything worth pointing out that didn't stop the program from compiling
+    pub fn warnings(&self) -> Option<&Diagnostics> {
+        self.warnings.as_ref()
+    }
+}
+
+/// Everything that went wrong while compiling or running, along with the rendered report
+#[derive(Debug)]
+pub struct Diagnostics {
+    errors: Vec<SkError>,
+    report: String,
+}
+
+impl Diagnostics {
+    pub fn errors(&self) -> &[SkError] {
+        &self.errors
+    }
+
+    /// The diagnostics rendered in the engine's error format, ready to be printed
+    pub fn report(&self) -> &str {
+        &self.report
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.report)
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Reading or writing a global went wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlobalError {
+    Undefined(String),
+    Constant(String),
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for GlobalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlobalError::Undefined(name) => write!(f, "no global named '{}'", name),
+            GlobalError::Constant(name) => write!(f, "'{}' is a constant", name),
+            GlobalError::Conversion(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for GlobalError {}
+
+impl From<ConversionError> for GlobalError {
+    fn from(err: ConversionError) -> Self {
+        GlobalError::Conversion(err)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let runtime = Runtime::initialize();
+        // The checker has to know about the prelude's built-ins and the standard library
+        let mut globals = check::Globals::default();
+        for (name, value) in &runtime.globals {
+            globals.define(name, value.constant);
+        }
+        for (module, members) in &runtime.modules {
+            members.keys().for_each(|name| globals.export(module, name));
+        }
+
+        Self {
+            source: String::new(),
+            segments: Vec::new(),
+            runtime,
+            globals,
+            format: ErrorFormat::Human,
+            color: false,
+        }
+    }
+
+    /// Sets the format that diagnostics are reported in
+    pub fn set_error_format(&mut self, format: ErrorFormat) {
+        self.format = format;
+    }
+
+    /// Sets whether human-readable diagnostics are colored, they aren't by default
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    /// Parses and checks a program, `name` is what diagnostics call it
+    pub fn compile(&mut self, name: &str, source: &str) -> Result<Program, Diagnostics> {
+        self.compile_source(name, source, false)
+    }
+
+    /// Compiles a program the way the REPL does, where a statement can be a bare expression
+    /// whose value is returned by `run`
+    pub fn compile_entry(&mut self, name: &str, source: &str) -> Result<Program, Diagnostics> {
+        self.compile_source(name, source, true)
+    }
+
+    fn compile_source(
+        &mut self,
+        name: &str,
+        source: &str,
+        bare_expressions: bool,
+    ) -> Result<Program, Diagnostics> {
+        // Each program starts on a line of its own, but the source is kept as it was given until
+        // then, so errors at its very end point just past its last character
+        if !self.source.is_empty() && !self.source.ends_with('\n') {
+            self.source.push('\n');
+        }
+        let start = self.source.len();
+        self.segments.push((start, name.to_string()));
+        self.source.push_str(source);
+
+        let formatter = formatter(&self.source, &self.segments);
+        let mut errors = Errors::initialize(&formatter);
+        errors.set_format(self.format);
+
+        let mut lexer = Lexer::resume(&self.source, start);
+        let tokens = lexer.tokenize(&mut errors);
+        let mut parser = Parser::new(&mut errors, tokens.iter());
+        if bare_expressions {
+            parser.allow_bare_expressions();
+        }
+        parser.parse();
+        let tree = parser.tree;
+
+        // Nothing a program that doesn't compile declares is kept
+        let before = self.globals.clone();
+        check::Checker::new(&mut errors, &mut self.globals).check(&tree);
+        if errors.has_errors() {
+            self.globals = before;
+            return Err(diagnostics(errors, self.color));
+        }
+
+        let warnings = (!errors.is_empty()).then(|| diagnostics(errors, self.color));
+        Ok(Program { tree, warnings })
+    }
+
+    /// Runs a compiled program
+    /// Returns the value of its last statement if that was a bare expression
+    pub fn run(&mut self, program: &Program) -> Result<Option<Type>, Diagnostics> {
+        match self.runtime.evaluate(&program.tree) {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                // Whatever ran before the error stays defined, the rest doesn't
+                let runtime = &self.runtime;
+                self.globals
+                    .retain(|name| runtime.globals.contains_key(name));
+
+                let formatter = formatter(&self.source, &self.segments);
+                let mut errors = Errors::initialize(&formatter);
+                errors.set_format(self.format);
+                errors.push(*err);
+                Err(diagnostics(errors, self.color))
+            }
+        }
+    }
+
+    /// Compiles and runs a program
+    pub fn eval(&mut self, name: &str, source: &str) -> Result<Option<Type>, Diagnostics> {
+        let program = self.compile(name, source)?;
+        self.run(&program)
+    }
+
+    /// Reads a global as a Rust value
+    pub fn get<T: FromValue>(&self, name: &str) -> Result<T, GlobalError> {
+        let value = self
+            .global(name)
+            .ok_or_else(|| GlobalError::Undefined(name.to_string()))?;
+        Ok(T::from_value(value)?)
+    }
+
+    /// Reads a global as it is
+    pub fn global(&self, name: &str) -> Option<&Type> {
+        self.runtime.globals.get(name).map(|v| &v.typ)
+    }
+
+    /// Sets a global, declaring it if it doesn't exist yet
+    /// An existing global has to be a variable, and keeps the type it was declared with
+    pub fn set<T: IntoValue>(&mut self, name: &str, value: T) -> Result<(), GlobalError> {
+        let value = value.into_value();
+        match self.runtime.globals.get_mut(name) {
+            Some(v) if v.constant => Err(GlobalError::Constant(name.to_string())),
+            Some(v) if v.typ.type_name() != value.type_name() => {
+                Err(GlobalError::Conversion(ConversionError {
+                    expected: v.typ.type_name(),
+                    found: value.type_name(),
+                }))
+            }
+            Some(v) => {
+                v.typ = value;
+                Ok(())
+            }
+            None => {
+                self.define(name, value, false);
+                Ok(())
+            }
+        }
+    }
+
+    /// Makes a Rust closure callable from Starkey, once imported with `from host import name;`
+    ///
+    /// The closure's parameter types are its signature: arguments of the wrong type are reported
+    /// at the argument before it's called, and the rest are converted with `FromValue`. It can
+    /// return anything `IntoValue`, or a `Result` whose error is reported at the call.
+    pub fn register_fn<Args>(&mut self, name: &str, func: impl HostFunction<Args>) {
+        self.export(HOST_MODULE, func.into_native(name));
+    }
+
+    /// Makes a Rust function that takes any number of arguments of any type callable from
+    /// Starkey, once imported with `from host import name;`
+    /// Whatever `func` returns as an `Err` is reported as an error at the call
+    pub fn register_variadic(
+        &mut self,
+        name: &str,
+        func: impl Fn(&[Type]) -> Result<Type, String> + 'static,
+    ) {
+        let function = name.to_string();
+        let native = NativeFunction::new(name, Vec::new(), move |args| {
+            func(args).map_err(|message| {
+                NativeError::call(ErrorKind::HostError {
+                    function: function.clone(),
+                    message,
+                })
+            })
+        });
+        self.export(HOST_MODULE, native.variadic("any"));
+    }
+
+    /// Makes a native function importable from `module`
+    fn export(&mut self, module: &str, native: NativeFunction) {
+        self.globals.export(module, &native.name);
+        self.runtime
+            .modules
+            .entry(module.to_string())
+            .or_default()
+            .insert(native.name.clone(), Type::Native(Rc::new(native)));
+    }
+
+    fn define(&mut self, name: &str, typ: Type, constant: bool) {
+        self.runtime.globals.insert(
+            name.to_string(),
+            Value {
+                typ,
+                name: name.to_string(),
+                constant,
+            },
+        );
+        self.globals.define(name, constant);
+    }
+}
+
+fn formatter<'a>(source: &'a String, segments: &'a [(usize, String)]) -> Formatter<'a> {
+    let segments = segments
+        .iter()
+        .map(|(offset, name)| (*offset, name.as_str()))
+        .collect();
+    Formatter::with_segments(source, segments)
+}
+
+fn diagnostics(errors: Errors, color: bool) -> Diagnostics {
+    let report = errors.report(color);
+    Diagnostics {
+        errors: errors.into_errors(),
+        report,
+    }
+}
+
+/// Runs `source` as a REPL entry, giving the value it ended with or the first error's code and
+/// message
+#[cfg(test)]
+pub(crate) fn evaluate(source: &str) -> Result<String, String> {
+    let mut engine = Engine::new();
+    let program = engine.compile_entry("test", source);
+    match program.and_then(|program| engine.run(&program)) {
+        Ok(value) => Ok(value.map(|v| v.to_string()).unwrap_or_default()),
+        Err(diagnostics) => {
+            let err = &diagnostics.errors()[0];
+            Err(format!("{}: {}", err.kind.code(), err.kind))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globals_last_between_programs() {
+        let mut engine = Engine::new();
+        engine
+            .eval(
+                "a",
+                "var total = 1;\ndef add(n: int) { total -> total + n; }\n",
+            )
+            .unwrap();
+        engine.eval("b", "add(2);\nadd(3);\n").unwrap();
+        assert_eq!(engine.get::<i32>("total").unwrap(), 6);
+        assert_eq!(evaluate("var a = 2;\na * 3;\n").unwrap(), "6");
+        assert_eq!(evaluate("var a = 2;\n").unwrap(), "");
+    }
+
+    #[test]
+    fn failed_programs() {
+        let mut engine = Engine::new();
+        // Nothing is declared by a program that doesn't compile
+        let diagnostics = engine
+            .eval("bad", "var a = 1;\nvar b = missing;\n")
+            .unwrap_err();
+        assert_eq!(diagnostics.errors().len(), 1);
+        assert!(diagnostics.report().contains("bad:2:9"));
+        assert!(engine.global("a").is_none());
+        assert!(engine.eval("again", "var a = 2;\n").is_ok());
+
+        // Errors at the end of a program point just past its last character
+        let diagnostics = engine.eval("end", "var x = 1;\n$x").unwrap_err();
+        assert!(diagnostics.report().contains("end:2:3"));
+        let diagnostics = engine.eval("next", "var y = ;\n").unwrap_err();
+        assert!(diagnostics.report().contains("next:1:1"));
+
+        // What ran before a runtime error stays declared
+        assert!(engine.eval("run", "var c = 1;\nvar d = 1 / 0;\n").is_err());
+        assert_eq!(engine.get::<i32>("c").unwrap(), 1);
+        assert!(engine.eval("later", "var d = 2;\n").is_ok());
+
+        // Errors point into the program a function was defined in
+        engine
+            .eval("lib", "def half(n: int): int {\n    return n / 0;\n}\n")
+            .unwrap();
+        let diagnostics = engine.eval("main", "var h = half(4);\n").unwrap_err();
+        assert!(diagnostics.report().contains("lib:2:"));
+        assert_eq!(
+            evaluate("$ 1 / 0;\n").unwrap_err(),
+            "SK0400: Attempted to divide by zero"
+        );
+    }
+
+    #[test]
+    fn reading_and_setting_globals() {
+        let mut engine = Engine::new();
+        engine
+            .eval("test", "var n = 2;\nconst name = \"sk\";\nvar f = 1.5;\n")
+            .unwrap();
+        assert_eq!(engine.get::<i64>("n").unwrap(), 2);
+        assert_eq!(engine.get::<String>("name").unwrap(), "sk");
+        assert_eq!(engine.get::<f32>("f").unwrap(), 1.5);
+        assert_eq!(
+            engine.get::<bool>("n"),
+            Err(GlobalError::Conversion(ConversionError {
+                expected: "bool",
+                found: "int",
+            }))
+        );
+        assert_eq!(
+            engine.get::<i32>("missing"),
+            Err(GlobalError::Undefined("missing".to_string()))
+        );
+
+        engine.set("n", 5).unwrap();
+        assert!(matches!(
+            engine.set("n", "five"),
+            Err(GlobalError::Conversion(_))
+        ));
+        assert_eq!(
+            engine.set("name", "other"),
+            Err(GlobalError::Constant("name".to_string()))
+        );
+        engine.set("fresh", true).unwrap();
+        engine
+            .eval("use", "var both = fresh;\nvar m = n + 1;\n")
+            .unwrap();
+        assert!(engine.get::<bool>("both").unwrap());
+        assert_eq!(engine.get::<i32>("m").unwrap(), 6);
+    }
+
+    #[test]
+    fn host_functions() {
+        let mut engine = Engine::new();
+        engine.register_fn("double", |i: i32| i * 2);
+        engine.register_fn("ratio", |a: f32, b: f32| {
+            if b == 0.0 {
+                Err("divided by zero")
+            } else {
+                Ok(a / b)
+            }
+        });
+        engine.register_fn("greet", |name: Option<String>| {
+            format!("hi {}", name.unwrap_or_else(|| "there".to_string()))
+        });
+        engine.register_variadic("sum", |args| {
+            let mut total = 0;
+            for arg in args {
+                match arg {
+                    Type::Int(i) => total += i,
+                    other => return Err(format!("can't add a {}", other.type_name())),
+                }
+            }
+            Ok(Type::Int(total))
+        });
+
+        let source = "from host import double, ratio, greet, sum;\n\
+                      var d = double(4);\n\
+                      var r = ratio(3.0, 2.0);\n\
+                      var g = greet(\"sk\");\n\
+                      var s = sum(1, 2, 3);\n";
+        engine.eval("test", source).unwrap();
+        assert_eq!(engine.get::<i32>("d").unwrap(), 8);
+        assert_eq!(engine.get::<f32>("r").unwrap(), 1.5);
+        assert_eq!(engine.get::<String>("g").unwrap(), "hi sk");
+        assert_eq!(engine.get::<i32>("s").unwrap(), 6);
+
+        // Host functions aren't globals until they're imported
+        assert!(Engine::new().eval("bare", "var d = double(1);\n").is_err());
+
+        let code = |source: &str| {
+            let mut engine = Engine::new();
+            engine.register_fn("double", |i: i32| i * 2);
+            engine.register_fn("ratio", |a: f32, b: f32| {
+                if b == 0.0 {
+                    Err("divided by zero")
+                } else {
+                    Ok(a / b)
+                }
+            });
+            let source = format!("from host import double, ratio;\n{}", source);
+            let err = engine.eval("test", &source).unwrap_err();
+            let err = &err.errors()[0];
+            format!("{}: {}", err.kind.code(), err.kind)
+        };
+        assert!(code("double(\"two\");\n").starts_with("SK0200"));
+        assert!(code("double(1, 2);\n").contains("takes 1 argument but 2 were given"));
+        assert!(code("var x = ratio(1.0, 0.0);\n").contains("divided by zero"));
+        assert!(code("from host import triple;\n").contains("triple"));
+
+        let err = engine.eval("bad", "var t = sum(1, \"2\");\n").unwrap_err();
+        assert!(err.report().contains("can't add a str"));
+    }
+}
@@ -1,11 +1,29 @@
-use std::ops::Range;
+use std::{
+    env, fmt,
+    io::{self, IsTerminal},
+    ops::Range,
+};
 
-use super::formatter::Formatter;
+use super::{
+    formatter::Formatter,
+    json,
+    trace::{self, Frame},
+};
 
 #[derive(Debug)]
 pub struct Errors<'a> {
     errs: Vec<SkError>,
     fmt: &'a Formatter<'a>,
+    format: ErrorFormat,
+}
+
+/// How `Errors::print` writes diagnostics out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// The rustc-style renderer
+    Human,
+    /// One JSON object per line, for tools and pipelines
+    Json,
 }
 
 impl<'a> Errors<'a> {
@@ -13,28 +31,326 @@ impl<'a> Errors<'a> {
         Self {
             errs: Vec::new(),
             fmt,
+            format: ErrorFormat::Human,
         }
     }
 
+    pub fn set_format(&mut self, format: ErrorFormat) {
+        self.format = format;
+    }
+
     /// Creates a new error an pushes it to the Errors vec
     /// Start and end are inclusive such that the range is start..=end
-    pub fn new(&mut self, class: ErrorClass, kind: ErrorKind, start: usize, end: usize) {
-        self.errs.push(SkError {
-            class,
-            kind,
-            span: start..end + 1,
-        })
+    /// Returns the error so that labels and help notes can be attached to it
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        &mut self,
+        class: ErrorClass,
+        kind: ErrorKind,
+        start: usize,
+        end: usize,
+    ) -> &mut SkError {
+        self.errs.push(SkError::new(class, kind, start, end));
+        self.errs.last_mut().unwrap()
+    }
+
+    /// Adds an error that was created elsewhere, like one raised while running
+    pub fn push(&mut self, err: SkError) {
+        self.errs.push(err);
+    }
+
+    /// The errors reported so far, in order
+    #[cfg(test)]
+    pub fn errors(&self) -> &[SkError] {
+        &self.errs
     }
 
     pub fn dbg(&self) {
         self.errs.iter().for_each(|x| println!("{:?}", x));
     }
 
-    fn print_error(&self, err: &SkError) {
-        // Get the line this belongs to
-        let (line_str, range) = self.fmt.get_line(err.span.start);
-        let underline = Formatter::get_underline(&line_str, range);
+    pub fn is_empty(&self) -> bool {
+        self.errs.is_empty()
+    }
+
+    /// True if anything at the `Error` class has been reported
+    pub fn has_errors(&self) -> bool {
+        self.errs
+            .iter()
+            .any(|e| matches!(e.class, ErrorClass::Error))
+    }
+
+    /// Prints every diagnostic to stderr in the chosen format
+    /// Human output is only colored when stderr is a terminal and `NO_COLOR` isn't set
+    pub fn print(&self) {
+        eprint!("{}", self.report(use_color()));
+    }
+
+    /// Renders every diagnostic in the chosen format, sorted by where they occur
+    /// Human output ends with a summary line
+    pub fn report(&self, color: bool) -> String {
+        let mut sorted: Vec<&SkError> = self.errs.iter().collect();
+        sorted.sort_by_key(|e| e.span.start);
+
+        let mut out = String::new();
+        if self.format == ErrorFormat::Json {
+            for e in sorted {
+                out.push_str(&json::to_json(e, self.fmt, &self.render(e, false)));
+                out.push('\n');
+            }
+            return out;
+        }
+
+        sorted
+            .into_iter()
+            .for_each(|e| out.push_str(&self.render(e, color)));
+
+        let errors = self
+            .errs
+            .iter()
+            .filter(|e| matches!(e.class, ErrorClass::Error))
+            .count();
+        let warnings = self.errs.len() - errors;
+        if errors > 0 {
+            out.push_str(&format!(
+                "{}: aborting due to {} previous error{}\n",
+                paint("error", CLASS_ERROR, color),
+                errors,
+                if errors == 1 { "" } else { "s" }
+            ));
+        }
+        if let Some(first) = self.errs.iter().min_by_key(|e| e.span.start) {
+            out.push_str(&format!(
+                "For more information about an error, try `starkey --explain {}`.\n",
+                first.kind.code()
+            ));
+        }
+        if warnings > 0 {
+            out.push_str(&format!(
+                "{}: {} warning{} emitted\n",
+                paint("warning", CLASS_WARNING, color),
+                warnings,
+                if warnings == 1 { "" } else { "s" }
+            ));
+        }
+        out
+    }
+
+    /// Gives up the diagnostics, once they've been reported
+    pub fn into_errors(self) -> Vec<SkError> {
+        self.errs
+    }
+
+    /// Renders an error the same way rustc does, with the offending source lines underneath a
+    /// header and location, the primary span underlined with `^` and any labels with `-`
+    pub fn render(&self, err: &SkError, color: bool) -> String {
+        let class_style = match err.class {
+            ErrorClass::Error => CLASS_ERROR,
+            ErrorClass::Warning => CLASS_WARNING,
+        };
+
+        // Everything that gets underlined, primary first
+        let mut annotations = vec![Annotation {
+            span: err.span.clone(),
+            message: err.primary.as_deref(),
+            mark: '^',
+            style: class_style,
+        }];
+        annotations.extend(err.labels.iter().map(|l| Annotation {
+            span: l.span.clone(),
+            message: Some(l.message.as_str()),
+            mark: '-',
+            style: GUTTER,
+        }));
+
+        // Every line touched by an annotation is shown
+        let mut line_numbers = Vec::new();
+        for a in &annotations {
+            let (first, last) = self.lines_of(&a.span);
+            line_numbers.extend(first..=last);
+        }
+        let lines = self.fmt.get_relevant_lines(line_numbers);
+        let width = lines
+            .iter()
+            .map(|(n, _)| self.fmt.display_line(*n).to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(width);
+        let bar = paint("|", GUTTER, color);
+
+        let mut out = String::new();
+        let (path, line, col) = self.fmt.location(err.span.start);
+        out.push_str(&format!(
+            "{}{}\n",
+            paint(&format!("{}[{}]", err.class, err.kind.code()), class_style, color),
+            paint(&format!(": {}", err.kind), BOLD, color),
+        ));
+        out.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            pad,
+            paint("-->", GUTTER, color),
+            path,
+            line,
+            col
+        ));
+        if self.fmt.is_empty() {
+            out.push('\n');
+            return out;
+        }
+        out.push_str(&format!("{} {}\n", pad, bar));
+
+        let mut previous: Option<usize> = None;
+        let mut current_path = path;
+        for (n, text) in lines {
+            // Lines from another file get a header of their own
+            let line_start = self.fmt.line_range(n).start;
+            let line_path = self.fmt.path_at(line_start);
+            if line_path != current_path {
+                out.push_str(&format!(
+                    "{}{} {}:{}\n",
+                    pad,
+                    paint(":::", GUTTER, color),
+                    line_path,
+                    self.fmt.display_line(n)
+                ));
+                current_path = line_path;
+            } else if previous.is_some_and(|p| n > p + 1) {
+                out.push_str(&format!("{}\n", paint("...", GUTTER, color)));
+            }
+            previous = Some(n);
+
+            out.push_str(&format!(
+                "{} {} {}\n",
+                paint(&format!("{:>width$}", self.fmt.display_line(n)), GUTTER, color),
+                bar,
+                text
+            ));
+
+            let line_range = self.fmt.line_range(n);
+            for a in &annotations {
+                let (first, last) = self.lines_of(&a.span);
+                if n < first || n > last {
+                    continue;
+                }
+
+                // Only the part of the span that falls on this line, relative to its start
+                let start = a.span.start.max(line_range.start) - line_range.start;
+                let end = a.span.end.min(line_range.end).saturating_sub(line_range.start);
+                let underline = Formatter::get_underline(text, start..end.max(start + 1), a.mark);
+                let message = match a.message {
+                    Some(m) if n == last => format!(" {}", m),
+                    _ => String::new(),
+                };
+                out.push_str(&format!(
+                    "{} {} {}\n",
+                    pad,
+                    bar,
+                    paint(&format!("{}{}", underline, message), a.style, color)
+                ));
+            }
+        }
+
+        if !err.help.is_empty() || !err.suggestions.is_empty() {
+            out.push_str(&format!("{} {}\n", pad, bar));
+        }
+        for h in &err.help {
+            out.push_str(&format!("{} {} {}\n", pad, paint("= help:", BOLD, color), h));
+        }
+        for s in &err.suggestions {
+            out.push_str(&format!("{} {} {}\n", pad, paint("= help:", BOLD, color), s.message));
+        }
+        if !err.trace.is_empty() {
+            out.push_str(&self.render_trace(&err.trace, color));
+        }
+        out.push('\n');
+        out
+    }
+
+    /// Renders the calls that led up to a runtime error, outermost first, with each call site's
+    /// source line underneath. Runs of the same call (deep recursion) are shown once
+    fn render_trace(&self, frames: &[Frame], color: bool) -> String {
+        let mut out = format!("{}\n", paint("traceback (most recent call last):", BOLD, color));
+        let mut caller = "<main>";
+
+        for (frame, repeats) in trace::collapse(frames) {
+            let (line, _) = self.fmt.position(frame.call_site.start);
+            let (path, shown, col) = self.fmt.location(frame.call_site.start);
+            let (_, text) = self.fmt.get_relevant_lines(vec![line])[0];
+            let line_range = self.fmt.line_range(line);
+            let pad = " ".repeat(shown.to_string().len());
+            let bar = paint("|", GUTTER, color);
+
+            let start = frame.call_site.start - line_range.start;
+            let end = frame.call_site.end.min(line_range.end).saturating_sub(line_range.start);
+            let underline = Formatter::get_underline(text, start..end.max(start + 1), '-');
+
+            out.push_str(&format!(
+                "  {} {}:{}:{} in {} ({})\n",
+                paint("-->", GUTTER, color),
+                path,
+                shown,
+                col,
+                caller,
+                frame.module
+            ));
+            out.push_str(&format!(
+                "  {} {} {}\n",
+                paint(&shown.to_string(), GUTTER, color),
+                bar,
+                text
+            ));
+            out.push_str(&format!(
+                "  {} {} {}\n",
+                pad,
+                bar,
+                paint(&format!("{} call to {}", underline, frame.function), GUTTER, color)
+            ));
+            if repeats > 0 {
+                out.push_str(&format!(
+                    "  [previous call repeated {} more time{}]\n",
+                    repeats,
+                    if repeats == 1 { "" } else { "s" }
+                ));
+            }
+            caller = &frame.function;
+        }
+        out
     }
+
+    /// First and last line numbers covered by a span
+    fn lines_of(&self, span: &Range<usize>) -> (usize, usize) {
+        let (first, _) = self.fmt.position(span.start);
+        let (last, _) = self.fmt.position(span.end.saturating_sub(1).max(span.start));
+        (first, last)
+    }
+}
+
+/// Whether diagnostics written to stderr should be colored
+/// Only when it's a terminal, and never when `NO_COLOR` is set
+pub fn use_color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+// ANSI styles used by the renderer
+const BOLD: &str = "1";
+const CLASS_ERROR: &str = "1;31";
+const CLASS_WARNING: &str = "1;33";
+const GUTTER: &str = "1;34";
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Something to underline in the rendered source
+struct Annotation<'e> {
+    span: Range<usize>,
+    message: Option<&'e str>,
+    mark: char,
+    style: &'static str,
 }
 
 #[derive(Debug)]
@@ -43,9 +359,394 @@ pub enum ErrorClass {
     Error,
 }
 
-#[derive(Debug)]
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorClass::Warning => write!(f, "warning"),
+            ErrorClass::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Everything that can go wrong, grouped by the phase that finds it
+/// Each variant has a stable code, see `errors::explain` for the long-form descriptions
+#[derive(Debug, Clone)]
 pub enum ErrorKind {
+    // Lexing and parsing (SK00xx)
     ParseError(String),
+    UnterminatedString,
+    UnexpectedCharacter(char),
+    InvalidNumber { literal: String, float: bool },
+    UnclosedDelimiter { close: char, context: &'static str },
+    MissingSemicolon { before: String },
+    /// `expected` describes what could have gone there, if anything in particular
+    UnexpectedToken { found: String, expected: Option<&'static str> },
+    /// An operator without a value on the side `before` says
+    MissingOperand { operator: String, before: bool },
+    /// `name = value;` on a name that already exists
+    AssignmentToExisting { name: String },
+
+    // Name resolution (SK01xx)
+    UndefinedName { name: String },
+    Redefinition { name: String },
+    /// Something other than a name after `var` or `const`
+    InvalidDeclaration { keyword: &'static str },
+
+    // Types (SK02xx)
+    TypeMismatch { expected: String, found: String },
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+        /// The most it takes, if there's a limit
+        at_most: Option<usize>,
+    },
+    NotCallable { found: String },
+    UnhashableKey { found: String },
+    UnknownMethod { typ: String, method: String },
+    UnknownParameter { function: String, name: String },
+
+    // Constants (SK03xx)
+    ConstantMutation { name: String },
+
+    // Arithmetic (SK04xx)
+    DivisionByZero,
+    Overflow { operation: &'static str },
+    NonFinite { operation: String, value: String },
+    LengthMismatch { left: usize, right: usize },
+    /// `location` is where in a series or table the missing value is
+    MissingResult { operation: String, location: Option<String> },
+
+    // I/O (SK05xx)
+    Io { path: String, reason: String },
+    InvalidCsv { path: String, line: usize, column: Option<usize>, message: String },
+    /// `path` is `None` when the JSON came from a string rather than a file
+    InvalidJson { path: Option<String>, offset: usize, message: String },
+
+    // Imports (SK06xx)
+    UnknownModule { module: String },
+    UnknownImport { module: String, symbol: String },
+
+    // Host functions (SK07xx)
+    HostError { function: String, message: String },
+
+    // Built-in functions (SK08xx)
+    InvalidArgument { function: String, message: String },
+    AssertionFailed { message: Option<String> },
+    Panic { message: String },
+    InvalidPattern { message: String },
+
+    // Evaluation (SK09xx)
+    RecursionLimit { limit: usize },
+    Unsupported { feature: &'static str },
+    ReturnOutsideFunction,
+    UnknownDirective { name: String },
+    InvalidDirective { name: String, message: String },
+    MissingKey { key: String },
+    IndexOutOfRange { index: i32, len: usize },
+    MissingColumn { column: String },
+    MissingValue { column: String, row: usize },
+    JoinCardinality { expected: &'static str, side: &'static str, key: String },
+    DuplicateEntry { index: String, column: String },
+}
+
+impl ErrorKind {
+    /// The code printed alongside the error class
+    /// These never change once assigned, so they can be searched for and explained
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::ParseError(_) => "SK0001",
+            ErrorKind::UnterminatedString => "SK0002",
+            ErrorKind::UnexpectedCharacter(_) => "SK0003",
+            ErrorKind::InvalidNumber { .. } => "SK0004",
+            ErrorKind::UnclosedDelimiter { .. } => "SK0005",
+            ErrorKind::MissingSemicolon { .. } => "SK0006",
+            ErrorKind::UnexpectedToken { .. } => "SK0007",
+            ErrorKind::MissingOperand { .. } => "SK0008",
+            ErrorKind::AssignmentToExisting { .. } => "SK0009",
+            ErrorKind::UndefinedName { .. } => "SK0100",
+            ErrorKind::Redefinition { .. } => "SK0101",
+            ErrorKind::InvalidDeclaration { .. } => "SK0102",
+            ErrorKind::TypeMismatch { .. } => "SK0200",
+            ErrorKind::ArgumentCount { .. } => "SK0201",
+            ErrorKind::NotCallable { .. } => "SK0202",
+            ErrorKind::UnhashableKey { .. } => "SK0203",
+            ErrorKind::UnknownMethod { .. } => "SK0204",
+            ErrorKind::UnknownParameter { .. } => "SK0205",
+            ErrorKind::ConstantMutation { .. } => "SK0300",
+            ErrorKind::DivisionByZero => "SK0400",
+            ErrorKind::Overflow { .. } => "SK0401",
+            ErrorKind::NonFinite { .. } => "SK0402",
+            ErrorKind::LengthMismatch { .. } => "SK0403",
+            ErrorKind::MissingResult { .. } => "SK0404",
+            ErrorKind::Io { .. } => "SK0500",
+            ErrorKind::InvalidCsv { .. } => "SK0501",
+            ErrorKind::InvalidJson { .. } => "SK0502",
+            ErrorKind::UnknownModule { .. } => "SK0600",
+            ErrorKind::UnknownImport { .. } => "SK0601",
+            ErrorKind::HostError { .. } => "SK0700",
+            ErrorKind::InvalidArgument { .. } => "SK0800",
+            ErrorKind::AssertionFailed { .. } => "SK0801",
+            ErrorKind::Panic { .. } => "SK0802",
+            ErrorKind::InvalidPattern { .. } => "SK0803",
+            ErrorKind::RecursionLimit { .. } => "SK0900",
+            ErrorKind::Unsupported { .. } => "SK0901",
+            ErrorKind::ReturnOutsideFunction => "SK0902",
+            ErrorKind::UnknownDirective { .. } => "SK0903",
+            ErrorKind::InvalidDirective { .. } => "SK0904",
+            ErrorKind::MissingKey { .. } => "SK0905",
+            ErrorKind::IndexOutOfRange { .. } => "SK0906",
+            ErrorKind::MissingColumn { .. } => "SK0907",
+            ErrorKind::MissingValue { .. } => "SK0908",
+            ErrorKind::JoinCardinality { .. } => "SK0909",
+            ErrorKind::DuplicateEntry { .. } => "SK0910",
+        }
+    }
+
+    /// The variant name, for machine-readable output
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::ParseError(_) => "ParseError",
+            ErrorKind::UnterminatedString => "UnterminatedString",
+            ErrorKind::UnexpectedCharacter(_) => "UnexpectedCharacter",
+            ErrorKind::InvalidNumber { .. } => "InvalidNumber",
+            ErrorKind::UnclosedDelimiter { .. } => "UnclosedDelimiter",
+            ErrorKind::MissingSemicolon { .. } => "MissingSemicolon",
+            ErrorKind::UnexpectedToken { .. } => "UnexpectedToken",
+            ErrorKind::MissingOperand { .. } => "MissingOperand",
+            ErrorKind::AssignmentToExisting { .. } => "AssignmentToExisting",
+            ErrorKind::UndefinedName { .. } => "UndefinedName",
+            ErrorKind::Redefinition { .. } => "Redefinition",
+            ErrorKind::InvalidDeclaration { .. } => "InvalidDeclaration",
+            ErrorKind::TypeMismatch { .. } => "TypeMismatch",
+            ErrorKind::ArgumentCount { .. } => "ArgumentCount",
+            ErrorKind::NotCallable { .. } => "NotCallable",
+            ErrorKind::UnhashableKey { .. } => "UnhashableKey",
+            ErrorKind::UnknownMethod { .. } => "UnknownMethod",
+            ErrorKind::UnknownParameter { .. } => "UnknownParameter",
+            ErrorKind::ConstantMutation { .. } => "ConstantMutation",
+            ErrorKind::DivisionByZero => "DivisionByZero",
+            ErrorKind::Overflow { .. } => "Overflow",
+            ErrorKind::NonFinite { .. } => "NonFinite",
+            ErrorKind::LengthMismatch { .. } => "LengthMismatch",
+            ErrorKind::MissingResult { .. } => "MissingResult",
+            ErrorKind::Io { .. } => "Io",
+            ErrorKind::InvalidCsv { .. } => "InvalidCsv",
+            ErrorKind::InvalidJson { .. } => "InvalidJson",
+            ErrorKind::UnknownModule { .. } => "UnknownModule",
+            ErrorKind::UnknownImport { .. } => "UnknownImport",
+            ErrorKind::HostError { .. } => "HostError",
+            ErrorKind::InvalidArgument { .. } => "InvalidArgument",
+            ErrorKind::AssertionFailed { .. } => "AssertionFailed",
+            ErrorKind::Panic { .. } => "Panic",
+            ErrorKind::InvalidPattern { .. } => "InvalidPattern",
+            ErrorKind::RecursionLimit { .. } => "RecursionLimit",
+            ErrorKind::Unsupported { .. } => "Unsupported",
+            ErrorKind::ReturnOutsideFunction => "ReturnOutsideFunction",
+            ErrorKind::UnknownDirective { .. } => "UnknownDirective",
+            ErrorKind::InvalidDirective { .. } => "InvalidDirective",
+            ErrorKind::MissingKey { .. } => "MissingKey",
+            ErrorKind::IndexOutOfRange { .. } => "IndexOutOfRange",
+            ErrorKind::MissingColumn { .. } => "MissingColumn",
+            ErrorKind::MissingValue { .. } => "MissingValue",
+            ErrorKind::JoinCardinality { .. } => "JoinCardinality",
+            ErrorKind::DuplicateEntry { .. } => "DuplicateEntry",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::ParseError(msg) => write!(f, "{}", msg),
+            ErrorKind::UnterminatedString => write!(f, "Unterminated string literal"),
+            ErrorKind::UnexpectedCharacter(c) => write!(f, "Unexpected character '{}'", c),
+            ErrorKind::InvalidNumber { literal, float } => write!(
+                f,
+                "Invalid {} literal '{}'",
+                if *float { "float" } else { "integer" },
+                literal
+            ),
+            ErrorKind::UnclosedDelimiter { close, context } => {
+                write!(f, "Expected '{}' to close {}", close, context)
+            }
+            ErrorKind::MissingSemicolon { before } => write!(f, "Expected ';' before {}", before),
+            ErrorKind::UnexpectedToken {
+                found,
+                expected: None,
+            } => write!(f, "Unexpected {}", found),
+            ErrorKind::UnexpectedToken {
+                found,
+                expected: Some(expected),
+            } => write!(f, "Expected {}, found {}", expected, found),
+            ErrorKind::MissingOperand { operator, before } => write!(
+                f,
+                "Expected an expression {} {}",
+                if *before { "before" } else { "after" },
+                operator
+            ),
+            ErrorKind::AssignmentToExisting { name } => write!(
+                f,
+                "Existing variables are mutated with '->', try '{} -> ...'",
+                name
+            ),
+            ErrorKind::UndefinedName { name } => write!(f, "Cannot find '{}' in this scope", name),
+            ErrorKind::Redefinition { name } => {
+                write!(f, "'{}' is already defined in this scope", name)
+            }
+            ErrorKind::InvalidDeclaration { keyword } => {
+                write!(f, "Expected a name to declare after '{}'", keyword)
+            }
+            ErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "Mismatched types, expected {} but found {}", expected, found)
+            }
+            ErrorKind::ArgumentCount {
+                name,
+                expected,
+                found,
+                at_most,
+            } => {
+                let count = match at_most {
+                    None => format!("at least {}", expected),
+                    Some(most) if most != expected => format!("{} to {}", expected, most),
+                    Some(_) => expected.to_string(),
+                };
+                write!(
+                    f,
+                    "'{}' takes {} argument{} but {} {} given",
+                    name,
+                    count,
+                    if at_most.unwrap_or(*expected) == 1 { "" } else { "s" },
+                    found,
+                    if *found == 1 { "was" } else { "were" }
+                )
+            }
+            ErrorKind::NotCallable { found } => write!(f, "Cannot call a value of type {}", found),
+            ErrorKind::UnhashableKey { found } => {
+                write!(f, "Cannot use a value of type {} as a map key", found)
+            }
+            ErrorKind::UnknownMethod { typ, method } => {
+                write!(f, "A {} has no method named '{}'", typ, method)
+            }
+            ErrorKind::UnknownParameter { function, name } => {
+                write!(f, "'{}' has no parameter named '{}'", function, name)
+            }
+            ErrorKind::ConstantMutation { name } => {
+                write!(f, "Cannot mutate '{}' because it is a constant", name)
+            }
+            ErrorKind::DivisionByZero => write!(f, "Attempted to divide by zero"),
+            ErrorKind::Overflow { operation } => {
+                write!(f, "Arithmetic overflow while trying to {}", operation)
+            }
+            ErrorKind::NonFinite { operation, value } => {
+                write!(f, "Trying to {} gave {}, which isn't a finite number", operation, value)
+            }
+            ErrorKind::LengthMismatch { left, right } => write!(
+                f,
+                "Series of different lengths, {} on the left and {} on the right",
+                left, right
+            ),
+            ErrorKind::MissingResult {
+                operation,
+                location: None,
+            } => write!(f, "Trying to {} gave a missing value", operation),
+            ErrorKind::MissingResult {
+                operation,
+                location: Some(location),
+            } => write!(
+                f,
+                "Trying to {} gave a missing value in {}",
+                operation, location
+            ),
+            ErrorKind::Io { path, reason } => write!(f, "Couldn't access '{}': {}", path, reason),
+            ErrorKind::InvalidCsv {
+                path,
+                line,
+                column: Some(column),
+                message,
+            } => write!(f, "Invalid CSV in '{}' at line {}, column {}: {}", path, line, column, message),
+            ErrorKind::InvalidCsv {
+                path,
+                line,
+                column: None,
+                message,
+            } => write!(f, "Invalid CSV in '{}' at line {}: {}", path, line, message),
+            ErrorKind::InvalidJson {
+                path: Some(path),
+                offset,
+                message,
+            } => write!(f, "Invalid JSON in '{}' at byte {}: {}", path, offset, message),
+            ErrorKind::InvalidJson {
+                path: None,
+                offset,
+                message,
+            } => write!(f, "Invalid JSON at byte {}: {}", offset, message),
+            ErrorKind::UnknownModule { module } => write!(f, "No module named '{}'", module),
+            ErrorKind::UnknownImport { module, symbol } => {
+                write!(f, "Module '{}' has nothing named '{}'", module, symbol)
+            }
+            ErrorKind::HostError { function, message } => write!(f, "'{}' failed: {}", function, message),
+            ErrorKind::InvalidArgument { function, message } => {
+                write!(f, "Invalid argument to '{}': {}", function, message)
+            }
+            ErrorKind::AssertionFailed { message: None } => write!(f, "Assertion failed"),
+            ErrorKind::AssertionFailed {
+                message: Some(message),
+            } => write!(f, "Assertion failed: {}", message),
+            ErrorKind::Panic { message } => write!(f, "Panicked: {}", message),
+            ErrorKind::InvalidPattern { message } => {
+                write!(f, "Invalid regular expression: {}", message)
+            }
+            ErrorKind::RecursionLimit { limit } => {
+                write!(f, "Maximum call depth of {} exceeded", limit)
+            }
+            ErrorKind::Unsupported { feature } => write!(f, "{} aren't supported yet", feature),
+            ErrorKind::ReturnOutsideFunction => write!(f, "'return' outside of a function"),
+            ErrorKind::UnknownDirective { name } => write!(f, "No directive named '{}'", name),
+            ErrorKind::InvalidDirective { name, message } => {
+                write!(f, "Invalid '{}' directive: {}", name, message)
+            }
+            ErrorKind::MissingKey { key } => write!(f, "No entry for the key {}", key),
+            ErrorKind::IndexOutOfRange { index, len } => write!(
+                f,
+                "Index {} is out of range for a list of {} items",
+                index, len
+            ),
+            ErrorKind::MissingColumn { column } => write!(f, "No column named '{}'", column),
+            ErrorKind::MissingValue { column, row } => {
+                write!(f, "Missing value in column '{}' at row {}", column, row)
+            }
+            ErrorKind::JoinCardinality {
+                expected,
+                side,
+                key,
+            } => write!(
+                f,
+                "Expected a {} join, but the key {} is in more than one row of the {} table",
+                expected, key, side
+            ),
+            ErrorKind::DuplicateEntry { index, column } => write!(
+                f,
+                "More than one value for the index {} in the column '{}'",
+                index, column
+            ),
+        }
+    }
+}
+
+/// A secondary span with something to say about it
+#[derive(Debug)]
+pub struct Label {
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// A replacement for part of the source that would fix the error
+#[derive(Debug)]
+pub struct Suggestion {
+    pub span: Range<usize>,
+    pub replacement: String,
+    pub message: String,
 }
 
 #[derive(Debug)]
@@ -53,4 +754,222 @@ pub struct SkError {
     pub class: ErrorClass,
     pub kind: ErrorKind,
     pub span: Range<usize>,
+    /// Shown beside the primary underline
+    pub primary: Option<String>,
+    pub labels: Vec<Label>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+    /// The calls being evaluated when a runtime error was raised, outermost first
+    pub trace: Vec<Frame>,
+}
+
+impl SkError {
+    /// Start and end are inclusive such that the range is start..=end
+    pub fn new(class: ErrorClass, kind: ErrorKind, start: usize, end: usize) -> Self {
+        Self {
+            class,
+            kind,
+            span: start..end + 1,
+            primary: None,
+            labels: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn primary(&mut self, message: impl Into<String>) -> &mut Self {
+        self.primary = Some(message.into());
+        self
+    }
+
+    /// Points at another part of the source, start and end are inclusive like `Errors::new`
+    pub fn label(&mut self, start: usize, end: usize, message: impl Into<String>) -> &mut Self {
+        self.labels.push(Label {
+            span: start..end + 1,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn help(&mut self, message: impl Into<String>) -> &mut Self {
+        self.help.push(message.into());
+        self
+    }
+
+    /// Suggests replacing `start..=end` with `replacement`
+    pub fn suggest(
+        &mut self,
+        start: usize,
+        end: usize,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+    ) -> &mut Self {
+        self.suggestions.push(Suggestion {
+            span: start..end + 1,
+            replacement: replacement.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Suggests inserting `text` just before the byte at `at`
+    pub fn suggest_insert(
+        &mut self,
+        at: usize,
+        text: impl Into<String>,
+        message: impl Into<String>,
+    ) -> &mut Self {
+        self.suggestions.push(Suggestion {
+            span: at..at,
+            replacement: text.into(),
+            message: message.into(),
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, build: impl FnOnce(&mut Errors), color: bool) -> String {
+        let source = source.to_string();
+        let formatter = Formatter::initialize("main.sk", &source);
+        let mut errors = Errors::initialize(&formatter);
+        build(&mut errors);
+        let err = &errors.errors()[0];
+        errors.render(err, color)
+    }
+
+    fn parse_error(msg: &str) -> ErrorKind {
+        ErrorKind::ParseError(msg.to_string())
+    }
+
+    #[test]
+    fn single_line() {
+        let out = render(
+            "var a = 1;\nvar b = a +;\n",
+            |errors| {
+                errors.new(
+                    ErrorClass::Error,
+                    parse_error("Expected an expression after '+'"),
+                    21,
+                    21,
+                );
+            },
+            false,
+        );
+        assert_eq!(
+            out,
+            "error[SK0001]: Expected an expression after '+'\n \
+             --> main.sk:2:11\n  \
+             |\n\
+             2 | var b = a +;\n  \
+             |           ^\n\n"
+        );
+    }
+
+    #[test]
+    fn labels_and_help() {
+        let out = render(
+            "var a = 1;\nvar a = 2;\n",
+            |errors| {
+                errors
+                    .new(ErrorClass::Warning, parse_error("Shadowed"), 15, 15)
+                    .primary("declared again here")
+                    .label(4, 4, "first declared here")
+                    .help("rename one of them");
+            },
+            false,
+        );
+        assert_eq!(
+            out,
+            "warning[SK0001]: Shadowed\n \
+             --> main.sk:2:5\n  \
+             |\n\
+             1 | var a = 1;\n  \
+             |     - first declared here\n\
+             2 | var a = 2;\n  \
+             |     ^ declared again here\n  \
+             |\n  \
+             = help: rename one of them\n\n"
+        );
+    }
+
+    #[test]
+    fn distant_lines_are_elided() {
+        let source = "var a = 1;\n\n\n\nvar b = a;\n";
+        let out = render(
+            source,
+            |errors| {
+                errors
+                    .new(ErrorClass::Error, parse_error("Here"), 19, 19)
+                    .label(4, 4, "there");
+            },
+            false,
+        );
+        assert!(out.contains("1 | var a = 1;\n"), "{}", out);
+        assert!(out.contains("...\n5 | var b = a;\n"), "{}", out);
+    }
+
+    #[test]
+    fn color() {
+        let out = render(
+            "$ 1",
+            |errors| {
+                errors.new(ErrorClass::Error, parse_error("Oops"), 2, 2);
+            },
+            true,
+        );
+        assert!(
+            out.starts_with("\x1b[1;31merror[SK0001]\x1b[0m\x1b[1m: Oops\x1b[0m\n"),
+            "{:?}",
+            out
+        );
+        assert!(out.contains("\x1b[1;31m  ^\x1b[0m"), "{:?}", out);
+    }
+
+    #[test]
+    fn has_errors_ignores_warnings() {
+        let source = String::from("$ 1;");
+        let formatter = Formatter::initialize("main.sk", &source);
+        let mut errors = Errors::initialize(&formatter);
+        errors.new(ErrorClass::Warning, parse_error("Careful"), 0, 0);
+        assert!(!errors.has_errors());
+        errors.new(ErrorClass::Error, parse_error("Broken"), 0, 0);
+        assert!(errors.has_errors());
+    }
+
+    #[test]
+    fn traceback() {
+        let source = String::from("def f(n: int): int { return f(n); }\n$ f(1);\n");
+        let formatter = Formatter::initialize("main.sk", &source);
+        let errors = Errors::initialize(&formatter);
+        let frame = |function: &str, start: usize, end: usize| Frame {
+            function: function.to_string(),
+            call_site: start..end,
+            module: String::from("main"),
+        };
+        let mut err = SkError::new(
+            ErrorClass::Error,
+            ErrorKind::RecursionLimit { limit: 3 },
+            28,
+            31,
+        );
+        err.trace = vec![frame("f", 38, 42), frame("f", 28, 32), frame("f", 28, 32)];
+        let out = errors.render(&err, false);
+        let trace = &out[out.find("traceback").unwrap()..];
+        assert_eq!(
+            trace,
+            "traceback (most recent call last):\n  \
+             --> main.sk:2:3 in <main> (main)\n  \
+             2 | $ f(1);\n    \
+             |   ---- call to f\n  \
+             --> main.sk:1:29 in f (main)\n  \
+             1 | def f(n: int): int { return f(n); }\n    \
+             |                             ---- call to f\n  \
+             [previous call repeated 1 more time]\n\n"
+        );
+    }
 }
@@ -0,0 +1,617 @@
+/// Long-form descriptions of every error code, printed by `starkey --explain <code>`
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "SK0001",
+        "The parser found something it couldn't make sense of.
+
+Every statement in Starkey ends with a `;` (function, class and `methods` definitions end at their
+closing `}` instead), and each one has a fixed shape:
+
+    var name: type = value;
+    const name = value;
+    name -> value;
+    $ value;
+    from module import a, b;
+
+The message says what the parser expected at the point it gave up. Parsing carries on from the
+next statement, so errors later in the file are still reported.",
+    ),
+    (
+        "SK0002",
+        "A string literal was opened with `\"` but never closed.
+
+    $ \"hello;
+
+Strings can't span past the end of the file. Add the closing quote:
+
+    $ \"hello\";",
+    ),
+    (
+        "SK0003",
+        "The source contains a character that isn't part of any Starkey token.
+
+    var total = 10 @ 2;
+
+Identifiers may contain ASCII letters, digits and `_`. Anything else outside of a string literal
+has to be one of the operators or punctuation the language defines.",
+    ),
+    (
+        "SK0004",
+        "A number literal couldn't be read as a number.
+
+    var x = 1.2.3;
+    var y = 99999999999;
+
+Integers must fit in 32 bits and floats may only contain a single `.`. Underscores can be used
+to group digits, `1_000_000` is the same as `1000000`.",
+    ),
+    (
+        "SK0005",
+        "A `(` or `{` was opened but the list or block it began was never closed.
+
+    add(1, 2;
+
+The error points at the token where the closing delimiter was expected, and a label points back
+at the one that was left open.",
+    ),
+    (
+        "SK0006",
+        "A statement wasn't terminated before the next one began.
+
+    var x = 10
+    var y = 20;
+
+Add a `;` at the end of the first statement.",
+    ),
+    (
+        "SK0007",
+        "The parser found a token that can't go where it was written.
+
+    var x = 1 );
+    methods Point { var y = 2; }
+
+The message says what was expected there when only one thing could have been. An unmatched
+closing delimiter usually means one was left out earlier, or there's one too many.",
+    ),
+    (
+        "SK0008",
+        "An operator is missing the value on one of its sides.
+
+    $ 2 * ;
+    $ max(1, * 2);
+
+Every operator but `-` needs a value on both sides. A `-` with nothing before it negates what
+comes after, so `max(1, -2)` passes -2. The `.` of a qualified name needs a value before it too.",
+    ),
+    (
+        "SK0009",
+        "A variable that already exists was given a new value with `=`.
+
+    var total = 0;
+    total = 10;
+
+`=` only goes in declarations. Existing variables are changed with `->`:
+
+    total -> 10;",
+    ),
+    (
+        "SK0100",
+        "A name was used that hasn't been defined.
+
+    $ totl;
+
+Variables, constants and functions have to be declared before they're used, and names defined
+inside a function body aren't visible outside of it. Check for typos, or for a missing import.",
+    ),
+    (
+        "SK0101",
+        "A name was declared twice in the same scope.
+
+    var x = 1;
+    var x = 2;
+
+Use `x -> 2;` to change the value of an existing variable instead of declaring it again.",
+    ),
+    (
+        "SK0102",
+        "A `var` or `const` statement declared something that isn't a name.
+
+    var x.y = 1;
+    var 2 = 1;
+
+Only a plain name can be declared. To change a value that already exists, use `name -> value;`.",
+    ),
+    (
+        "SK0200",
+        "A value didn't have the type that was required where it was used.
+
+    var x: int = \"ten\";
+    $ 1 + \"2\";
+
+Starkey never converts between types on its own. Convert explicitly with `int`, `float` or `str`,
+or fix the type annotation.",
+    ),
+    (
+        "SK0201",
+        "A function was called with the wrong number of arguments.
+
+    def add(a: int, b: int): int { return a + b; }
+    add(1);
+
+Every parameter has to be given a value.",
+    ),
+    (
+        "SK0202",
+        "Something that isn't a function was called.
+
+    var x = 10;
+    x(1);
+
+Only functions, methods and built-ins can be called.",
+    ),
+    (
+        "SK0203",
+        "A value that can't be a map key was used as one.
+
+    const m = { range(3): 1 };
+
+Keys are compared by value, so only ints, strings and bools can be keys. Floats are left out
+because values that print the same can differ, and lists, maps and tables because they're
+containers.",
+    ),
+    (
+        "SK0204",
+        "A method was called that the value's type doesn't have.
+
+    const names = { \"a\": 1 };
+    $ names.select(\"a\");
+
+Methods are looked up by the type of the value before the dot. Tables have the verbs like
+`select`, `filter` and `sort_by`, and maps have `keys`, `values`, `items` and `has`.",
+    ),
+    (
+        "SK0205",
+        "An argument was passed by a name that none of the function's parameters have.
+
+    def area(width: int, height: int): int { return width * height; }
+    $ area(width: 2, hieght: 3);
+
+Named arguments come after the positional ones and use the names of the parameters. Built-in
+functions that take their arguments by name list them in their documentation; the rest only take
+their arguments in order.",
+    ),
+    (
+        "SK0300",
+        "A constant was mutated.
+
+    const rate = 0.25;
+    rate -> 0.3;
+
+Values declared with `const` can never change. Declare it with `var` if it needs to.",
+    ),
+    (
+        "SK0400",
+        "A number was divided by zero, with `/` or `%`.
+
+    $ 10 / 0;
+
+Integer division by zero has no answer, so rather than producing a nonsense value the program
+stops. Check the divisor before dividing.",
+    ),
+    (
+        "SK0401",
+        "An integer operation produced a result that doesn't fit in an integer.
+
+    $ 2147483647 + 1;
+
+Integers are 32 bits and never silently wrap around. Use floats for values this large.",
+    ),
+    (
+        "SK0402",
+        "A float operation produced NaN or an infinity while `directive nonfinite(\"error\");` was
+in effect.
+
+    directive nonfinite(\"error\");
+    from std.math import sqrt;
+    $ sqrt(0.0 - 1.0);
+
+By default these values carry on through whatever uses them. Under the directive the operation
+that first produced one is reported instead, which is usually where the problem is.",
+    ),
+    (
+        "SK0403",
+        "An operator was given two series with different numbers of values.
+
+    from std.table import series;
+    $ series(range(3)) + series(range(4));
+
+Operators pair up the values of two series by position, so both need the same length. A single
+value on either side is applied to every value of the series instead.",
+    ),
+    (
+        "SK0404",
+        "An operation produced a missing value, or a series or table holding one, while
+`directive missing(\"error\");` was in effect.
+
+    directive missing(\"error\");
+    from std.io import read_csv;
+    const sales = read_csv(\"sales.csv\");
+
+By default missing values carry on through whatever uses them: arithmetic with one gives another
+of the same type, and so does comparing one, since nothing is known about what it would have
+been. Under the directive the operation that first produced one is reported instead, so a strict
+script can rely on every value being there. The directive only applies to what runs after it, so
+missing values that are expected can be dealt with first, using `fill_na`, `drop_na` or
+`coalesce`.",
+    ),
+    (
+        "SK0500",
+        "A file couldn't be read or written.
+
+The message includes the path and the reason the operating system gave, for example that the
+file doesn't exist or that permission was denied.",
+    ),
+    (
+        "SK0501",
+        "A CSV file couldn't be read as a table.
+
+    from std.io import read_csv_strict;
+    from std.table import schema;
+    const people = read_csv_strict(\"people.csv\", schema(template));
+
+Lines and columns count from 1. Without a schema the usual causes are a quote that's never
+closed, or a row with a different number of fields than the header. With one, every value that
+doesn't have its column's type is reported, the first as the error and the rest underneath it.",
+    ),
+    (
+        "SK0502",
+        "Some text couldn't be read as JSON.
+
+    from std.json import read_ndjson;
+    const logs = read_ndjson(\"service.ndjson\");
+
+Byte offsets count from 0, from the start of the file or string. Besides malformed JSON, this
+covers numbers too big for an int or a float, and in strict mode an object with the same key
+twice. For NDJSON, every line has to be an object whose values are null, bools, numbers or
+strings.",
+    ),
+    (
+        "SK0600",
+        "An import named a module that doesn't exist.
+
+    from std.maths import sqrt;
+
+Check the module name, the standard library lives under `std`.",
+    ),
+    (
+        "SK0601",
+        "An import asked a module for something it doesn't define.
+
+    from std.math import squareroot;
+
+Check the spelling of the imported name against the module's contents.",
+    ),
+    (
+        "SK0700",
+        "A function provided by the program running Starkey reported an error.
+
+Host functions are written in Rust by whoever embeds Starkey, and they can refuse their arguments
+or fail for reasons of their own. The message is the one the host function gave. They're imported
+from the `host` module like anything else:
+
+    from host import convert;",
+    ),
+    (
+        "SK0800",
+        "A built-in function was given an argument of the right type but an unusable value.
+
+    $ int(\"twelve\");
+    $ range(0, 10, 0);
+
+The message says what was wrong with it. The argument is pointed at rather than the whole call.",
+    ),
+    (
+        "SK0801",
+        "An `assert` was given a condition that was false.
+
+    assert(balanced, \"shares don't add up\");
+
+The message is the one passed to `assert`, if any. The traceback shows where it was called from.",
+    ),
+    (
+        "SK0802",
+        "The program called `panic` to stop itself.
+
+    panic(\"unreachable\");
+
+This is deliberate, the message says why the program gave up.",
+    ),
+    (
+        "SK0803",
+        "A regular expression couldn't be compiled.
+
+    $ r\"(\\d+\";
+
+Pattern literals, written `r\"...\"`, are checked before the program runs and the error points
+into the pattern. Patterns built from strings at runtime, with `regex` or by passing a string to
+a `std.regex` function, are checked when they're used. The message says what was wrong.",
+    ),
+    (
+        "SK0900",
+        "Functions called each other too deeply.
+
+    def forever(n: int): int { return forever(n + 1); }
+
+This almost always means a recursive function never reaches its base case. The traceback shows
+the chain of calls, with repeats of the same call collapsed into a single line.",
+    ),
+    (
+        "SK0901",
+        "The program used a feature that parses, but that the evaluator can't run yet.
+
+The message names the feature. Everything else in the program is checked as usual.",
+    ),
+    (
+        "SK0902",
+        "A `return` statement was used outside of a function body.
+
+    return 10;
+
+There's nothing to return to at the top level of a script.",
+    ),
+    (
+        "SK0903",
+        "A `directive` statement named a directive that doesn't exist.
+
+    directive nonfinit(\"error\");
+
+The directives are:
+
+    directive nonfinite(\"propagate\" | \"error\");   how NaN and infinite floats are treated
+    directive missing(\"propagate\" | \"error\");     how missing values are treated
+    directive seed(42);                           the seed std.random draws from",
+    ),
+    (
+        "SK0904",
+        "A directive was given arguments it doesn't accept.
+
+    directive nonfinite(\"ignore\");
+
+Directive arguments have to be literals, so that they can be checked before the program runs.
+The message lists what the directive accepts.",
+    ),
+    (
+        "SK0905",
+        "A map was indexed with a key it doesn't have.
+
+    const ages = { \"ada\": 36 };
+    $ ages[\"grace\"];
+
+Use `has` from `std.map` to check for a key first. Keys are matched by type as well as value,
+so the int key `1` and the string key `\"1\"` are different entries.",
+    ),
+    (
+        "SK0906",
+        "A list was indexed past its end, or with a negative index.
+
+    const xs = range(3);
+    $ xs[3];
+
+Indexes count from 0, so the last item of a list of `n` items is at `n - 1`.",
+    ),
+    (
+        "SK0907",
+        "A table was asked for a column it doesn't have, by indexing or by one of its methods.
+
+    from std.io import read_csv;
+    const sales = read_csv(\"sales.csv\");
+    $ sales.select(\"reveune\");
+
+Column names are matched exactly, including case. When there's a column with a similar name,
+the error suggests it. `columns` from `std.table` lists every column a table has.",
+    ),
+    (
+        "SK0908",
+        "A column had a missing value somewhere missing values were asked to be an error.
+
+    const totals = sales.group_by(\"region\").agg({ \"total\": \"sum(revenue)\" }, \"error\");
+
+Fill the missing values in first, or pick a policy that handles them: `\"skip\"` leaves them
+out and `\"propagate\"` makes the result for their group missing too.",
+    ),
+    (
+        "SK0909",
+        "A join was asked to check how many rows each key matches, and a key that should have been
+unique on one side wasn't.
+
+    const orders = read_csv(\"orders.csv\");
+    const customers = read_csv(\"customers.csv\");
+    $ join(orders, customers, on: \"customer_id\", validate: \"many_to_one\");
+
+`many_to_one` means each key is in at most one row of the right table, `one_to_many` the same
+for the left table, and `one_to_one` both. A duplicate key would otherwise multiply rows
+silently, so deduplicate the table with `distinct` first if the duplicates are expected.",
+    ),
+    (
+        "SK0910",
+        "A pivot found more than one row with the same index and column values, so there's no single
+value to put in that cell.
+
+    const sales = read_csv(\"sales.csv\");
+    $ sales.pivot(\"region\", \"month\", \"revenue\");
+
+Pass `agg` to combine the values, like `agg: \"sum\"` or `agg: \"mean\"`, or a function that's given
+each cell's rows as a table. Otherwise, add the column that tells the rows apart to the index.
+`unstack` uses every other column as the index, so it can only fail this way on rows that differ
+only in their values; use `pivot` with `agg` for those.",
+    ),
+];
+
+/// Returns the long-form description of an error code
+/// The code may be given with or without the `SK` prefix and in any case
+pub fn explain(code: &str) -> Option<&'static str> {
+    let code = code.trim().to_ascii_uppercase();
+    let code = if code.starts_with("SK") {
+        code
+    } else {
+        format!("SK{}", code)
+    };
+
+    EXPLANATIONS
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::error::ErrorKind;
+
+    #[test]
+    fn lookup() {
+        let text = explain("SK0002").unwrap();
+        assert!(text.starts_with("A string literal was opened"));
+        assert_eq!(explain("sk0002"), Some(text));
+        assert_eq!(explain("0002"), Some(text));
+        assert_eq!(explain(" SK0002\n"), Some(text));
+        assert_eq!(explain("SK9999"), None);
+        assert_eq!(explain(""), None);
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let mut codes: Vec<_> = EXPLANATIONS.iter().map(|(code, _)| *code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), EXPLANATIONS.len());
+        assert!(codes.iter().all(|c| c.len() == 6
+            && c.starts_with("SK")
+            && c[2..].bytes().all(|b| b.is_ascii_digit())));
+    }
+
+    #[test]
+    fn every_kind_is_explained() {
+        let string = String::new;
+        let kinds = [
+            ErrorKind::ParseError(string()),
+            ErrorKind::UnterminatedString,
+            ErrorKind::UnexpectedCharacter('@'),
+            ErrorKind::InvalidNumber {
+                literal: string(),
+                float: false,
+            },
+            ErrorKind::UnclosedDelimiter {
+                close: ')',
+                context: "",
+            },
+            ErrorKind::MissingSemicolon { before: string() },
+            ErrorKind::UnexpectedToken {
+                found: string(),
+                expected: None,
+            },
+            ErrorKind::MissingOperand {
+                operator: string(),
+                before: true,
+            },
+            ErrorKind::AssignmentToExisting { name: string() },
+            ErrorKind::UndefinedName { name: string() },
+            ErrorKind::Redefinition { name: string() },
+            ErrorKind::InvalidDeclaration { keyword: "" },
+            ErrorKind::TypeMismatch {
+                expected: string(),
+                found: string(),
+            },
+            ErrorKind::ArgumentCount {
+                name: string(),
+                expected: 0,
+                found: 0,
+                at_most: None,
+            },
+            ErrorKind::NotCallable { found: string() },
+            ErrorKind::UnhashableKey { found: string() },
+            ErrorKind::ConstantMutation { name: string() },
+            ErrorKind::DivisionByZero,
+            ErrorKind::Overflow { operation: "" },
+            ErrorKind::LengthMismatch { left: 0, right: 0 },
+            ErrorKind::NonFinite {
+                operation: string(),
+                value: string(),
+            },
+            ErrorKind::Io {
+                path: string(),
+                reason: string(),
+            },
+            ErrorKind::InvalidCsv {
+                path: string(),
+                line: 0,
+                column: None,
+                message: string(),
+            },
+            ErrorKind::InvalidJson {
+                path: None,
+                offset: 0,
+                message: string(),
+            },
+            ErrorKind::UnknownModule { module: string() },
+            ErrorKind::UnknownImport {
+                module: string(),
+                symbol: string(),
+            },
+            ErrorKind::HostError {
+                function: string(),
+                message: string(),
+            },
+            ErrorKind::UnknownParameter {
+                function: string(),
+                name: string(),
+            },
+            ErrorKind::UnknownMethod {
+                typ: string(),
+                method: string(),
+            },
+            ErrorKind::InvalidArgument {
+                function: string(),
+                message: string(),
+            },
+            ErrorKind::AssertionFailed { message: None },
+            ErrorKind::Panic { message: string() },
+            ErrorKind::InvalidPattern { message: string() },
+            ErrorKind::RecursionLimit { limit: 0 },
+            ErrorKind::Unsupported { feature: "" },
+            ErrorKind::ReturnOutsideFunction,
+            ErrorKind::MissingKey { key: string() },
+            ErrorKind::IndexOutOfRange { index: 0, len: 0 },
+            ErrorKind::MissingColumn { column: string() },
+            ErrorKind::JoinCardinality {
+                expected: "",
+                side: "",
+                key: string(),
+            },
+            ErrorKind::DuplicateEntry {
+                index: string(),
+                column: string(),
+            },
+            ErrorKind::MissingResult {
+                operation: string(),
+                location: None,
+            },
+            ErrorKind::MissingValue {
+                column: string(),
+                row: 0,
+            },
+            ErrorKind::UnknownDirective { name: string() },
+            ErrorKind::InvalidDirective {
+                name: string(),
+                message: string(),
+            },
+        ];
+        for kind in &kinds {
+            assert!(
+                explain(kind.code()).is_some(),
+                "{} isn't explained",
+                kind.name()
+            );
+        }
+    }
+}
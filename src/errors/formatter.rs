@@ -4,67 +4,138 @@ use std::ops::Range;
 pub struct Formatter<'a> {
     source: &'a String,
     lines: Vec<&'a str>,
+    /// Byte offset that each line begins at
+    line_starts: Vec<usize>,
+    /// The files (or REPL entries) the source is made of, as the byte offset each one begins at
+    /// and its name. Positions are reported relative to the segment they fall in
+    segments: Vec<(usize, &'a str)>,
 }
 
 impl<'a> Formatter<'a> {
-    pub fn initialize(source: &'a String) -> Self {
+    pub fn initialize(path: &'a str, source: &'a String) -> Self {
+        Self::with_segments(source, vec![(0, path)])
+    }
+
+    /// Creates a formatter for source made of several named pieces laid end to end
+    /// `segments` must be sorted by offset and the first must begin at 0
+    pub fn with_segments(source: &'a String, segments: Vec<(usize, &'a str)>) -> Self {
         let lines: Vec<&'a str> = source.lines().collect();
-        Self { source, lines }
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            source,
+            lines,
+            line_starts,
+            segments,
+        }
     }
 
-    fn get_relevant_lines(&self, lines: Vec<usize>) -> String {
-        let mut buffer = String::new();
-        lines
-            .iter()
-            .for_each(|x| buffer.push_str((self.lines[*x].to_owned() + "\n").as_str()));
-        return buffer;
+    /// The name of the file a byte index is in
+    pub fn path_at(&self, index: usize) -> &'a str {
+        self.segment(index).1
     }
 
-    pub fn get_line(&self, mut index: usize) -> (String, Range<usize>) {
-        let mut buffer = String::new();
-        let start: usize;
-        let end: usize;
+    /// Returns the file, 1-based line and 1-based column of a byte index, as they should be shown
+    /// to the user
+    pub fn location(&self, index: usize) -> (&'a str, usize, usize) {
+        let (line, col) = self.position(index);
+        (self.path_at(index), self.display_line(line), col)
+    }
 
-        // Get to the beginning of this line
-        while let Some(c) = self.source.get(index.saturating_sub(1)..index) {
-            if index == 0 {
-                break;
-            }
+    /// Converts a line number in the whole source into one relative to the start of its file
+    pub fn display_line(&self, line: usize) -> usize {
+        let start = self.line_starts.get(line - 1).copied().unwrap_or(0);
+        let (offset, _) = self.segment(start);
+        line - self.position(offset).0 + 1
+    }
 
-            match c {
-                "\r" | "\n" => break,
-                _ => index = index.saturating_sub(1),
-            }
-        }
-        start = index;
-
-        // Take until the end
-        while let Some(c) = self.source.get(index..index + 1) {
-            match c {
-                "\r" | "\n" => break,
-                _ => {
-                    buffer.push_str(c);
-                    index += 1;
-                }
-            }
-        }
-        end = index;
+    fn segment(&self, index: usize) -> (usize, &'a str) {
+        let i = self.segments.partition_point(|&(offset, _)| offset <= index);
+        self.segments[i.max(1) - 1]
+    }
 
-        return (buffer, start..end + 1);
+    /// True when there's no source to show, like when the file couldn't be read at all
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty()
     }
 
-    /// Returns a string that underlines the desired range with `^`
-    pub fn get_underline(line: &String, range: Range<usize>) -> String {
+    /// Returns the numbered lines that were asked for, in order and without duplicates
+    /// Line numbers start at 1, anything past the end of the source comes back empty
+    pub fn get_relevant_lines(&self, mut lines: Vec<usize>) -> Vec<(usize, &'a str)> {
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+            .into_iter()
+            .map(|x| (x, self.lines.get(x - 1).copied().unwrap_or("")))
+            .collect()
+    }
+
+    /// Returns the 1-based line and column (counted in chars) of a byte index in the whole source
+    pub fn position(&self, index: usize) -> (usize, usize) {
+        let index = index.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= index);
+        let start = self.line_starts[line - 1];
+        let col = self
+            .source
+            .get(start..index)
+            .map_or(index - start, |s| s.chars().count());
+        (line, col + 1)
+    }
+
+    /// Returns the byte range covered by a 1-based line number, excluding the line break
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line - 1];
+        let text = self.lines.get(line - 1).copied().unwrap_or("");
+        start..start + text.len()
+    }
+
+    /// Returns a string that underlines the desired range of the line with `mark`
+    /// The range is relative to the start of the line, tabs are kept so the marks line up
+    pub fn get_underline(line: &str, range: Range<usize>, mark: char) -> String {
         let mut buffer = String::new();
-        let chars = line.char_indices();
-        for (i, _) in chars {
+        for (i, c) in line.char_indices() {
             if range.contains(&i) {
-                buffer.push('^');
+                buffer.push(mark);
+            } else if i >= range.end {
+                break;
+            } else if c == '\t' {
+                buffer.push('\t');
             } else {
                 buffer.push(' ');
             }
         }
 
-        return buffer;
+        // Spans at the very end of a line (or the file) still deserve a mark
+        if range.start >= line.len() {
+            buffer.push(mark);
+        }
+
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments() {
+        let source = String::from("var a = 1;\nvar b = 2;\n$ a\n+ b;\n");
+        let formatter = Formatter::with_segments(&source, vec![(0, "main.sk"), (22, "<repl:1>")]);
+        assert_eq!(formatter.location(4), ("main.sk", 1, 5));
+        assert_eq!(formatter.location(15), ("main.sk", 2, 5));
+        assert_eq!(formatter.location(24), ("<repl:1>", 1, 3));
+        assert_eq!(formatter.location(28), ("<repl:1>", 2, 3));
+        assert_eq!(formatter.path_at(0), "main.sk");
+        assert_eq!(formatter.path_at(30), "<repl:1>");
+    }
+
+    #[test]
+    fn positions_count_chars() {
+        let source = String::from("$ \"héllo\";\n$ x;");
+        let formatter = Formatter::initialize("main.sk", &source);
+        // 'é' is two bytes but one column
+        assert_eq!(formatter.position(9), (1, 9));
+        assert_eq!(formatter.position(14), (2, 3));
     }
 }
@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+use super::{error::SkError, formatter::Formatter};
+
+/// Serializes an error as a single line of JSON
+/// Byte spans and line/column ranges are both end-exclusive, lines and columns start at 1
+pub fn to_json(err: &SkError, fmt: &Formatter, rendered: &str) -> String {
+    let labels: Vec<String> = err
+        .labels
+        .iter()
+        .map(|l| {
+            format!(
+                "{{\"message\":{},{}}}",
+                string(&l.message),
+                location(&l.span, fmt)
+            )
+        })
+        .collect();
+
+    let suggestions: Vec<String> = err
+        .suggestions
+        .iter()
+        .map(|s| {
+            format!(
+                "{{\"message\":{},\"replacement\":{},{}}}",
+                string(&s.message),
+                string(&s.replacement),
+                location(&s.span, fmt)
+            )
+        })
+        .collect();
+
+    let help: Vec<String> = err.help.iter().map(|h| string(h)).collect();
+
+    let trace: Vec<String> = err
+        .trace
+        .iter()
+        .map(|f| {
+            format!(
+                "{{\"function\":{},\"module\":{},\"file\":{},{}}}",
+                string(&f.function),
+                string(&f.module),
+                string(fmt.path_at(f.call_site.start)),
+                location(&f.call_site, fmt)
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"class\":{},\"kind\":{},\"code\":{},\"message\":{},\"file\":{},{},\"primary\":{},\"labels\":[{}],\"help\":[{}],\"suggestions\":[{}],\"trace\":[{}],\"rendered\":{}}}",
+        string(&err.class.to_string()),
+        string(err.kind.name()),
+        string(err.kind.code()),
+        string(&err.kind.to_string()),
+        string(fmt.path_at(err.span.start)),
+        location(&err.span, fmt),
+        err.primary.as_deref().map_or(String::from("null"), string),
+        labels.join(","),
+        help.join(","),
+        suggestions.join(","),
+        trace.join(","),
+        string(rendered),
+    )
+}
+
+/// The `"span"` and `"range"` members describing where something is
+fn location(span: &Range<usize>, fmt: &Formatter) -> String {
+    let (_, start_line, start_col) = fmt.location(span.start);
+    let (_, end_line, end_col) = fmt.location(span.end);
+    format!(
+        "\"span\":{{\"start\":{},\"end\":{}}},\"range\":{{\"start\":{{\"line\":{},\"column\":{}}},\"end\":{{\"line\":{},\"column\":{}}}}}",
+        span.start, span.end, start_line, start_col, end_line, end_col
+    )
+}
+
+/// Quotes and escapes a string literal
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::error::{ErrorClass, ErrorKind, Errors};
+
+    #[test]
+    fn shape() {
+        let source = String::from("var a = 1\n$ a;\n");
+        let formatter = Formatter::initialize("main.sk", &source);
+        let mut errors = Errors::initialize(&formatter);
+        errors
+            .new(
+                ErrorClass::Error,
+                ErrorKind::ParseError(String::from("Expected ';' before '$'")),
+                10,
+                10,
+            )
+            .label(8, 8, "expected ';' after this")
+            .suggest_insert(9, ";", "add ';' here");
+        let err = &errors.errors()[0];
+        assert_eq!(
+            to_json(err, &formatter, "rendered"),
+            concat!(
+                r#"{"class":"error","kind":"ParseError","code":"SK0001","#,
+                r#""message":"Expected ';' before '$'","file":"main.sk","#,
+                r#""span":{"start":10,"end":11},"#,
+                r#""range":{"start":{"line":2,"column":1},"end":{"line":2,"column":2}},"#,
+                r#""primary":null,"#,
+                r#""labels":[{"message":"expected ';' after this","span":{"start":8,"end":9},"#,
+                r#""range":{"start":{"line":1,"column":9},"end":{"line":1,"column":10}}}],"#,
+                r#""help":[],"#,
+                r#""suggestions":[{"message":"add ';' here","replacement":";","#,
+                r#""span":{"start":9,"end":9},"#,
+                r#""range":{"start":{"line":1,"column":10},"end":{"line":1,"column":10}}}],"#,
+                r#""trace":[],"rendered":"rendered"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(string("plain"), r#""plain""#);
+        assert_eq!(string("a \"b\" \\ c"), r#""a \"b\" \\ c""#);
+        assert_eq!(string("line\nnext\ttab\r"), r#""line\nnext\ttab\r""#);
+        assert_eq!(string("\u{1}é"), "\"\\u0001é\"");
+    }
+}
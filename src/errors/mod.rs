@@ -1,3 +1,7 @@
 pub mod error;
+pub mod explain;
 pub mod formatter;
+pub mod json;
 pub mod recovery;
+pub mod suggest;
+pub mod trace;
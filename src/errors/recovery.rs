@@ -1,15 +1,114 @@
-use crate::frontend::{expr::Expr, parser::State, token::Token};
+use super::error::ErrorKind;
+use crate::frontend::{
+    expr::{Expr, ExprKind},
+    parser::State,
+    token::{Token, TokenKind},
+};
 
+/// A change to the source that would get rid of an unexpected token error
+pub enum Fix {
+    /// Replace the unexpected token with this
+    Replace(&'static str),
+    /// Insert this right after the token before the unexpected one
+    InsertAfterPrevious(&'static str),
+}
+
+/// Decides what to do with a token that isn't valid in the current state
+/// Returns an expression to push in its place if parsing can carry on from here, along with
+/// the error to report and possibly a fix to suggest. When no expression is returned the
+/// parser synchronizes instead
 pub fn unexpected_token<'a>(
-    _last: Option<&'a Expr>,
-    _token: &'a Token,
+    last: Option<&'a Expr>,
+    token: &'a Token,
     state: &'a State,
-) -> (Option<Expr>, String) {
-    match state {
-        State::PreParamFunctionExpr => (
+) -> (Option<Expr>, ErrorKind, Option<Fix>) {
+    let (expr, msg) = match (&token.0, state) {
+        (TokenKind::Equal, State::PreParamFunctionExpr) => (
             None,
             String::from("'=' operator isn't supported in function parameters"),
         ),
-        _ => (None, String::from("Unexpected token '='")),
+        (TokenKind::Equal, State::Empty) => match last {
+            Some(Expr(ExprKind::Ident(name), _)) => {
+                return (
+                    None,
+                    ErrorKind::AssignmentToExisting { name: name.clone() },
+                    Some(Fix::Replace("->")),
+                )
+            }
+            _ => (None, String::from("Unexpected token '='")),
+        },
+        // A statement keyword can only turn up mid-statement if the last one wasn't terminated
+        (
+            TokenKind::Var
+            | TokenKind::Const
+            | TokenKind::Def
+            | TokenKind::From
+            | TokenKind::Class
+            | TokenKind::Methods
+            | TokenKind::Print
+            | TokenKind::Return
+            | TokenKind::Directive,
+            _,
+        ) => {
+            return (
+                None,
+                ErrorKind::MissingSemicolon {
+                    before: token.0.to_string(),
+                },
+                Some(Fix::InsertAfterPrevious(";")),
+            )
+        }
+        (TokenKind::Import, _) => (
+            None,
+            String::from("'import' is only valid after 'from <module>'"),
+        ),
+        (TokenKind::RCurl, _) => (None, String::from("Unmatched '}'")),
+        (TokenKind::RPar, _) => (None, String::from("Unmatched ')'")),
+        (TokenKind::Colon, _) => (
+            None,
+            String::from("':' is only valid in type annotations and parameters"),
+        ),
+        (TokenKind::LCurl, _) => (
+            None,
+            String::from("'{' is only valid after a function signature or class name, or as a map"),
+        ),
+        (kind, _) => {
+            return (
+                None,
+                ErrorKind::UnexpectedToken {
+                    found: kind.to_string(),
+                    expected: None,
+                },
+                None,
+            )
+        }
+    };
+    (expr, ErrorKind::ParseError(msg), None)
+}
+
+/// Describes the statement the parser expected when the stack couldn't be reduced
+pub fn malformed_statement(state: &State) -> String {
+    match state {
+        State::Empty => String::from("Expected a statement, found a bare expression"),
+        State::ReturnExpr => String::from("Expected a single expression after 'return'"),
+        State::PrintExpr => String::from("Expected a single expression after '$'"),
+        State::DirectiveExpr => String::from("Expected a single directive after 'directive'"),
+        State::ImportExpr => {
+            String::from("Expected an import of the form 'from module import symbols;'")
+        }
+        State::ClassExpr => {
+            String::from("Expected a class of the form 'class Name { field: type, ... }'")
+        }
+        State::ClassMethods => String::from("Expected a block of the form 'methods Name { ... }'"),
+        State::PreParamFunctionExpr | State::PostParamFunctionExpr => String::from(
+            "Expected a function of the form 'def name(param: type, ...): type { ... }'",
+        ),
+        State::UntypedVarExpr | State::TypedVarExpr => {
+            String::from("Expected a variable of the form 'var name: type = value;'")
+        }
+        State::UntypedConstExpr | State::TypedConstExpr => {
+            String::from("Expected a constant of the form 'const name: type = value;'")
+        }
+        State::MutationExpr => String::from("Expected a mutation of the form 'name -> value;'"),
     }
 }
@@ -0,0 +1,56 @@
+//! Finding the name someone probably meant when they wrote one that doesn't exist
+
+/// The candidate closest to `name`, if any is close enough to be worth suggesting
+/// Ties go to the candidate that comes first
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // Allow roughly one edit for every three characters, and always at least one
+    let limit = (name.chars().count() / 3).max(1);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        let distance = distance(&name.to_lowercase(), &candidate.to_lowercase());
+        if distance <= limit && best.is_none_or(|(d, _)| distance < d) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+/// How many characters have to be inserted, deleted or replaced to turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("sales", "slaes"), 2);
+        assert_eq!(distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn closest_names() {
+        let names = ["select", "sort_by", "head", "tail"];
+        assert_eq!(closest("selct", names), Some("select"));
+        assert_eq!(closest("SELECT", names), Some("select"));
+        assert_eq!(closest("hed", names), Some("head"));
+        assert_eq!(closest("mutate", names), None);
+        // Ties go to the first candidate
+        assert_eq!(closest("tead", ["head", "tail", "read"]), Some("head"));
+    }
+}
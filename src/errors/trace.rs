@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+/// One call on the evaluator's call stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The function being called
+    pub function: String,
+    /// Where it was called from
+    pub call_site: Range<usize>,
+    /// The module the call site is in
+    pub module: String,
+}
+
+/// Groups consecutive identical frames, as deep recursion would otherwise bury the rest of the
+/// trace. Returns each distinct frame along with how many more times it repeats right after
+pub fn collapse(frames: &[Frame]) -> Vec<(&Frame, usize)> {
+    let mut out: Vec<(&Frame, usize)> = Vec::new();
+    for frame in frames {
+        match out.last_mut() {
+            Some((last, repeats)) if *last == frame => *repeats += 1,
+            _ => out.push((frame, 0)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(function: &str, at: usize) -> Frame {
+        Frame {
+            function: function.to_string(),
+            call_site: at..at + 1,
+            module: String::from("main"),
+        }
+    }
+
+    #[test]
+    fn collapse_groups_consecutive_repeats() {
+        let frames = [
+            frame("main", 0),
+            frame("fact", 5),
+            frame("fact", 5),
+            frame("fact", 5),
+            frame("other", 9),
+            frame("fact", 5),
+        ];
+        let collapsed: Vec<_> = collapse(&frames)
+            .into_iter()
+            .map(|(frame, repeats)| (frame.function.as_str(), repeats))
+            .collect();
+        assert_eq!(
+            collapsed,
+            [("main", 0), ("fact", 2), ("other", 0), ("fact", 0)]
+        );
+        // The same function called from somewhere else is a different frame
+        assert_eq!(collapse(&[frame("f", 1), frame("f", 2)]).len(), 2);
+        assert!(collapse(&[]).is_empty());
+    }
+}
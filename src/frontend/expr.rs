@@ -1,9 +1,38 @@
-#[derive(Debug)]
-pub enum Expr {
+use std::fmt;
+
+use crate::errors::formatter::Formatter;
+
+use super::token::TokenSpan;
+
+/// Stores beginning and end in the source, inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span(pub usize, pub usize);
+
+impl Span {
+    /// Covers everything from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span(self.0.min(other.0), self.1.max(other.1))
+    }
+}
+
+impl From<&TokenSpan> for Span {
+    fn from(span: &TokenSpan) -> Self {
+        Span(span.0, span.1)
+    }
+}
+
+/// Expr stores the expression variant and the part of the source it was parsed from
+#[derive(Debug, Clone)]
+pub struct Expr(pub ExprKind, pub Span);
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     // Atoms
     Integer(i32),
     Float(f32),
     Str(String),
+    /// A pattern literal, checked when the program is
+    Regex(String),
     Ident(String),
     Parameter(Box<Expr>, Box<Expr>),
     QMark(Box<Expr>),
@@ -13,8 +42,15 @@ pub enum Expr {
 
     ParensExpr(Box<Expr>),
     BlockExpr(Vec<Box<Expr>>),
+    ListExpr(Vec<Box<Expr>>),
+    /// Keys and values, in the order they were written
+    MapExpr(Vec<(Box<Expr>, Box<Expr>)>),
+    /// Value, Index
+    IndexExpr(Box<Expr>, Box<Expr>),
 
     BinaryExpr(Box<Expr>, Box<Expr>, BinaryOperator),
+    /// A leading '-', on anything but a number literal which takes the sign itself
+    Negate(Box<Expr>),
 
     PrintExpr(Box<Expr>),
 
@@ -37,6 +73,8 @@ pub enum Expr {
     /// Ident, Arguments
     FunctionCall(Box<Expr>, Box<Expr>),
     FunctionArgs(Vec<Box<Expr>>),
+    /// Name, Value, an argument passed by the name of its parameter
+    NamedArg(Box<Expr>, Box<Expr>),
     ReturnExpr(Box<Expr>),
 
     VariableExpr(Box<Expr>, Option<Box<Expr>>, Box<Expr>),
@@ -44,7 +82,7 @@ pub enum Expr {
     MutateExpr(Box<Expr>, Box<Expr>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinaryOperator {
     Plus,
     Minus,
@@ -52,4 +90,152 @@ pub enum BinaryOperator {
     Divide,
     Modulo,
     Exponent,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl BinaryOperator {
+    /// What applying the operator does, for error messages
+    pub fn verb(&self) -> &'static str {
+        match self {
+            BinaryOperator::Plus => "add",
+            BinaryOperator::Minus => "subtract",
+            BinaryOperator::Multiply => "multiply",
+            BinaryOperator::Divide => "divide",
+            BinaryOperator::Modulo => "take the remainder",
+            BinaryOperator::Exponent => "raise to a power",
+            _ => "compare",
+        }
+    }
+
+    /// True for the operators that compare their operands and give a bool
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual
+        )
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOperator::Plus => "+",
+            BinaryOperator::Minus => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Exponent => "^",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl Expr {
+    /// Renders the node and everything under it as an indented tree, one node per line with the
+    /// line and column range it was parsed from
+    pub fn pretty(&self, fmt: &Formatter) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0, fmt);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize, fmt: &Formatter) {
+        let (label, children): (String, Vec<&Expr>) = match &self.0 {
+            ExprKind::Integer(i) => (format!("Integer {}", i), vec![]),
+            ExprKind::Float(x) => (format!("Float {:?}", x), vec![]),
+            ExprKind::Str(s) => (format!("Str {:?}", s), vec![]),
+            ExprKind::Regex(s) => (format!("Regex {:?}", s), vec![]),
+            ExprKind::Ident(name) => (format!("Ident {}", name), vec![]),
+            ExprKind::Wildcard => (String::from("Wildcard"), vec![]),
+            ExprKind::Parameter(name, typ) => (String::from("Parameter"), vec![name, typ]),
+            ExprKind::QMark(inner) => (String::from("QMark"), vec![inner]),
+            ExprKind::QualifiedIdent(left, right) => {
+                (String::from("QualifiedIdent"), vec![left, right])
+            }
+            ExprKind::ParensExpr(inner) => (String::from("ParensExpr"), vec![inner]),
+            ExprKind::BlockExpr(body) => (String::from("BlockExpr"), boxed(body)),
+            ExprKind::ListExpr(items) => (String::from("ListExpr"), boxed(items)),
+            ExprKind::MapExpr(entries) => (
+                String::from("MapExpr"),
+                entries
+                    .iter()
+                    .flat_map(|(key, value)| [key.as_ref(), value.as_ref()])
+                    .collect(),
+            ),
+            ExprKind::IndexExpr(value, index) => (String::from("IndexExpr"), vec![value, index]),
+            ExprKind::BinaryExpr(left, right, op) => {
+                (format!("BinaryExpr {}", op), vec![left, right])
+            }
+            ExprKind::Negate(inner) => (String::from("Negate"), vec![inner]),
+            ExprKind::PrintExpr(inner) => (String::from("PrintExpr"), vec![inner]),
+            ExprKind::ClassExpr(name, fields) => (String::from("ClassExpr"), vec![name, fields]),
+            ExprKind::ClassFields(fields) => (String::from("ClassFields"), boxed(fields)),
+            ExprKind::ClassMethods(name, methods) => {
+                let mut children = vec![name.as_ref()];
+                children.extend(boxed(methods));
+                (String::from("ClassMethods"), children)
+            }
+            ExprKind::ImportExpr(module, symbols) => {
+                (String::from("ImportExpr"), vec![module, symbols])
+            }
+            ExprKind::ImportArgs(symbols) => (String::from("ImportArgs"), boxed(symbols)),
+            ExprKind::Directive(inner) => (String::from("Directive"), vec![inner]),
+            ExprKind::FunctionExpr(name, params, returns, body) => {
+                let mut children = vec![name.as_ref(), params.as_ref()];
+                children.extend(returns.as_deref());
+                children.push(body);
+                (String::from("FunctionExpr"), children)
+            }
+            ExprKind::FunctionCall(callee, args) => (String::from("FunctionCall"), vec![callee, args]),
+            ExprKind::FunctionArgs(args) => (String::from("FunctionArgs"), boxed(args)),
+            ExprKind::NamedArg(name, value) => (String::from("NamedArg"), vec![name, value]),
+            ExprKind::ReturnExpr(value) => (String::from("ReturnExpr"), vec![value]),
+            ExprKind::VariableExpr(name, typ, value) | ExprKind::ConstExpr(name, typ, value) => {
+                let mut children = vec![name.as_ref()];
+                children.extend(typ.as_deref());
+                children.push(value);
+                let label = match self.0 {
+                    ExprKind::VariableExpr(..) => "VariableExpr",
+                    _ => "ConstExpr",
+                };
+                (String::from(label), children)
+            }
+            ExprKind::MutateExpr(name, value) => (String::from("MutateExpr"), vec![name, value]),
+        };
+
+        let (start_line, start_col) = fmt.position(self.1 .0);
+        let (end_line, end_col) = fmt.position(self.1 .1 + 1);
+        out.push_str(&format!(
+            "{}{} @ {}:{}-{}:{}\n",
+            "  ".repeat(depth),
+            label,
+            start_line,
+            start_col,
+            end_line,
+            end_col
+        ));
+        children
+            .into_iter()
+            .for_each(|c| c.write_tree(out, depth + 1, fmt));
+    }
+}
+
+fn boxed(exprs: &[Box<Expr>]) -> Vec<&Expr> {
+    exprs.iter().map(|e| e.as_ref()).collect()
 }
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
-use crate::frontend::token::{Token, TokenKind};
+use crate::{
+    errors::error::{ErrorClass, ErrorKind, Errors},
+    frontend::token::{Token, TokenKind},
+};
 
 #[derive(Debug)]
 pub struct Lexer<'a> {
@@ -11,14 +14,20 @@ pub struct Lexer<'a> {
 
 impl<'a> Lexer<'a> {
     pub fn new(source: &'a String) -> Self {
+        Self::resume(source, 0)
+    }
+
+    /// Creates a lexer that starts partway through the source, for text that was appended to it
+    /// Spans stay relative to the start of the whole source
+    pub fn resume(source: &'a String, pos: usize) -> Self {
         Self {
             stream: source.as_bytes(),
-            pos: 0usize,
+            pos,
             output: Vec::new(),
         }
     }
 
-    pub fn tokenize(&mut self) -> &Vec<Token> {
+    pub fn tokenize(&mut self, errors: &mut Errors) -> &Vec<Token<'a>> {
         let kw_map = generate_keyword_map();
 
         loop {
@@ -60,15 +69,33 @@ impl<'a> Lexer<'a> {
                 [b'$', ..] => self.add_token(TokenKind::Print, self.pos, 1),
                 [b',', ..] => self.add_token(TokenKind::Comma, self.pos, 1),
                 [b'?', ..] => self.add_token(TokenKind::QMark, self.pos, 1),
-                [b'"', ..] => {
+                [b'r', b'"', ..] | [b'"', ..] => {
                     let begin = self.pos;
+                    let pattern = self.stream[self.pos] == b'r';
+                    if pattern {
+                        self.pos += 1;
+                    }
                     let literal = self.str();
                     match literal {
+                        Some(s) if pattern => {
+                            let len = s.len() + 3;
+                            self.add_token(TokenKind::Regex(s), begin, len);
+                        }
                         Some(s) => {
                             let len = s.len() + 2;
                             self.add_token(TokenKind::Str(s), begin, len);
                         }
-                        None => todo!("Non-terminating literal"),
+                        None => {
+                            errors
+                                .new(
+                                    ErrorClass::Error,
+                                    ErrorKind::UnterminatedString,
+                                    begin,
+                                    begin,
+                                )
+                                .primary("string begins here")
+                                .help("add a closing '\"' to end the string");
+                        }
                     }
                 }
                 _ => {
@@ -81,7 +108,9 @@ impl<'a> Lexer<'a> {
                         self.pos -= 1;
 
                     // Tokenize identifiers or keywords
-                    } else if self.stream[self.pos].is_ascii_alphanumeric() {
+                    } else if self.stream[self.pos].is_ascii_alphanumeric()
+                        || self.stream[self.pos] == b'_'
+                    {
                         let begin = self.pos;
                         let chars = self.ident();
 
@@ -97,6 +126,17 @@ impl<'a> Lexer<'a> {
                         }
 
                         self.pos -= 1;
+
+                    // Anything else that isn't whitespace is a stray character
+                    } else if !self.stream[self.pos].is_ascii_whitespace() {
+                        let begin = self.pos;
+                        let ch = self.stray_char();
+                        errors.new(
+                            ErrorClass::Error,
+                            ErrorKind::UnexpectedCharacter(ch),
+                            begin,
+                            self.pos,
+                        );
                     }
                 }
             }
@@ -113,7 +153,7 @@ impl<'a> Lexer<'a> {
         loop {
             self.pos += 1;
             if self.pos >= self.stream.len() {
-                todo!("Non-terminating string literal")
+                return None;
             } else if self.stream[self.pos] != b'"' {
                 continue;
             } else {
@@ -128,31 +168,37 @@ impl<'a> Lexer<'a> {
         let start = self.pos;
         loop {
             self.pos += 1;
-            if self.pos >= self.stream.len() {
-                break;
-            } else if !self.stream[self.pos].is_ascii_digit()
-                && self.stream[self.pos] != b'_'
-                && self.stream[self.pos] != b'.'
+            if self.pos >= self.stream.len()
+                || !self.stream[self.pos].is_ascii_digit()
+                    && self.stream[self.pos] != b'_'
+                    && self.stream[self.pos] != b'.'
             {
                 break;
             }
         }
-        return std::str::from_utf8(&self.stream[start..self.pos]).unwrap();
+        std::str::from_utf8(&self.stream[start..self.pos]).unwrap()
     }
 
     fn ident(&mut self) -> &'a str {
         let start = self.pos;
         loop {
             self.pos += 1;
-            if self.pos >= self.stream.len() {
-                break;
-            } else if !self.stream[self.pos].is_ascii_alphanumeric()
-                && self.stream[self.pos] != b'_'
+            if self.pos >= self.stream.len()
+                || !self.stream[self.pos].is_ascii_alphanumeric() && self.stream[self.pos] != b'_'
             {
                 break;
             }
         }
-        return std::str::from_utf8(&self.stream[start..self.pos]).unwrap();
+        std::str::from_utf8(&self.stream[start..self.pos]).unwrap()
+    }
+
+    /// Consumes a character the lexer doesn't recognize, leaving `pos` on its last byte
+    /// so that multi-byte UTF-8 characters are reported once
+    fn stray_char(&mut self) -> char {
+        let rest = std::str::from_utf8(&self.stream[self.pos..]).unwrap_or("");
+        let ch = rest.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.pos += ch.len_utf8().max(1) - 1;
+        ch
     }
 
     fn add_token(&mut self, kind: TokenKind<'a>, begin: usize, width: usize) {
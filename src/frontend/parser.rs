@@ -2,11 +2,11 @@ use std::iter::Peekable;
 
 use crate::errors::{
     error::{ErrorClass, ErrorKind, Errors},
-    recovery,
+    recovery::{self, Fix},
 };
 
 use super::{
-    expr::{BinaryOperator, Expr},
+    expr::{BinaryOperator, Expr, ExprKind, Span},
     token::{Token, TokenKind},
 };
 
@@ -29,18 +29,28 @@ pub enum State {
 }
 
 #[derive(Debug)]
-pub struct Parser<'a, Iter: Iterator<Item = &'a Token<'a>>> {
-    errors: &'a mut Errors<'a>,
+pub struct Parser<'a, 'e, Iter: Iterator<Item = &'a Token<'a>>> {
+    errors: &'a mut Errors<'e>,
     tokens: Peekable<Iter>,
     stack: Vec<Expr>,
     pub tree: Vec<Expr>,
     state: State,
     phrase_start: usize,
     phrase_end: usize,
+    /// The last token taken from the stream and the one before it
+    last: Option<&'a Token<'a>>,
+    prev: Option<&'a Token<'a>>,
+    /// Whether a lone expression is accepted as a statement, as it is in the REPL
+    bare_expressions: bool,
+    /// Maps whose closing '}' hasn't been reached, which recovery has to skip past
+    open_maps: usize,
+    /// The length of the stack when the innermost value being parsed began, an operator can't
+    /// take its left operand from below it
+    floor: usize,
 }
 
-impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
-    pub fn new(errors: &'a mut Errors<'a>, tokens: Iter) -> Self {
+impl<'a, 'e, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, 'e, Iter> {
+    pub fn new(errors: &'a mut Errors<'e>, tokens: Iter) -> Self {
         Self {
             errors,
             tokens: tokens.peekable(),
@@ -49,28 +59,225 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
             state: State::Empty,
             phrase_start: 0,
             phrase_end: 0,
+            last: None,
+            prev: None,
+            bare_expressions: false,
+            open_maps: 0,
+            floor: 0,
         }
     }
 
+    /// Accepts statements made of a single expression, like `1 + 2;`, which are otherwise an error
+    pub fn allow_bare_expressions(&mut self) {
+        self.bare_expressions = true;
+    }
+
     pub fn parse(&mut self) {
-        while let Some(token) = self.tokens.next() {
-            self.parse_expr(token);
+        while let Some(token) = self.advance() {
+            self.mark_phrase(token);
+            if self.parse_expr(token).is_none() {
+                self.synchronize();
+            }
         }
     }
 
-    fn push_recovery(&mut self, new_expr: Option<Expr>) {
-        match new_expr {
-            Some(e) => self.stack.push(e),
-            None => {}
+    /// Takes the next token from the stream, keeping track of the current phrase
+    fn advance(&mut self) -> Option<&'a Token<'a>> {
+        let token = self.tokens.next()?;
+        self.prev = self.last;
+        self.last = Some(token);
+        self.phrase_end = token.1 .1;
+        Some(token)
+    }
+
+    /// Records where the current statement begins if `token` is its first token
+    fn mark_phrase(&mut self, token: &'a Token) {
+        if self.stack.is_empty() && self.state == State::Empty {
+            self.phrase_start = token.1 .0;
         }
     }
 
-    fn reduce(&mut self) -> Option<()> {
-        println!("Attempting to reduce the stack");
-        println!("State = {:?}", self.state);
-        dbg!(&self.stack);
-        dbg!(&self.tree);
+    /// The span of the current statement up to the last token taken
+    fn phrase(&self) -> Span {
+        Span(self.phrase_start, self.phrase_end)
+    }
+
+    /// The span of the last token taken from the stream
+    fn last_span(&self) -> Span {
+        match self.last {
+            Some(token) => Span::from(&token.1),
+            None => Span::default(),
+        }
+    }
+
+    /// Pushes a finished statement to the tree, spanning the whole phrase
+    fn push_statement(&mut self, kind: ExprKind) {
+        let span = self.phrase();
+        self.tree.push(Expr(kind, span));
+    }
+
+    /// Reports an error of `kind` spanning `start..=end`
+    /// Always returns `None` so that callers can bail out with `?`
+    fn report<T>(&mut self, kind: ErrorKind, start: usize, end: usize) -> Option<T> {
+        self.errors.new(ErrorClass::Error, kind, start, end);
+        None
+    }
+
+    fn report_at<T>(&mut self, kind: ErrorKind, token: &'a Token) -> Option<T> {
+        self.report(kind, token.1 .0, token.1 .1)
+    }
+
+    /// Reports a parse error with no more specific kind, spanning `start..=end`
+    fn error<T>(&mut self, msg: impl Into<String>, start: usize, end: usize) -> Option<T> {
+        self.report(ErrorKind::ParseError(msg.into()), start, end)
+    }
+
+    fn error_at<T>(&mut self, msg: impl Into<String>, token: &'a Token) -> Option<T> {
+        self.error(msg, token.1 .0, token.1 .1)
+    }
+
+    /// Reports a list or block that ran into `found` before being closed, pointing back at `open`
+    fn unclosed<T>(
+        &mut self,
+        context: &'static str,
+        open: &'a Token,
+        found: &'a Token,
+    ) -> Option<T> {
+        let close = match open.0 {
+            TokenKind::LCurl => '}',
+            TokenKind::LBrac => ']',
+            _ => ')',
+        };
+        self.errors
+            .new(
+                ErrorClass::Error,
+                ErrorKind::UnclosedDelimiter { close, context },
+                found.1 .0,
+                found.1 .1,
+            )
+            .primary(format!("found {}", found.0))
+            .label(open.1 .0, open.1 .1, "unclosed delimiter");
+        None
+    }
+
+    fn error_phrase<T>(&mut self, msg: impl Into<String>) -> Option<T> {
+        self.error(msg, self.phrase_start, self.phrase_end)
+    }
+
+    /// Pops the top of the stack, reporting `msg` over the current phrase if there's nothing there
+    fn pop(&mut self, msg: &str) -> Option<Expr> {
+        match self.stack.pop() {
+            Some(e) => Some(e),
+            None => self.error_phrase(msg),
+        }
+    }
+
+    /// Takes the item between two delimiters of a list that began when the stack was `base` long
+    /// Returns `Some(None)` if nothing was written there, which is only an error for the caller
+    fn pop_item(&mut self, base: usize, msg: &str) -> Option<Option<Box<Expr>>> {
+        if self.stack.len() == base {
+            Some(None)
+        } else if self.stack.len() == base + 1 {
+            Some(Some(Box::new(self.stack.pop().unwrap())))
+        } else {
+            self.error_phrase(msg)
+        }
+    }
+
+    /// Takes the token that begins the operand of `op`
+    /// Anything that would close the surrounding phrase instead is left alone and reported
+    fn operand(&mut self, missing: ErrorKind, op: &'a Token) -> Option<&'a Token<'a>> {
+        match self.tokens.peek().map(|t| &t.0) {
+            Some(TokenKind::SemiColon)
+            | Some(TokenKind::Comma)
+            | Some(TokenKind::RPar)
+            | Some(TokenKind::RBrac)
+            | Some(TokenKind::RCurl)
+            | Some(TokenKind::EOF)
+            | None => self.report_at(missing, op),
+            _ => self.advance(),
+        }
+    }
+
+    /// Reports a token that isn't valid in the current state
+    /// Parsing carries on only if the recovery module has something to replace it with
+    fn unexpected(&mut self, token: &'a Token) -> Option<()> {
+        let (new_expr, kind, fix) =
+            recovery::unexpected_token(self.stack.last(), token, &self.state);
+        let err = self.errors.new(
+            ErrorClass::Error,
+            kind,
+            token.1 .0,
+            token.1 .1,
+        );
+        match (fix, self.prev) {
+            (Some(Fix::Replace(text)), _) => {
+                err.suggest(token.1 .0, token.1 .1, text, format!("use '{}' instead", text));
+            }
+            (Some(Fix::InsertAfterPrevious(text)), Some(prev)) => {
+                err.suggest_insert(prev.1 .1 + 1, text, format!("add '{}' here", text))
+                    .label(prev.1 .0, prev.1 .1, format!("expected '{}' after this", text));
+            }
+            _ => {}
+        }
+        self.stack.push(new_expr?);
+        Some(())
+    }
+
+    /// Throws away the statement that failed to parse by skipping ahead to the next `;`, or to
+    /// the `}` that closes the enclosing block, so parsing can pick back up at the next statement
+    /// Braces opened while skipping are balanced first, since a malformed function or class
+    /// definition ends at its own `}`, and so are the braces of any map the error was inside
+    fn synchronize(&mut self) {
+        let last = self.last.map(|t| &t.0);
+        let at_boundary = self.open_maps == 0
+            && matches!(last, Some(TokenKind::SemiColon) | Some(TokenKind::RCurl));
+
+        if !at_boundary {
+            // The error may have been found on the `{` opening a body that now needs skipping
+            let mut depth = match last {
+                Some(TokenKind::LCurl) => 1usize,
+                _ => 0usize,
+            };
+            let mut maps = self.open_maps;
+            while let Some(token) = self.tokens.peek() {
+                match token.0 {
+                    TokenKind::SemiColon if depth == 0 && maps == 0 => {
+                        self.advance();
+                        break;
+                    }
+                    TokenKind::RCurl if depth == 0 && maps == 0 => break,
+                    TokenKind::RCurl if depth == 0 => {
+                        maps -= 1;
+                        self.advance();
+                    }
+                    TokenKind::RCurl => {
+                        depth -= 1;
+                        self.advance();
+                        // Braces inside a map are more maps, the statement goes on after them
+                        if depth == 0 && maps == 0 {
+                            break;
+                        }
+                    }
+                    TokenKind::LCurl => {
+                        depth += 1;
+                        self.advance();
+                    }
+                    TokenKind::EOF => break,
+                    _ => {
+                        self.advance();
+                    }
+                }
+            }
+        }
+
+        self.stack.clear();
+        self.state = State::Empty;
+        self.open_maps = 0;
+        self.floor = 0;
+    }
 
+    fn reduce(&mut self) -> Option<()> {
         match self.state {
             State::ClassExpr => self.reduce_class_expr(),
             State::DirectiveExpr => self.reduce_flag_expr(),
@@ -84,14 +291,21 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
             State::MutationExpr => self.reduce_mutation(),
             State::PostParamFunctionExpr => self.reduce_function_expr(),
             State::Empty => match self.stack.last() {
-                Some(Expr::FunctionCall(_, _)) => {
+                // Empty statement
+                None => Some(()),
+                Some(Expr(ExprKind::FunctionCall(_, _), _)) if self.stack.len() == 1 => {
                     let fn_call = self.stack.pop().unwrap();
                     self.tree.push(fn_call);
                     Some(())
                 }
-                _ => panic!("Unexpected state {:?}", self.state),
+                Some(_) if self.bare_expressions && self.stack.len() == 1 => {
+                    let expr = self.stack.pop().unwrap();
+                    self.tree.push(expr);
+                    Some(())
+                }
+                _ => None,
             },
-            _ => panic!("Unexpected state {:?}", self.state),
+            State::PreParamFunctionExpr | State::ClassMethods => None,
         }
     }
 
@@ -99,8 +313,7 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
         if self.stack.len() == 2 {
             let fields = self.stack.pop().unwrap();
             let ident = self.stack.pop().unwrap();
-            self.tree
-                .push(Expr::ClassExpr(Box::new(ident), Box::new(fields)));
+            self.push_statement(ExprKind::ClassExpr(Box::new(ident), Box::new(fields)));
             Some(())
         } else {
             None
@@ -110,7 +323,7 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
     fn reduce_flag_expr(&mut self) -> Option<()> {
         if self.stack.len() == 1 {
             let flag = self.stack.pop().unwrap();
-            self.tree.push(Expr::Directive(Box::new(flag)));
+            self.push_statement(ExprKind::Directive(Box::new(flag)));
             Some(())
         } else {
             None
@@ -121,8 +334,7 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
         if self.stack.len() == 2 {
             let args = self.stack.pop().unwrap();
             let package = self.stack.pop().unwrap();
-            self.tree
-                .push(Expr::ImportExpr(Box::new(package), Box::new(args)));
+            self.push_statement(ExprKind::ImportExpr(Box::new(package), Box::new(args)));
             Some(())
         } else {
             None
@@ -135,7 +347,7 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
             let block = self.stack.pop().unwrap();
             let params = self.stack.pop().unwrap();
             let ident = self.stack.pop().unwrap();
-            self.tree.push(Expr::FunctionExpr(
+            self.push_statement(ExprKind::FunctionExpr(
                 Box::new(ident),
                 Box::new(params),
                 None,
@@ -148,7 +360,7 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
             let returns = self.stack.pop().unwrap();
             let params = self.stack.pop().unwrap();
             let ident = self.stack.pop().unwrap();
-            self.tree.push(Expr::FunctionExpr(
+            self.push_statement(ExprKind::FunctionExpr(
                 Box::new(ident),
                 Box::new(params),
                 Some(Box::new(returns)),
@@ -161,9 +373,9 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
     }
 
     fn reduce_print_expr(&mut self) -> Option<()> {
-        if self.stack.len() >= 1 {
+        if self.stack.len() == 1 {
             let expr = self.stack.pop().unwrap();
-            self.tree.push(Expr::PrintExpr(Box::new(expr)));
+            self.push_statement(ExprKind::PrintExpr(Box::new(expr)));
             Some(())
         } else {
             None
@@ -171,9 +383,9 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
     }
 
     fn reduce_return_expr(&mut self) -> Option<()> {
-        if self.stack.len() >= 1 {
+        if self.stack.len() == 1 {
             let expr = self.stack.pop().unwrap();
-            self.tree.push(Expr::ReturnExpr(Box::new(expr)));
+            self.push_statement(ExprKind::ReturnExpr(Box::new(expr)));
             Some(())
         } else {
             None
@@ -185,8 +397,7 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
             // Ident -> Value;
             let value = self.stack.pop().unwrap();
             let name = self.stack.pop().unwrap();
-            self.tree
-                .push(Expr::MutateExpr(Box::new(name), Box::new(value)));
+            self.push_statement(ExprKind::MutateExpr(Box::new(name), Box::new(value)));
             Some(())
         } else {
             None
@@ -199,11 +410,9 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
             let value = self.stack.pop().unwrap();
             let name = self.stack.pop().unwrap();
             if constant {
-                self.tree
-                    .push(Expr::ConstExpr(Box::new(name), None, Box::new(value)));
+                self.push_statement(ExprKind::ConstExpr(Box::new(name), None, Box::new(value)));
             } else {
-                self.tree
-                    .push(Expr::VariableExpr(Box::new(name), None, Box::new(value)));
+                self.push_statement(ExprKind::VariableExpr(Box::new(name), None, Box::new(value)));
             }
             Some(())
 
@@ -213,13 +422,13 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
             let typ = self.stack.pop().unwrap();
             let name = self.stack.pop().unwrap();
             if constant {
-                self.tree.push(Expr::ConstExpr(
+                self.push_statement(ExprKind::ConstExpr(
                     Box::new(name),
                     Some(Box::new(typ)),
                     Box::new(value),
                 ));
             } else {
-                self.tree.push(Expr::VariableExpr(
+                self.push_statement(ExprKind::VariableExpr(
                     Box::new(name),
                     Some(Box::new(typ)),
                     Box::new(value),
@@ -233,333 +442,494 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
         }
     }
 
-    fn try_reduce(&mut self) {
+    fn try_reduce(&mut self) -> Option<()> {
         match self.reduce() {
-            Some(_) => self.state = State::Empty,
+            Some(_) => {
+                self.state = State::Empty;
+                self.floor = 0;
+                Some(())
+            }
             None => {
-                eprintln!("There was an error reducing the stack!");
-                dbg!(&self.stack);
-                dbg!(&self.tree);
+                let msg = recovery::malformed_statement(&self.state);
+                self.error_phrase(msg)
             }
-        };
+        }
     }
 
-    fn parse_expr(&mut self, token: &'a Token) {
-        self.phrase_start = token.1 .0;
+    fn parse_expr(&mut self, token: &'a Token) -> Option<()> {
         match token.0 {
-            TokenKind::Number(n) => self.expr_number(n),
-            TokenKind::Ident(i) => self.expr_ident(i),
-            TokenKind::Str(s) => self.expr_str(s),
-            TokenKind::Dot => self.expr_qualified_ident(),
-            TokenKind::EOF => println!("ENDING"),
-
-            TokenKind::Var => self.state = State::UntypedVarExpr,
-            TokenKind::Const => self.state = State::UntypedConstExpr,
-            TokenKind::Def => self.state = State::PreParamFunctionExpr,
-            TokenKind::From => self.state = State::ImportExpr,
-            TokenKind::Methods => self.state = State::ClassMethods,
-            TokenKind::Class => self.state = State::ClassExpr,
-            TokenKind::Import => {
-                if self.state != State::ImportExpr {
-                    panic!("Unexpected `import` in state: {:?}", self.state)
-                } else {
-                    self.expr_import_args();
-                }
-            }
+            TokenKind::Number(n) => self.expr_number(n, Span::from(&token.1)),
+            TokenKind::Ident(i) => self.expr_ident(i, token),
+            TokenKind::Str(s) => self.expr_str(s, token),
+            TokenKind::Regex(s) => self.expr_regex(s, token),
+            TokenKind::Dot => self.expr_qualified_ident(token),
+            TokenKind::EOF => self.expr_eof(token),
 
-            TokenKind::Arrow => self.state = State::MutationExpr,
+            TokenKind::Var => self.set_state(State::UntypedVarExpr, token),
+            TokenKind::Const => self.set_state(State::UntypedConstExpr, token),
+            TokenKind::Def => self.set_state(State::PreParamFunctionExpr, token),
+            TokenKind::From => self.set_state(State::ImportExpr, token),
+            TokenKind::Methods => self.set_state(State::ClassMethods, token),
+            TokenKind::Class => self.set_state(State::ClassExpr, token),
+            TokenKind::Import => match self.state {
+                State::ImportExpr => self.expr_import_args(),
+                _ => self.unexpected(token),
+            },
+
+            TokenKind::Arrow => match self.state {
+                State::Empty => {
+                    self.set_state(State::MutationExpr, token)?;
+                    // The value can't reach back to the name for an operand
+                    self.floor = self.stack.len();
+                    Some(())
+                }
+                _ => self.unexpected(token),
+            },
 
             TokenKind::LPar => match self.state {
-                State::PreParamFunctionExpr => self.expr_parameters(),
-                _ => match self.stack.last() {
-                    Some(Expr::Ident(_)) => {
-                        self.expr_arguments();
-                        self.expr_function_call();
+                State::PreParamFunctionExpr => self.expr_parameters(token),
+                _ => match (self.stack.last(), self.prev.map(|t| &t.0)) {
+                    // Only a call if the parenthesis directly follows the identifier, which can be
+                    // a method name after a '.'
+                    (
+                        Some(Expr(ExprKind::Ident(_) | ExprKind::QualifiedIdent(..), _)),
+                        Some(TokenKind::Ident(_)),
+                    ) => {
+                        self.expr_arguments(token)?;
+                        self.expr_function_call()
                     }
-                    _ => self.expr_parens(),
+                    _ => self.expr_parens(token),
                 },
             },
 
+            // Braces hold a block only after a function signature or a class name, anywhere a
+            // value can go they hold a map
             TokenKind::LCurl => match self.state {
-                State::PostParamFunctionExpr => self.expr_block(),
-                State::ClassMethods => self.expr_class_methods(),
-                State::ClassExpr => self.expr_class_expr(),
-                _ => panic!("Unexpected `{{` in state: {:?}", self.state),
+                State::PostParamFunctionExpr => self.expr_block(token),
+                State::ClassMethods => self.expr_class_methods(token),
+                State::ClassExpr => self.expr_class_expr(token),
+                State::Empty
+                | State::ReturnExpr
+                | State::PrintExpr
+                | State::UntypedVarExpr
+                | State::UntypedConstExpr
+                | State::TypedVarExpr
+                | State::TypedConstExpr
+                | State::MutationExpr => self.expr_map(token),
+                _ => self.unexpected(token),
             },
 
-            TokenKind::Print => self.state = State::PrintExpr,
-            TokenKind::Return => self.state = State::ReturnExpr,
-            TokenKind::Directive => self.state = State::DirectiveExpr,
-            TokenKind::QMark => self.expr_qmark(),
+            TokenKind::LBrac => match self.prev.map(|t| &t.0) {
+                // Only an index if the bracket directly follows a value
+                Some(
+                    TokenKind::Ident(_)
+                    | TokenKind::Number(_)
+                    | TokenKind::Str(_)
+                    | TokenKind::RPar
+                    | TokenKind::RBrac
+                    | TokenKind::RCurl,
+                ) if !self.stack.is_empty() => self.expr_index(token),
+                _ => self.expr_list(token),
+            },
+
+            TokenKind::Print => self.set_state(State::PrintExpr, token),
+            TokenKind::Return => self.set_state(State::ReturnExpr, token),
+            TokenKind::Directive => self.set_state(State::DirectiveExpr, token),
+            TokenKind::QMark => self.expr_qmark(token),
 
-            TokenKind::Plus => self.expr_binaryop(BinaryOperator::Plus),
-            TokenKind::Minus => self.expr_binaryop(BinaryOperator::Minus),
+            TokenKind::Plus => self.expr_binaryop(BinaryOperator::Plus, token),
+            TokenKind::Minus => self.expr_binaryop(BinaryOperator::Minus, token),
             TokenKind::Star => match self.state {
-                State::ImportExpr => self.stack.push(Expr::Wildcard),
-                _ => self.expr_binaryop(BinaryOperator::Multiply),
+                State::ImportExpr => {
+                    self.stack.push(Expr(ExprKind::Wildcard, Span::from(&token.1)));
+                    Some(())
+                }
+                _ => self.expr_binaryop(BinaryOperator::Multiply, token),
             },
-            TokenKind::Slash => self.expr_binaryop(BinaryOperator::Divide),
-            TokenKind::Modulo => self.expr_binaryop(BinaryOperator::Modulo),
-            TokenKind::Exponent => self.expr_binaryop(BinaryOperator::Exponent),
+            TokenKind::Slash => self.expr_binaryop(BinaryOperator::Divide, token),
+            TokenKind::Modulo => self.expr_binaryop(BinaryOperator::Modulo, token),
+            TokenKind::Exponent => self.expr_binaryop(BinaryOperator::Exponent, token),
+            TokenKind::EqualEqual => self.expr_binaryop(BinaryOperator::Equal, token),
+            TokenKind::BangEqual => self.expr_binaryop(BinaryOperator::NotEqual, token),
+            TokenKind::Less => self.expr_binaryop(BinaryOperator::Less, token),
+            TokenKind::LessEqual => self.expr_binaryop(BinaryOperator::LessEqual, token),
+            TokenKind::More => self.expr_binaryop(BinaryOperator::Greater, token),
+            TokenKind::MoreEqual => self.expr_binaryop(BinaryOperator::GreaterEqual, token),
 
             TokenKind::Equal => match self.state {
                 // Make sure equal is being used in the correct state here
                 State::UntypedVarExpr
                 | State::TypedVarExpr
                 | State::TypedConstExpr
-                | State::UntypedConstExpr => {}
-                _ => {
-                    let (new_expr, msg) =
-                        recovery::unexpected_token(self.stack.last(), token, &self.state);
-                    self.push_recovery(new_expr);
-                    self.errors.new(
-                        ErrorClass::Error,
-                        ErrorKind::ParseError(msg),
-                        self.phrase_start,
-                        token.1 .1,
-                    );
+                | State::UntypedConstExpr => {
+                    self.floor = self.stack.len();
+                    Some(())
                 }
+                _ => self.unexpected(token),
             },
 
             TokenKind::Colon => {
                 // Again, make sure colon is being used in the correct state here
                 match self.state {
-                    State::UntypedVarExpr => self.state = State::TypedVarExpr,
-                    State::UntypedConstExpr => self.state = State::TypedConstExpr,
+                    State::UntypedVarExpr => self.set_state(State::TypedVarExpr, token),
+                    State::UntypedConstExpr => self.set_state(State::TypedConstExpr, token),
                     State::PreParamFunctionExpr | State::ClassExpr => self.expr_parameter(),
-                    State::PostParamFunctionExpr => {}
-                    _ => panic!("Unexpected `:` in state: {:?}", self.state),
+                    State::PostParamFunctionExpr => Some(()),
+                    _ => self.unexpected(token),
                 }
             }
 
             TokenKind::SemiColon => self.try_reduce(),
-            _ => panic!("Unexpected token! {:?}", token),
+            _ => self.unexpected(token),
+        }
+    }
+
+    /// Moves into a new state, as long as nothing else is being parsed at the moment
+    fn set_state(&mut self, state: State, token: &'a Token) -> Option<()> {
+        let allowed = match state {
+            // These only ever follow a keyword that has already set the state
+            State::TypedVarExpr | State::TypedConstExpr | State::MutationExpr => true,
+            _ => self.state == State::Empty && self.stack.is_empty(),
+        };
+
+        if allowed {
+            self.state = state;
+            Some(())
+        } else {
+            self.unexpected(token)
         }
     }
 
-    fn expr_class_methods(&mut self) {
+    fn expr_eof(&mut self, token: &'a Token) -> Option<()> {
+        if self.stack.is_empty() && self.state == State::Empty {
+            Some(())
+        } else {
+            self.errors.new(
+                ErrorClass::Error,
+                ErrorKind::MissingSemicolon {
+                    before: token.0.to_string(),
+                },
+                token.1 .0,
+                token.1 .1,
+            );
+            None
+        }
+    }
+
+    fn expr_class_methods(&mut self, open: &'a Token) -> Option<()> {
         let mut len = self.tree.len();
         let mut methods = Vec::<Box<Expr>>::new();
-        let ident = self
-            .stack
-            .pop()
-            .unwrap_or_else(|| panic!("Missing identifier for def block!"));
-        while let Some(token) = self.tokens.next() {
-            match token.0 {
-                TokenKind::Def => self.state = State::PreParamFunctionExpr,
-                TokenKind::Ident(i) => self.expr_ident(i),
+        let ident = self.pop("Expected a class name for 'methods' block")?;
+        if !self.stack.is_empty() {
+            return self.error_phrase("Expected a single class name for 'methods' block");
+        }
+        let original_phrase = self.phrase_start;
+        self.state = State::Empty;
+
+        while let Some(token) = self.advance() {
+            self.mark_phrase(token);
+            let parsed = match token.0 {
+                TokenKind::Def => self.set_state(State::PreParamFunctionExpr, token),
+                TokenKind::Ident(i) => self.expr_ident(i, token),
                 TokenKind::Colon => match self.state {
                     State::PreParamFunctionExpr => self.expr_parameter(),
-                    State::PostParamFunctionExpr => {}
-                    _ => panic!("Unexpected `:` in state: {:?}", self.state),
+                    State::PostParamFunctionExpr => Some(()),
+                    _ => self.unexpected(token),
                 },
                 TokenKind::LPar => match self.state {
-                    State::PreParamFunctionExpr => self.expr_parameters(),
-                    _ => panic!("Unexpected '(' in class def block"),
+                    State::PreParamFunctionExpr => self.expr_parameters(token),
+                    _ => self.error_at("Expected 'def' before a method's parameters", token),
                 },
                 TokenKind::LCurl => match self.state {
-                    State::PostParamFunctionExpr => self.expr_block(),
-                    _ => panic!("Unexpected '{{' in class def block"),
+                    State::PostParamFunctionExpr => self.expr_block(token),
+                    _ => self.error_at("Expected a method signature before '{'", token),
                 },
                 TokenKind::RCurl => break,
-                _ => panic!("Unexpected thing in class method block: '{:?}'", token.0),
+                TokenKind::EOF => {
+                    return self.unclosed("'methods' block", open, token);
+                }
+                _ => self.report_at(
+                    ErrorKind::UnexpectedToken {
+                        found: token.0.to_string(),
+                        expected: Some("a method definition"),
+                    },
+                    token,
+                ),
+            };
+
+            if parsed.is_none() {
+                self.synchronize();
             }
 
             if self.tree.len() > len {
                 // Snatch that function expr off the tree
                 let func = self.tree.pop().unwrap();
                 match func {
-                    Expr::FunctionExpr(_, _, _, _) => methods.push(Box::new(func)),
-                    _ => panic!("Tried to add something that wasn't a function expr to a class method block")
+                    Expr(ExprKind::FunctionExpr(_, _, _, _), _) => methods.push(Box::new(func)),
+                    _ => {
+                        self.error_phrase::<()>("Only functions can be defined in a 'methods' block");
+                    }
                 }
                 len = self.tree.len();
             }
         }
+
+        if !self.stack.is_empty() || self.state != State::Empty {
+            self.stack.clear();
+            self.error_phrase::<()>("Expected a method body before '}'");
+        }
+
         // Push this to the tree without reducing (sketchy)
-        self.tree.push(Expr::ClassMethods(Box::new(ident), methods));
+        self.phrase_start = original_phrase;
+        self.push_statement(ExprKind::ClassMethods(Box::new(ident), methods));
         self.state = State::Empty;
+        Some(())
     }
 
-    fn expr_class_expr(&mut self) {
+    fn expr_class_expr(&mut self, open: &'a Token) -> Option<()> {
+        let base = self.stack.len();
         let mut fields = Vec::<Box<Expr>>::new();
-        let mut last_token_was_comma = true;
-        while let Some(token) = self.tokens.next() {
+        while let Some(token) = self.advance() {
             match token.0 {
                 TokenKind::RCurl => {
-                    if !last_token_was_comma {
-                        let expr = self.stack.pop().unwrap_or_else(|| {
-                            panic!("Expected a valid expr for struct fields");
-                        });
-                        fields.push(Box::new(expr));
+                    if let Some(expr) = self.pop_item(base, "Expected ',' between class fields")? {
+                        fields.push(expr);
                     }
                     break;
                 }
                 TokenKind::Comma => {
-                    dbg!(&self.stack);
-                    last_token_was_comma = true;
-                    let expr = self.stack.pop().unwrap_or_else(|| {
-                        panic!("Expected a valid expr for struct field");
-                    });
-                    fields.push(Box::new(expr));
+                    match self.pop_item(base, "Expected ',' between class fields")? {
+                        Some(expr) => fields.push(expr),
+                        None => return self.error_at("Expected a field before ','", token),
+                    }
                 }
-                _ => {
-                    self.parse_expr(token);
-                    last_token_was_comma = false;
+                TokenKind::SemiColon => {
+                    // Carry on as if it were a comma, it's obvious what was meant
+                    self.errors
+                        .new(
+                            ErrorClass::Error,
+                            ErrorKind::ParseError(String::from(
+                                "Class fields are separated by ',' not ';'",
+                            )),
+                            token.1 .0,
+                            token.1 .1,
+                        )
+                        .suggest(token.1 .0, token.1 .1, ",", "use ',' instead");
+                    if let Some(expr) = self.pop_item(base, "Expected ',' between class fields")? {
+                        fields.push(expr);
+                    }
+                }
+                TokenKind::EOF => {
+                    return self.unclosed("class definition", open, token);
                 }
+                _ => self.parse_expr(token)?,
             }
         }
-        self.stack.push(Expr::ClassFields(fields));
-        self.try_reduce();
+        let span = Span::from(&open.1).to(self.last_span());
+        self.stack.push(Expr(ExprKind::ClassFields(fields), span));
+        self.try_reduce()
     }
 
-    fn expr_import_args(&mut self) {
+    fn expr_import_args(&mut self) -> Option<()> {
+        let base = self.stack.len();
         let mut args = Vec::<Box<Expr>>::new();
-        while let Some(token) = self.tokens.next() {
-            dbg!(&self.stack);
-            dbg!(&args);
+        while let Some(token) = self.advance() {
             match token.0 {
                 TokenKind::SemiColon => {
-                    let expr = self.stack.pop().unwrap_or_else(|| {
-                        panic!("Expected a valid expr for import arg");
-                    });
-                    args.push(Box::new(expr));
+                    if let Some(expr) = self.pop_item(base, "Expected ',' between imports")? {
+                        args.push(expr);
+                    }
                     if args.is_empty() {
-                        panic!("Expected symbols to import from module!");
+                        return self.error_phrase("Expected symbols to import from module");
                     }
                     break;
                 }
-                TokenKind::Comma => {
-                    let expr = self.stack.pop().unwrap_or_else(|| {
-                        panic!("Expected a valid expr for import arg");
-                    });
-                    args.push(Box::new(expr));
+                TokenKind::Comma => match self.pop_item(base, "Expected ',' between imports")? {
+                    Some(expr) => args.push(expr),
+                    None => return self.error_at("Expected a symbol before ','", token),
+                },
+                TokenKind::EOF => {
+                    return self.error_at("Expected ';' after imported symbols", token);
                 }
-                _ => self.parse_expr(token),
+                _ => self.parse_expr(token)?,
             }
         }
-        self.stack.push(Expr::ImportArgs(args));
-        self.try_reduce();
+        let span = args[0].1.to(args[args.len() - 1].1);
+        self.stack.push(Expr(ExprKind::ImportArgs(args), span));
+        self.try_reduce()
     }
 
-    fn expr_function_call(&mut self) {
-        let args = self.stack.pop().unwrap_or_else(|| {
-            panic!("Expected a valid params for function call");
-        });
-        let ident = self.stack.pop().unwrap_or_else(|| {
-            panic!("Expected a valid identifier for function call");
-        });
-        self.stack
-            .push(Expr::FunctionCall(Box::new(ident), Box::new(args)));
+    fn expr_function_call(&mut self) -> Option<()> {
+        let args = self.pop("Expected arguments for function call")?;
+        let ident = self.pop("Expected a function name to call")?;
+        let span = ident.1.to(args.1);
+        self.stack.push(Expr(
+            ExprKind::FunctionCall(Box::new(ident), Box::new(args)),
+            span,
+        ));
+        Some(())
     }
 
-    fn expr_arguments(&mut self) {
+    fn expr_arguments(&mut self, open: &'a Token) -> Option<()> {
+        let base = self.stack.len();
+        let floor = std::mem::replace(&mut self.floor, base);
         let mut args = Vec::<Box<Expr>>::new();
-        while let Some(token) = self.tokens.next() {
+        // The name of the argument being parsed, once its ':' has been reached
+        let mut name: Option<Expr> = None;
+        while let Some(token) = self.advance() {
             match token.0 {
-                TokenKind::RPar => {
-                    if !args.is_empty() {
-                        let expr = self.stack.pop().unwrap_or_else(|| {
-                            panic!("Expected a valid expr for parameter");
-                        });
-                        args.push(Box::new(expr));
+                TokenKind::Colon => match self.stack.last() {
+                    Some(Expr(ExprKind::Ident(_), _))
+                        if self.stack.len() == base + 1 && name.is_none() =>
+                    {
+                        name = self.stack.pop();
+                    }
+                    _ => return self.error_at("Expected an argument name before ':'", token),
+                },
+                TokenKind::Comma | TokenKind::RPar => {
+                    let item = self.pop_item(base, "Expected ',' between arguments")?;
+                    let named = args
+                        .last()
+                        .is_some_and(|arg| matches!(arg.0, ExprKind::NamedArg(..)));
+                    match (name.take(), item) {
+                        (Some(name), Some(value)) => {
+                            let span = name.1.to(value.1);
+                            args.push(Box::new(Expr(
+                                ExprKind::NamedArg(Box::new(name), value),
+                                span,
+                            )));
+                        }
+                        (Some(_), None) => {
+                            return self.error_at("Expected a value after ':'", token)
+                        }
+                        (None, Some(value)) if named => return self.error(
+                            "Expected a named argument, positional arguments have to come first",
+                            value.1 .0,
+                            value.1 .1,
+                        ),
+                        (None, Some(value)) => args.push(value),
+                        (None, None) if token.0 == TokenKind::Comma => {
+                            return self.error_at("Expected an argument before ','", token)
+                        }
+                        (None, None) => {}
+                    }
+                    if token.0 == TokenKind::RPar {
+                        break;
                     }
-                    break;
                 }
-                TokenKind::Comma => {
-                    let expr = self.stack.pop().unwrap_or_else(|| {
-                        panic!("Expected a valid expr for parameter");
-                    });
-                    args.push(Box::new(expr));
+                TokenKind::SemiColon | TokenKind::EOF => {
+                    return self.unclosed("argument list", open, token);
                 }
-                _ => self.parse_expr(token),
+                _ => self.parse_expr(token)?,
             }
         }
-        self.stack.push(Expr::FunctionArgs(args));
+        self.floor = floor;
+        let span = Span::from(&open.1).to(self.last_span());
+        self.stack.push(Expr(ExprKind::FunctionArgs(args), span));
+        Some(())
     }
 
-    fn expr_parameter(&mut self) {
-        let ident = self.stack.pop().unwrap_or_else(|| {
-            panic!("Expected a valid LHS identifier for parameter");
-        });
+    fn expr_parameter(&mut self) -> Option<()> {
+        let ident = self.pop("Expected a name before ':'")?;
+        if !matches!(ident.0, ExprKind::Ident(_)) {
+            return self.error_phrase("Expected an identifier before ':'");
+        }
 
-        if let Some(token) = self.tokens.next() {
-            self.parse_expr(token);
-            // Make sure we keep parsing just in case the identifier is qualified
-            while let Some(token) = self.tokens.peek() {
-                if token.0 != TokenKind::Dot {
-                    break;
-                }
-                let token = self.tokens.next().unwrap();
-                self.parse_expr(token);
-            }
-            let typ = self.stack.pop().unwrap_or_else(|| {
-                panic!("Expected a valid RHS identifier for parameter");
-            });
-            self.stack
-                .push(Expr::Parameter(Box::new(ident), Box::new(typ)));
-        } else {
-            panic!("No RHS identifier for parameter")
+        let colon = self.last.unwrap();
+        let missing = ErrorKind::ParseError(String::from("Expected a type after ':'"));
+        let token = self.operand(missing, colon)?;
+        let base = self.stack.len();
+        self.parse_expr(token)?;
+        // Make sure we keep parsing just in case the identifier is qualified
+        while let Some(token) = self.tokens.peek() {
+            if token.0 != TokenKind::Dot {
+                break;
+            }
+            let token = self.advance().unwrap();
+            self.parse_expr(token)?;
+        }
+        if self.stack.len() != base + 1 {
+            return self.error_at("Expected a type after ':'", token);
         }
+        let typ = self.stack.pop().unwrap();
+        let span = ident.1.to(typ.1);
+        self.stack
+            .push(Expr(ExprKind::Parameter(Box::new(ident), Box::new(typ)), span));
+        Some(())
     }
 
-    fn expr_parameters(&mut self) {
+    fn expr_parameters(&mut self, open: &'a Token) -> Option<()> {
+        let base = self.stack.len();
         let mut params = Vec::<Box<Expr>>::new();
-        let mut empty_params = true;
-        while let Some(token) = self.tokens.next() {
+        while let Some(token) = self.advance() {
             match token.0 {
                 TokenKind::RPar => {
-                    if !empty_params {
-                        let expr = self.stack.pop().unwrap_or_else(|| {
-                            panic!("Expected a valid expr for parameter");
-                        });
-                        params.push(Box::new(expr));
+                    if let Some(expr) = self.pop_item(base, "Expected ',' between parameters")? {
+                        params.push(expr);
                     }
                     break;
                 }
-                TokenKind::Comma => {
-                    let expr = self.stack.pop().unwrap_or_else(|| {
-                        panic!("Expected a valid expr for parameter");
-                    });
-                    params.push(Box::new(expr));
-                }
-                _ => {
-                    self.parse_expr(token);
-                    empty_params = false;
+                TokenKind::Comma => match self.pop_item(base, "Expected ',' between parameters")?
+                {
+                    Some(expr) => params.push(expr),
+                    None => return self.error_at("Expected a parameter before ','", token),
+                },
+                TokenKind::SemiColon | TokenKind::LCurl | TokenKind::EOF => {
+                    return self.unclosed("parameter list", open, token);
                 }
+                _ => self.parse_expr(token)?,
             }
         }
-        self.stack.push(Expr::FunctionArgs(params));
+        let span = Span::from(&open.1).to(self.last_span());
+        self.stack.push(Expr(ExprKind::FunctionArgs(params), span));
         self.state = State::PostParamFunctionExpr;
+        Some(())
     }
 
-    fn expr_parens(&mut self) {
-        while let Some(token) = self.tokens.next() {
-            if token.0 == TokenKind::RPar {
-                break;
+    fn expr_parens(&mut self, open: &'a Token) -> Option<()> {
+        let base = self.stack.len();
+        let floor = std::mem::replace(&mut self.floor, base);
+        while let Some(token) = self.advance() {
+            match token.0 {
+                TokenKind::RPar => break,
+                TokenKind::SemiColon | TokenKind::EOF => {
+                    return self.unclosed("parentheses", open, token);
+                }
+                _ => self.parse_expr(token)?,
             }
-            self.parse_expr(token);
         }
-        let expr = self.stack.pop().unwrap_or_else(|| {
-            panic!("Expected a valid expression for ParensExpr");
-        });
-        // This will only push the last thing on the stack!!! If something went wrong and it wasn't
-        // reduced to just 1 Expr then there will be an undetected parse error...
-        // TODO: Fix that ^^^^^^^^
-        self.stack.push(Expr::ParensExpr(Box::new(expr)));
+        self.floor = floor;
+        if self.stack.len() != base + 1 {
+            return self.error_phrase("Expected a single expression inside parentheses");
+        }
+        let expr = self.stack.pop().unwrap();
+        let span = Span::from(&open.1).to(self.last_span());
+        self.stack
+            .push(Expr(ExprKind::ParensExpr(Box::new(expr)), span));
+        Some(())
     }
 
-    fn expr_block(&mut self) {
+    fn expr_block(&mut self, open: &'a Token) -> Option<()> {
         // Effectively hi-jacking the rest of the parser, taking things off the tree and sticking them
         // into the block expression
         let mut len = self.tree.len();
         let mut block = Vec::<Box<Expr>>::new();
         let original_stack: Vec<Expr> = self.stack.drain(0..).collect();
-        let original_state = self.state.clone();
-        while let Some(token) = self.tokens.next() {
-            if token.0 == TokenKind::RCurl {
-                break;
+        let original_state = self.state;
+        let original_phrase = self.phrase_start;
+        let original_floor = std::mem::replace(&mut self.floor, 0);
+        self.state = State::Empty;
+        while let Some(token) = self.advance() {
+            match token.0 {
+                TokenKind::RCurl => break,
+                TokenKind::EOF => return self.unclosed("block", open, token),
+                _ => {
+                    self.mark_phrase(token);
+                    if self.parse_expr(token).is_none() {
+                        self.synchronize();
+                    }
+                }
             }
-            self.parse_expr(token);
             // When something was added to the tree
             if self.tree.len() > len {
                 // Yank and shove in the BlockExpr
@@ -568,84 +938,674 @@ impl<'a, Iter: Iterator<Item = &'a Token<'a>>> Parser<'a, Iter> {
                 len = self.tree.len();
             }
         }
+
+        if !self.stack.is_empty() || self.state != State::Empty {
+            self.error_phrase::<()>("Expected ';' before '}'");
+        }
+
         // Push the original stack
+        let span = Span::from(&open.1).to(self.last_span());
         self.stack = original_stack;
-        self.stack.push(Expr::BlockExpr(block));
+        self.stack.push(Expr(ExprKind::BlockExpr(block), span));
         self.state = original_state;
+        self.phrase_start = original_phrase;
+        self.floor = original_floor;
         match self.state {
             State::PostParamFunctionExpr => self.try_reduce(),
-            _ => {}
+            _ => Some(()),
         }
     }
 
-    fn expr_qmark(&mut self) {
-        let ident = self.stack.pop().unwrap_or_else(|| {
-            panic!("Expected a valid LHS expression for QMark");
-        });
-        self.stack.push(Expr::QMark(Box::new(ident)));
+    fn expr_map(&mut self, open: &'a Token) -> Option<()> {
+        let base = self.stack.len();
+        let floor = std::mem::replace(&mut self.floor, base);
+        let mut entries = Vec::<(Box<Expr>, Box<Expr>)>::new();
+        // The key of the entry being parsed, once its ':' has been reached
+        let mut key: Option<Box<Expr>> = None;
+        self.open_maps += 1;
+        while let Some(token) = self.advance() {
+            match token.0 {
+                TokenKind::Colon => {
+                    if key.is_some() {
+                        return self.error_at("Expected ',' between map entries", token);
+                    }
+                    match self.pop_item(base, "Expected ':' between a key and its value")? {
+                        Some(expr) => key = Some(expr),
+                        None => return self.error_at("Expected a key before ':'", token),
+                    }
+                }
+                TokenKind::Comma | TokenKind::RCurl => {
+                    if token.0 == TokenKind::RCurl {
+                        self.open_maps -= 1;
+                    }
+                    let item = self.pop_item(base, "Expected ',' between map entries")?;
+                    match (key.take(), item) {
+                        (Some(key), Some(value)) => entries.push((key, value)),
+                        (Some(_), None) => return self.error_at("Expected a value after ':'", token),
+                        (None, Some(_)) => {
+                            return self.error_at("Expected ':' and a value after the key", token)
+                        }
+                        (None, None) if token.0 == TokenKind::Comma => {
+                            return self.error_at("Expected a map entry before ','", token)
+                        }
+                        (None, None) => {}
+                    }
+                    if token.0 == TokenKind::RCurl {
+                        break;
+                    }
+                }
+                TokenKind::SemiColon | TokenKind::EOF => {
+                    // The statement is over, so are any maps it left open
+                    self.open_maps = 0;
+                    return self.unclosed("map", open, token);
+                }
+                _ => self.parse_expr(token)?,
+            }
+        }
+        self.floor = floor;
+        let span = Span::from(&open.1).to(self.last_span());
+        self.stack.push(Expr(ExprKind::MapExpr(entries), span));
+        Some(())
     }
 
-    fn expr_binaryop(&mut self, operator: BinaryOperator) {
-        let left = self.stack.pop().unwrap_or_else(|| {
-            panic!("Expected a valid LHS expression for BinOp");
-        });
-
-        if let Some(token) = self.tokens.next() {
-            self.parse_expr(token);
-            // Make sure we keep parsing just in case an identifier is qualified
-            while let Some(token) = self.tokens.peek() {
-                if token.0 != TokenKind::Dot {
+    fn expr_list(&mut self, open: &'a Token) -> Option<()> {
+        let base = self.stack.len();
+        let floor = std::mem::replace(&mut self.floor, base);
+        let mut items = Vec::<Box<Expr>>::new();
+        while let Some(token) = self.advance() {
+            match token.0 {
+                TokenKind::RBrac => {
+                    if let Some(expr) = self.pop_item(base, "Expected ',' between list items")? {
+                        items.push(expr);
+                    }
                     break;
                 }
-                let token = self.tokens.next().unwrap();
-                self.parse_expr(token);
-            }
-            let right = self.stack.pop().unwrap_or_else(|| {
-                panic!("Expected a valid RHS expression for BinOp");
-            });
-            self.stack
-                .push(Expr::BinaryExpr(Box::new(left), Box::new(right), operator));
-        } else {
-            panic!("No RHS expression for BinOp")
+                TokenKind::Comma => match self.pop_item(base, "Expected ',' between list items")? {
+                    Some(expr) => items.push(expr),
+                    None => return self.error_at("Expected a list item before ','", token),
+                },
+                TokenKind::SemiColon | TokenKind::EOF => {
+                    return self.unclosed("list", open, token);
+                }
+                _ => self.parse_expr(token)?,
+            }
         }
+        self.floor = floor;
+        let span = Span::from(&open.1).to(self.last_span());
+        self.stack.push(Expr(ExprKind::ListExpr(items), span));
+        Some(())
     }
 
-    fn expr_qualified_ident(&mut self) {
-        let left = self.stack.pop().unwrap_or_else(|| {
-            panic!("Expected a valid LHS identifier for QI");
-        });
+    fn expr_index(&mut self, open: &'a Token) -> Option<()> {
+        let value = self.stack.pop().unwrap();
+        let base = self.stack.len();
+        let floor = std::mem::replace(&mut self.floor, base);
+        while let Some(token) = self.advance() {
+            match token.0 {
+                TokenKind::RBrac => break,
+                TokenKind::SemiColon | TokenKind::EOF => {
+                    return self.unclosed("index", open, token);
+                }
+                _ => self.parse_expr(token)?,
+            }
+        }
+        self.floor = floor;
+        if self.stack.len() != base + 1 {
+            return self.error_phrase("Expected a single expression inside brackets");
+        }
+        let index = self.stack.pop().unwrap();
+        let span = value.1.to(self.last_span());
+        self.stack.push(Expr(
+            ExprKind::IndexExpr(Box::new(value), Box::new(index)),
+            span,
+        ));
+        Some(())
+    }
 
-        if let Some(token) = self.tokens.next() {
-            self.parse_expr(token);
-            let right = self.stack.pop().unwrap_or_else(|| {
-                panic!("Expected a valid RHS identifier for QI");
-            });
-            self.stack
-                .push(Expr::QualifiedIdent(Box::new(left), Box::new(right)));
-        } else {
-            panic!("No RHS identifier for QualifiedIdent")
+    fn expr_qmark(&mut self, qmark: &'a Token) -> Option<()> {
+        let ident = self.pop("Expected an expression before '?'")?;
+        let span = ident.1.to(Span::from(&qmark.1));
+        self.stack.push(Expr(ExprKind::QMark(Box::new(ident)), span));
+        Some(())
+    }
+
+    fn expr_binaryop(&mut self, operator: BinaryOperator, op: &'a Token) -> Option<()> {
+        if self.stack.len() == self.floor {
+            return match operator {
+                BinaryOperator::Minus => self.expr_negate(op),
+                _ => self.report_at(missing_operand(op, true), op),
+            };
         }
+        let left = self.stack.pop().unwrap();
+        let right = self.expr_operand(op)?;
+        let span = left.1.to(right.1);
+        self.stack.push(Expr(
+            ExprKind::BinaryExpr(Box::new(left), Box::new(right), operator),
+            span,
+        ));
+        Some(())
     }
 
-    fn expr_ident(&mut self, name: &'a str) {
-        self.stack.push(Expr::Ident(name.to_string()));
+    /// A '-' with nothing before it, which negates what follows
+    /// A number right after it is read as a negative literal, so the smallest int can be written
+    fn expr_negate(&mut self, minus: &'a Token) -> Option<()> {
+        if let Some(TokenKind::Number(n)) = self.tokens.peek().map(|t| &t.0) {
+            let token = self.advance().unwrap();
+            let span = Span::from(&minus.1).to(Span::from(&token.1));
+            return self.expr_number(&format!("-{}", n), span);
+        }
+        let value = self.expr_operand(minus)?;
+        let span = Span::from(&minus.1).to(value.1);
+        self.stack
+            .push(Expr(ExprKind::Negate(Box::new(value)), span));
+        Some(())
     }
 
-    fn expr_str(&mut self, string: &'a str) {
-        self.stack.push(Expr::Str(string.to_string()));
+    /// Parses the value after the operator `op`, along with anything that qualifies, calls or
+    /// indexes it
+    fn expr_operand(&mut self, op: &'a Token) -> Option<Expr> {
+        let token = self.operand(missing_operand(op, false), op)?;
+        let base = self.stack.len();
+        let floor = std::mem::replace(&mut self.floor, base);
+        self.parse_expr(token)?;
+        // Make sure we keep parsing just in case an identifier is qualified, called or indexed
+        while let Some(token) = self.tokens.peek() {
+            let call = token.0 == TokenKind::LPar
+                && matches!(self.last.map(|t| &t.0), Some(TokenKind::Ident(_)));
+            if token.0 != TokenKind::Dot && token.0 != TokenKind::LBrac && !call {
+                break;
+            }
+            let token = self.advance().unwrap();
+            self.parse_expr(token)?;
+        }
+        self.floor = floor;
+        if self.stack.len() != base + 1 {
+            return self.report_at(missing_operand(op, false), op);
+        }
+        self.stack.pop()
     }
 
-    fn expr_number(&mut self, number: &'a str) {
-        if number.contains('.') {
-            match number.parse::<f32>() {
-                Ok(v) => self.stack.push(Expr::Float(v)),
-                Err(_) => panic!("Error parsing integer"),
-            };
+    fn expr_qualified_ident(&mut self, dot: &'a Token) -> Option<()> {
+        if self.stack.len() == self.floor {
+            return self.report_at(missing_operand(dot, true), dot);
+        }
+        let left = self.stack.pop().unwrap();
+
+        let token = self.advance()?;
+        match token.0 {
+            TokenKind::Ident(i) => {
+                let right = Expr(ExprKind::Ident(i.to_string()), Span::from(&token.1));
+                let span = left.1.to(right.1);
+                self.stack.push(Expr(
+                    ExprKind::QualifiedIdent(Box::new(left), Box::new(right)),
+                    span,
+                ));
+                Some(())
+            }
+            _ => self.report_at(
+                ErrorKind::UnexpectedToken {
+                    found: token.0.to_string(),
+                    expected: Some("an identifier after '.'"),
+                },
+                token,
+            ),
+        }
+    }
+
+    fn expr_ident(&mut self, name: &'a str, token: &'a Token) -> Option<()> {
+        self.stack
+            .push(Expr(ExprKind::Ident(name.to_string()), Span::from(&token.1)));
+        Some(())
+    }
+
+    fn expr_str(&mut self, string: &'a str, token: &'a Token) -> Option<()> {
+        self.stack
+            .push(Expr(ExprKind::Str(string.to_string()), Span::from(&token.1)));
+        Some(())
+    }
+
+    fn expr_regex(&mut self, pattern: &'a str, token: &'a Token) -> Option<()> {
+        self.stack.push(Expr(
+            ExprKind::Regex(pattern.to_string()),
+            Span::from(&token.1),
+        ));
+        Some(())
+    }
+
+    fn expr_number(&mut self, number: &str, span: Span) -> Option<()> {
+        // Underscores are only there for readability
+        let digits = number.replace('_', "");
+        let float = number.contains('.');
+        let kind = if float {
+            digits.parse::<f32>().ok().map(ExprKind::Float)
         } else {
-            match number.parse::<i32>() {
-                Ok(v) => self.stack.push(Expr::Integer(v)),
-                Err(_) => panic!("Error parsing integer"),
-            };
+            digits.parse::<i32>().ok().map(ExprKind::Integer)
+        };
+
+        match kind {
+            Some(kind) => {
+                self.stack.push(Expr(kind, span));
+                Some(())
+            }
+            None => {
+                self.errors.new(
+                    ErrorClass::Error,
+                    ErrorKind::InvalidNumber {
+                        literal: number.to_string(),
+                        float,
+                    },
+                    span.0,
+                    span.1,
+                );
+                None
+            }
+        }
+    }
+}
+
+/// The error for an operator, or the '.' of a qualified name, that's missing the value on one side
+fn missing_operand(op: &Token, before: bool) -> ErrorKind {
+    ErrorKind::MissingOperand {
+        operator: op.0.to_string(),
+        before,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        errors::{
+            error::{Errors, SkError},
+            formatter::Formatter,
+        },
+        frontend::lexer::Lexer,
+    };
+
+    /// Some text about the source, and the start and end of the span it's about
+    type Spanned = (String, (usize, usize));
+
+    /// Parses `source` and hands the tree and every error reported to `inspect`
+    fn with_parsed<T>(source: &str, inspect: impl FnOnce(&[Expr], &[SkError]) -> T) -> T {
+        let source = source.to_string();
+        let formatter = Formatter::initialize("test", &source);
+        let mut errors = Errors::initialize(&formatter);
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize(&mut errors);
+        let mut parser = Parser::new(&mut errors, tokens.iter());
+        parser.parse();
+        let tree = std::mem::take(&mut parser.tree);
+        inspect(&tree, errors.errors())
+    }
+
+    /// Parses `source`, giving each statement that made it into the tree as an s-expression,
+    /// and the message and span of every error
+    fn parse(source: &str) -> (Vec<String>, Vec<Spanned>) {
+        with_parsed(source, |tree, errors| {
+            let errors = errors
+                .iter()
+                .map(|err| (err.kind.to_string(), (err.span.start, err.span.end)))
+                .collect();
+            (tree.iter().map(sexp).collect(), errors)
+        })
+    }
+
+    /// The messages of the errors in `source`
+    fn messages(source: &str) -> Vec<String> {
+        parse(source).1.into_iter().map(|(msg, _)| msg).collect()
+    }
+
+    fn sexp(expr: &Expr) -> String {
+        let list = |items: &[Box<Expr>]| {
+            let items: Vec<_> = items.iter().map(|e| sexp(e)).collect();
+            format!("[{}]", items.join(" "))
+        };
+        match &expr.0 {
+            ExprKind::Integer(i) => i.to_string(),
+            ExprKind::Float(x) => format!("{:?}", x),
+            ExprKind::Str(s) => format!("{:?}", s),
+            ExprKind::Ident(name) => name.clone(),
+            ExprKind::QualifiedIdent(left, right) => format!("{}.{}", sexp(left), sexp(right)),
+            ExprKind::ParensExpr(inner) => sexp(inner),
+            ExprKind::BinaryExpr(left, right, op) => {
+                format!("({:?} {} {})", op, sexp(left), sexp(right))
+            }
+            ExprKind::Negate(inner) => format!("(neg {})", sexp(inner)),
+            ExprKind::FunctionCall(callee, args) => {
+                format!("(call {} {})", sexp(callee), sexp(args))
+            }
+            ExprKind::FunctionArgs(items)
+            | ExprKind::BlockExpr(items)
+            | ExprKind::ListExpr(items) => list(items),
+            ExprKind::MapExpr(entries) => {
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", sexp(k), sexp(v)))
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            ExprKind::NamedArg(name, value) => format!("{}: {}", sexp(name), sexp(value)),
+            ExprKind::PrintExpr(inner) => format!("($ {})", sexp(inner)),
+            ExprKind::ReturnExpr(inner) => format!("(return {})", sexp(inner)),
+            ExprKind::VariableExpr(name, _, value) => {
+                format!("(var {} {})", sexp(name), sexp(value))
+            }
+            ExprKind::ConstExpr(name, _, value) => {
+                format!("(const {} {})", sexp(name), sexp(value))
+            }
+            ExprKind::MutateExpr(name, value) => format!("(-> {} {})", sexp(name), sexp(value)),
+            ExprKind::FunctionExpr(name, params, _, body) => {
+                format!("(def {} {} {})", sexp(name), sexp(params), sexp(body))
+            }
+            ExprKind::Parameter(name, typ) => format!("{}: {}", sexp(name), sexp(typ)),
+            other => format!("{:?}", other),
         }
     }
+
+    #[test]
+    fn statements() {
+        let (tree, errors) = parse(
+            "var a = 1 + 2;\na -> f(a, 2).b;\n$ (a - 1) / 2;\ndef f(x: int): int { return x; }\n",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            tree,
+            [
+                "(var a (Plus 1 2))",
+                "(-> a (call f [a 2]).b)",
+                "($ (Divide (Minus a 1) 2))",
+                "(def f [x: int] [(return x)])",
+            ]
+        );
+    }
+
+    #[test]
+    fn recovery_resumes_at_the_next_statement() {
+        let (tree, errors) = parse("var a = ;\nvar b = 2;\n$ 1 +;\n$ b;\n");
+        assert_eq!(tree, ["(var b 2)", "($ b)"]);
+        assert_eq!(
+            errors,
+            [
+                (
+                    "Expected a variable of the form 'var name: type = value;'".to_string(),
+                    (0, 9)
+                ),
+                ("Expected an expression after '+'".to_string(), (25, 26)),
+            ]
+        );
+
+        // A malformed definition is skipped up to its closing '}'
+        let (tree, errors) = parse("def f(x: int { $ x; }\n$ 3;\n");
+        assert_eq!(tree, ["($ 3)"]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn messages_and_spans() {
+        assert_eq!(
+            parse("f(1, 2;\n").1,
+            [("Expected ')' to close argument list".to_string(), (6, 7))]
+        );
+        assert_eq!(
+            parse("x = 2;").1,
+            [(
+                "Existing variables are mutated with '->', try 'x -> ...'".to_string(),
+                (2, 3)
+            )]
+        );
+        assert_eq!(messages("}}; $ 1;"), ["Unmatched '}'", "Unmatched '}'"]);
+        assert_eq!(messages("$ 1\n"), ["Expected ';' before end of file"]);
+        assert_eq!(
+            messages("99999999999;"),
+            ["Invalid integer literal '99999999999'"]
+        );
+        assert_eq!(
+            messages("$ (1 2);"),
+            ["Expected a single expression inside parentheses"]
+        );
+    }
+
+    #[test]
+    fn malformed_input_never_panics() {
+        let sources = [
+            "(((",
+            ")))",
+            "{{{",
+            "a.;",
+            "a..b;",
+            "var = 1;",
+            "var x: = 1;",
+            "const;",
+            "def;",
+            "def f(,) {}",
+            "def f(x: int) { return; }",
+            "from import x;",
+            "from m import ,;",
+            "class { }",
+            "class A { a: int; b: }",
+            "methods X { 1; }",
+            "methods { def }",
+            "$ * 2;",
+            "f(1,, 2);",
+            "-> 1;",
+            "directive;",
+            "? ? ?",
+            "\"unterminated",
+        ];
+        for source in sources {
+            assert!(!messages(source).is_empty(), "no error for {:?}", source);
+        }
+    }
+
+    /// The span of every node in `expr`, outermost first, as the source text it covers
+    fn spans<'s>(source: &'s str, expr: &Expr, out: &mut Vec<&'s str>) {
+        out.push(&source[expr.1 .0..=expr.1 .1]);
+        match &expr.0 {
+            ExprKind::BinaryExpr(left, right, _)
+            | ExprKind::QualifiedIdent(left, right)
+            | ExprKind::FunctionCall(left, right)
+            | ExprKind::MutateExpr(left, right)
+            | ExprKind::VariableExpr(left, None, right) => {
+                spans(source, left, out);
+                spans(source, right, out);
+            }
+            ExprKind::ParensExpr(inner) | ExprKind::PrintExpr(inner) => spans(source, inner, out),
+            ExprKind::FunctionArgs(items) => {
+                for item in items {
+                    spans(source, item, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn tree_spans(source: &str) -> Vec<&str> {
+        with_parsed(source, |tree, _| {
+            let mut out = Vec::new();
+            for expr in tree {
+                spans(source, expr, &mut out);
+            }
+            out
+        })
+    }
+
+    #[test]
+    fn compound_spans_cover_their_parts() {
+        assert_eq!(
+            tree_spans("var total = (a + b) * c;"),
+            [
+                "var total = (a + b) * c;",
+                "total",
+                "(a + b) * c",
+                "(a + b)",
+                "a + b",
+                "a",
+                "b",
+                "c"
+            ]
+        );
+        assert_eq!(
+            tree_spans("$ point.x;\nf(1, g(2));"),
+            [
+                "$ point.x;",
+                "point.x",
+                "point",
+                "x",
+                "f(1, g(2))",
+                "f",
+                "(1, g(2))",
+                "1",
+                "g(2)",
+                "g",
+                "(2)",
+                "2"
+            ]
+        );
+    }
+
+    /// The replacement and span of every fix suggested for `source`
+    fn suggestions(source: &str) -> Vec<Spanned> {
+        with_parsed(source, |_, errors| {
+            errors
+                .iter()
+                .flat_map(|err| &err.suggestions)
+                .map(|s| (s.replacement.clone(), (s.span.start, s.span.end)))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn suggested_fixes() {
+        assert_eq!(suggestions("x = 2;"), [("->".to_string(), (2, 3))]);
+        assert_eq!(suggestions("var a = 1\n$ a;"), [(";".to_string(), (9, 9))]);
+        assert_eq!(
+            suggestions("class A { a: int; b: int }"),
+            [(",".to_string(), (16, 17))]
+        );
+    }
+
+    /// The code of every error reported for `source`
+    fn codes(source: &str) -> Vec<&'static str> {
+        with_parsed(source, |_, errors| {
+            errors.iter().map(|err| err.kind.code()).collect()
+        })
+    }
+
+    #[test]
+    fn typed_kinds() {
+        assert_eq!(codes("$ \"open;")[0], "SK0002");
+        assert_eq!(codes("$ 1 @ 2;")[0], "SK0003");
+        assert_eq!(codes("99999999999;"), ["SK0004"]);
+        assert_eq!(codes("f(1, 2;"), ["SK0005"]);
+        assert_eq!(codes("var a = 1\n$ a;"), ["SK0006"]);
+        assert_eq!(codes("$ 1 +;"), ["SK0008"]);
+    }
+
+    #[test]
+    fn operator_without_left_operand() {
+        // Nothing before an operator inside brackets can be its left operand, the callee or the
+        // name being declared included
+        assert_eq!(
+            parse("f(1, + 2);").1,
+            [("Expected an expression before '+'".to_string(), (5, 6))]
+        );
+        assert_eq!(messages("f(.x);"), ["Expected an expression before '.'"]);
+        assert_eq!(messages("$ (* 2);"), ["Expected an expression before '*'"]);
+        assert_eq!(messages("$ a[/ 2];"), ["Expected an expression before '/'"]);
+        assert_eq!(
+            messages("var a = * 2;"),
+            ["Expected an expression before '*'"]
+        );
+        assert_eq!(messages("a -> + 1;"), ["Expected an expression before '+'"]);
+        assert_eq!(
+            parse("f(a.b, c - 1);\na -> a * 2;").0,
+            ["(call f [a.b (Minus c 1)])", "(-> a (Multiply a 2))"]
+        );
+    }
+
+    #[test]
+    fn leading_minus() {
+        let (tree, errors) = parse(
+            "max(1, -2);\nf(-x, 1 - 2, by: -1);\nvar a = -1;\na -> -a;\n$ 3 - -2;\n\
+             $ -(a + 1) * 2;\n$ -s.len();\n$ -2147483648;\n$ -0.0;\n",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            tree,
+            [
+                "(call max [1 -2])",
+                "(call f [(neg x) (Minus 1 2) by: -1])",
+                "(var a -1)",
+                "(-> a (neg a))",
+                "($ (Minus 3 -2))",
+                "($ (Multiply (neg (Plus a 1)) 2))",
+                "($ (neg (call s.len [])))",
+                "($ -2147483648)",
+                "($ -0.0)",
+            ]
+        );
+        // A negative literal covers its sign
+        assert_eq!(tree_spans("$ -12;"), ["$ -12;", "-12"]);
+    }
+
+    #[test]
+    fn typed_errors() {
+        // Errors that carry a token or name have a kind of their own
+        let first = |source: &str| {
+            let code = codes(source)[0];
+            format!("{}: {}", code, messages(source)[0])
+        };
+        assert_eq!(first("$ 2 * ;"), "SK0008: Expected an expression after '*'");
+        assert_eq!(
+            first("f(, * 2);"),
+            "SK0001: Expected an argument before ','"
+        );
+        assert_eq!(
+            first("$ max(1, * 2);"),
+            "SK0008: Expected an expression before '*'"
+        );
+        assert_eq!(
+            first("var a = 1;\na = 2;"),
+            "SK0009: Existing variables are mutated with '->', try 'a -> ...'"
+        );
+        assert_eq!(first("var x = 1 );"), "SK0001: Unmatched ')'");
+        assert_eq!(
+            first("$ a.1;"),
+            "SK0007: Expected an identifier after '.', found number '1'"
+        );
+        assert_eq!(
+            first("class P { x: int } methods P { 1; }"),
+            "SK0007: Expected a method definition, found number '1'"
+        );
+        assert_eq!(first("$ 1, 2;"), "SK0007: Unexpected ','");
+    }
+
+    #[test]
+    fn leading_minus_in_literals() {
+        // Items of list and map literals can't take an earlier item, or the name being
+        // declared, as an operand either
+        let (tree, errors) = parse(
+            "var a = [-1];\n$ [0.1, 1.5, -0.0];\n$ [a, -a, 1 - a];\nvar m = {-1: -2};\n\
+             f([1], on: [-1, 2]);\n",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            tree,
+            [
+                "(var a [-1])",
+                "($ [0.1 1.5 -0.0])",
+                "($ [a (neg a) (Minus 1 a)])",
+                "(var m {-1: -2})",
+                "(call f [[1] on: [-1 2]])",
+            ]
+        );
+        assert_eq!(
+            messages("var a = [* 2];"),
+            ["Expected an expression before '*'"]
+        );
+        assert_eq!(
+            messages("$ {1: * 2};"),
+            ["Expected an expression before '*'"]
+        );
+    }
 }
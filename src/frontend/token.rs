@@ -1,10 +1,12 @@
+use std::fmt;
+
 /// Token stores the token variant and position in the source code
 #[derive(Debug)]
 pub struct Token<'a>(pub TokenKind<'a>, pub TokenSpan);
 
 impl<'a> Token<'a> {
     pub fn new(kind: TokenKind<'a>, begin: usize, end: usize) -> Self {
-        Self(kind, TokenSpan(begin + 1, end - 1))
+        Self(kind, TokenSpan(begin, end - 1))
     }
 }
 
@@ -58,6 +60,8 @@ pub enum TokenKind<'a> {
     Ident(&'a str),
     Number(&'a str),
     Str(&'a str),
+    /// A pattern literal, `r"..."`
+    Regex(&'a str),
 
     // Keywords
     Var,
@@ -79,3 +83,59 @@ pub enum TokenKind<'a> {
     // Other
     EOF,
 }
+
+impl<'a> fmt::Display for TokenKind<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::LPar => write!(f, "'('"),
+            TokenKind::RPar => write!(f, "')'"),
+            TokenKind::LBrac => write!(f, "'['"),
+            TokenKind::RBrac => write!(f, "']'"),
+            TokenKind::LCurl => write!(f, "'{{'"),
+            TokenKind::RCurl => write!(f, "'}}'"),
+            TokenKind::Arrow => write!(f, "'->'"),
+            TokenKind::Colon => write!(f, "':'"),
+            TokenKind::ColonColon => write!(f, "'::'"),
+            TokenKind::SemiColon => write!(f, "';'"),
+            TokenKind::Dot => write!(f, "'.'"),
+            TokenKind::DotDot => write!(f, "'..'"),
+            TokenKind::Print => write!(f, "'$'"),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::QMark => write!(f, "'?'"),
+            TokenKind::Equal => write!(f, "'='"),
+            TokenKind::EqualEqual => write!(f, "'=='"),
+            TokenKind::Bang => write!(f, "'!'"),
+            TokenKind::BangEqual => write!(f, "'!='"),
+            TokenKind::Less => write!(f, "'<'"),
+            TokenKind::LessEqual => write!(f, "'<='"),
+            TokenKind::More => write!(f, "'>'"),
+            TokenKind::MoreEqual => write!(f, "'>='"),
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Minus => write!(f, "'-'"),
+            TokenKind::Star => write!(f, "'*'"),
+            TokenKind::Slash => write!(f, "'/'"),
+            TokenKind::Modulo => write!(f, "'%'"),
+            TokenKind::Exponent => write!(f, "'^'"),
+            TokenKind::Ident(i) => write!(f, "identifier '{}'", i),
+            TokenKind::Number(n) => write!(f, "number '{}'", n),
+            TokenKind::Str(s) => write!(f, "string \"{}\"", s),
+            TokenKind::Regex(s) => write!(f, "pattern r\"{}\"", s),
+            TokenKind::Var => write!(f, "'var'"),
+            TokenKind::Const => write!(f, "'const'"),
+            TokenKind::If => write!(f, "'if'"),
+            TokenKind::Else => write!(f, "'else'"),
+            TokenKind::Elif => write!(f, "'elif'"),
+            TokenKind::Def => write!(f, "'def'"),
+            TokenKind::From => write!(f, "'from'"),
+            TokenKind::Import => write!(f, "'import'"),
+            TokenKind::Is => write!(f, "'is'"),
+            TokenKind::Class => write!(f, "'class'"),
+            TokenKind::Enum => write!(f, "'enum'"),
+            TokenKind::Return => write!(f, "'return'"),
+            TokenKind::Directive => write!(f, "'directive'"),
+            TokenKind::Methods => write!(f, "'methods'"),
+            TokenKind::This => write!(f, "'this'"),
+            TokenKind::EOF => write!(f, "end of file"),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Starkey, a data-science programming language made to make the manipulation and handling of
+//! data precise, deliberate, and free of error.
+//!
+//! `Engine` is the way to embed it: compile and run source, read and set globals, and give
+//! programs Rust functions to call.
+//!
+//! ```
+//! use starkey::Engine;
+//!
+//! let mut engine = Engine::new();
+//! engine.register_fn("double", |i: i32| i * 2);
+//! engine.register_fn("ratio", |a: f32, b: f32| {
+//!     if b == 0.0 {
+//!         Err("the ratio of anything to zero is undefined")
+//!     } else {
+//!         Ok(a / b)
+//!     }
+//! });
+//! engine.set("base", 20).unwrap();
+//!
+//! let source = "from host import double, ratio;\nvar answer = double(base) + 2;";
+//! engine.eval("example", source).unwrap();
+//! assert_eq!(engine.get::<i32>("answer").unwrap(), 42);
+//!
+//! // Arguments are checked against the closure's parameter types
+//! let err = engine.eval("wrong", "double(\"two\");").unwrap_err();
+//! assert_eq!(err.errors()[0].kind.code(), "SK0200");
+//! ```
+
+pub mod backend;
+pub mod errors;
+pub mod frontend;
+
+mod engine;
+
+pub use backend::{
+    convert::{ConversionError, FromValue, IntoValue},
+    native::{HostFunction, HostReturn},
+    value::{Type, Value},
+};
+pub use engine::{Diagnostics, Engine, GlobalError, Program, HOST_MODULE};
+pub use errors::error::ErrorFormat;
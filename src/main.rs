@@ -1,40 +1,178 @@
-use std::{env, fs};
+use std::{
+    env, fs,
+    io::{self, Read},
+    process::ExitCode,
+};
 
-mod errors;
-mod frontend;
-mod backend;
+use cli::{Action, Command, Input};
+use starkey::{
+    errors::{
+        self,
+        error::{ErrorClass, ErrorFormat, ErrorKind, Errors},
+        formatter::Formatter,
+    },
+    frontend::{lexer::Lexer, parser::Parser},
+    Engine,
+};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let dbga = cfg!(debug_assertions);
+mod cli;
+mod repl;
 
-    if args.len() == 2 && dbga {
-        // File path is specified
-        let path = &args[1];
-        let source = fs::read_to_string(&path).expect("Error reading source file");
+fn main() -> ExitCode {
+    let action = match cli::parse_args(env::args().skip(1)) {
+        Ok(action) => action,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            return ExitCode::from(cli::EXIT_USAGE);
+        }
+    };
 
-        // Initialize errors
-        let formatter = errors::formatter::Formatter::initialize(&source);
-        let mut error_handler = errors::error::Errors::initialize(&formatter);
+    match action {
+        Action::Help => {
+            print!("{}", cli::USAGE);
+            ExitCode::from(cli::EXIT_SUCCESS)
+        }
+        Action::Version => {
+            println!("starkey {}", env!("CARGO_PKG_VERSION"));
+            ExitCode::from(cli::EXIT_SUCCESS)
+        }
+        Action::Explain(code) => match errors::explain::explain(&code) {
+            Some(text) => {
+                println!("{}", text);
+                ExitCode::from(cli::EXIT_SUCCESS)
+            }
+            None => {
+                eprintln!("error: no explanation for '{}'", code);
+                ExitCode::from(cli::EXIT_USAGE)
+            }
+        },
+        Action::Repl { format } => {
+            // The whole session runs on the evaluator's thread, since values can't leave it
+            with_deep_stack(move || repl::Session::new(format, repl::history_path()).run());
+            ExitCode::from(cli::EXIT_SUCCESS)
+        }
+        Action::Compile {
+            command,
+            input,
+            format,
+        } => ExitCode::from(compile(command, input, format)),
+    }
+}
 
-        // Create lexer and tokenize
-        let mut lexer = frontend::lexer::Lexer::new(&source);
-        let tokens = lexer.tokenize();
+/// Runs one of the commands that work on a program, returning the exit code
+fn compile(command: Command, input: Input, format: ErrorFormat) -> u8 {
+    let source = match read_input(&input) {
+        Ok(source) => source,
+        Err(reason) => {
+            report_io_error(input.name(), &reason, format);
+            return cli::EXIT_IO;
+        }
+    };
 
-        // Create parser and parse
-        let mut parser = frontend::parser::Parser::new(&mut error_handler, tokens.iter());
-        parser.parse();
+    match command {
+        Command::Tokens | Command::Ast => dump(command, input.name(), &source, format),
+        Command::Run | Command::Check => with_deep_stack(move || {
+            let mut engine = Engine::new();
+            engine.set_error_format(format);
+            engine.set_color(errors::error::use_color());
 
-        dbg!(&parser.tree);
+            let program = match engine.compile(input.name(), &source) {
+                Ok(program) => program,
+                Err(diagnostics) => {
+                    eprint!("{}", diagnostics);
+                    return cli::EXIT_DIAGNOSTICS;
+                }
+            };
+            if let Some(warnings) = program.warnings() {
+                eprint!("{}", warnings);
+            }
+            if command == Command::Check {
+                return cli::EXIT_SUCCESS;
+            }
 
-        // Create runtime
-        let mut runtime = backend::eval::Runtime::initialize(parser.tree.into_iter());
+            match engine.run(&program) {
+                Ok(_) => cli::EXIT_SUCCESS,
+                Err(diagnostics) => {
+                    eprint!("{}", diagnostics);
+                    cli::EXIT_RUNTIME
+                }
+            }
+        }),
+    }
+}
+
+/// Prints what the lexer or the parser makes of the source
+fn dump(command: Command, name: &str, source: &String, format: ErrorFormat) -> u8 {
+    let formatter = Formatter::initialize(name, source);
+    let mut error_handler = Errors::initialize(&formatter);
+    error_handler.set_format(format);
+
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize(&mut error_handler);
+
+    if command == Command::Tokens {
+        for token in tokens {
+            let (start_line, start_col) = formatter.position(token.1 .0);
+            let (end_line, end_col) = formatter.position(token.1 .1 + 1);
+            println!(
+                "{}:{}-{}:{}\t{}",
+                start_line, start_col, end_line, end_col, token.0
+            );
+        }
+    } else {
+        let mut parser = Parser::new(&mut error_handler, tokens.iter());
+        parser.parse();
+        parser
+            .tree
+            .iter()
+            .for_each(|e| print!("{}", e.pretty(&formatter)));
+    }
 
-        //dbg!(&parser);
-    } else if args.len() < 2 && dbga {
-        eprintln!("Please specify a file path");
-        std::process::exit(1);
+    error_handler.print();
+    if error_handler.has_errors() {
+        cli::EXIT_DIAGNOSTICS
     } else {
-        todo!("Non DBGA logic");
+        cli::EXIT_SUCCESS
+    }
+}
+
+/// Runs `f` on a thread with enough stack for deep recursion
+/// The evaluator gives up at `MAX_CALL_DEPTH` calls long before this runs out
+fn with_deep_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new()
+        .stack_size(256 * 1024 * 1024)
+        .spawn(f)
+        .expect("Error spawning the evaluator thread")
+        .join()
+        .expect("Evaluator thread panicked")
+}
+
+/// Reports a file that couldn't be read
+/// There's no source to point into, so the error is reported against an empty one
+pub fn report_io_error(path: &str, reason: &io::Error, format: ErrorFormat) {
+    let empty = String::new();
+    let formatter = Formatter::initialize(path, &empty);
+    let mut error_handler = Errors::initialize(&formatter);
+    error_handler.set_format(format);
+    error_handler.new(
+        ErrorClass::Error,
+        ErrorKind::Io {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        },
+        0,
+        0,
+    );
+    error_handler.print();
+}
+
+fn read_input(input: &Input) -> io::Result<String> {
+    match input {
+        Input::Stdin => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source)?;
+            Ok(source)
+        }
+        Input::File(path) => fs::read_to_string(path),
     }
 }
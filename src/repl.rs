@@ -0,0 +1,356 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use starkey::{
+    errors::{
+        error::{self, ErrorFormat, Errors},
+        formatter::Formatter,
+    },
+    frontend::{
+        lexer::Lexer,
+        parser::Parser,
+        token::{Token, TokenKind},
+    },
+    Diagnostics, Engine, Type,
+};
+
+const HELP: &str = "\
+Statements end with ';' and run as soon as they're complete. A bare expression like `x + 1;`
+prints its value. Input with an open '{' or '(' or without its ';' continues on the next line.
+
+    :type <expr>    Evaluate an expression and print its type
+    :ast <expr>     Print the syntax tree of a statement or expression without running it
+    :load <file>    Run a file, keeping everything it defines
+    :history        Show previous entries
+    :help           Show this message
+    :quit           Leave (so does end of input)
+";
+
+/// How many entries `:history` shows
+const HISTORY_SHOWN: usize = 50;
+
+/// Everything that lasts between entries
+pub struct Session {
+    engine: Engine,
+    format: ErrorFormat,
+    entries: usize,
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+}
+
+/// Where entries are kept between sessions, `~/.starkey_history`
+/// Only typed entries are kept, so there's nowhere when stdin isn't a terminal, and nowhere when
+/// `STARKEY_NO_HISTORY` is set
+pub fn history_path() -> Option<PathBuf> {
+    if !io::stdin().is_terminal() || env::var_os("STARKEY_NO_HISTORY").is_some() {
+        return None;
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".starkey_history"))
+}
+
+impl Session {
+    /// Starts a session with the entries already in `history_path`, adding new ones to it
+    pub fn new(format: ErrorFormat, history_path: Option<PathBuf>) -> Self {
+        let history = history_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        let mut engine = Engine::new();
+        engine.set_error_format(format);
+        engine.set_color(error::use_color());
+
+        Self {
+            engine,
+            format,
+            entries: 0,
+            history,
+            history_path,
+        }
+    }
+
+    /// Reads entries from stdin until it ends or `:quit` is entered
+    pub fn run(&mut self) {
+        let interactive = io::stdin().is_terminal();
+        if interactive {
+            println!("starkey {}, type :help for help", env!("CARGO_PKG_VERSION"));
+        }
+
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        let mut pending = String::new();
+        loop {
+            if interactive {
+                print!("{}", if pending.is_empty() { ">> " } else { ".. " });
+                let _ = io::stdout().flush();
+            }
+
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                // Whatever was left unfinished is run as it is, so its errors are still reported
+                _ => {
+                    if !pending.trim().is_empty() {
+                        self.entry(&pending);
+                    }
+                    break;
+                }
+            };
+
+            if pending.is_empty() && line.trim_start().starts_with(':') {
+                if !self.command(line.trim()) {
+                    break;
+                }
+                continue;
+            }
+
+            pending.push_str(&line);
+            pending.push('\n');
+            if pending.trim().is_empty() {
+                pending.clear();
+            } else if is_complete(&pending) {
+                self.entry(&pending);
+                pending.clear();
+            }
+        }
+    }
+
+    /// Runs a meta-command, returning false if the session should end
+    fn command(&mut self, line: &str) -> bool {
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (line, ""),
+        };
+
+        match name {
+            ":quit" | ":q" => return false,
+            ":help" | ":h" => print!("{}", HELP),
+            ":history" => {
+                let skip = self.history.len().saturating_sub(HISTORY_SHOWN);
+                for (i, entry) in self.history.iter().enumerate().skip(skip) {
+                    println!("{:>4}  {}", i + 1, entry);
+                }
+            }
+            ":type" | ":ast" | ":load" if arg.is_empty() => {
+                eprintln!("error: '{}' needs an argument, see :help", name)
+            }
+            ":type" => {
+                self.record(line);
+                if let Some(value) = self.submit(&terminated(arg)) {
+                    println!("{}", value.type_name());
+                }
+            }
+            ":ast" => {
+                self.record(line);
+                self.print_ast(&terminated(arg));
+            }
+            ":load" => {
+                self.record(line);
+                self.load(arg);
+            }
+            _ => eprintln!("error: unknown command '{}', see :help", name),
+        }
+        true
+    }
+
+    fn entry(&mut self, text: &str) {
+        self.record(text);
+        match self.submit(text) {
+            Some(Type::Nil) | None => {}
+            Some(value) => println!("{}", value),
+        }
+    }
+
+    fn load(&mut self, path: &str) {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                let result = self
+                    .engine
+                    .compile(path, &text)
+                    .and_then(|program| run(&mut self.engine, &program));
+                if let Err(diagnostics) = result {
+                    eprint!("{}", diagnostics);
+                }
+            }
+            Err(reason) => crate::report_io_error(path, &reason, self.format),
+        }
+    }
+
+    /// Compiles and runs an entry
+    /// Returns its value if it ended with a bare expression and ran without errors
+    fn submit(&mut self, text: &str) -> Option<Type> {
+        self.entries += 1;
+        let name = format!("<repl:{}>", self.entries);
+        let result = self
+            .engine
+            .compile_entry(&name, text)
+            .and_then(|program| run(&mut self.engine, &program));
+        match result {
+            Ok(value) => value,
+            Err(diagnostics) => {
+                eprint!("{}", diagnostics);
+                None
+            }
+        }
+    }
+
+    fn print_ast(&self, text: &str) {
+        let source = text.to_string();
+        let formatter = Formatter::initialize("<ast>", &source);
+        let mut errors = Errors::initialize(&formatter);
+        errors.set_format(self.format);
+
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize(&mut errors);
+        let mut parser = Parser::new(&mut errors, tokens.iter());
+        parser.allow_bare_expressions();
+        parser.parse();
+        parser
+            .tree
+            .iter()
+            .for_each(|e| print!("{}", e.pretty(&formatter)));
+        errors.print();
+    }
+
+    /// Adds an entry to the history, on a single line
+    fn record(&mut self, text: &str) {
+        let entry = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if let Some(path) = &self.history_path {
+            let file = fs::OpenOptions::new().create(true).append(true).open(path);
+            if let Ok(mut file) = file {
+                let _ = writeln!(file, "{}", entry);
+            }
+        }
+        self.history.push(entry);
+    }
+}
+
+/// Runs a compiled entry, after printing any warnings it came with
+fn run(engine: &mut Engine, program: &starkey::Program) -> Result<Option<Type>, Diagnostics> {
+    if let Some(warnings) = program.warnings() {
+        eprint!("{}", warnings);
+    }
+    engine.run(program)
+}
+
+/// Whether the input is a whole statement yet, rather than one still being typed
+/// Anything with an unclosed `(`, `[` or `{`, or that doesn't end in `;` or `}`, isn't
+fn is_complete(text: &str) -> bool {
+    let source = text.to_string();
+    let formatter = Formatter::initialize("<repl>", &source);
+    let mut errors = Errors::initialize(&formatter);
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize(&mut errors);
+
+    let mut depth = 0i32;
+    for Token(kind, _) in tokens {
+        match kind {
+            TokenKind::LPar | TokenKind::LBrac | TokenKind::LCurl => depth += 1,
+            TokenKind::RPar | TokenKind::RBrac | TokenKind::RCurl => depth -= 1,
+            _ => {}
+        }
+    }
+
+    let last = tokens.iter().rev().find(|t| t.0 != TokenKind::EOF);
+    depth <= 0
+        && matches!(
+            last.map(|t| &t.0),
+            Some(TokenKind::SemiColon) | Some(TokenKind::RCurl)
+        )
+}
+
+/// Adds the `;` that meta-command arguments are allowed to leave off
+fn terminated(text: &str) -> String {
+    let text = text.trim_end();
+    if text.ends_with(';') || text.ends_with('}') {
+        text.to_string()
+    } else {
+        format!("{};", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continuation() {
+        assert!(is_complete("var a = 1;\n"));
+        assert!(is_complete("def f(): int {\n    return 1;\n}\n"));
+        assert!(!is_complete("var a = 1\n"));
+        assert!(!is_complete("def f(): int {\n"));
+        assert!(!is_complete("def f(): int {\n    return 1;\n"));
+        assert!(!is_complete("$ f(1,\n"));
+        // A ';' inside a string doesn't end the statement
+        assert!(!is_complete("$ \"a;\"\n"));
+        assert!(is_complete("$ \"a;\";\n"));
+    }
+
+    #[test]
+    fn meta_command_arguments() {
+        assert_eq!(terminated("1 + 2"), "1 + 2;");
+        assert_eq!(terminated("x; "), "x;");
+        assert_eq!(terminated("def f() { }"), "def f() { }");
+    }
+
+    #[test]
+    fn globals_last_between_entries() {
+        let mut session = Session::new(ErrorFormat::Human, None);
+        assert!(session.submit("var a = 1;\n").is_none());
+        assert!(session
+            .submit("def twice(x: int): int { return x * 2; }\n")
+            .is_none());
+        let value = session.submit("twice(a) + 1;\n").unwrap();
+        assert_eq!(value.to_string(), "3");
+    }
+
+    #[test]
+    fn failed_entries_define_nothing() {
+        let mut session = Session::new(ErrorFormat::Human, None);
+        // Stopped by the checker, so nothing ran
+        assert!(session.submit("var a = 1;\nvar b = missing;\n").is_none());
+        assert!(session.submit("a;\n").is_none());
+
+        // Stopped while running, after the first declaration
+        assert!(session.submit("var c = 1;\nvar d = 1 / 0;\n").is_none());
+        assert_eq!(session.submit("c;\n").unwrap().to_string(), "1");
+        assert!(session.submit("d;\n").is_none());
+        // So `d` can be declared again
+        assert!(session.submit("var d = 2;\n").is_none());
+        assert_eq!(session.submit("d;\n").unwrap().to_string(), "2");
+    }
+
+    #[test]
+    fn history_file() {
+        let path = env::temp_dir().join(format!("starkey_history_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut session = Session::new(ErrorFormat::Human, Some(path.clone()));
+        session.record("var a =\n    1;\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "var a = 1;\n");
+
+        // A new session starts with the entries of the last one
+        let session = Session::new(ErrorFormat::Human, Some(path.clone()));
+        assert_eq!(session.history, ["var a = 1;"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn errors_point_into_their_entry() {
+        let mut session = Session::new(ErrorFormat::Human, None);
+        let diagnostics = session
+            .engine
+            .compile_entry("<repl:1>", "var x = 1;\n$x")
+            .map(|_| ())
+            .unwrap_err();
+        assert!(diagnostics.report().contains("<repl:1>:2:3"));
+        let diagnostics = session
+            .engine
+            .compile_entry("<repl:2>", "var y = ;\n")
+            .map(|_| ())
+            .unwrap_err();
+        assert!(diagnostics.report().contains("<repl:2>:1:1"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Runs the built binary to check its output and documented exit codes
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Runs `starkey` with `args`, feeding `stdin` to it
+fn starkey(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_starkey"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        // Keeps the REPL away from the real history file
+        .env_remove("HOME")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn run_prints_and_succeeds() {
+    let output = starkey(&["run", "-"], "var a = 2;\n$ a * 3;\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "6\n");
+    // Running is the default
+    let output = starkey(&["-"], "$ \"hi\";\n");
+    assert_eq!(stdout(&output), "hi\n");
+}
+
+#[test]
+fn exit_codes() {
+    // Diagnostics stop the program from running
+    let output = starkey(&["run", "-"], "$ 1;\n$ missing;\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("error[SK0100]: Cannot find 'missing' in this scope"));
+
+    assert_eq!(starkey(&["check", "-"], "$ 1;\n").status.code(), Some(0));
+    assert_eq!(starkey(&["check", "-"], "$ 1 +;\n").status.code(), Some(1));
+    assert_eq!(starkey(&["build", "-"], "").status.code(), Some(2));
+    assert_eq!(starkey(&["--explain", "SK9999"], "").status.code(), Some(2));
+
+    let output = starkey(&["run", "-"], "$ 1;\n$ 1 / 0;\n");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains("error[SK0400]: Attempted to divide by zero"));
+
+    let output = starkey(&["run", "no/such/file.sk"], "");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr(&output).contains("error[SK0500]: Couldn't access 'no/such/file.sk'"));
+}
+
+#[test]
+fn tokens_and_ast() {
+    let output = starkey(&["tokens", "-"], "$ 1;");
+    assert_eq!(output.status.code(), Some(0));
+    let lines: Vec<_> = stdout(&output).lines().map(String::from).collect();
+    assert_eq!(lines[0], "1:1-1:2\t'$'");
+    assert_eq!(lines[1], "1:3-1:4\tnumber '1'");
+
+    let output = starkey(&["ast", "-"], "$ 1 + 2;");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        stdout(&output).starts_with("PrintExpr"),
+        "{}",
+        stdout(&output)
+    );
+    assert!(
+        stdout(&output).contains("BinaryExpr +"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn json_diagnostics() {
+    let output = starkey(&["check", "--error-format=json", "-"], "$ a;\n$ b;\n");
+    assert_eq!(output.status.code(), Some(1));
+    let lines: Vec<_> = stderr(&output).lines().map(String::from).collect();
+    assert_eq!(lines.len(), 2, "{:?}", lines);
+    assert!(lines[0].starts_with(r#"{"class":"error","kind":"UndefinedName","code":"SK0100","message":"Cannot find 'a' in this scope","file":"<stdin>""#));
+    assert!(lines.iter().all(|l| l.starts_with('{') && l.ends_with('}')));
+}
+
+#[test]
+fn usage_and_explain() {
+    let output = starkey(&["--help"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("Usage: starkey"));
+
+    let output = starkey(&["--explain", "sk0400"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!stdout(&output).is_empty());
+}
+
+#[test]
+fn repl_reads_stdin() {
+    // Entries carry on over lines until they're complete, and globals last between them
+    let output = starkey(
+        &["repl"],
+        "var a =\n  2;\ndef f(x: int): int {\n  return x * 10;\n}\nf(a);\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "20\n");
+
+    let output = starkey(&[], ":type 1.5\n:ast 1 + 2\n:nope\n");
+    assert_eq!(stdout(&output).lines().next(), Some("float"));
+    assert!(stdout(&output).contains("BinaryExpr +"));
+    assert!(stderr(&output).contains("error: unknown command ':nope', see :help"));
+}
+
+#[test]
+fn piped_repl_keeps_no_history() {
+    let home = std::env::temp_dir().join(format!("starkey_home_{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_starkey"))
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"1 + 2;\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(stdout(&output), "3\n");
+    assert!(!home.join(".starkey_history").exists());
+    std::fs::remove_dir_all(&home).unwrap();
+}